@@ -10,5 +10,5 @@ mod negative;
 mod paths;
 
 pub use lock::RepoLock;
-pub use negative::NegativeCache;
-pub use paths::CachePaths;
+pub use negative::{Absence, NegativeCache};
+pub use paths::{CachePaths, SHARD_MIRRORS_ENV, is_complete_mirror, owner_shard};
@@ -2,19 +2,35 @@
 //!
 //! This module provides a simple in-memory cache to remember repositories
 //! that have failed to clone (404, private, etc.) to avoid repeatedly
-//! hitting GitHub with requests for non-existent repos.
+//! hitting GitHub with requests for non-existent repos. Repos GitHub
+//! refuses to serve (DMCA takedowns, disabled repos) are kept too, for
+//! longer, along with git's message.
 
+use crate::github::{ApiClient, ApiError};
 use crate::types::RepoKey;
 use dashmap::DashMap;
 use std::time::{Duration, Instant};
 
 /// Default TTL for negative cache entries (1 hour).
 const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+/// TTL for repos GitHub refuses to serve (1 day). Takedowns are rarely
+/// lifted, so these are retried far less often than missing repos.
+const UNAVAILABLE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Why a repo is in the negative cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Absence {
+    /// The API confirmed the repo doesn't exist.
+    NotFound,
+    /// GitHub refuses to serve the repo; holds git's message.
+    Unavailable(String),
+}
 
 /// Entry in the negative cache.
 struct NegativeCacheEntry {
     /// When this entry was created.
     cached_at: Instant,
+    absence: Absence,
 }
 
 /// Cache of repositories that are known not to exist.
@@ -54,15 +70,37 @@ impl NegativeCache {
 
     /// Check if a repo is in the negative cache (and not expired).
     pub fn contains(&self, key: &RepoKey) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Why a repo is in the negative cache, if it is (and not expired).
+    pub fn get(&self, key: &RepoKey) -> Option<Absence> {
         if let Some(entry) = self.entries.get(key) {
-            if entry.cached_at.elapsed() < self.ttl {
-                return true;
+            let ttl = match entry.absence {
+                Absence::NotFound => self.ttl,
+                Absence::Unavailable(_) => self.ttl.max(UNAVAILABLE_TTL),
+            };
+            if entry.cached_at.elapsed() < ttl {
+                return Some(entry.absence.clone());
             }
             // Entry expired, remove it
             drop(entry);
             self.entries.remove(key);
         }
-        false
+        None
+    }
+
+    /// Remember that GitHub refuses to serve a repo. Unlike missing repos
+    /// there's nothing to confirm: git's message already says why.
+    pub fn insert_unavailable(&self, key: &RepoKey, message: String) {
+        log::info!("{key} is unavailable, adding to negative cache");
+        self.entries.insert(
+            key.clone(),
+            NegativeCacheEntry {
+                cached_at: Instant::now(),
+                absence: Absence::Unavailable(message),
+            },
+        );
     }
 
     /// Add a repo to the negative cache after verifying it doesn't exist via GitHub API.
@@ -77,6 +115,7 @@ impl NegativeCache {
                     key.clone(),
                     NegativeCacheEntry {
                         cached_at: Instant::now(),
+                        absence: Absence::NotFound,
                     },
                 );
                 true
@@ -111,43 +150,18 @@ enum RepoStatus {
     Unknown(String),
 }
 
-/// Check if a GitHub repo exists using the public API.
+/// Check if a GitHub repo exists using the GitHub API.
 ///
-/// Makes a HEAD request to `https://api.github.com/repos/{owner}/{repo}`
-/// - 200 = repo exists (public)
-/// - 404 = repo does not exist
-/// - 403 = rate limited or private (treat as unknown)
+/// Makes a HEAD request to `repos/{owner}/{repo}` through the shared
+/// [`ApiClient`]:
+/// - 2xx = repo exists
+/// - 404 = repo does not exist (or is private and the token can't see it)
+/// - anything else, including rate limits = unknown
 fn check_repo_exists(owner: &str, repo: &str) -> RepoStatus {
-    let url = format!("https://api.github.com/repos/{}/{}", owner, repo);
-
-    // Use a short timeout since this is just a quick existence check
-    let result = ureq::builder()
-        .timeout(Duration::from_secs(5))
-        .build()
-        .head(&url)
-        .set("User-Agent", "ghfs")
-        .call();
-
-    match result {
-        Ok(response) => {
-            if response.status() == 200 {
-                RepoStatus::Exists
-            } else {
-                // Unexpected success status
-                RepoStatus::Unknown(format!("unexpected status: {}", response.status()))
-            }
-        }
-        Err(ureq::Error::Status(404, _)) => RepoStatus::NotFound,
-        Err(ureq::Error::Status(403, _)) => {
-            // Could be rate limited or private repo
-            RepoStatus::Unknown("forbidden (rate limited or private)".to_string())
-        }
-        Err(ureq::Error::Status(401, _)) => {
-            // Auth required - could be private
-            RepoStatus::Unknown("unauthorized".to_string())
-        }
-        Err(ureq::Error::Status(code, _)) => RepoStatus::Unknown(format!("HTTP {}", code)),
-        Err(ureq::Error::Transport(e)) => RepoStatus::Unknown(format!("transport error: {}", e)),
+    match ApiClient::shared().head(&format!("repos/{owner}/{repo}")) {
+        Ok(()) => RepoStatus::Exists,
+        Err(ApiError::NotFound) => RepoStatus::NotFound,
+        Err(e) => RepoStatus::Unknown(e.to_string()),
     }
 }
 
@@ -172,6 +186,7 @@ mod tests {
             key.clone(),
             NegativeCacheEntry {
                 cached_at: Instant::now(),
+                absence: Absence::NotFound,
             },
         );
         assert!(cache.contains(&key));
@@ -180,6 +195,19 @@ mod tests {
         assert!(!cache.contains(&key));
     }
 
+    #[test]
+    fn unavailable_repos_outlive_the_ttl() {
+        let cache = NegativeCache::with_ttl(Duration::from_millis(10));
+        let key: RepoKey = "octocat/taken-down".parse().unwrap();
+        cache.insert_unavailable(&key, "DMCA takedown".to_string());
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(
+            cache.get(&key),
+            Some(Absence::Unavailable("DMCA takedown".to_string()))
+        );
+    }
+
     // Network tests - only run with GHFS_RUN_NETWORK_TESTS=1
     fn network_tests_enabled() -> bool {
         match std::env::var("GHFS_RUN_NETWORK_TESTS") {
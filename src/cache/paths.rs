@@ -10,28 +10,64 @@
 //! ├── blobs/
 //! │   └── <algo>/                # Content-addressed hydrated blobs (shared)
 //! │       └── <oid>
-//! └── locks/
-//!     └── <owner>__<repo>.lock   # flock-based per-repo serialization
+//! ├── locks/
+//! │   └── <owner>__<repo>.lock   # flock-based per-repo serialization
+//! ├── references/
+//! │   └── <owner>__<repo>        # `ghfs link` target: repo to borrow objects from
+//! └── branches/
+//!     └── <owner>__<repo>        # `ghfs set-branch`: branch served instead of HEAD
 //! ```
 //!
+//! A repo that GitHub reports as renamed may have its old mirror path as a
+//! symlink to the new one (see [`CachePaths::alias`]).
+//!
+//! Caches holding thousands of owners can shard `mirrors/` by the first two
+//! characters of the lowercased owner, as `mirrors/<oc>/<owner>/<repo>.git`,
+//! so no single directory holds every owner. A `.sharded` marker in
+//! `mirrors/` selects that layout; see [`SHARD_MIRRORS_ENV`].
+//!
 //! There are no per-generation worktree directories and no `current` symlink:
 //! each commit's tree is served directly from git objects, and blobs live in a
 //! global content-addressed cache shared across all repos and refs.
 
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 
 use crate::types::RepoKey;
 
+/// Environment variable that, when truthy, has the daemon move mirrors into
+/// the sharded layout at startup.
+pub const SHARD_MIRRORS_ENV: &str = "GHFS_SHARD_MIRRORS";
+/// File in `mirrors/` marking the sharded layout.
+const SHARDED_MARKER: &str = ".sharded";
+
 /// Manages all filesystem paths for the cache directory layout.
 #[derive(Debug, Clone)]
 pub struct CachePaths {
     root: PathBuf,
+    /// Whether owner directories sit in two-character shard directories.
+    sharded: bool,
 }
 
 impl CachePaths {
-    /// Creates a new `CachePaths` with the specified root directory.
+    /// Creates a new `CachePaths` with the specified root directory. The
+    /// layout of `mirrors/` is read from disk here, once.
     pub fn new(root: impl Into<PathBuf>) -> Self {
-        Self { root: root.into() }
+        let root = root.into();
+        let sharded = root.join("mirrors").join(SHARDED_MARKER).exists();
+        Self { root, sharded }
+    }
+
+    /// Whether owner directories are sharded by prefix.
+    pub fn is_sharded(&self) -> bool {
+        self.sharded
+    }
+
+    /// Returns the marker file selecting the sharded layout:
+    /// `{root}/mirrors/.sharded`.
+    pub fn sharded_marker(&self) -> PathBuf {
+        self.mirrors_dir().join(SHARDED_MARKER)
     }
 
     /// Returns the root cache directory.
@@ -55,28 +91,272 @@ impl CachePaths {
         self.root.join("locks")
     }
 
-    /// Returns the mirror directory for a specific repository:
-    /// `{root}/mirrors/{owner}/{repo}.git`.
-    pub fn mirror_dir(&self, key: &RepoKey) -> PathBuf {
-        let repo = key.repo.as_str();
-        let mut path = self.mirrors_dir().join(key.owner.as_str());
-        // Avoid double ".git" suffix if the repo name already ends with it.
-        if repo.ends_with(".git") {
-            path.push(repo);
+    /// Returns the directory holding `owner`'s mirrors:
+    /// `{root}/mirrors/{owner}`, or `{root}/mirrors/{shard}/{owner}` when
+    /// sharded. The name is used as given.
+    pub fn owner_dir(&self, owner: &str) -> PathBuf {
+        if self.sharded {
+            self.mirrors_dir().join(owner_shard(owner)).join(owner)
         } else {
-            path.push(format!("{}.git", repo));
+            self.mirrors_dir().join(owner)
         }
-        path
     }
 
-    /// Returns the lock file path: `{root}/locks/{owner}__{repo}.lock`.
+    /// Every owner directory under `mirrors/`, across shards when sharded.
+    /// Hidden entries are skipped.
+    pub fn owner_dirs(&self) -> Vec<PathBuf> {
+        let subdirs = |dir: &Path| -> Vec<PathBuf> {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                return Vec::new();
+            };
+            entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+                .map(|entry| entry.path())
+                .collect()
+        };
+        let top = subdirs(&self.mirrors_dir());
+        if !self.sharded {
+            return top;
+        }
+        top.iter().flat_map(|shard| subdirs(shard)).collect()
+    }
+
+    /// Returns the mirror directory for a specific repository:
+    /// `{root}/mirrors/{owner}/{repo}.git` (under the owner's shard when
+    /// sharded), using the key's [canonical](RepoKey::canonical) form.
+    pub fn mirror_dir(&self, key: &RepoKey) -> PathBuf {
+        let key = key.canonical();
+        self.owner_dir(key.owner.as_str())
+            .join(format!("{}.git", key.repo))
+    }
+
+    /// Returns the lock file path: `{root}/locks/{owner}__{repo}.lock`,
+    /// using the key's canonical form.
     pub fn lock_path(&self, key: &RepoKey) -> PathBuf {
-        self.locks_dir().join(format!(
-            "{}__{}.lock",
-            key.owner.as_str(),
-            key.repo.as_str()
-        ))
+        let key = key.canonical();
+        self.locks_dir()
+            .join(format!("{}__{}.lock", key.owner, key.repo))
+    }
+
+    /// Returns the references directory path: `{root}/references`.
+    pub fn references_dir(&self) -> PathBuf {
+        self.root.join("references")
     }
+
+    /// Returns the file recording which repo `key` borrows objects from:
+    /// `{root}/references/{owner}__{repo}`, using the key's canonical form.
+    pub fn reference_path(&self, key: &RepoKey) -> PathBuf {
+        let key = key.canonical();
+        self.references_dir()
+            .join(format!("{}__{}", key.owner, key.repo))
+    }
+
+    /// The repo `key` was linked to with [`CachePaths::set_reference`], if
+    /// any.
+    pub fn reference(&self, key: &RepoKey) -> Option<RepoKey> {
+        std::fs::read_to_string(self.reference_path(key))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// Record that `key`'s mirror borrows objects from `reference`'s.
+    pub fn set_reference(&self, key: &RepoKey, reference: &RepoKey) -> std::io::Result<()> {
+        std::fs::create_dir_all(self.references_dir())?;
+        std::fs::write(
+            self.reference_path(key),
+            format!("{}\n", reference.canonical()),
+        )
+    }
+
+    /// Returns the branches directory path: `{root}/branches`.
+    pub fn branches_dir(&self) -> PathBuf {
+        self.root.join("branches")
+    }
+
+    /// Returns the file recording which branch `key` serves instead of the
+    /// remote's default: `{root}/branches/{owner}__{repo}`, using the key's
+    /// canonical form.
+    pub fn default_branch_path(&self, key: &RepoKey) -> PathBuf {
+        let key = key.canonical();
+        self.branches_dir()
+            .join(format!("{}__{}", key.owner, key.repo))
+    }
+
+    /// The branch set with [`CachePaths::set_default_branch`], if any.
+    pub fn default_branch(&self, key: &RepoKey) -> Option<String> {
+        let branch = std::fs::read_to_string(self.default_branch_path(key)).ok()?;
+        let branch = branch.trim();
+        (!branch.is_empty()).then(|| branch.to_string())
+    }
+
+    /// Record that `key` serves `branch` instead of the remote's default.
+    pub fn set_default_branch(&self, key: &RepoKey, branch: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(self.branches_dir())?;
+        std::fs::write(self.default_branch_path(key), format!("{branch}\n"))
+    }
+
+    /// Forget `key`'s branch, going back to the remote's default.
+    pub fn clear_default_branch(&self, key: &RepoKey) -> std::io::Result<()> {
+        match std::fs::remove_file(self.default_branch_path(key)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Point `from`'s mirror path at `to`'s mirror with a relative symlink,
+    /// so the old name of a renamed repo keeps resolving. `to` must be a
+    /// [complete mirror](is_complete_mirror); see [`atomic_symlink_swap`].
+    pub fn alias(&self, from: &RepoKey, to: &RepoKey) -> std::io::Result<()> {
+        let link = self.mirror_dir(from);
+        let up = if self.sharded { "../.." } else { ".." };
+        let target = Path::new(up).join(
+            self.mirror_dir(to)
+                .strip_prefix(self.mirrors_dir())
+                .expect("mirrors live under mirrors_dir"),
+        );
+        if let Some(parent) = link.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        atomic_symlink_swap(&link, &target, is_complete_mirror)
+    }
+
+    /// The repo `key`'s mirror path is aliased to, if it is a symlink made
+    /// by [`CachePaths::alias`].
+    pub fn alias_target(&self, key: &RepoKey) -> Option<RepoKey> {
+        let target = std::fs::read_link(self.mirror_dir(key)).ok()?;
+        let repo = target.file_name()?.to_str()?.strip_suffix(".git")?;
+        let owner = target.parent()?.file_name()?.to_str()?;
+        format!("{owner}/{repo}").parse().ok()
+    }
+
+    /// Create the root and its `mirrors`, `blobs`, and `locks` directories
+    /// if they don't exist.
+    pub fn ensure_created(&self) -> std::io::Result<()> {
+        for dir in [self.mirrors_dir(), self.blobs_dir(), self.locks_dir()] {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(())
+    }
+
+    /// Check that the root is writable by creating and removing a probe
+    /// file in it.
+    pub fn validate(&self) -> std::io::Result<()> {
+        let probe = self
+            .root
+            .join(format!(".ghfs-write-test-{}", std::process::id()));
+        std::fs::write(&probe, b"")?;
+        std::fs::remove_file(&probe)
+    }
+}
+
+/// The shard directory for `owner`: its first two characters, lowercased.
+pub fn owner_shard(owner: &str) -> String {
+    owner
+        .chars()
+        .take(2)
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Whether `path` looks like a usable bare mirror: git needs `HEAD`,
+/// `objects`, and `refs` to open a repository there.
+pub fn is_complete_mirror(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
+
+/// How many times [`atomic_symlink_swap`] replaces the link before giving
+/// up on a transient failure.
+const SWAP_ATTEMPTS: u32 = 5;
+
+/// Point the symlink at `link` to `target` (relative to `link`'s directory
+/// when relative), replacing any previous link atomically.
+///
+/// The target must pass `verify` before anything changes, so an incomplete
+/// target is rejected with the old link intact. The new link is created
+/// beside `link` under a name unique to this call and renamed over it, then
+/// verified again through `link`; if that fails (the target vanished in
+/// between), the previous target is restored, or the link removed if there
+/// was none.
+///
+/// Another process may swap the same link concurrently. A rename that fails
+/// or leaves no link behind is retried a few times, and a link that points
+/// elsewhere afterwards means the other swap landed later and wins.
+pub fn atomic_symlink_swap(
+    link: &Path,
+    target: &Path,
+    verify: impl Fn(&Path) -> bool,
+) -> std::io::Result<()> {
+    let incomplete = |target: &Path| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} is not a complete mirror", target.display()),
+        )
+    };
+    let resolved = match link.parent() {
+        Some(parent) => parent.join(target),
+        None => target.to_path_buf(),
+    };
+    if !verify(&resolved) {
+        return Err(incomplete(target));
+    }
+
+    let previous = std::fs::read_link(link).ok();
+    let mut attempt = 1;
+    loop {
+        let current = replace_link(link, target).and_then(|()| std::fs::read_link(link));
+        match current {
+            Ok(current) if current == target => break,
+            Ok(current) => {
+                log::debug!(
+                    "{} was swapped to {} concurrently; keeping it",
+                    link.display(),
+                    current.display()
+                );
+                return Ok(());
+            }
+            Err(e) if attempt < SWAP_ATTEMPTS && is_transient_swap_error(&e) => {
+                std::thread::sleep(Duration::from_millis(u64::from(attempt) * 5));
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    if verify(link) {
+        return Ok(());
+    }
+    match previous {
+        Some(previous) => replace_link(link, &previous)?,
+        None => std::fs::remove_file(link)?,
+    }
+    Err(incomplete(target))
+}
+
+/// Rename a new symlink to `target` over `link`.
+fn replace_link(link: &Path, target: &Path) -> std::io::Result<()> {
+    static SWAPS: AtomicU64 = AtomicU64::new(0);
+    let temporary = link.with_extension(format!(
+        "link.tmp.{}.{}",
+        std::process::id(),
+        SWAPS.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::os::unix::fs::symlink(target, &temporary)?;
+    std::fs::rename(&temporary, link).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temporary);
+    })
+}
+
+/// Whether a failed swap may succeed if retried: a concurrent swap can
+/// briefly leave `link` missing, or its directory entries in flux.
+fn is_transient_swap_error(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::NotFound | std::io::ErrorKind::AlreadyExists
+    )
 }
 
 impl Default for CachePaths {
@@ -146,6 +426,236 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_paths_use_canonical_key() {
+        let key: RepoKey = "Octocat/Hello-World".parse().unwrap();
+        assert_eq!(
+            test_paths().mirror_dir(&key),
+            test_paths().mirror_dir(&test_repo_key())
+        );
+        assert_eq!(
+            test_paths().lock_path(&key),
+            test_root_path()
+                .join("locks")
+                .join("octocat__hello-world.lock")
+        );
+    }
+
+    #[test]
+    fn sharded_mirrors_sit_under_owner_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("mirrors")).unwrap();
+        std::fs::write(dir.path().join("mirrors").join(".sharded"), b"").unwrap();
+        let paths = CachePaths::new(dir.path());
+        assert!(paths.is_sharded());
+        assert!(!CachePaths::new(test_root_path()).is_sharded());
+
+        let key: RepoKey = "Octocat/Hello-World".parse().unwrap();
+        assert_eq!(
+            paths.mirror_dir(&key),
+            dir.path()
+                .join("mirrors")
+                .join("oc")
+                .join("octocat")
+                .join("hello-world.git")
+        );
+        assert_eq!(
+            paths.owner_dir("x"),
+            dir.path().join("mirrors").join("x").join("x")
+        );
+
+        for repo in ["octocat/hello-world", "octo-org/a", "rust-lang/rust", "x/y"] {
+            std::fs::create_dir_all(paths.mirror_dir(&repo.parse().unwrap())).unwrap();
+        }
+        let mut owners: Vec<_> = paths
+            .owner_dirs()
+            .iter()
+            .map(|dir| dir.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        owners.sort();
+        assert_eq!(owners, ["octo-org", "octocat", "rust-lang", "x"]);
+
+        // Aliases climb out of the shard too.
+        let old: RepoKey = "octo-org/old-name".parse().unwrap();
+        let new: RepoKey = "rust-lang/rust".parse().unwrap();
+        git2::Repository::init_bare(paths.mirror_dir(&new)).unwrap();
+        paths.alias(&old, &new).unwrap();
+        assert_eq!(paths.alias_target(&old), Some(new));
+        assert!(paths.mirror_dir(&old).join("HEAD").exists());
+    }
+
+    #[test]
+    fn alias_links_old_name_to_new_mirror() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let old = test_repo_key();
+        let new: RepoKey = "octo-org/Hello-World-2".parse().unwrap();
+        git2::Repository::init_bare(paths.mirror_dir(&new)).unwrap();
+
+        assert_eq!(paths.alias_target(&old), None);
+        paths.alias(&old, &new).unwrap();
+        assert_eq!(paths.alias_target(&old), Some(new.canonical()));
+        assert_eq!(paths.alias_target(&new), None);
+        assert!(paths.mirror_dir(&old).join("HEAD").exists());
+    }
+
+    #[test]
+    fn swap_to_incomplete_target_keeps_old_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let complete = dir.path().join("complete.git");
+        let partial = dir.path().join("partial.git");
+        git2::Repository::init_bare(&complete).unwrap();
+        std::fs::create_dir_all(partial.join("objects")).unwrap();
+        let link = dir.path().join("alias.git");
+
+        atomic_symlink_swap(&link, Path::new("complete.git"), is_complete_mirror).unwrap();
+        assert_eq!(
+            std::fs::read_link(&link).unwrap(),
+            PathBuf::from("complete.git")
+        );
+
+        let err =
+            atomic_symlink_swap(&link, Path::new("partial.git"), is_complete_mirror).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(
+            std::fs::read_link(&link).unwrap(),
+            PathBuf::from("complete.git")
+        );
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn racing_swaps_leave_an_openable_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let targets = ["a.git", "b.git", "c.git"];
+        for name in targets {
+            git2::Repository::init_bare(dir.path().join(name)).unwrap();
+        }
+        let link = dir.path().join("alias.git");
+
+        std::thread::scope(|scope| {
+            for thread in 0..6 {
+                let link = &link;
+                scope.spawn(move || {
+                    for round in 0..50 {
+                        let target = Path::new(targets[(thread + round) % targets.len()]);
+                        atomic_symlink_swap(link, target, is_complete_mirror).unwrap();
+                        assert!(is_complete_mirror(link));
+                    }
+                });
+            }
+        });
+
+        let current = std::fs::read_link(&link).unwrap();
+        assert!(targets.iter().any(|target| current == Path::new(target)));
+        git2::Repository::open_bare(&link).unwrap();
+        // Every temporary link was renamed into place or cleaned up.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn failed_post_swap_check_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["old.git", "new.git"] {
+            git2::Repository::init_bare(dir.path().join(name)).unwrap();
+        }
+        let link = dir.path().join("alias.git");
+        atomic_symlink_swap(&link, Path::new("old.git"), is_complete_mirror).unwrap();
+
+        // The target passes the first check but is gone by the second.
+        let checks = std::cell::Cell::new(0);
+        let flaky = |path: &Path| {
+            checks.set(checks.get() + 1);
+            checks.get() == 1 && is_complete_mirror(path)
+        };
+        assert!(atomic_symlink_swap(&link, Path::new("new.git"), flaky).is_err());
+        assert_eq!(std::fs::read_link(&link).unwrap(), PathBuf::from("old.git"));
+
+        let fresh = dir.path().join("fresh.git");
+        let checks = std::cell::Cell::new(0);
+        let flaky = |path: &Path| {
+            checks.set(checks.get() + 1);
+            checks.get() == 1 && is_complete_mirror(path)
+        };
+        assert!(atomic_symlink_swap(&fresh, Path::new("new.git"), flaky).is_err());
+        assert!(std::fs::symlink_metadata(&fresh).is_err());
+    }
+
+    #[test]
+    fn reference_round_trips_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let fork: RepoKey = "Someone/Hello-World".parse().unwrap();
+        let upstream = test_repo_key();
+
+        assert_eq!(
+            paths.reference_path(&fork),
+            dir.path().join("references").join("someone__hello-world")
+        );
+        assert_eq!(paths.reference(&fork), None);
+        paths.set_reference(&fork, &upstream).unwrap();
+        assert_eq!(paths.reference(&fork), Some(upstream.clone()));
+        assert_eq!(paths.reference(&upstream), None);
+
+        std::fs::write(paths.reference_path(&fork), "not a repo\n").unwrap();
+        assert_eq!(paths.reference(&fork), None);
+    }
+
+    #[test]
+    fn default_branch_round_trips_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "Octocat/Hello-World".parse().unwrap();
+
+        assert_eq!(
+            paths.default_branch_path(&key),
+            dir.path().join("branches").join("octocat__hello-world")
+        );
+        assert_eq!(paths.default_branch(&key), None);
+        paths.set_default_branch(&key, "develop").unwrap();
+        assert_eq!(paths.default_branch(&key), Some("develop".to_string()));
+
+        paths.clear_default_branch(&key).unwrap();
+        assert_eq!(paths.default_branch(&key), None);
+        paths.clear_default_branch(&key).unwrap();
+    }
+
+    #[test]
+    fn ensure_created_builds_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path().join("nested").join("ghfs"));
+
+        paths.ensure_created().unwrap();
+        assert!(paths.mirrors_dir().is_dir());
+        assert!(paths.blobs_dir().is_dir());
+        assert!(paths.locks_dir().is_dir());
+        paths.validate().unwrap();
+
+        // Idempotent, and the probe file is cleaned up.
+        paths.ensure_created().unwrap();
+        assert_eq!(std::fs::read_dir(paths.root()).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn read_only_parent_is_rejected() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Root ignores directory permissions.
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let parent = dir.path().join("ro");
+        std::fs::create_dir(&parent).unwrap();
+        std::fs::set_permissions(&parent, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+        let paths = CachePaths::new(parent.join("ghfs"));
+        let err = paths.ensure_created().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+
+        std::fs::set_permissions(&parent, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
     #[test]
     fn test_lock_path() {
         let key = test_repo_key();
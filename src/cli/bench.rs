@@ -0,0 +1,190 @@
+//! `ghfs bench`: time the store code paths that back the mount.
+//!
+//! Runs in-process against the local cache (cloning the repo if it isn't
+//! cached yet) so the numbers exclude FUSE/NFS and socket overhead and are
+//! reproducible when filing performance issues.
+
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+use git2::Oid;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::cache::CachePaths;
+use crate::store::{EntryKind, Store, StoreError};
+use crate::types::RepoKey;
+
+/// Errors returned by [`bench`].
+#[derive(Debug, Error)]
+pub enum BenchError {
+    #[error(transparent)]
+    Store(#[from] StoreError),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Operation count and wall time for one benchmark phase.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PhaseStats {
+    pub ops: u64,
+    pub bytes: u64,
+    pub elapsed_ns: u64,
+}
+
+impl PhaseStats {
+    fn record(&mut self, elapsed: Duration) {
+        self.elapsed_ns = elapsed.as_nanos().max(1) as u64;
+    }
+
+    fn ops_per_sec(&self) -> f64 {
+        self.ops as f64 / (self.elapsed_ns as f64 / 1e9)
+    }
+
+    fn mib_per_sec(&self) -> f64 {
+        self.bytes as f64 / (1024.0 * 1024.0) / (self.elapsed_ns as f64 / 1e9)
+    }
+}
+
+/// Results of [`bench`].
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub repo: String,
+    pub commit: String,
+    /// HEAD + root tree resolution with nothing open (clones if uncached).
+    pub resolve_cold: PhaseStats,
+    /// The same resolution with the mirror already open.
+    pub resolve_warm: PhaseStats,
+    /// Listing every directory; `ops` counts entries seen.
+    pub walk: PhaseStats,
+    /// Hydrating and reading every regular file.
+    pub read: PhaseStats,
+}
+
+impl BenchReport {
+    /// Human-readable report.
+    pub fn render(&self) -> String {
+        let ms = |p: &PhaseStats| p.elapsed_ns as f64 / 1e6;
+        let mut out = String::new();
+        let _ = writeln!(out, "{} @ {}", self.repo, self.commit);
+        let _ = writeln!(out, "  Resolve (cold): {:>10.3} ms", ms(&self.resolve_cold));
+        let _ = writeln!(out, "  Resolve (warm): {:>10.3} ms", ms(&self.resolve_warm));
+        let _ = writeln!(
+            out,
+            "  Walk:           {:>10.3} ms  {} entries ({:.0}/s)",
+            ms(&self.walk),
+            self.walk.ops,
+            self.walk.ops_per_sec()
+        );
+        let _ = writeln!(
+            out,
+            "  Read:           {:>10.3} ms  {} files, {} bytes ({:.1} MiB/s)",
+            ms(&self.read),
+            self.read.ops,
+            self.read.bytes,
+            self.read.mib_per_sec()
+        );
+        out
+    }
+}
+
+/// Benchmark `key` against the cache at `paths`.
+pub fn bench(paths: &CachePaths, key: &RepoKey) -> Result<BenchReport, BenchError> {
+    let store = Store::new(paths.clone());
+
+    let mut resolve_cold = PhaseStats::default();
+    let start = Instant::now();
+    let commit = store.resolve_head(key)?;
+    let root = store.root_tree(key, commit)?;
+    resolve_cold.ops = 1;
+    resolve_cold.record(start.elapsed());
+
+    let mut resolve_warm = PhaseStats::default();
+    let start = Instant::now();
+    store.resolve_head(key)?;
+    store.root_tree(key, commit)?;
+    resolve_warm.ops = 1;
+    resolve_warm.record(start.elapsed());
+
+    // A fresh store so the walk isn't served from the tree cache.
+    let store = Store::new(paths.clone());
+    let mut walk = PhaseStats::default();
+    let mut files = Vec::new();
+    let start = Instant::now();
+    let mut pending = vec![root];
+    while let Some(tree) = pending.pop() {
+        for entry in store.tree_entries(key, tree)?.iter() {
+            walk.ops += 1;
+            match entry.kind {
+                EntryKind::Tree => pending.push(entry.oid),
+                EntryKind::Blob | EntryKind::Executable => files.push(entry.oid),
+                _ => {}
+            }
+        }
+    }
+    walk.record(start.elapsed());
+
+    let mut read = PhaseStats::default();
+    let start = Instant::now();
+    for oid in files {
+        read.bytes += read_blob(&store, key, oid)?;
+        read.ops += 1;
+    }
+    read.record(start.elapsed());
+
+    Ok(BenchReport {
+        repo: key.to_string(),
+        commit: commit.to_string(),
+        resolve_cold,
+        resolve_warm,
+        walk,
+        read,
+    })
+}
+
+fn read_blob(store: &Store, key: &RepoKey, oid: Oid) -> Result<u64, BenchError> {
+    let (path, _size) = store.hydrate_blob(key, oid)?;
+    Ok(std::fs::read(path)?.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_fixtures::{commit_tree, init_bare};
+    use tempfile::tempdir;
+
+    #[test]
+    fn bench_small_repo_reports_nonzero_timings() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+
+        let repo = init_bare(&paths.mirror_dir(&key));
+        let readme = repo.blob(b"hello\n").unwrap();
+        let lib = repo.blob(b"fn main() {}\n").unwrap();
+        let mut src = repo.treebuilder(None).unwrap();
+        src.insert("lib.rs", lib, 0o100644).unwrap();
+        let src = src.write().unwrap();
+        let mut root = repo.treebuilder(None).unwrap();
+        root.insert("README", readme, 0o100644).unwrap();
+        root.insert("src", src, 0o040000).unwrap();
+        let tree = repo.find_tree(root.write().unwrap()).unwrap();
+        let commit = commit_tree(&repo, "refs/heads/main", "init", &tree);
+
+        let report = bench(&paths, &key).unwrap();
+        assert_eq!(report.commit, commit.to_string());
+        assert_eq!(report.walk.ops, 3);
+        assert_eq!(report.read.ops, 2);
+        assert_eq!(report.read.bytes, 19);
+        for phase in [
+            &report.resolve_cold,
+            &report.resolve_warm,
+            &report.walk,
+            &report.read,
+        ] {
+            assert!(phase.elapsed_ns > 0);
+        }
+        assert!(report.render().contains("2 files, 19 bytes"));
+    }
+}
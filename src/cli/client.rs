@@ -1,10 +1,13 @@
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::os::unix::net::UnixStream;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use crate::protocol::{
-    GcResult, ListResult, Request, Response, RpcError, RpcRequest, StatusResult, SyncResult,
-    VersionResult, read_response, write_message,
+    CloneMissingResult, FsStatsResult, GcResult, ListResult, OpStatsResult, PruneResult,
+    RemoteStatusResult, Request, Response, RpcError, RpcRequest, ScheduleResult, StatusResult,
+    SyncEvent, SyncResult, SyncStatusResult, VersionResult, WatchResult, WatchesResult,
+    read_response, write_message,
 };
 
 /// Get the socket path
@@ -120,16 +123,69 @@ impl Client {
         }
     }
 
-    /// Convenience: sync a repo
-    pub fn sync(&mut self, repo: &str) -> Result<SyncResult, ClientError> {
+    /// Convenience: sync a repo, or advance it to `commit` until the next sync.
+    /// With `if_older_than`, a mirror fetched more recently is left alone
+    pub fn sync(
+        &mut self,
+        repo: &str,
+        commit: Option<&str>,
+        if_older_than: Option<Duration>,
+    ) -> Result<SyncResult, ClientError> {
         match self.call(Request::Sync {
             repo: repo.to_string(),
+            commit: commit.map(str::to_string),
+            if_older_than_secs: if_older_than.map(|age| age.as_secs()),
         })? {
             Response::Sync(s) => Ok(s),
             other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
         }
     }
 
+    /// Convenience: queue a sync without waiting, returning its id
+    pub fn submit_sync(
+        &mut self,
+        repo: &str,
+        commit: Option<&str>,
+        if_older_than: Option<Duration>,
+    ) -> Result<u64, ClientError> {
+        match self.call(Request::SubmitSync {
+            repo: repo.to_string(),
+            commit: commit.map(str::to_string),
+            if_older_than_secs: if_older_than.map(|age| age.as_secs()),
+        })? {
+            Response::SubmitSync(s) => Ok(s.sync_id),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: check on a sync queued by `submit_sync`
+    pub fn sync_status(&mut self, sync_id: u64) -> Result<SyncStatusResult, ClientError> {
+        match self.call(Request::SyncStatus { sync_id })? {
+            Response::SyncStatus(s) => Ok(s),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: delete a repo from the cache
+    pub fn remove(&mut self, repo: &str) -> Result<(), ClientError> {
+        match self.call(Request::Remove {
+            repo: repo.to_string(),
+        })? {
+            Response::Ok(()) => Ok(()),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: repack a repo's mirror and drop unreachable objects
+    pub fn prune(&mut self, repo: &str) -> Result<PruneResult, ClientError> {
+        match self.call(Request::Prune {
+            repo: repo.to_string(),
+        })? {
+            Response::Prune(p) => Ok(p),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
     /// Convenience: run cache metadata garbage collection
     pub fn gc(&mut self) -> Result<GcResult, ClientError> {
         match self.call(Request::Gc)? {
@@ -146,6 +202,84 @@ impl Client {
         }
     }
 
+    /// Convenience: compare synced commits with the remotes' heads
+    pub fn remote_status(&mut self) -> Result<RemoteStatusResult, ClientError> {
+        match self.call(Request::RemoteStatus)? {
+            Response::RemoteStatus(r) => Ok(r),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: scheduler refresh plan
+    pub fn schedule(&mut self) -> Result<ScheduleResult, ClientError> {
+        match self.call(Request::Schedule)? {
+            Response::Schedule(s) => Ok(s),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: filesystem operation counters
+    pub fn op_stats(&mut self) -> Result<OpStatsResult, ClientError> {
+        match self.call(Request::OpStats)? {
+            Response::OpStats(s) => Ok(s),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: filesystem table sizes
+    pub fn fs_stats(&mut self) -> Result<FsStatsResult, ClientError> {
+        match self.call(Request::FsStats)? {
+            Response::FsStats(s) => Ok(s),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: re-clone known repos whose mirror is missing
+    pub fn clone_missing(&mut self) -> Result<CloneMissingResult, ClientError> {
+        match self.call(Request::CloneMissing)? {
+            Response::CloneMissing(r) => Ok(r),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: watch every repo of an owner
+    pub fn watch(&mut self, owner: &str) -> Result<WatchResult, ClientError> {
+        match self.call(Request::Watch {
+            owner: owner.to_string(),
+        })? {
+            Response::Watch(r) => Ok(r),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: stop watching an owner
+    pub fn unwatch(&mut self, owner: &str) -> Result<WatchResult, ClientError> {
+        match self.call(Request::Unwatch {
+            owner: owner.to_string(),
+        })? {
+            Response::Watch(r) => Ok(r),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Convenience: watched owners
+    pub fn watches(&mut self) -> Result<WatchesResult, ClientError> {
+        match self.call(Request::Watches)? {
+            Response::Watches(r) => Ok(r),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
+    /// Subscribe to sync events, turning the connection into an event stream
+    pub fn subscribe(mut self) -> Result<EventStream, ClientError> {
+        match self.call(Request::Subscribe)? {
+            Response::Ok(()) => Ok(EventStream {
+                reader: self.reader,
+            }),
+            other => Err(ClientError::InvalidResponse(format!("{:?}", other))),
+        }
+    }
+
     /// Convenience: daemon version
     pub fn version(&mut self) -> Result<VersionResult, ClientError> {
         match self.call(Request::Version)? {
@@ -155,6 +289,28 @@ impl Client {
     }
 }
 
+/// Sync events pushed by the daemon after [`Client::subscribe`]
+pub struct EventStream {
+    reader: BufReader<UnixStream>,
+}
+
+impl Iterator for EventStream {
+    type Item = Result<SyncEvent, ClientError>;
+
+    /// Block until the next event; `None` once the daemon closes the stream.
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(
+                serde_json::from_str(&line)
+                    .map_err(|e| ClientError::InvalidResponse(e.to_string())),
+            ),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::ClientError;
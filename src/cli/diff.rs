@@ -0,0 +1,205 @@
+//! `ghfs diff`: what changed between two commits of a cached repo.
+//!
+//! Both sides are ref selectors (branch, tag, or commit SHA) resolved in the
+//! on-disk mirror, so it works without a running daemon. Changed paths come
+//! from comparing trees, which the blobless mirror always has; `--stat` and
+//! `--patch` shell out to `git diff`, which fetches any blobs it needs from
+//! the remote.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::cache::CachePaths;
+use crate::store::git::{self, GitCli, GitError};
+use crate::types::RepoKey;
+
+/// Errors returned by [`diff`] and [`git_diff`].
+#[derive(Debug, Error)]
+pub enum DiffError {
+    #[error("{key} is not cached (no mirror at {})", path.display())]
+    NotCached { key: RepoKey, path: PathBuf },
+
+    #[error("git diff failed: {0}")]
+    Command(String),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+}
+
+/// One changed path.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileChange {
+    /// `added`, `deleted`, `modified`, `renamed`, or `typechange`.
+    pub status: &'static str,
+    pub path: String,
+}
+
+/// Paths changed between two resolved commits.
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoDiff {
+    pub from: String,
+    pub to: String,
+    pub files: Vec<FileChange>,
+}
+
+/// Output form for `git diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    Stat,
+    Patch,
+}
+
+fn open_mirror(paths: &CachePaths, key: &RepoKey) -> Result<git2::Repository, DiffError> {
+    let mirror = paths.mirror_dir(key);
+    if !mirror.exists() {
+        return Err(DiffError::NotCached {
+            key: key.clone(),
+            path: mirror,
+        });
+    }
+    Ok(git::open_repository(&mirror)?)
+}
+
+fn resolve(repo: &git2::Repository, selector: &str) -> Result<git2::Oid, DiffError> {
+    git::validate_selector(selector)?;
+    Ok(git::resolve_revision(repo, selector)?)
+}
+
+/// List the paths that differ between `from` and `to`.
+pub fn diff(
+    paths: &CachePaths,
+    key: &RepoKey,
+    from: &str,
+    to: &str,
+) -> Result<RepoDiff, DiffError> {
+    let repo = open_mirror(paths, key)?;
+    let from = resolve(&repo, from)?;
+    let to = resolve(&repo, to)?;
+    let tree = |oid: git2::Oid| repo.find_commit(oid).and_then(|c| c.tree());
+    let changes = tree(from)
+        .and_then(|old| repo.diff_tree_to_tree(Some(&old), Some(&tree(to)?), None))
+        .map_err(GitError::from)?;
+
+    let files = changes
+        .deltas()
+        .map(|delta| {
+            let status = match delta.status() {
+                git2::Delta::Added => "added",
+                git2::Delta::Deleted => "deleted",
+                git2::Delta::Renamed => "renamed",
+                git2::Delta::Typechange => "typechange",
+                _ => "modified",
+            };
+            let file = match delta.status() {
+                git2::Delta::Deleted => delta.old_file(),
+                _ => delta.new_file(),
+            };
+            let path = file
+                .path()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            FileChange { status, path }
+        })
+        .collect();
+
+    Ok(RepoDiff {
+        from: from.to_string(),
+        to: to.to_string(),
+        files,
+    })
+}
+
+/// Run `git diff` between `from` and `to` in the mirror and return its
+/// output.
+pub fn git_diff(
+    paths: &CachePaths,
+    key: &RepoKey,
+    from: &str,
+    to: &str,
+    format: DiffFormat,
+) -> Result<String, DiffError> {
+    let repo = open_mirror(paths, key)?;
+    let from = resolve(&repo, from)?.to_string();
+    let to = resolve(&repo, to)?.to_string();
+
+    let mut cmd = GitCli::new().command();
+    cmd.arg("-C").arg(paths.mirror_dir(key)).arg("diff");
+    if format == DiffFormat::Stat {
+        cmd.arg("--stat");
+    }
+    let output = cmd.args([&from, &to]).output().map_err(GitError::from)?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        return Err(DiffError::Command(crate::store::redact_creds(&stderr)));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_fixtures::{commit, init_bare};
+    use tempfile::tempdir;
+
+    #[test]
+    fn diff_lists_changes_between_commits() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let repo = init_bare(&paths.mirror_dir(&key));
+        let first = commit(&repo, "c", &[("README", "hi\n"), ("old.txt", "x\n")]);
+        let second = commit(&repo, "c", &[("README", "hello\n"), ("new.txt", "y\n")]);
+
+        let result = diff(&paths, &key, &first.to_string(), "main").unwrap();
+        assert_eq!(result.from, first.to_string());
+        assert_eq!(result.to, second.to_string());
+        let mut files = result.files.clone();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(
+            files,
+            [
+                FileChange {
+                    status: "modified",
+                    path: "README".to_string()
+                },
+                FileChange {
+                    status: "added",
+                    path: "new.txt".to_string()
+                },
+                FileChange {
+                    status: "deleted",
+                    path: "old.txt".to_string()
+                },
+            ]
+        );
+
+        let stat = git_diff(&paths, &key, &first.to_string(), "main", DiffFormat::Stat).unwrap();
+        assert!(stat.contains("3 files changed"), "{stat}");
+        let patch = git_diff(&paths, &key, &first.to_string(), "main", DiffFormat::Patch).unwrap();
+        assert!(patch.contains("+hello"), "{patch}");
+    }
+
+    #[test]
+    fn diff_rejects_unknown_revisions_and_uncached_repos() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        assert!(matches!(
+            diff(&paths, &key, "main", "main"),
+            Err(DiffError::NotCached { .. })
+        ));
+
+        let repo = init_bare(&paths.mirror_dir(&key));
+        commit(&repo, "c", &[("README", "hi\n")]);
+        assert!(matches!(
+            diff(&paths, &key, "main", "nope"),
+            Err(DiffError::Git(GitError::RefNotFound(_)))
+        ));
+        assert!(matches!(
+            diff(&paths, &key, "--output=x", "main"),
+            Err(DiffError::Git(GitError::InvalidInput(_)))
+        ));
+    }
+}
@@ -0,0 +1,323 @@
+//! `ghfs doctor`: check that the system can run ghfs.
+//!
+//! Each check reports `ok`, `fail`, or `info`. Only failures (git or the
+//! mount backend missing, or a failed mount test) fail the command;
+//! `info` covers things that are fine to be absent, like a stopped daemon.
+//! `--json` prints the same checks for CI to gate on.
+
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use crate::daemon;
+use crate::service;
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Fail,
+    Info,
+}
+
+impl CheckStatus {
+    fn marker(self) -> &'static str {
+        match self {
+            Self::Ok => "OK",
+            Self::Fail => "FAIL",
+            Self::Info => "INFO",
+        }
+    }
+}
+
+/// One diagnostic check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    /// Stable identifier, e.g. `git` or `mount_point`.
+    pub name: &'static str,
+    /// Heading in the human-readable output.
+    #[serde(skip)]
+    pub label: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(
+        name: &'static str,
+        label: &'static str,
+        status: CheckStatus,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            name,
+            label,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Every check run, and whether none failed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DoctorReport {
+    pub passed: bool,
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    pub fn new(checks: Vec<Check>) -> Self {
+        Self {
+            passed: checks.iter().all(|check| check.status != CheckStatus::Fail),
+            checks,
+        }
+    }
+
+    /// Number of checks that failed.
+    pub fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .count()
+    }
+
+    /// Human-readable report, one `[STATUS] label: detail` line per check.
+    pub fn render(&self) -> String {
+        let mut out = String::from("GHFS System Check\n\n");
+        for check in &self.checks {
+            let _ = writeln!(
+                out,
+                "[{}] {}: {}",
+                check.status.marker(),
+                check.label,
+                check.detail
+            );
+        }
+        out
+    }
+}
+
+/// Run every check, including a test mount when `mount_test` is set.
+pub fn doctor(mount_test: bool) -> DoctorReport {
+    let mut checks = vec![
+        check_git(),
+        check_backend(),
+        check_cache_dir(),
+        check_daemon(),
+        check_service(),
+        check_mount_point(),
+    ];
+    if mount_test {
+        checks.push(match crate::cli::mount_test() {
+            Ok(path) => Check::new(
+                "mount_test",
+                "Mount test",
+                CheckStatus::Ok,
+                format!("mounted, stat'd, and unmounted {}", path.display()),
+            ),
+            Err(err) => Check::new(
+                "mount_test",
+                "Mount test",
+                CheckStatus::Fail,
+                err.to_string(),
+            ),
+        });
+    }
+    DoctorReport::new(checks)
+}
+
+fn check_git() -> Check {
+    let ok = std::process::Command::new("git")
+        .args(["--version"])
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false);
+    if ok {
+        Check::new("git", "git", CheckStatus::Ok, "available")
+    } else {
+        Check::new("git", "git", CheckStatus::Fail, "not found")
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check_backend() -> Check {
+    let dev_fuse_ok = std::path::Path::new("/dev/fuse").exists();
+    let fuse_helper_ok = command_in_path("fusermount3") || command_in_path("fusermount");
+    let (status, detail) = match (dev_fuse_ok, fuse_helper_ok) {
+        (true, true) => (CheckStatus::Ok, "available (/dev/fuse + fusermount helper)"),
+        (false, false) => (
+            CheckStatus::Fail,
+            "missing /dev/fuse and fusermount helper (install/enable FUSE)",
+        ),
+        (false, true) => (
+            CheckStatus::Fail,
+            "missing /dev/fuse (install/enable FUSE kernel support)",
+        ),
+        (true, false) => (
+            CheckStatus::Fail,
+            "missing fusermount helper (install FUSE userspace tools, usually fuse3)",
+        ),
+    };
+    Check::new("backend", "FUSE backend", status, detail)
+}
+
+#[cfg(target_os = "macos")]
+fn check_backend() -> Check {
+    let mount_nfs_ok = std::process::Command::new("/sbin/mount_nfs")
+        .arg("-h")
+        .output()
+        .is_ok();
+    if mount_nfs_ok {
+        Check::new(
+            "backend",
+            "NFS backend",
+            CheckStatus::Ok,
+            "mount_nfs available",
+        )
+    } else {
+        Check::new(
+            "backend",
+            "NFS backend",
+            CheckStatus::Fail,
+            "mount_nfs not found (install/enable macOS NFS client tools)",
+        )
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn check_backend() -> Check {
+    Check::new(
+        "backend",
+        "Backend",
+        CheckStatus::Fail,
+        "unsupported platform",
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn command_in_path(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join(name))
+                .any(|candidate| candidate.is_file())
+        })
+        .unwrap_or(false)
+}
+
+fn check_cache_dir() -> Check {
+    let cache_dir = dirs::cache_dir().map(|p| p.join("ghfs"));
+    let ok = cache_dir.as_ref().is_some_and(|p| p.exists());
+    Check::new(
+        "cache_dir",
+        "Cache dir",
+        if ok {
+            CheckStatus::Ok
+        } else {
+            CheckStatus::Info
+        },
+        cache_dir
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or("unknown".into()),
+    )
+}
+
+fn check_daemon() -> Check {
+    if daemon::is_daemon_running() {
+        Check::new("daemon", "Daemon", CheckStatus::Ok, "running")
+    } else {
+        Check::new("daemon", "Daemon", CheckStatus::Info, "not running")
+    }
+}
+
+fn check_service() -> Check {
+    let (status, detail) = match service::installation_status() {
+        Ok(install) if install.installed => (
+            CheckStatus::Ok,
+            format!("installed ({})", install.backend.installed_kind()),
+        ),
+        Ok(_) => (
+            CheckStatus::Info,
+            "not installed (run 'ghfs service install')".to_string(),
+        ),
+        Err(service::ServiceError::UnsupportedPlatform)
+        | Err(service::ServiceError::BackendUnavailable(_)) => (
+            CheckStatus::Info,
+            "unsupported on this platform".to_string(),
+        ),
+        Err(err) => (CheckStatus::Info, format!("check failed ({err})")),
+    };
+    Check::new("service", "Service", status, detail)
+}
+
+fn check_mount_point() -> Check {
+    let mount_point = daemon::mount_point();
+    Check::new(
+        "mount_point",
+        "Mount point",
+        if mount_point.exists() {
+            CheckStatus::Ok
+        } else {
+            CheckStatus::Info
+        },
+        mount_point.display().to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_check_appears_in_the_json() {
+        let report = doctor(false);
+        let json = serde_json::to_value(&report).unwrap();
+
+        let names: Vec<&str> = json["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|check| check["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "git",
+                "backend",
+                "cache_dir",
+                "daemon",
+                "service",
+                "mount_point"
+            ]
+        );
+        for check in json["checks"].as_array().unwrap() {
+            let fields: Vec<&String> = check.as_object().unwrap().keys().collect();
+            assert_eq!(fields, ["detail", "name", "status"]);
+            assert!(matches!(
+                check["status"].as_str().unwrap(),
+                "ok" | "fail" | "info"
+            ));
+        }
+        assert_eq!(json["passed"], report.passed);
+    }
+
+    #[test]
+    fn any_failure_fails_the_report() {
+        let ok = Check::new("git", "git", CheckStatus::Ok, "available");
+        let info = Check::new("daemon", "Daemon", CheckStatus::Info, "not running");
+        let fail = Check::new("backend", "FUSE backend", CheckStatus::Fail, "missing");
+
+        assert!(DoctorReport::new(vec![ok.clone(), info.clone()]).passed);
+        let report = DoctorReport::new(vec![ok, info, fail]);
+        assert!(!report.passed);
+        assert_eq!(
+            report.render(),
+            "GHFS System Check\n\n\
+             [OK] git: available\n\
+             [INFO] Daemon: not running\n\
+             [FAIL] FUSE backend: missing\n"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Error type shared by every `ghfs` subcommand.
+
+use thiserror::Error;
+
+use crate::cli::{BenchError, ClientError, DiffError, InfoError, WhichError};
+use crate::config::ConfigError;
+use crate::daemon::DaemonError;
+use crate::service::ServiceError;
+use crate::store::StoreError;
+use crate::types::ParseError;
+
+/// Errors returned by CLI commands. Each variant maps to an exit code via
+/// [`crate::cli::exit_code::for_cli_error`].
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("invalid repo format: {0}")]
+    InvalidRepo(#[from] ParseError),
+
+    #[error(transparent)]
+    Client(#[from] ClientError),
+
+    #[error(transparent)]
+    Service(#[from] ServiceError),
+
+    #[error(transparent)]
+    Daemon(#[from] DaemonError),
+
+    #[error(transparent)]
+    Which(#[from] WhichError),
+
+    #[error(transparent)]
+    Info(#[from] InfoError),
+
+    #[error(transparent)]
+    Bench(#[from] BenchError),
+
+    #[error(transparent)]
+    Diff(#[from] DiffError),
+
+    #[error(transparent)]
+    Store(#[from] StoreError),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error("{repo} has open files held by: {holders}")]
+    FilesOpen { repo: String, holders: String },
+
+    #[error("{failed} of {total} repos failed to sync")]
+    BatchFailed { failed: usize, total: usize },
+
+    #[error("sync {0} failed")]
+    SyncFailed(u64),
+
+    #[error("{0} is unchanged")]
+    Unchanged(String),
+
+    #[error("{0}")]
+    Usage(String),
+
+    #[error("the daemon is running; stop it first with: ghfs service stop")]
+    DaemonRunning,
+
+    #[error(
+        "{0} mismatches between the state database and the cache; fix with: ghfs fsck-cache --fix"
+    )]
+    CacheMismatch(usize),
+
+    #[error("{0} doctor checks failed")]
+    DoctorFailed(usize),
+
+    #[error("failed to encode output: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl CliError {
+    /// Whether the failure was caused by the daemon not running, directly
+    /// or through a service command.
+    pub fn is_not_running(&self) -> bool {
+        matches!(
+            self,
+            Self::Client(ClientError::NotRunning)
+                | Self::Service(ServiceError::Client(ClientError::NotRunning))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CachePaths;
+    use crate::types::RepoKey;
+    use tempfile::tempdir;
+
+    fn parse_repo(input: &str) -> Result<RepoKey, CliError> {
+        Ok(input.parse()?)
+    }
+
+    #[test]
+    fn bad_repo_is_invalid_repo() {
+        let err = parse_repo("no-slash").unwrap_err();
+        assert!(matches!(
+            err,
+            CliError::InvalidRepo(ParseError::MissingSeparator)
+        ));
+        assert_eq!(
+            err.to_string(),
+            "invalid repo format: missing separator '/' in repo key"
+        );
+    }
+
+    #[test]
+    fn uncached_repo_is_which_error() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let err: CliError = crate::cli::which(&CachePaths::new(dir.path()), &key)
+            .unwrap_err()
+            .into();
+        assert!(matches!(err, CliError::Which(WhichError::NotCached { .. })));
+    }
+
+    #[test]
+    fn not_running_is_detected_through_service_errors() {
+        assert!(CliError::from(ClientError::NotRunning).is_not_running());
+        assert!(CliError::from(ServiceError::Client(ClientError::NotRunning)).is_not_running());
+        assert!(!CliError::from(ServiceError::NotInstalled).is_not_running());
+        assert!(!CliError::DaemonRunning.is_not_running());
+    }
+}
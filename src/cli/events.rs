@@ -0,0 +1,80 @@
+//! `ghfs events`: follow sync events as the daemon publishes them.
+
+use crate::cli::{CliError, Client};
+use crate::protocol::SyncEvent;
+
+/// Abbreviate a commit OID for display.
+fn short(commit: &str) -> &str {
+    commit.get(..7).unwrap_or(commit)
+}
+
+/// One human-readable line per event.
+pub fn render_event(event: &SyncEvent) -> String {
+    match event {
+        SyncEvent::Synced {
+            repo,
+            commit,
+            previous: Some(previous),
+            ..
+        } => format!("{repo} synced {} (was {})", short(commit), short(previous)),
+        SyncEvent::Synced { repo, commit, .. } => format!("{repo} synced {}", short(commit)),
+        SyncEvent::Failed { repo, error, .. } => format!("{repo} failed: {error}"),
+        SyncEvent::Progress {
+            repo,
+            phase,
+            percent,
+        } => format!("{repo} {phase}: {percent}%"),
+    }
+}
+
+/// Print events until the daemon stops, as JSON lines if `json` is set.
+pub fn events(json: bool) -> Result<(), CliError> {
+    for event in Client::connect()?.subscribe()? {
+        let event = event?;
+        if json {
+            println!("{}", serde_json::to_string(&event)?);
+        } else {
+            println!("{}", render_event(&event));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_shortens_commits() {
+        let synced = SyncEvent::Synced {
+            repo: "octocat/hello-world".to_string(),
+            commit: "0123456789abcdef".to_string(),
+            previous: Some("fedcba9876543210".to_string()),
+            at: 0,
+        };
+        assert_eq!(
+            render_event(&synced),
+            "octocat/hello-world synced 0123456 (was fedcba9)"
+        );
+
+        let failed = SyncEvent::Failed {
+            repo: "octocat/hello-world".to_string(),
+            error: "fetch failed".to_string(),
+            at: 0,
+        };
+        assert_eq!(
+            render_event(&failed),
+            "octocat/hello-world failed: fetch failed"
+        );
+
+        let progress = SyncEvent::Progress {
+            repo: "octocat/hello-world".to_string(),
+            phase: "Receiving objects".to_string(),
+            percent: 45,
+        };
+        assert_eq!(
+            render_event(&progress),
+            "octocat/hello-world Receiving objects: 45%"
+        );
+    }
+}
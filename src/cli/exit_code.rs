@@ -0,0 +1,139 @@
+//! Process exit codes for CLI commands.
+//!
+//! Scripts can branch on these instead of parsing stderr:
+//!
+//! | Code | Meaning                                  |
+//! |------|------------------------------------------|
+//! | 0    | success                                  |
+//! | 1    | any other failure                        |
+//! | 2    | invalid arguments (e.g. bad `owner/repo`) |
+//! | 3    | repository not found                     |
+//! | 4    | network failure (clone/fetch)            |
+//! | 5    | daemon not running                       |
+//! | 6    | repository blocked (takedown, disabled)  |
+//! | 7    | HEAD unchanged (`sync --exit-unchanged`) |
+
+use crate::cli::{CliError, ClientError, DiffError, InfoError, WhichError};
+use crate::config::ConfigError;
+use crate::protocol::RpcError;
+use crate::service::ServiceError;
+use crate::store::GitError;
+
+/// Catch-all failure.
+pub const FAILURE: i32 = 1;
+/// Invalid command-line input.
+pub const USAGE: i32 = 2;
+/// The repository does not exist (or is not visible).
+pub const NOT_FOUND: i32 = 3;
+/// Cloning or fetching from GitHub failed.
+pub const NETWORK: i32 = 4;
+/// No daemon is listening on the socket.
+pub const NOT_RUNNING: i32 = 5;
+/// GitHub refuses to serve the repository; retrying won't help.
+pub const UNAVAILABLE: i32 = 6;
+/// `ghfs sync --exit-unchanged` found HEAD where it was.
+pub const UNCHANGED: i32 = 7;
+
+/// Exit code for an RPC error returned by the daemon.
+pub fn for_rpc_error(err: &RpcError) -> i32 {
+    match err.code {
+        RpcError::NOT_FOUND => NOT_FOUND,
+        RpcError::NETWORK => NETWORK,
+        RpcError::UNAVAILABLE => UNAVAILABLE,
+        RpcError::INVALID_PARAMS => USAGE,
+        _ => FAILURE,
+    }
+}
+
+/// Exit code for a client-side error.
+pub fn for_client_error(err: &ClientError) -> i32 {
+    match err {
+        ClientError::NotRunning => NOT_RUNNING,
+        ClientError::Rpc(e) => for_rpc_error(e),
+        ClientError::Io(_) | ClientError::InvalidResponse(_) => FAILURE,
+    }
+}
+
+/// Exit code for any command error.
+pub fn for_cli_error(err: &CliError) -> i32 {
+    match err {
+        CliError::InvalidRepo(_)
+        | CliError::Usage(_)
+        | CliError::Config(ConfigError::UnknownKey(_) | ConfigError::InvalidValue { .. }) => USAGE,
+        CliError::Client(e) | CliError::Service(ServiceError::Client(e)) => for_client_error(e),
+        CliError::Which(WhichError::NotCached { .. })
+        | CliError::Info(InfoError::Unknown(_))
+        | CliError::Info(InfoError::Which(WhichError::NotCached { .. }))
+        | CliError::Diff(DiffError::NotCached { .. })
+        | CliError::Diff(DiffError::Git(GitError::RefNotFound(_))) => NOT_FOUND,
+        CliError::Unchanged(_) => UNCHANGED,
+        _ => FAILURE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_client_errors() {
+        assert_eq!(for_client_error(&ClientError::NotRunning), NOT_RUNNING);
+        assert_eq!(
+            for_client_error(&ClientError::Io(std::io::Error::from(
+                std::io::ErrorKind::PermissionDenied
+            ))),
+            FAILURE
+        );
+        assert_eq!(
+            for_client_error(&ClientError::InvalidResponse("bad".to_string())),
+            FAILURE
+        );
+    }
+
+    #[test]
+    fn maps_rpc_error_codes() {
+        let rpc = |e: RpcError| for_client_error(&ClientError::Rpc(e));
+        assert_eq!(rpc(RpcError::not_found("missing")), NOT_FOUND);
+        assert_eq!(rpc(RpcError::network("fetch failed")), NETWORK);
+        assert_eq!(rpc(RpcError::unavailable("DMCA takedown")), UNAVAILABLE);
+        assert_eq!(rpc(RpcError::invalid_params("bad repo")), USAGE);
+        assert_eq!(rpc(RpcError::internal("boom")), FAILURE);
+    }
+
+    #[test]
+    fn maps_cli_errors() {
+        use crate::types::{ParseError, RepoKey};
+
+        let parse = CliError::from(ParseError::MissingSeparator);
+        assert_eq!(for_cli_error(&parse), USAGE);
+
+        assert_eq!(
+            for_cli_error(&CliError::from(ClientError::NotRunning)),
+            NOT_RUNNING
+        );
+        assert_eq!(
+            for_cli_error(&CliError::from(ServiceError::Client(
+                ClientError::NotRunning
+            ))),
+            NOT_RUNNING
+        );
+
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        assert_eq!(
+            for_cli_error(&CliError::from(InfoError::Unknown(key))),
+            NOT_FOUND
+        );
+
+        assert_eq!(
+            for_cli_error(&CliError::from(ConfigError::UnknownKey("typo".into()))),
+            USAGE
+        );
+
+        assert_eq!(for_cli_error(&CliError::DaemonRunning), FAILURE);
+        assert_eq!(for_cli_error(&CliError::DoctorFailed(2)), FAILURE);
+        assert_eq!(
+            for_cli_error(&CliError::Unchanged("octocat/hello-world".into())),
+            UNCHANGED
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! `ghfs fsstats`: sizes of the mounted filesystem's in-memory tables.
+
+use crate::cli::{CliError, Client};
+use crate::protocol::FsStatsResult;
+
+/// Human-readable table of `stats`.
+pub fn render_fs_stats(stats: &FsStatsResult) -> String {
+    let rows = [
+        ("passthrough inodes", stats.passthrough_inodes),
+        ("virtual inodes", stats.virtual_inodes),
+        ("open files", stats.open_files),
+        ("cached trees", stats.cached_trees),
+        ("cached blobs", stats.cached_blobs),
+    ];
+    rows.iter()
+        .map(|(label, value)| format!("{label:<20}{value:>10}\n"))
+        .collect()
+}
+
+/// Print the daemon's current table sizes, as JSON if `json` is set.
+pub fn fs_stats(json: bool) -> Result<(), CliError> {
+    let stats = Client::connect()?.fs_stats()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        print!("{}", render_fs_stats(&stats));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_lists_every_table() {
+        let out = render_fs_stats(&FsStatsResult {
+            passthrough_inodes: 1234,
+            open_files: 2,
+            ..Default::default()
+        });
+        assert_eq!(out.lines().count(), 5);
+        assert!(out.contains("passthrough inodes        1234"), "{out}");
+        assert!(out.contains("open files                   2"), "{out}");
+    }
+}
@@ -0,0 +1,141 @@
+//! `ghfs history`: the generations the state database retains for a repo.
+//!
+//! Like `info`, it reads the state database directly and works without a
+//! running daemon.
+
+use std::fmt::Write as _;
+
+use serde::Serialize;
+
+use crate::cache::CachePaths;
+use crate::cli::InfoError;
+use crate::daemon::format_timestamp;
+use crate::daemon::state::{GenerationState, State};
+use crate::types::RepoKey;
+
+/// One retained generation, serializable for `--json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GenerationEntry {
+    pub generation: u64,
+    pub commit: String,
+    /// Unix timestamp the generation was recorded at.
+    pub created_at: i64,
+    pub size_bytes: u64,
+    pub current: bool,
+}
+
+/// A repo's retained generations, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoHistory {
+    pub repo: String,
+    pub generations: Vec<GenerationEntry>,
+}
+
+impl RepoHistory {
+    /// Build the listing, marking `current` as the generation in use.
+    pub fn new(key: &RepoKey, generations: &[GenerationState], current: Option<u64>) -> Self {
+        Self {
+            repo: key.to_string(),
+            generations: generations
+                .iter()
+                .map(|g| GenerationEntry {
+                    generation: g.generation,
+                    commit: g.commit.clone(),
+                    created_at: g.created_at,
+                    size_bytes: g.size_bytes,
+                    current: current == Some(g.generation),
+                })
+                .collect(),
+        }
+    }
+
+    /// Human-readable table, `*` marking the current generation.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", self.repo);
+        if self.generations.is_empty() {
+            let _ = writeln!(out, "  (no generations recorded)");
+            return out;
+        }
+        let _ = writeln!(
+            out,
+            "  {:>6}  {:<12}  {:<16}  {:>12}",
+            "GEN", "COMMIT", "CREATED", "SIZE"
+        );
+        for entry in &self.generations {
+            let commit = entry.commit.get(..12).unwrap_or(&entry.commit);
+            let _ = writeln!(
+                out,
+                "{} {:>6}  {:<12}  {:<16}  {:>12}",
+                if entry.current { '*' } else { ' ' },
+                entry.generation,
+                commit,
+                format_timestamp(entry.created_at),
+                format!("{} bytes", entry.size_bytes)
+            );
+        }
+        out
+    }
+}
+
+/// The generations recorded for `key`.
+pub fn history(paths: &CachePaths, key: &RepoKey) -> Result<RepoHistory, InfoError> {
+    // Don't create a database just to report on it.
+    let db_path = paths.root().join("ghfs.db");
+    if !db_path.exists() {
+        return Err(InfoError::Unknown(key.clone()));
+    }
+    let state = State::open(&db_path)?;
+    let stats = state
+        .repo_stats(key)?
+        .ok_or_else(|| InfoError::Unknown(key.clone()))?;
+    Ok(RepoHistory::new(
+        key,
+        &state.list_generations(key)?,
+        stats.current_generation,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_marks_the_current_generation() {
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let generation = |generation: u64, commit: &str| GenerationState {
+            generation,
+            commit: commit.to_string(),
+            size_bytes: generation * 100,
+            created_at: 1_000 + generation as i64,
+        };
+        let history = RepoHistory::new(
+            &key,
+            &[
+                generation(1, "0123456789abcdef0123"),
+                generation(2, "fedcba9876543210fedc"),
+            ],
+            Some(2),
+        );
+
+        let current: Vec<bool> = history.generations.iter().map(|g| g.current).collect();
+        assert_eq!(current, [false, true]);
+
+        let text = history.render();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "octocat/hello-world");
+        assert!(lines[2].starts_with("       1  0123456789ab"), "{text}");
+        assert!(lines[2].ends_with("100 bytes"), "{text}");
+        assert!(lines[3].starts_with("*      2  fedcba987654"), "{text}");
+
+        let json = serde_json::to_value(&history).unwrap();
+        assert_eq!(json["generations"][1]["current"], true);
+        assert_eq!(json["generations"][0]["created_at"], 1_001);
+
+        assert!(
+            RepoHistory::new(&key, &[], None)
+                .render()
+                .contains("no generations recorded")
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! `ghfs info`: everything known about one repo in a single view.
+//!
+//! Combines the on-disk mirror (via [`which`]) with the state database's
+//! [`RepoStats`]. Like `which`, it reads local state directly and works
+//! without a running daemon.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::cache::CachePaths;
+use crate::cli::which::{WhichError, WhichInfo, which};
+use crate::daemon::state::{RepoStats, State};
+use crate::daemon::{MAX_AGE_SECS, format_timestamp};
+use crate::store::git;
+use crate::types::RepoKey;
+
+/// Errors returned by [`info`].
+#[derive(Debug, Error)]
+pub enum InfoError {
+    #[error("{0} is not cached and has no state")]
+    Unknown(RepoKey),
+
+    #[error("refresh interval {}s is longer than the {}s limit", .0.as_secs(), MAX_REFRESH_INTERVAL.as_secs())]
+    IntervalTooLong(Duration),
+
+    #[error(transparent)]
+    Which(#[from] WhichError),
+
+    #[error("state database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+/// Size breakdown in bytes, as last recorded in the state database.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SizeInfo {
+    pub mirror_bytes: u64,
+    pub generations_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Consolidated view of a repo, serializable for `--json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepoDetails {
+    pub repo: String,
+    pub mirror: PathBuf,
+    pub cached: bool,
+    /// `true` for a blobless partial clone, `false` for a full clone, `None`
+    /// when there's no mirror to inspect.
+    pub blobless: Option<bool>,
+    pub head_ref: Option<String>,
+    pub commit: Option<String>,
+    pub current_generation: Option<u64>,
+    pub generation_count: u64,
+    pub commit_count: u64,
+    pub size: SizeInfo,
+    pub refresh_interval_secs: u64,
+    /// Unix timestamp of the next scheduled refresh; `None` if never synced.
+    pub next_refresh_at: Option<i64>,
+    pub last_sync_at: Option<i64>,
+    pub last_access_at: Option<i64>,
+}
+
+impl RepoDetails {
+    /// Assemble the view from whatever parts are available.
+    pub fn new(
+        key: &RepoKey,
+        mirror: PathBuf,
+        mirror_info: Option<&WhichInfo>,
+        blobless: Option<bool>,
+        stats: Option<&RepoStats>,
+    ) -> Self {
+        let last_sync_at = stats.and_then(|s| s.last_sync_at);
+        let refresh_interval = stats.and_then(|s| s.max_age_secs).unwrap_or(MAX_AGE_SECS);
+        let size = stats
+            .map(|s| SizeInfo {
+                mirror_bytes: s.mirror_size_bytes,
+                generations_bytes: s.total_size_bytes.saturating_sub(s.mirror_size_bytes),
+                total_bytes: s.total_size_bytes,
+            })
+            .unwrap_or_default();
+
+        Self {
+            repo: key.to_string(),
+            mirror,
+            cached: mirror_info.is_some(),
+            blobless,
+            head_ref: mirror_info.and_then(|m| m.head_ref.clone()),
+            // Prefer the mirror's live HEAD over the last recorded sync.
+            commit: mirror_info
+                .and_then(|m| m.commit.clone())
+                .or_else(|| stats.and_then(|s| s.head_commit.clone())),
+            current_generation: stats.and_then(|s| s.current_generation),
+            generation_count: stats.map_or(0, |s| s.generation_count),
+            commit_count: stats.map_or(0, |s| s.commit_count),
+            size,
+            refresh_interval_secs: refresh_interval as u64,
+            next_refresh_at: last_sync_at.map(|ts| ts + refresh_interval),
+            last_sync_at,
+            last_access_at: stats.and_then(|s| s.last_access_at),
+        }
+    }
+
+    /// Human-readable multi-line rendering.
+    pub fn render(&self) -> String {
+        let or_dash = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+        let mut out = String::new();
+        let _ = writeln!(out, "{}", self.repo);
+        let _ = writeln!(out, "  Mirror:       {}", self.mirror.display());
+        let _ = writeln!(
+            out,
+            "  Clone:        {}",
+            match self.blobless {
+                Some(true) => "blobless",
+                Some(false) => "full",
+                None => "not cached",
+            }
+        );
+        let _ = writeln!(out, "  HEAD:         {}", or_dash(self.head_ref.clone()));
+        let _ = writeln!(out, "  Commit:       {}", or_dash(self.commit.clone()));
+        let _ = writeln!(
+            out,
+            "  Generation:   {} ({} total, {} commits)",
+            or_dash(self.current_generation.map(|g| g.to_string())),
+            self.generation_count,
+            self.commit_count
+        );
+        let _ = writeln!(
+            out,
+            "  Size:         {} bytes (mirror {}, generations {})",
+            self.size.total_bytes, self.size.mirror_bytes, self.size.generations_bytes
+        );
+        let _ = writeln!(
+            out,
+            "  Refresh:      every {}s, next {}",
+            self.refresh_interval_secs,
+            self.next_refresh_at
+                .map(format_timestamp)
+                .unwrap_or_else(|| "now".to_string())
+        );
+        let _ = writeln!(
+            out,
+            "  Last sync:    {}",
+            or_dash(self.last_sync_at.map(format_timestamp))
+        );
+        let _ = writeln!(
+            out,
+            "  Last access:  {}",
+            or_dash(self.last_access_at.map(format_timestamp))
+        );
+        out
+    }
+}
+
+/// Gather details for `key` from the mirror and the state database.
+pub fn info(paths: &CachePaths, key: &RepoKey) -> Result<RepoDetails, InfoError> {
+    let mirror_info = match which(paths, key) {
+        Ok(info) => Some(info),
+        Err(WhichError::NotCached { .. }) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let blobless = mirror_info.as_ref().map(|m| is_blobless(&m.mirror));
+
+    // Don't create a database just to report on it.
+    let db_path = paths.root().join("ghfs.db");
+    let stats = if db_path.exists() {
+        State::open(&db_path)?.repo_stats(key)?
+    } else {
+        None
+    };
+
+    if mirror_info.is_none() && stats.is_none() {
+        return Err(InfoError::Unknown(key.clone()));
+    }
+
+    Ok(RepoDetails::new(
+        key,
+        paths.mirror_dir(key),
+        mirror_info.as_ref(),
+        blobless,
+        stats.as_ref(),
+    ))
+}
+
+/// Whether the mirror is a promisor (partial) clone.
+fn is_blobless(mirror: &std::path::Path) -> bool {
+    git::open_repository(mirror)
+        .ok()
+        .and_then(|repo| repo.config().ok())
+        .and_then(|config| config.get_bool("remote.origin.promisor").ok())
+        .unwrap_or(false)
+}
+
+/// Longest refresh interval [`set_max_age`] accepts (ten years).
+pub const MAX_REFRESH_INTERVAL: Duration = Duration::from_secs(10 * 365 * 24 * 60 * 60);
+
+/// Refresh `key` every `max_age` instead of the scheduler's default, or go
+/// back to the default with `None`. A running daemon picks it up at its next
+/// check. `key` must already be known to the state database.
+pub fn set_max_age(
+    paths: &CachePaths,
+    key: &RepoKey,
+    max_age: Option<Duration>,
+) -> Result<(), InfoError> {
+    if let Some(max_age) = max_age
+        && max_age > MAX_REFRESH_INTERVAL
+    {
+        return Err(InfoError::IntervalTooLong(max_age));
+    }
+    let db_path = paths.root().join("ghfs.db");
+    if !db_path.exists() {
+        return Err(InfoError::Unknown(key.clone()));
+    }
+    let state = State::open(&db_path)?;
+    state.init()?;
+    let max_age_secs = max_age.map(|d| d.as_secs() as i64);
+    if !state.set_max_age(&key.canonical(), max_age_secs)? {
+        return Err(InfoError::Unknown(key.clone()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> RepoStats {
+        RepoStats {
+            owner: "octocat".to_string(),
+            repo: "hello-world".to_string(),
+            current_generation: Some(3),
+            head_commit: Some("abc123".to_string()),
+            last_access_at: Some(2_000),
+            last_sync_at: Some(1_000),
+            max_age_secs: None,
+            generation_count: 2,
+            commit_count: 2,
+            mirror_size_bytes: 400,
+            total_size_bytes: 1_000,
+        }
+    }
+
+    #[test]
+    fn details_combine_mirror_and_stats() {
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mirror = PathBuf::from("/cache/mirrors/octocat/hello-world.git");
+        let which = WhichInfo {
+            mirror: mirror.clone(),
+            head_ref: Some("refs/heads/main".to_string()),
+            commit: Some("def456".to_string()),
+        };
+
+        let details = RepoDetails::new(&key, mirror, Some(&which), Some(true), Some(&stats()));
+
+        assert_eq!(details.repo, "octocat/hello-world");
+        assert!(details.cached);
+        assert_eq!(details.blobless, Some(true));
+        assert_eq!(details.head_ref.as_deref(), Some("refs/heads/main"));
+        assert_eq!(details.commit.as_deref(), Some("def456"));
+        assert_eq!(details.current_generation, Some(3));
+        assert_eq!(
+            details.size,
+            SizeInfo {
+                mirror_bytes: 400,
+                generations_bytes: 600,
+                total_bytes: 1_000,
+            }
+        );
+        assert_eq!(details.next_refresh_at, Some(1_000 + MAX_AGE_SECS));
+        assert_eq!(details.last_access_at, Some(2_000));
+
+        let text = details.render();
+        assert!(text.contains("Clone:        blobless"));
+        assert!(text.contains("Commit:       def456"));
+        assert!(text.contains("Generation:   3 (2 total, 2 commits)"));
+
+        let json = serde_json::to_value(&details).unwrap();
+        assert_eq!(json["size"]["generations_bytes"], 600);
+        assert_eq!(json["last_sync_at"], 1_000);
+    }
+
+    #[test]
+    fn details_use_the_repos_refresh_interval() {
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let stats = RepoStats {
+            max_age_secs: Some(600),
+            ..stats()
+        };
+        let details = RepoDetails::new(&key, PathBuf::new(), None, None, Some(&stats));
+        assert_eq!(details.refresh_interval_secs, 600);
+        assert_eq!(details.next_refresh_at, Some(1_600));
+    }
+
+    #[test]
+    fn details_fall_back_to_recorded_commit_without_mirror() {
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let details = RepoDetails::new(&key, PathBuf::new(), None, None, Some(&stats()));
+        assert!(!details.cached);
+        assert_eq!(details.commit.as_deref(), Some("abc123"));
+        assert!(details.render().contains("Clone:        not cached"));
+    }
+
+    #[test]
+    fn info_errors_for_unknown_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/missing".parse().unwrap();
+        assert!(matches!(info(&paths, &key), Err(InfoError::Unknown(_))));
+    }
+
+    #[test]
+    fn set_max_age_shows_up_in_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        assert!(matches!(
+            set_max_age(&paths, &key, Some(Duration::from_secs(900))),
+            Err(InfoError::Unknown(_))
+        ));
+
+        let state = State::open(&paths.root().join("ghfs.db")).unwrap();
+        state.init().unwrap();
+        state.get_or_create_repo_id(&key).unwrap();
+        assert!(matches!(
+            set_max_age(&paths, &key, Some(Duration::MAX)),
+            Err(InfoError::IntervalTooLong(_))
+        ));
+        set_max_age(&paths, &key, Some(Duration::from_secs(900))).unwrap();
+        assert_eq!(info(&paths, &key).unwrap().refresh_interval_secs, 900);
+        set_max_age(&paths, &key, None).unwrap();
+        assert_eq!(
+            info(&paths, &key).unwrap().refresh_interval_secs,
+            MAX_AGE_SECS as u64
+        );
+    }
+}
@@ -1,5 +1,46 @@
+mod bench;
 mod client;
+mod diff;
+mod doctor;
+mod duration;
+mod error;
+mod events;
+pub mod exit_code;
+mod fsstats;
+mod history;
+mod info;
+mod mount_test;
+mod paths;
+mod progress;
+mod remove;
+mod shell_init;
 mod status;
+mod sync_status;
+mod sync_stdin;
+mod top;
+mod upgrade;
+mod watch;
+mod which;
 
-pub use client::{Client, ClientError, socket_path};
+pub use bench::{BenchError, BenchReport, PhaseStats, bench};
+pub use client::{Client, ClientError, EventStream, socket_path};
+pub use diff::{DiffError, DiffFormat, FileChange, RepoDiff, diff, git_diff};
+pub use doctor::{Check, CheckStatus, DoctorReport, doctor};
+pub use duration::parse_duration;
+pub use error::CliError;
+pub use events::{events, render_event};
+pub use fsstats::{fs_stats, render_fs_stats};
+pub use history::{GenerationEntry, RepoHistory, history};
+pub use info::{InfoError, RepoDetails, SizeInfo, info, set_max_age};
+pub use mount_test::{MountTestError, mount_test, mount_test_with};
+pub use paths::ResolvedPaths;
+pub use progress::{SyncProgress, render_progress_bar};
+pub use remove::{ensure_not_open, remove};
+pub use shell_init::{Shell, shell_init};
 pub use status::print_status;
+pub use sync_status::{describe_sync_status, submit_sync, sync_status};
+pub use sync_stdin::{BatchOutcome, sync_lines, sync_stdin};
+pub use top::{OpRates, render_top, top};
+pub use upgrade::{UpgradeAction, upgrade, upgrade_action};
+pub use watch::{describe_unwatch, describe_watch, unwatch, watch};
+pub use which::{WhichError, WhichInfo, which};
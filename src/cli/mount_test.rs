@@ -0,0 +1,238 @@
+//! `ghfs doctor --mount-test`: prove the backend can really mount.
+//!
+//! Mounts an empty filesystem (backed by a throwaway cache) on a temporary
+//! directory, stats its root, and unmounts. Failures from permissions,
+//! AppArmor, or a missing kernel module surface here instead of when the
+//! daemon starts.
+
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::cache::CachePaths;
+use crate::daemon::{Quotas, WorkerHandle};
+use crate::fs::GhFs;
+use crate::store::Store;
+
+/// How long to wait for the mount to appear.
+const MOUNT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The step of the mount test that failed.
+#[derive(Debug, Error)]
+pub enum MountTestError {
+    #[error("failed to create temporary directories: {0}")]
+    Setup(std::io::Error),
+
+    #[error("mount failed: {0}")]
+    Mount(std::io::Error),
+
+    #[error("stat of mounted root failed: {0}")]
+    Stat(std::io::Error),
+
+    #[error("unmount failed: {0}")]
+    Unmount(std::io::Error),
+
+    #[error("failed to remove {}: {source}", path.display())]
+    Cleanup {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Run the mount test under the system temp directory. Returns the mount
+/// point that was used.
+pub fn mount_test() -> Result<PathBuf, MountTestError> {
+    let root = std::env::temp_dir().join(format!("ghfs-mount-test-{}", std::process::id()));
+    mount_test_with(&root, mount_empty_fs, |mount| mount.unmount())?;
+    Ok(root.join("mnt"))
+}
+
+/// Orchestrate a mount test rooted at `root`: create `root/mnt` and
+/// `root/cache`, `mount`, stat the mounted root, `unmount`, and remove the
+/// directories. Unmount and cleanup run whenever the earlier steps got far
+/// enough to need them; the first error is returned.
+pub fn mount_test_with<H>(
+    root: &Path,
+    mount: impl FnOnce(&Path, &Path) -> std::io::Result<H>,
+    unmount: impl FnOnce(H) -> std::io::Result<()>,
+) -> Result<(), MountTestError> {
+    let mountpoint = root.join("mnt");
+    let cache = root.join("cache");
+    let result = std::fs::create_dir_all(&mountpoint)
+        .and_then(|()| std::fs::create_dir_all(&cache))
+        .map_err(MountTestError::Setup)
+        .and_then(|()| mount(&mountpoint, &cache).map_err(MountTestError::Mount))
+        .and_then(|handle| {
+            let stat = stat_root(&mountpoint).map_err(MountTestError::Stat);
+            let unmounted = unmount(handle).map_err(MountTestError::Unmount);
+            stat.and(unmounted)
+        });
+    let cleaned = cleanup(root, &mountpoint, &cache);
+    result.and(cleaned)
+}
+
+fn stat_root(mountpoint: &Path) -> std::io::Result<()> {
+    let meta = std::fs::metadata(mountpoint)?;
+    if !meta.is_dir() {
+        return Err(std::io::Error::other("mounted root is not a directory"));
+    }
+    Ok(())
+}
+
+/// Remove the test directories. The mount point is removed non-recursively
+/// so a mount that failed to detach is reported rather than walked.
+fn cleanup(root: &Path, mountpoint: &Path, cache: &Path) -> Result<(), MountTestError> {
+    let remove = |path: &Path, result: std::io::Result<()>| match result {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(source) => Err(MountTestError::Cleanup {
+            path: path.to_path_buf(),
+            source,
+        }),
+    };
+    remove(mountpoint, std::fs::remove_dir(mountpoint))?;
+    remove(cache, std::fs::remove_dir_all(cache))?;
+    remove(root, std::fs::remove_dir(root))
+}
+
+/// A live test mount served on a background thread.
+struct TestMount {
+    shutdown: Arc<AtomicBool>,
+    thread: JoinHandle<std::io::Result<()>>,
+}
+
+impl TestMount {
+    fn unmount(self) -> std::io::Result<()> {
+        self.shutdown.store(true, Ordering::SeqCst);
+        self.thread
+            .join()
+            .map_err(|_| std::io::Error::other("mount thread panicked"))?
+    }
+}
+
+/// Mount an empty [`GhFs`] over `cache` at `mountpoint` and wait until the
+/// kernel reports it mounted.
+fn mount_empty_fs(mountpoint: &Path, cache: &Path) -> std::io::Result<TestMount> {
+    let parent_dev = std::fs::metadata(mountpoint.parent().unwrap_or(mountpoint))?.dev();
+    let store = Store::new(CachePaths::new(cache));
+    let worker = Arc::new(WorkerHandle::spawn(store.clone(), None, Quotas::default()));
+    let fs = GhFs::new(store, worker);
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let thread = {
+        let shutdown = Arc::clone(&shutdown);
+        let mountpoint = mountpoint.to_path_buf();
+        std::thread::spawn(move || fs.mount(&mountpoint, shutdown))
+    };
+    let mount = TestMount { shutdown, thread };
+
+    let deadline = Instant::now() + MOUNT_TIMEOUT;
+    loop {
+        if mount.thread.is_finished() {
+            // The backend returned before mounting: surface its error.
+            mount.unmount()?;
+            return Err(std::io::Error::other("backend exited without mounting"));
+        }
+        if std::fs::metadata(mountpoint).is_ok_and(|m| m.dev() != parent_dev) {
+            return Ok(mount);
+        }
+        if Instant::now() >= deadline {
+            let _ = mount.unmount();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "timed out waiting for the mount to appear",
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use tempfile::tempdir;
+
+    #[test]
+    fn successful_mount_is_unmounted_and_cleaned_up() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("probe");
+        let unmounted = Cell::new(false);
+
+        mount_test_with(
+            &root,
+            |_, _| Ok(()),
+            |()| {
+                unmounted.set(true);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert!(unmounted.get());
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn failed_mount_still_cleans_up() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("probe");
+        let unmounted = Cell::new(false);
+
+        let err = mount_test_with(
+            &root,
+            |_, _| Err::<(), _>(std::io::Error::from(std::io::ErrorKind::PermissionDenied)),
+            |()| {
+                unmounted.set(true);
+                Ok(())
+            },
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, MountTestError::Mount(_)));
+        assert!(!unmounted.get());
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn failed_stat_still_unmounts() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("probe");
+        let unmounted = Cell::new(false);
+
+        let err = mount_test_with(
+            &root,
+            |mnt, _| std::fs::remove_dir(mnt),
+            |()| {
+                unmounted.set(true);
+                Ok(())
+            },
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, MountTestError::Stat(_)));
+        assert!(unmounted.get());
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn failed_unmount_is_reported() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("probe");
+
+        let err = mount_test_with(
+            &root,
+            |_, _| Ok(()),
+            |()| Err(std::io::Error::from(std::io::ErrorKind::ResourceBusy)),
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, MountTestError::Unmount(_)));
+        assert!(!root.exists());
+    }
+}
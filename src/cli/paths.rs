@@ -0,0 +1,53 @@
+//! `ghfs cache-path` / `ghfs mount-path`: where ghfs keeps things.
+//!
+//! Resolves the same defaults and environment overrides the daemon uses, so
+//! scripts don't need to know them.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::cache::CachePaths;
+use crate::cli::socket_path;
+use crate::daemon::mount_point;
+
+/// Resolved locations, serializable for `--json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResolvedPaths {
+    pub cache_root: PathBuf,
+    pub mount_point: PathBuf,
+    pub socket: PathBuf,
+}
+
+impl ResolvedPaths {
+    /// Resolve every path from the current environment.
+    pub fn resolve() -> Self {
+        Self {
+            cache_root: CachePaths::default().root().to_path_buf(),
+            mount_point: mount_point(),
+            socket: socket_path(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_honors_env_overrides() {
+        // SAFETY: no other test reads or writes GHFS_MOUNT_POINT.
+        unsafe { std::env::set_var("GHFS_MOUNT_POINT", "/tmp/ghfs-paths-test") };
+        let paths = ResolvedPaths::resolve();
+        unsafe { std::env::remove_var("GHFS_MOUNT_POINT") };
+
+        assert_eq!(paths.mount_point, PathBuf::from("/tmp/ghfs-paths-test"));
+        assert_eq!(paths.cache_root, CachePaths::default().root());
+        assert_eq!(paths.socket, socket_path());
+
+        let json = serde_json::to_value(&paths).unwrap();
+        assert_eq!(json["mount_point"], "/tmp/ghfs-paths-test");
+        assert!(json["cache_root"].is_string());
+        assert!(json["socket"].is_string());
+    }
+}
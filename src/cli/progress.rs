@@ -0,0 +1,114 @@
+//! Progress bar for `ghfs sync`.
+//!
+//! The worker publishes git's clone/fetch progress as
+//! [`SyncEvent::Progress`] events. While a sync runs, a second connection
+//! subscribes to them and redraws a one-line bar on stderr for the repo
+//! being synced. It only draws when stderr is a terminal, so scripts and
+//! logs see the same output as before.
+
+use std::io::{IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::cli::Client;
+use crate::protocol::SyncEvent;
+use crate::types::RepoKey;
+
+/// Cells in the bar itself.
+const BAR_WIDTH: usize = 30;
+
+/// One line of progress, e.g. `Receiving objects [#####.....]  45%`.
+pub fn render_progress_bar(phase: &str, percent: u8) -> String {
+    let percent = percent.min(100);
+    let filled = BAR_WIDTH * usize::from(percent) / 100;
+    format!(
+        "{phase} [{}{}] {percent:>3}%",
+        "#".repeat(filled),
+        ".".repeat(BAR_WIDTH - filled)
+    )
+}
+
+#[derive(Default)]
+struct BarState {
+    done: bool,
+    drawn: bool,
+}
+
+/// Draws progress for one repo until dropped, which clears the bar.
+pub struct SyncProgress {
+    state: Arc<Mutex<BarState>>,
+}
+
+impl SyncProgress {
+    /// Start drawing progress for `repo`, or `None` if stderr isn't a
+    /// terminal or the daemon can't be subscribed to.
+    pub fn start(repo: &RepoKey) -> Option<Self> {
+        if !std::io::stderr().is_terminal() {
+            return None;
+        }
+        let events = Client::connect().ok()?.subscribe().ok()?;
+        let state = Arc::new(Mutex::new(BarState::default()));
+        let repo = repo.canonical();
+        let drawing = Arc::clone(&state);
+        // The thread blocks reading events; it's left to exit with the
+        // process once the sync returns.
+        std::thread::spawn(move || {
+            for event in events {
+                let Ok(SyncEvent::Progress {
+                    repo: name,
+                    phase,
+                    percent,
+                }) = event
+                else {
+                    continue;
+                };
+                if name.parse::<RepoKey>().map(|k| k.canonical()).ok() != Some(repo.clone()) {
+                    continue;
+                }
+                let mut state = drawing.lock().unwrap();
+                if state.done {
+                    return;
+                }
+                eprint!("\r\x1b[2K{}", render_progress_bar(&phase, percent));
+                let _ = std::io::stderr().flush();
+                state.drawn = true;
+            }
+        });
+        Some(Self { state })
+    }
+}
+
+impl Drop for SyncProgress {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.done = true;
+        if state.drawn {
+            eprint!("\r\x1b[2K");
+            let _ = std::io::stderr().flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bar_fills_with_percent() {
+        assert_eq!(
+            render_progress_bar("Receiving objects", 0),
+            format!("Receiving objects [{}]   0%", ".".repeat(30))
+        );
+        assert_eq!(
+            render_progress_bar("Resolving deltas", 50),
+            format!(
+                "Resolving deltas [{}{}]  50%",
+                "#".repeat(15),
+                ".".repeat(15)
+            )
+        );
+        assert_eq!(
+            render_progress_bar("Receiving objects", 255),
+            format!("Receiving objects [{}] 100%", "#".repeat(30))
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! `ghfs rm`: delete a repo's mirror and state via the daemon.
+
+use std::path::Path;
+
+use crate::cli::status::find_open_files;
+use crate::cli::{CliError, Client, ClientError};
+use crate::fs::Layout;
+use crate::protocol::RpcError;
+use crate::store::BY_REF_ROOT;
+use crate::types::RepoKey;
+
+/// Remove `key` from the cache. The daemon refuses while the mount has
+/// files open under the repo, checking its own open handles under the
+/// repo lock; the processes holding them are looked up here for the error.
+pub fn remove(key: &RepoKey) -> Result<(), CliError> {
+    let mut client = Client::connect()?;
+    match client.remove(&key.to_string()) {
+        Err(ClientError::Rpc(err)) if err.code == RpcError::BUSY => {
+            let status = client.status()?;
+            let holders = holders(Path::new(&status.mount_point), key);
+            Err(CliError::FilesOpen {
+                repo: key.to_string(),
+                holders: holders
+                    .unwrap_or_else(|| "processes not visible to this user".to_string()),
+            })
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Fail with [`CliError::FilesOpen`] if any process has files open under
+/// `key`'s paths on the mount at `mount`.
+pub fn ensure_not_open(mount: &Path, key: &RepoKey) -> Result<(), CliError> {
+    match holders(mount, key) {
+        Some(holders) => Err(CliError::FilesOpen {
+            repo: key.to_string(),
+            holders,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Processes with files open under `key`'s paths on the mount at `mount`,
+/// as `pid (comm)` pairs, or `None` if there are none.
+fn holders(mount: &Path, key: &RepoKey) -> Option<String> {
+    let repo_paths = [
+        Layout::from_env().repo_path(mount, key),
+        mount
+            .join(BY_REF_ROOT)
+            .join(key.owner.as_str())
+            .join(key.repo.as_str()),
+    ];
+
+    let busy: Vec<_> = repo_paths
+        .iter()
+        .flat_map(|path| find_open_files(&path.to_string_lossy()))
+        .collect();
+    if busy.is_empty() {
+        return None;
+    }
+    Some(
+        busy.iter()
+            .map(|p| format!("{} ({})", p.pid, p.comm))
+            .collect::<Vec<_>>()
+            .join(", "),
+    )
+}
@@ -0,0 +1,93 @@
+//! `ghfs shell-init`: print a `ghcd` shell function.
+//!
+//! `ghcd owner/repo` syncs the repo (cloning it if needed, skipping the
+//! fetch when the mirror is recent) and then changes into it on the mount.
+//! The mount point is resolved when the script is generated, so the
+//! function doesn't run `ghfs mount-path` on every call.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+
+/// Shells `ghfs shell-init` can generate for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Syncs older than this are skipped by `ghcd`, so hopping between repos
+/// doesn't fetch every time.
+const MAX_AGE: &str = "1h";
+
+/// The `ghcd` function for `shell`, changing into repos under `mount_point`.
+pub fn shell_init(shell: Shell, mount_point: &Path) -> String {
+    let mount = mount_point.to_string_lossy();
+    match shell {
+        Shell::Bash | Shell::Zsh => format!(
+            r#"# ghcd owner/repo: sync a repo and cd into it on the ghfs mount.
+ghcd() {{
+    if [ "$#" -ne 1 ]; then
+        echo "usage: ghcd owner/repo" >&2
+        return 2
+    fi
+    ghfs sync --quiet --if-older-than {MAX_AGE} "$1" && cd {}/"$1"
+}}
+"#,
+            sh_quote(&mount)
+        ),
+        Shell::Fish => format!(
+            r#"# ghcd owner/repo: sync a repo and cd into it on the ghfs mount.
+function ghcd --description 'sync a repo and cd into it on the ghfs mount'
+    if test (count $argv) -ne 1
+        echo "usage: ghcd owner/repo" >&2
+        return 2
+    end
+    ghfs sync --quiet --if-older-than {MAX_AGE} $argv[1]; and cd {}/$argv[1]
+end
+"#,
+            fish_quote(&mount)
+        ),
+    }
+}
+
+/// Single-quote `value` for POSIX shells.
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Single-quote `value` for fish, which escapes `\` and `'` inside quotes.
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_cds_into_repo_under_resolved_mount() {
+        let mount = Path::new("/home/me/github");
+        for shell in [Shell::Bash, Shell::Zsh] {
+            let script = shell_init(shell, mount);
+            assert!(script.contains("ghcd() {"), "{script}");
+            assert!(
+                script.contains(r#"ghfs sync --quiet --if-older-than 1h "$1""#),
+                "{script}"
+            );
+            assert!(script.contains(r#"cd '/home/me/github'/"$1""#), "{script}");
+        }
+
+        let fish = shell_init(Shell::Fish, mount);
+        assert!(fish.contains("function ghcd"), "{fish}");
+        assert!(fish.contains("cd '/home/me/github'/$argv[1]"), "{fish}");
+    }
+
+    #[test]
+    fn mount_path_is_quoted() {
+        let mount = Path::new("/mnt/it's here");
+        assert!(shell_init(Shell::Bash, mount).contains(r#"cd '/mnt/it'\''s here'/"$1""#));
+        assert!(shell_init(Shell::Fish, mount).contains(r"cd '/mnt/it\'s here'/$argv[1]"));
+    }
+}
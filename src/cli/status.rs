@@ -1,21 +1,23 @@
 //! Text-based status command implementation.
 
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 
-use crate::cli::{Client, ClientError};
+use crate::cli::{CliError, Client, ClientError};
+use crate::protocol::RemoteStatus;
 
 /// Information about a process with open files under a path.
 #[derive(Debug)]
-struct ProcessInfo {
-    pid: u32,
-    comm: String,
-    paths: Vec<String>,
+pub(crate) struct ProcessInfo {
+    pub(crate) pid: u32,
+    pub(crate) comm: String,
+    pub(crate) paths: Vec<String>,
 }
 
 /// Find all processes that have files open under the given path.
 /// This is a pure /proc-based implementation (no lsof).
-fn find_open_files(target_path: &str) -> Vec<ProcessInfo> {
+pub(crate) fn find_open_files(target_path: &str) -> Vec<ProcessInfo> {
     let mut results = Vec::new();
 
     let Ok(proc_dir) = fs::read_dir("/proc") else {
@@ -47,7 +49,8 @@ fn find_open_files(target_path: &str) -> Vec<ProcessInfo> {
             // Resolve the symlink to see what file is actually open
             if let Ok(link_target) = fs::read_link(fd_entry.path()) {
                 let link_str = link_target.to_string_lossy();
-                if link_str.starts_with(target_path) {
+                // Component-wise so `/mnt/o/repo` doesn't match `/mnt/o/repo2`.
+                if link_target.starts_with(target_path) {
                     // Get relative path for display
                     let relative = link_str
                         .strip_prefix(target_path)
@@ -105,8 +108,18 @@ fn format_uptime(secs: u64) -> String {
     }
 }
 
-/// Print the daemon status.
-pub fn print_status() -> Result<(), Box<dyn std::error::Error>> {
+/// Column text for a repo's remote status.
+fn remote_label(status: RemoteStatus) -> &'static str {
+    match status {
+        RemoteStatus::UpToDate => "up to date",
+        RemoteStatus::Behind => "behind",
+        RemoteStatus::Unknown => "unknown",
+    }
+}
+
+/// Print the daemon status. With `remote`, each repo also shows whether
+/// its commit matches the remote's current head.
+pub fn print_status(remote: bool) -> Result<(), CliError> {
     // Try to connect to daemon
     let mut client = match Client::connect() {
         Ok(c) => c,
@@ -114,7 +127,7 @@ pub fn print_status() -> Result<(), Box<dyn std::error::Error>> {
             println!("Daemon: not running");
             return Ok(());
         }
-        Err(e) => return Err(Box::new(e)),
+        Err(e) => return Err(e.into()),
     };
 
     // Get status from daemon. If the daemon exits between connect and request,
@@ -125,7 +138,7 @@ pub fn print_status() -> Result<(), Box<dyn std::error::Error>> {
             println!("Daemon: not running");
             return Ok(());
         }
-        Err(e) => return Err(Box::new(e)),
+        Err(e) => return Err(e.into()),
     };
 
     // Get repo list up front so output is consistent if daemon exits mid-command.
@@ -135,7 +148,17 @@ pub fn print_status() -> Result<(), Box<dyn std::error::Error>> {
             println!("Daemon: not running");
             return Ok(());
         }
-        Err(e) => return Err(Box::new(e)),
+        Err(e) => return Err(e.into()),
+    };
+    let remote_statuses: HashMap<String, RemoteStatus> = if remote {
+        client
+            .remote_status()?
+            .checks
+            .into_iter()
+            .map(|c| (format!("{}/{}", c.owner, c.repo), c.status))
+            .collect()
+    } else {
+        HashMap::new()
     };
 
     // Daemon status section
@@ -159,7 +182,19 @@ pub fn print_status() -> Result<(), Box<dyn std::error::Error>> {
                 .as_ref()
                 .map(|c| if c.len() > 12 { &c[..12] } else { c.as_str() })
                 .unwrap_or("not synced");
-            println!("  {:<40} {}", name, commit_str);
+            let remote_str = remote_statuses
+                .get(&name)
+                .map(|status| format!(" {:<10}", remote_label(*status)))
+                .unwrap_or_default();
+            match &repo.renamed_to {
+                Some(new_name) => {
+                    println!(
+                        "  {:<40} {:<12}{} (renamed to {})",
+                        name, commit_str, remote_str, new_name
+                    )
+                }
+                None => println!("  {:<40} {:<12}{}", name, commit_str, remote_str),
+            }
         }
     }
     println!();
@@ -0,0 +1,98 @@
+//! `ghfs sync --async` and `ghfs sync-status`: queue a sync without waiting,
+//! then check on it by id.
+//!
+//! The daemon remembers the latest thousand submitted syncs, and only
+//! while it keeps running; an id from before a restart is unknown.
+
+use std::time::Duration;
+
+use crate::cli::{CliError, Client};
+use crate::protocol::{SyncState, SyncStatusResult};
+
+/// One line describing a submitted sync's status.
+pub fn describe_sync_status(status: &SyncStatusResult) -> String {
+    match status.state {
+        SyncState::Pending => format!("{}: pending\n", status.repo),
+        SyncState::Done => {
+            let commit = status.commit.as_deref().unwrap_or_default();
+            let commit = commit.get(..12).unwrap_or(commit);
+            let verb = match (status.fresh, status.changed) {
+                (true, _) => "fresh at",
+                (false, false) => "unchanged at",
+                (false, true) => "synced",
+            };
+            format!("{}: {verb} {commit}\n", status.repo)
+        }
+        SyncState::Failed => format!(
+            "{}: failed: {}\n",
+            status.repo,
+            status.error.as_deref().unwrap_or("unknown error")
+        ),
+    }
+}
+
+/// Queue a sync of `repo` and print its id.
+pub fn submit_sync(
+    repo: &str,
+    commit: Option<&str>,
+    if_older_than: Option<Duration>,
+) -> Result<(), CliError> {
+    let sync_id = Client::connect()?.submit_sync(repo, commit, if_older_than)?;
+    println!("{sync_id}");
+    Ok(())
+}
+
+/// Print the status of sync `sync_id`, failing if the sync failed.
+pub fn sync_status(sync_id: u64, json: bool) -> Result<(), CliError> {
+    let status = Client::connect()?.sync_status(sync_id)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+    } else {
+        print!("{}", describe_sync_status(&status));
+    }
+    if status.state == SyncState::Failed {
+        return Err(CliError::SyncFailed(sync_id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_each_state() {
+        let mut status = SyncStatusResult {
+            sync_id: 3,
+            repo: "octocat/hello-world".to_string(),
+            state: SyncState::Pending,
+            commit: None,
+            fresh: false,
+            changed: false,
+            error: None,
+        };
+        assert_eq!(
+            describe_sync_status(&status),
+            "octocat/hello-world: pending\n"
+        );
+
+        status.state = SyncState::Done;
+        status.commit = Some("0123456789abcdef0123".to_string());
+        assert_eq!(
+            describe_sync_status(&status),
+            "octocat/hello-world: unchanged at 0123456789ab\n"
+        );
+        status.changed = true;
+        assert_eq!(
+            describe_sync_status(&status),
+            "octocat/hello-world: synced 0123456789ab\n"
+        );
+
+        status.state = SyncState::Failed;
+        status.error = Some("repository not found".to_string());
+        assert_eq!(
+            describe_sync_status(&status),
+            "octocat/hello-world: failed: repository not found\n"
+        );
+    }
+}
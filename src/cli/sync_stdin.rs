@@ -0,0 +1,153 @@
+//! `ghfs sync -`: sync every repo listed on stdin.
+//!
+//! Each line holds one `owner/repo`; blank lines and `#` comments are
+//! skipped. Every repo gets a result line, on stdout when it synced and on
+//! stderr when it didn't, and an invalid or failing repo doesn't stop the
+//! rest.
+
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+use crate::cli::{CliError, Client, ClientError};
+use crate::protocol::SyncResult;
+use crate::types::RepoKey;
+
+/// How many of the listed repos synced and how many didn't.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOutcome {
+    pub synced: usize,
+    pub failed: usize,
+}
+
+/// Sync the repos listed in `input` with `sync`, writing one result line
+/// per repo to `out` or, for failures, `err`. Lines that aren't valid repos
+/// are reported and never reach `sync`.
+pub fn sync_lines(
+    input: impl BufRead,
+    mut sync: impl FnMut(&RepoKey) -> Result<SyncResult, ClientError>,
+    out: &mut impl Write,
+    err: &mut impl Write,
+) -> std::io::Result<BatchOutcome> {
+    let mut outcome = BatchOutcome::default();
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let key: RepoKey = match line.parse() {
+            Ok(key) => key,
+            Err(e) => {
+                writeln!(err, "{line}: invalid repo format: {e}")?;
+                outcome.failed += 1;
+                continue;
+            }
+        };
+        match sync(&key) {
+            Ok(result) => {
+                let commit = result.commit.get(..12).unwrap_or(&result.commit);
+                let verb = match (result.fresh, result.changed) {
+                    (true, _) => "fresh at",
+                    (false, false) => "unchanged at",
+                    (false, true) => "synced",
+                };
+                writeln!(out, "{key}: {verb} {commit}")?;
+                outcome.synced += 1;
+            }
+            Err(e) => {
+                writeln!(err, "{key}: {e}")?;
+                outcome.failed += 1;
+            }
+        }
+    }
+    Ok(outcome)
+}
+
+/// Sync every repo listed on stdin, failing at the end if any didn't.
+/// `quiet` drops the lines for repos that synced.
+pub fn sync_stdin(quiet: bool, if_older_than: Option<Duration>) -> Result<(), CliError> {
+    let mut client = Client::connect()?;
+    let sync = |key: &RepoKey| client.sync(&key.to_string(), None, if_older_than);
+    let mut err = std::io::stderr().lock();
+    let outcome = if quiet {
+        sync_lines(
+            std::io::stdin().lock(),
+            sync,
+            &mut std::io::sink(),
+            &mut err,
+        )
+    } else {
+        sync_lines(
+            std::io::stdin().lock(),
+            sync,
+            &mut std::io::stdout().lock(),
+            &mut err,
+        )
+    }
+    .map_err(ClientError::Io)?;
+    if outcome.failed > 0 {
+        return Err(CliError::BatchFailed {
+            failed: outcome.failed,
+            total: outcome.synced + outcome.failed,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_valid_lines_are_synced() {
+        let input = "octocat/hello-world\n\
+                     \n\
+                     # a comment\n\
+                     not-a-repo\n\
+                     rust-lang/rust\n\
+                     bad/repo/path\n\
+                     missing/repo\n";
+        let mut synced = Vec::new();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let outcome = sync_lines(
+            input.as_bytes(),
+            |key| {
+                synced.push(key.to_string());
+                if key.repo.as_str() == "repo" {
+                    return Err(ClientError::InvalidResponse("gone".to_string()));
+                }
+                Ok(SyncResult {
+                    commit: "0123456789abcdef0123".to_string(),
+                    fresh: key.owner.as_str() == "rust-lang",
+                    changed: key.owner.as_str() != "rust-lang",
+                })
+            },
+            &mut out,
+            &mut err,
+        )
+        .unwrap();
+
+        assert_eq!(
+            synced,
+            ["octocat/hello-world", "rust-lang/rust", "missing/repo"]
+        );
+        assert_eq!(
+            outcome,
+            BatchOutcome {
+                synced: 2,
+                failed: 3
+            }
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "octocat/hello-world: synced 0123456789ab\n\
+             rust-lang/rust: fresh at 0123456789ab\n"
+        );
+        let err = String::from_utf8(err).unwrap();
+        let lines: Vec<&str> = err.lines().collect();
+        assert_eq!(lines.len(), 3, "{err}");
+        assert!(lines[0].starts_with("not-a-repo: invalid repo format"));
+        assert!(lines[1].starts_with("bad/repo/path: invalid repo format"));
+        assert!(lines[2].starts_with("missing/repo: "));
+    }
+}
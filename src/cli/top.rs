@@ -0,0 +1,142 @@
+//! `ghfs top`: live filesystem operation rates.
+//!
+//! Polls the daemon's cumulative op counters and renders per-second rates
+//! from the difference between consecutive snapshots.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use crate::cli::{CliError, Client};
+use crate::protocol::OpStatsResult;
+
+/// Per-second rate of each counted operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OpRates {
+    pub lookup: f64,
+    pub getattr: f64,
+    pub readdir: f64,
+    pub read: f64,
+    pub materialize: f64,
+}
+
+impl OpRates {
+    /// Rates between two snapshots taken `elapsed` apart. Counters that went
+    /// backwards (a daemon restart) and a zero interval yield zero.
+    pub fn between(prev: &OpStatsResult, cur: &OpStatsResult, elapsed: Duration) -> Self {
+        let secs = elapsed.as_secs_f64();
+        let rate = |before: u64, after: u64| {
+            if secs == 0.0 {
+                0.0
+            } else {
+                after.saturating_sub(before) as f64 / secs
+            }
+        };
+        Self {
+            lookup: rate(prev.lookup, cur.lookup),
+            getattr: rate(prev.getattr, cur.getattr),
+            readdir: rate(prev.readdir, cur.readdir),
+            read: rate(prev.read, cur.read),
+            materialize: rate(prev.materialize, cur.materialize),
+        }
+    }
+}
+
+/// Render one frame: rates alongside the cumulative totals.
+pub fn render_top(rates: &OpRates, totals: &OpStatsResult) -> String {
+    let rows = [
+        ("lookup", rates.lookup, totals.lookup),
+        ("getattr", rates.getattr, totals.getattr),
+        ("readdir", rates.readdir, totals.readdir),
+        ("read", rates.read, totals.read),
+        ("materialize", rates.materialize, totals.materialize),
+    ];
+    let mut out = String::new();
+    let _ = writeln!(out, "{:<12} {:>10} {:>12}", "OP", "PER SEC", "TOTAL");
+    for (name, rate, total) in rows {
+        let _ = writeln!(out, "{:<12} {:>10.1} {:>12}", name, rate, total);
+    }
+    out
+}
+
+/// Poll the daemon every `interval` and redraw until interrupted.
+pub fn top(interval: Duration) -> Result<(), CliError> {
+    let mut client = Client::connect()?;
+    let mut prev = client.op_stats()?;
+    loop {
+        std::thread::sleep(interval);
+        let cur = client.op_stats()?;
+        let elapsed = Duration::from_millis(cur.uptime_ms.saturating_sub(prev.uptime_ms));
+        let rates = OpRates::between(&prev, &cur, elapsed);
+        // Clear the screen and home the cursor.
+        print!("\x1b[2J\x1b[H{}", render_top(&rates, &cur));
+        prev = cur;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_are_deltas_over_elapsed_time() {
+        let prev = OpStatsResult {
+            lookup: 100,
+            read: 10,
+            ..Default::default()
+        };
+        let cur = OpStatsResult {
+            lookup: 300,
+            read: 15,
+            materialize: 1,
+            ..Default::default()
+        };
+        let rates = OpRates::between(&prev, &cur, Duration::from_secs(2));
+        assert_eq!(
+            rates,
+            OpRates {
+                lookup: 100.0,
+                read: 2.5,
+                materialize: 0.5,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn reset_counters_and_zero_interval_give_zero() {
+        let prev = OpStatsResult {
+            lookup: 50,
+            ..Default::default()
+        };
+        let cur = OpStatsResult {
+            lookup: 5,
+            ..Default::default()
+        };
+        assert_eq!(
+            OpRates::between(&prev, &cur, Duration::from_secs(1)).lookup,
+            0.0
+        );
+        assert_eq!(
+            OpRates::between(&cur, &prev, Duration::ZERO),
+            OpRates::default()
+        );
+    }
+
+    #[test]
+    fn render_lists_every_op() {
+        let text = render_top(
+            &OpRates {
+                read: 2.5,
+                ..Default::default()
+            },
+            &OpStatsResult {
+                read: 42,
+                ..Default::default()
+            },
+        );
+        assert!(text.contains("read"));
+        assert!(text.contains("2.5"));
+        assert!(text.contains("42"));
+        assert_eq!(text.lines().count(), 6);
+    }
+}
@@ -0,0 +1,134 @@
+//! `ghfs upgrade`: restart the service when the daemon runs a different
+//! version than this binary, e.g. after updating ghfs.
+
+use std::io::{BufRead, IsTerminal, Write};
+
+use crate::cli::{CliError, Client};
+use crate::service;
+
+/// What `ghfs upgrade` does, given the daemon's and the CLI's versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpgradeAction {
+    /// Versions match; nothing to do.
+    UpToDate,
+    /// Restart without asking (`--yes`).
+    Restart,
+    /// Ask before restarting.
+    Prompt,
+    /// Mismatch, but there is no terminal to ask on and no `--yes`.
+    Skip,
+}
+
+/// Decide what to do about the daemon running `daemon_version` when this
+/// binary is `cli_version`. `interactive` is whether stdin is a terminal.
+pub fn upgrade_action(
+    daemon_version: &str,
+    cli_version: &str,
+    assume_yes: bool,
+    interactive: bool,
+) -> UpgradeAction {
+    if daemon_version == cli_version {
+        UpgradeAction::UpToDate
+    } else if assume_yes {
+        UpgradeAction::Restart
+    } else if interactive {
+        UpgradeAction::Prompt
+    } else {
+        UpgradeAction::Skip
+    }
+}
+
+/// Compare the running daemon's version with this binary's and restart the
+/// service on a mismatch, after confirmation unless `assume_yes`.
+pub fn upgrade(assume_yes: bool) -> Result<(), CliError> {
+    let cli_version = env!("CARGO_PKG_VERSION");
+    let daemon = Client::connect()?.version()?;
+
+    let restart = match upgrade_action(
+        &daemon.version,
+        cli_version,
+        assume_yes,
+        std::io::stdin().is_terminal(),
+    ) {
+        UpgradeAction::UpToDate => {
+            println!("Daemon is up to date ({cli_version})");
+            return Ok(());
+        }
+        UpgradeAction::Restart => true,
+        UpgradeAction::Prompt => confirm(&format!(
+            "Daemon runs {}, this binary is {cli_version}. Restart the service now? [y/N] ",
+            daemon.version
+        )),
+        UpgradeAction::Skip => {
+            println!(
+                "Daemon runs {}, this binary is {cli_version}. \
+                 Run 'ghfs upgrade --yes' to restart it.",
+                daemon.version
+            );
+            return Ok(());
+        }
+    };
+    if !restart {
+        println!("Not restarted");
+        return Ok(());
+    }
+
+    service::restart()?;
+    println!("Restarted daemon: {} -> {cli_version}", daemon.version);
+    Ok(())
+}
+
+/// Print `question` and read a yes/no answer from stdin. Anything but `y`
+/// or `yes`, including EOF, is no.
+fn confirm(question: &str) -> bool {
+    print!("{question}");
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    is_yes(&answer)
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mismatch_prompts_only_when_interactive() {
+        assert_eq!(
+            upgrade_action("0.4.0", "0.4.0", false, true),
+            UpgradeAction::UpToDate
+        );
+        assert_eq!(
+            upgrade_action("0.4.0", "0.4.0", true, false),
+            UpgradeAction::UpToDate
+        );
+        assert_eq!(
+            upgrade_action("0.3.9", "0.4.0", false, true),
+            UpgradeAction::Prompt
+        );
+        assert_eq!(
+            upgrade_action("0.3.9", "0.4.0", false, false),
+            UpgradeAction::Skip
+        );
+        assert_eq!(
+            upgrade_action("0.3.9", "0.4.0", true, false),
+            UpgradeAction::Restart
+        );
+    }
+
+    #[test]
+    fn only_yes_confirms() {
+        for answer in ["y\n", "Y\n", " yes \n", "YES"] {
+            assert!(is_yes(answer), "{answer:?}");
+        }
+        for answer in ["", "\n", "n\n", "no", "yep"] {
+            assert!(!is_yes(answer), "{answer:?}");
+        }
+    }
+}
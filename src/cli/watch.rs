@@ -0,0 +1,98 @@
+//! `ghfs watch` and `ghfs unwatch`: follow or stop following an owner.
+//!
+//! Both report whether the owner was already watched, and since when, so a
+//! script can tell a change from a no-op.
+
+use std::fmt::Write as _;
+
+use crate::cli::{CliError, Client};
+use crate::daemon::format_timestamp;
+use crate::protocol::WatchResult;
+
+/// What `ghfs watch owner` did, given the daemon's reply.
+pub fn describe_watch(owner: &str, result: &WatchResult) -> String {
+    let mut out = match result.watched_since {
+        Some(since) => format!(
+            "{owner} was already watched (since {}); checked for new repos\n",
+            format_timestamp(since)
+        ),
+        None => format!("Watching {owner}\n"),
+    };
+    if !result.added.is_empty() {
+        let _ = writeln!(out, "Queued {} repos for cloning:", result.added.len());
+        for repo in &result.added {
+            let _ = writeln!(out, "  {repo}");
+        }
+    }
+    out
+}
+
+/// What `ghfs unwatch owner` did, given the daemon's reply.
+pub fn describe_unwatch(owner: &str, result: &WatchResult) -> String {
+    match result.watched_since {
+        Some(since) => format!(
+            "Stopped watching {owner} (watched since {}); its repos stay cached\n",
+            format_timestamp(since)
+        ),
+        None => format!("{owner} was not watched; nothing changed\n"),
+    }
+}
+
+/// Watch `owner`, or list watched owners when it's `None`.
+pub fn watch(owner: Option<&str>) -> Result<(), CliError> {
+    let mut client = Client::connect()?;
+    let Some(owner) = owner else {
+        let result = client.watches()?;
+        if result.owners.is_empty() {
+            println!("No owners are watched");
+        }
+        for owner in &result.owners {
+            println!("{owner}");
+        }
+        return Ok(());
+    };
+    print!("{}", describe_watch(owner, &client.watch(owner)?));
+    Ok(())
+}
+
+/// Stop watching `owner`.
+pub fn unwatch(owner: &str) -> Result<(), CliError> {
+    let result = Client::connect()?.unwatch(owner)?;
+    print!("{}", describe_unwatch(owner, &result));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prior_state_shapes_the_message() {
+        let since = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+            - 7200;
+        let result = |added: &[&str], watched_since| WatchResult {
+            added: added.iter().map(ToString::to_string).collect(),
+            watched_since,
+        };
+
+        assert_eq!(
+            describe_watch("octocat", &result(&["octocat/new"], None)),
+            "Watching octocat\nQueued 1 repos for cloning:\n  octocat/new\n"
+        );
+        assert_eq!(
+            describe_watch("octocat", &result(&[], Some(since))),
+            "octocat was already watched (since 2h ago); checked for new repos\n"
+        );
+        assert_eq!(
+            describe_unwatch("octocat", &result(&[], Some(since))),
+            "Stopped watching octocat (watched since 2h ago); its repos stay cached\n"
+        );
+        assert_eq!(
+            describe_unwatch("octocat", &result(&[], None)),
+            "octocat was not watched; nothing changed\n"
+        );
+    }
+}
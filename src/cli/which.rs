@@ -0,0 +1,89 @@
+//! `ghfs which`: inspect what a repo's default-branch alias currently serves.
+//!
+//! Reads the on-disk mirror directly so it works without a running daemon.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::cache::CachePaths;
+use crate::store::git::{self, GitError};
+use crate::types::RepoKey;
+
+/// Errors returned by [`which`].
+#[derive(Debug, Error)]
+pub enum WhichError {
+    #[error("{key} is not cached (no mirror at {})", path.display())]
+    NotCached { key: RepoKey, path: PathBuf },
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+}
+
+/// What `/<owner>/<repo>` resolves to in the local cache.
+#[derive(Debug, Clone)]
+pub struct WhichInfo {
+    /// Path of the blobless bare mirror.
+    pub mirror: PathBuf,
+    /// Full ref name HEAD points at (e.g. `refs/heads/main`), or `None` when
+    /// HEAD is detached.
+    pub head_ref: Option<String>,
+    /// Commit OID the default-branch alias serves, or `None` before the
+    /// repo's first commit.
+    pub commit: Option<String>,
+}
+
+/// Resolve the mirror path, HEAD ref, and commit for `key` from the cache.
+pub fn which(paths: &CachePaths, key: &RepoKey) -> Result<WhichInfo, WhichError> {
+    let mirror = paths.mirror_dir(key);
+    if !mirror.exists() {
+        return Err(WhichError::NotCached {
+            key: key.clone(),
+            path: mirror,
+        });
+    }
+
+    let repo = git::open_repository(&mirror)?;
+    let head_ref = repo
+        .find_reference("HEAD")
+        .ok()
+        .and_then(|head| head.symbolic_target().map(str::to_string));
+    let commit = git::resolve_head(&repo)?.map(|oid| oid.to_string());
+
+    Ok(WhichInfo {
+        mirror,
+        head_ref,
+        commit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_fixtures::mirror_with_commit;
+    use tempfile::tempdir;
+
+    #[test]
+    fn which_reports_mirror_head_and_commit() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let commit = mirror_with_commit(&paths, &key);
+
+        let info = which(&paths, &key).unwrap();
+        assert_eq!(info.mirror, paths.mirror_dir(&key));
+        assert_eq!(info.head_ref.as_deref(), Some("refs/heads/main"));
+        assert_eq!(info.commit, Some(commit.to_string()));
+    }
+
+    #[test]
+    fn which_errors_when_not_cached() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/missing".parse().unwrap();
+
+        let err = which(&paths, &key).unwrap_err();
+        assert!(matches!(err, WhichError::NotCached { .. }));
+        assert!(err.to_string().contains("octocat/missing is not cached"));
+    }
+}
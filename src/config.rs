@@ -0,0 +1,630 @@
+//! Persistent configuration file.
+//!
+//! Every setting mirrors one of the `GHFS_*` environment variables, which
+//! stay the highest-precedence source: an exported variable overrides the
+//! file, and the file overrides the built-in default. Code that reads a
+//! setting calls [`var`] instead of [`std::env::var`] so both sources are
+//! honored.
+//!
+//! The file is a flat TOML table of `key = value` lines, at
+//! `~/.config/ghfs/config.toml` (or `$GHFS_CONFIG`). `ghfs config` reads and
+//! writes it; only the scalar subset of TOML those settings need is
+//! understood.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::types::RepoKey;
+
+/// Environment variable overriding the config file location.
+pub const CONFIG_ENV: &str = "GHFS_CONFIG";
+
+/// Errors reading, parsing, or validating configuration.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("unknown config key '{0}' (run 'ghfs config list' to see all keys)")]
+    UnknownKey(String),
+    #[error("invalid value for {key}: {reason}")]
+    InvalidValue { key: String, reason: String },
+    #[error("{path}:{line}: {reason}")]
+    Parse {
+        path: String,
+        line: usize,
+        reason: String,
+    },
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The shape a setting's value must have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// `true`/`false` (also `1`/`0`, `yes`/`no`).
+    Bool,
+    /// A positive integer.
+    Count,
+    /// A whole number of seconds.
+    Seconds,
+    /// A byte size with an optional `K`, `M`, `G`, or `T` suffix.
+    Size,
+    /// Comma-separated `owner/repo` names.
+    RepoList,
+    /// One of a fixed set of words.
+    OneOf(&'static [&'static str]),
+    /// Any non-empty string.
+    Text,
+}
+
+/// One configurable setting.
+#[derive(Debug)]
+pub struct Setting {
+    pub key: &'static str,
+    pub env: &'static str,
+    pub kind: Kind,
+    /// Effective value when neither the environment nor the file sets it.
+    pub default: Option<&'static str>,
+}
+
+const fn setting(
+    key: &'static str,
+    env: &'static str,
+    kind: Kind,
+    default: Option<&'static str>,
+) -> Setting {
+    Setting {
+        key,
+        env,
+        kind,
+        default,
+    }
+}
+
+/// Every setting the config file accepts.
+pub const SETTINGS: &[Setting] = &[
+    setting(
+        "mount_point",
+        "GHFS_MOUNT_POINT",
+        Kind::Text,
+        Some(crate::daemon::DEFAULT_MOUNT_POINT),
+    ),
+    setting(
+        "allow_other",
+        crate::fs::ALLOW_OTHER_ENV,
+        Kind::Bool,
+        Some("false"),
+    ),
+    setting(
+        "shared_repos",
+        crate::fs::SHARED_REPOS_ENV,
+        Kind::RepoList,
+        None,
+    ),
+    setting(
+        "post_sync_hook",
+        crate::daemon::POST_SYNC_HOOK_ENV,
+        Kind::Text,
+        None,
+    ),
+    setting(
+        "pre_mount_hook",
+        crate::daemon::PRE_MOUNT_HOOK_ENV,
+        Kind::Text,
+        None,
+    ),
+    setting(
+        "post_unmount_hook",
+        crate::daemon::POST_UNMOUNT_HOOK_ENV,
+        Kind::Text,
+        None,
+    ),
+    setting(
+        "remote_precheck",
+        crate::store::REMOTE_PRECHECK_ENV,
+        Kind::Bool,
+        Some("false"),
+    ),
+    setting(
+        "follow_renames",
+        crate::store::FOLLOW_RENAMES_ENV,
+        Kind::Bool,
+        Some("false"),
+    ),
+    setting(
+        "list_remote_repos",
+        crate::fs::LIST_REMOTE_REPOS_ENV,
+        Kind::Bool,
+        Some("false"),
+    ),
+    setting(
+        "deterministic_inodes",
+        crate::fs::DETERMINISTIC_INODES_ENV,
+        Kind::Bool,
+        Some("false"),
+    ),
+    setting(
+        "persisted_inodes",
+        crate::fs::PERSISTED_INODES_ENV,
+        Kind::Count,
+        Some("100000"),
+    ),
+    setting(
+        "layout",
+        crate::fs::LAYOUT_ENV,
+        Kind::OneOf(&["nested", "flat"]),
+        Some("nested"),
+    ),
+    setting(
+        "flat_delimiter",
+        crate::fs::FLAT_DELIMITER_ENV,
+        Kind::Text,
+        Some(crate::fs::DEFAULT_FLAT_DELIMITER),
+    ),
+    setting(
+        "meta_dir",
+        crate::fs::META_DIR_ENV,
+        Kind::Text,
+        Some(crate::fs::META_DIR),
+    ),
+    setting(
+        "hide_meta_dir",
+        crate::fs::HIDE_META_DIR_ENV,
+        Kind::Bool,
+        Some("false"),
+    ),
+    setting(
+        "hidden_paths",
+        crate::fs::HIDDEN_PATHS_ENV,
+        Kind::Text,
+        None,
+    ),
+    setting(
+        "strict_cache_fs",
+        crate::daemon::STRICT_CACHE_FS_ENV,
+        Kind::Bool,
+        Some("false"),
+    ),
+    setting(
+        "shard_mirrors",
+        crate::cache::SHARD_MIRRORS_ENV,
+        Kind::Bool,
+        Some("false"),
+    ),
+    setting(
+        "sync_concurrency",
+        crate::daemon::SYNC_CONCURRENCY_ENV,
+        Kind::Count,
+        Some("4"),
+    ),
+    setting(
+        "shutdown_grace",
+        crate::daemon::SHUTDOWN_GRACE_ENV,
+        Kind::Seconds,
+        Some("30"),
+    ),
+    setting(
+        "repo_quota",
+        crate::daemon::REPO_QUOTA_ENV,
+        Kind::Size,
+        None,
+    ),
+    setting(
+        "owner_quota",
+        crate::daemon::OWNER_QUOTA_ENV,
+        Kind::Size,
+        None,
+    ),
+    setting(
+        "quota_exempt",
+        crate::daemon::QUOTA_EXEMPT_ENV,
+        Kind::RepoList,
+        None,
+    ),
+    setting(
+        "virtual_ttl",
+        crate::fs::VIRTUAL_TTL_ENV,
+        Kind::Seconds,
+        Some("60"),
+    ),
+    setting(
+        "block_size",
+        crate::fs::BLOCK_SIZE_ENV,
+        Kind::Size,
+        Some("4K"),
+    ),
+    setting("max_read", crate::fs::MAX_READ_ENV, Kind::Size, None),
+    setting("ref_ttl", crate::fs::REF_TTL_ENV, Kind::Seconds, Some("5")),
+    setting(
+        "commit_ttl",
+        crate::fs::COMMIT_TTL_ENV,
+        Kind::Seconds,
+        Some("3600"),
+    ),
+    setting("http_proxy", "GHFS_HTTP_PROXY", Kind::Text, None),
+    setting(
+        "user_agent",
+        crate::store::git::USER_AGENT_ENV,
+        Kind::Text,
+        Some(crate::store::git::DEFAULT_USER_AGENT),
+    ),
+];
+
+/// Look up the setting named `key`.
+pub fn lookup(key: &str) -> Result<&'static Setting, ConfigError> {
+    SETTINGS
+        .iter()
+        .find(|s| s.key == key)
+        .ok_or_else(|| ConfigError::UnknownKey(key.to_string()))
+}
+
+impl Setting {
+    /// Check `value` against the setting's [`Kind`] and return it in the
+    /// form stored in the file.
+    pub fn validate(&self, value: &str) -> Result<String, ConfigError> {
+        let value = value.trim();
+        let invalid = |reason: &str| ConfigError::InvalidValue {
+            key: self.key.to_string(),
+            reason: format!("{reason}, got {value:?}"),
+        };
+        match self.kind {
+            Kind::Bool => match value.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" => Ok("true".to_string()),
+                "0" | "false" | "no" => Ok("false".to_string()),
+                _ => Err(invalid("expected true or false")),
+            },
+            Kind::Count => match value.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(n.to_string()),
+                _ => Err(invalid("expected a positive number")),
+            },
+            Kind::Seconds => value
+                .parse::<u64>()
+                .map(|n| n.to_string())
+                .map_err(|_| invalid("expected a number of seconds")),
+            Kind::Size => crate::daemon::parse_size(value)
+                .map(|_| value.to_string())
+                .ok_or_else(|| invalid("expected a size like 500M or 2G")),
+            Kind::RepoList => {
+                let repos = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(|s| {
+                        s.parse::<RepoKey>()
+                            .map(|_| s)
+                            .map_err(|_| invalid("expected comma-separated owner/repo names"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(repos.join(","))
+            }
+            Kind::OneOf(choices) if choices.contains(&value) => Ok(value.to_string()),
+            Kind::OneOf(choices) => {
+                Err(invalid(&format!("expected one of {}", choices.join(", "))))
+            }
+            Kind::Text if value.is_empty() => Err(invalid("expected a non-empty value")),
+            Kind::Text => Ok(value.to_string()),
+        }
+    }
+
+    /// The value as a TOML literal: numbers and booleans bare, everything
+    /// else a quoted string.
+    fn toml_value(&self, value: &str) -> String {
+        match self.kind {
+            Kind::Bool | Kind::Count | Kind::Seconds => value.to_string(),
+            Kind::Size | Kind::RepoList | Kind::OneOf(_) | Kind::Text => {
+                let escaped = value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n")
+                    .replace('\t', "\\t");
+                format!("\"{escaped}\"")
+            }
+        }
+    }
+}
+
+/// Where a setting's effective value comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Env,
+    File,
+    Default,
+    Unset,
+}
+
+/// A setting's effective value and its source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Effective {
+    pub key: &'static str,
+    pub env: &'static str,
+    pub value: Option<String>,
+    pub source: Source,
+}
+
+/// Validated settings read from, or to be written to, the config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    values: BTreeMap<&'static str, String>,
+}
+
+impl ConfigFile {
+    /// Parse file contents. `path` is only used in error messages. Unknown
+    /// keys and invalid values are errors, so typos don't go unnoticed.
+    pub fn parse(contents: &str, path: &Path) -> Result<Self, ConfigError> {
+        let mut file = Self::default();
+        for (index, line) in contents.lines().enumerate() {
+            let parse_err = |reason: String| ConfigError::Parse {
+                path: path.display().to_string(),
+                line: index + 1,
+                reason,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| parse_err("expected key = value".to_string()))?;
+            let raw = parse_toml_value(value.trim()).map_err(parse_err)?;
+            let setting = lookup(key.trim()).map_err(|e| parse_err(e.to_string()))?;
+            let value = setting
+                .validate(&raw)
+                .map_err(|e| parse_err(e.to_string()))?;
+            file.values.insert(setting.key, value);
+        }
+        Ok(file)
+    }
+
+    /// Read the file at `path`; a missing file is empty.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Self::parse(&contents, path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the file to `path`, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.render())?;
+        Ok(())
+    }
+
+    /// The file contents, one `key = value` line per set key in
+    /// [`SETTINGS`] order.
+    pub fn render(&self) -> String {
+        let mut out = String::from("# ghfs configuration; edit with `ghfs config set`\n");
+        for setting in SETTINGS {
+            if let Some(value) = self.values.get(setting.key) {
+                out.push_str(&format!(
+                    "{} = {}\n",
+                    setting.key,
+                    setting.toml_value(value)
+                ));
+            }
+        }
+        out
+    }
+
+    /// The value set for `key` in the file, if any.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(String::as_str)
+    }
+
+    /// Validate and set `key` to `value`.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let setting = lookup(key)?;
+        let value = setting.validate(value)?;
+        self.values.insert(setting.key, value);
+        Ok(())
+    }
+
+    /// The effective value of `setting`: from the environment (via `env`)
+    /// if set there, else from this file, else its default.
+    pub fn effective(
+        &self,
+        setting: &'static Setting,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Effective {
+        let (value, source) = if let Some(value) = env(setting.env) {
+            (Some(value), Source::Env)
+        } else if let Some(value) = self.get(setting.key) {
+            (Some(value.to_string()), Source::File)
+        } else if let Some(value) = setting.default {
+            (Some(value.to_string()), Source::Default)
+        } else {
+            (None, Source::Unset)
+        };
+        Effective {
+            key: setting.key,
+            env: setting.env,
+            value,
+            source,
+        }
+    }
+}
+
+/// Parse the TOML scalars settings use: basic and literal strings, booleans,
+/// and integers. A trailing `# comment` is allowed.
+fn parse_toml_value(value: &str) -> Result<String, String> {
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return check_trailing(chars.as_str()).map(|()| out),
+                '\\' => match chars.next() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    other => return Err(format!("unsupported escape \\{}", other.unwrap_or(' '))),
+                },
+                c => out.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    } else if let Some(rest) = value.strip_prefix('\'') {
+        let (inner, trailing) = rest
+            .split_once('\'')
+            .ok_or_else(|| "unterminated string".to_string())?;
+        check_trailing(trailing).map(|()| inner.to_string())
+    } else {
+        let bare = value.split_once('#').map_or(value, |(v, _)| v).trim();
+        if bare.is_empty() {
+            return Err("missing value".to_string());
+        }
+        if bare == "true" || bare == "false" || bare.chars().all(|c| c.is_ascii_digit() || c == '_')
+        {
+            Ok(bare.replace('_', ""))
+        } else {
+            Err(format!("strings must be quoted: {bare}"))
+        }
+    }
+}
+
+fn check_trailing(rest: &str) -> Result<(), String> {
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("unexpected text after value: {rest}"))
+    }
+}
+
+/// The config file path: `$GHFS_CONFIG`, or `ghfs/config.toml` under the
+/// user's config directory.
+pub fn config_path() -> PathBuf {
+    if let Some(custom) = std::env::var_os(CONFIG_ENV) {
+        return PathBuf::from(custom);
+    }
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from(".config"))
+        .join("ghfs")
+        .join("config.toml")
+}
+
+/// The config file at [`config_path`], loaded once per process. A file that
+/// fails to load is logged and treated as empty.
+fn loaded() -> &'static ConfigFile {
+    static FILE: OnceLock<ConfigFile> = OnceLock::new();
+    FILE.get_or_init(|| {
+        ConfigFile::load(&config_path()).unwrap_or_else(|e| {
+            log::warn!("Ignoring config file: {e}");
+            ConfigFile::default()
+        })
+    })
+}
+
+/// Read the environment variable `name`, falling back to the config file
+/// setting it mirrors. Names no setting mirrors read the environment only.
+pub fn var(name: &str) -> Option<String> {
+    if let Ok(value) = std::env::var(name) {
+        return Some(value);
+    }
+    let setting = SETTINGS.iter().find(|s| s.env == name)?;
+    loaded().get(setting.key).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn set_get_and_list_round_trip_through_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("ghfs").join("config.toml");
+
+        let mut file = ConfigFile::load(&path).unwrap();
+        assert_eq!(file, ConfigFile::default());
+        file.set("sync_concurrency", "8").unwrap();
+        file.set("allow_other", "yes").unwrap();
+        file.set("post_sync_hook", r#"notify "{repo}" \ done"#)
+            .unwrap();
+        file.set("quota_exempt", "torvalds/linux, rust-lang/rust")
+            .unwrap();
+        file.save(&path).unwrap();
+
+        let loaded = ConfigFile::load(&path).unwrap();
+        assert_eq!(loaded, file);
+        assert_eq!(loaded.get("allow_other"), Some("true"));
+        assert_eq!(
+            loaded.get("post_sync_hook"),
+            Some(r#"notify "{repo}" \ done"#)
+        );
+        assert_eq!(
+            loaded.get("quota_exempt"),
+            Some("torvalds/linux,rust-lang/rust")
+        );
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("sync_concurrency = 8\n")
+        );
+
+        let no_env = |_: &str| None;
+        let list: Vec<_> = SETTINGS
+            .iter()
+            .map(|s| loaded.effective(s, no_env))
+            .collect();
+        let find = |key: &str| list.iter().find(|e| e.key == key).unwrap();
+        assert_eq!(find("sync_concurrency").source, Source::File);
+        assert_eq!(find("ref_ttl").value.as_deref(), Some("5"));
+        assert_eq!(find("ref_ttl").source, Source::Default);
+        assert_eq!(find("repo_quota").source, Source::Unset);
+
+        // The environment wins over the file.
+        let env = |name: &str| (name == "GHFS_SYNC_CONCURRENCY").then(|| "2".to_string());
+        let effective = loaded.effective(lookup("sync_concurrency").unwrap(), env);
+        assert_eq!(effective.value.as_deref(), Some("2"));
+        assert_eq!(effective.source, Source::Env);
+    }
+
+    #[test]
+    fn unknown_keys_and_invalid_values_are_rejected() {
+        let mut file = ConfigFile::default();
+        assert!(matches!(
+            file.set("no_such_key", "1"),
+            Err(ConfigError::UnknownKey(key)) if key == "no_such_key"
+        ));
+        for (key, value) in [
+            ("allow_other", "maybe"),
+            ("sync_concurrency", "0"),
+            ("commit_ttl", "forever"),
+            ("repo_quota", "lots"),
+            ("shared_repos", "not-a-repo"),
+            ("user_agent", " "),
+        ] {
+            assert!(
+                matches!(file.set(key, value), Err(ConfigError::InvalidValue { .. })),
+                "{key}={value}"
+            );
+        }
+        assert_eq!(file, ConfigFile::default());
+
+        let path = Path::new("config.toml");
+        let err = ConfigFile::parse("# ok\nallow_other = true\ntypo = 1\n", path).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { line: 3, .. }), "{err}");
+        assert!(ConfigFile::parse("user_agent = unquoted\n", path).is_err());
+        assert!(ConfigFile::parse("user_agent = \"open\n", path).is_err());
+    }
+
+    #[test]
+    fn parses_toml_scalars() {
+        let file = ConfigFile::parse(
+            "ref_ttl = 1_000 # comment\nhttp_proxy = 'http://proxy:3128'\nallow_other = false\n",
+            Path::new("config.toml"),
+        )
+        .unwrap();
+        assert_eq!(file.get("ref_ttl"), Some("1000"));
+        assert_eq!(file.get("http_proxy"), Some("http://proxy:3128"));
+        assert_eq!(file.get("allow_other"), Some("false"));
+    }
+}
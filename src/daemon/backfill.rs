@@ -11,33 +11,32 @@ use crate::types::{Owner, Repo, RepoKey};
 
 /// Scan the cache on disk and ensure every mirrored repo has a DB row.
 pub fn backfill_cache_state(state: &State, cache_paths: &CachePaths) {
-    let owners = match std::fs::read_dir(cache_paths.mirrors_dir()) {
-        Ok(entries) => entries,
-        Err(err) => {
-            log::debug!("Backfill skipped: cannot read mirrors dir: {err}");
-            return;
-        }
-    };
+    for key in mirrored_repos(cache_paths) {
+        let _ = state.get_or_create_repo(&key);
+    }
+}
 
-    for owner_entry in owners.flatten() {
-        if !owner_entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
-            continue;
-        }
-        let owner_name = match owner_entry.file_name().into_string() {
-            Ok(name) => name,
-            Err(_) => continue,
+/// Every repo with a mirror directory on disk, including the old names of
+/// renamed repos.
+pub(super) fn mirrored_repos(cache_paths: &CachePaths) -> Vec<RepoKey> {
+    let mut keys = Vec::new();
+    for owner_dir in cache_paths.owner_dirs() {
+        let owner_name = match owner_dir.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name,
+            None => continue,
         };
         let owner: Owner = match owner_name.parse() {
             Ok(owner) => owner,
             Err(_) => continue,
         };
 
-        let repos = match std::fs::read_dir(owner_entry.path()) {
+        let repos = match std::fs::read_dir(&owner_dir) {
             Ok(entries) => entries,
             Err(_) => continue,
         };
         for repo_entry in repos.flatten() {
-            if !repo_entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            // Follows symlinks, so a renamed repo's old name is kept too.
+            if !repo_entry.path().is_dir() {
                 continue;
             }
             let name = match repo_entry.file_name().into_string() {
@@ -50,8 +49,8 @@ pub fn backfill_cache_state(state: &State, cache_paths: &CachePaths) {
             let Ok(repo) = stripped.parse::<Repo>() else {
                 continue;
             };
-            let key = RepoKey::new(owner.clone(), repo);
-            let _ = state.get_or_create_repo(&key);
+            keys.push(RepoKey::new(owner.clone(), repo));
         }
     }
+    keys
 }
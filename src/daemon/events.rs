@@ -0,0 +1,82 @@
+//! Fan-out of sync events to socket subscribers.
+//!
+//! The worker publishes a [`SyncEvent`] whenever a sync or refresh moves a
+//! repo's HEAD or fails. Each subscriber gets its own bounded queue; a
+//! subscriber that falls behind misses events rather than stalling the
+//! worker, and one that disconnects is dropped on the next publish.
+
+use std::sync::Mutex;
+
+use crossbeam_channel::{Receiver, Sender, TrySendError, bounded};
+
+use crate::protocol::SyncEvent;
+
+/// Events queued per subscriber before new ones are dropped.
+const SUBSCRIBER_QUEUE: usize = 256;
+
+/// Broadcasts [`SyncEvent`]s to any number of subscribers.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Sender<SyncEvent>>>,
+}
+
+impl EventBus {
+    /// Register a subscriber. Events published from now on are delivered to
+    /// the returned receiver until it is dropped.
+    pub fn subscribe(&self) -> Receiver<SyncEvent> {
+        let (sender, receiver) = bounded(SUBSCRIBER_QUEUE);
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Deliver `event` to every live subscriber without blocking.
+    pub fn publish(&self, event: SyncEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|sender| match sender.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    log::debug!("Event subscriber is behind; dropping event");
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failed(repo: &str) -> SyncEvent {
+        SyncEvent::Failed {
+            repo: repo.to_string(),
+            error: "boom".to_string(),
+            at: 0,
+        }
+    }
+
+    #[test]
+    fn publish_reaches_live_subscribers_only() {
+        let bus = EventBus::default();
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+        drop(second);
+
+        bus.publish(failed("octocat/hello-world"));
+
+        assert_eq!(first.try_recv().unwrap(), failed("octocat/hello-world"));
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn slow_subscriber_does_not_block_publish() {
+        let bus = EventBus::default();
+        let slow = bus.subscribe();
+        for _ in 0..SUBSCRIBER_QUEUE + 10 {
+            bus.publish(failed("octocat/hello-world"));
+        }
+        assert_eq!(slow.len(), SUBSCRIBER_QUEUE);
+    }
+}
@@ -0,0 +1,224 @@
+//! Cross-check the state database against the mirrors on disk.
+//!
+//! The two drift apart when mirrors are deleted by hand, a database is
+//! restored from a backup, or a daemon dies between writing one and the
+//! other. There are no generation directories to compare against: a repo
+//! is on disk exactly when its mirror is, and generations exist only as
+//! database rows.
+
+use std::fmt;
+
+use crate::cache::CachePaths;
+use crate::daemon::state::State;
+use crate::daemon::{DaemonError, is_daemon_running};
+use crate::types::RepoKey;
+
+use super::{backfill, restore};
+
+/// One disagreement between the database and the cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// A row records a sync, but the repo's mirror is gone.
+    MissingMirror(RepoKey),
+    /// A mirror exists with no row for it.
+    UntrackedMirror(RepoKey),
+    /// The row's current generation has no generation row, though others
+    /// do. `latest` is the newest recorded one.
+    DanglingGeneration {
+        key: RepoKey,
+        current: u64,
+        latest: u64,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingMirror(key) => write!(f, "{key}: synced in the database, no mirror"),
+            Self::UntrackedMirror(key) => write!(f, "{key}: mirror on disk, no database row"),
+            Self::DanglingGeneration {
+                key,
+                current,
+                latest,
+            } => write!(
+                f,
+                "{key}: current generation {current} is not recorded (latest is {latest})"
+            ),
+        }
+    }
+}
+
+impl Mismatch {
+    /// Bring the database in line with the disk. A missing mirror keeps its
+    /// row, so `ghfs daemon --clone-missing` can still bring it back, but
+    /// loses its sync metadata and generations.
+    pub fn fix(&self, state: &State) -> Result<(), rusqlite::Error> {
+        match self {
+            Self::MissingMirror(key) => {
+                state.clear_sync(key)?;
+                state.delete_generations_except(key, &[])
+            }
+            Self::UntrackedMirror(key) => state.get_or_create_repo_id(key).map(drop),
+            Self::DanglingGeneration { key, latest, .. } => {
+                state.set_current_generation(key, *latest)
+            }
+        }
+    }
+}
+
+/// Every mismatch between `state` and the mirrors under `cache_paths`.
+///
+/// Rows that never synced aren't expected to have a mirror, and a current
+/// generation is only checked when the repo has generation rows at all:
+/// syncs record their generation on the repo row alone.
+pub fn check(state: &State, cache_paths: &CachePaths) -> Result<Vec<Mismatch>, rusqlite::Error> {
+    let rows = state.list_repos()?;
+    let mut mismatches = Vec::new();
+
+    for key in restore::missing_mirrors(state, cache_paths)? {
+        let synced = rows.iter().any(|row| {
+            row.owner == key.owner.as_str()
+                && row.repo == key.repo.as_str()
+                && (row.current_generation.is_some() || row.last_sync_at.is_some())
+        });
+        if synced {
+            mismatches.push(Mismatch::MissingMirror(key));
+        }
+    }
+
+    let mut untracked: Vec<RepoKey> = backfill::mirrored_repos(cache_paths)
+        .into_iter()
+        .filter(|key| {
+            !rows
+                .iter()
+                .any(|row| row.owner == key.owner.as_str() && row.repo == key.repo.as_str())
+        })
+        .collect();
+    untracked.sort_by_key(|key| key.to_string());
+    mismatches.extend(untracked.into_iter().map(Mismatch::UntrackedMirror));
+
+    for row in &rows {
+        let Some(current) = row.current_generation else {
+            continue;
+        };
+        let Ok(key) = format!("{}/{}", row.owner, row.repo).parse::<RepoKey>() else {
+            continue;
+        };
+        let generations = state.list_generations(&key)?;
+        let Some(latest) = generations.last().map(|g| g.generation) else {
+            continue;
+        };
+        if !generations.iter().any(|g| g.generation == current) {
+            mismatches.push(Mismatch::DanglingGeneration {
+                key,
+                current,
+                latest,
+            });
+        }
+    }
+
+    Ok(mismatches)
+}
+
+/// Check the cache under `cache_paths`, fixing what's found when `fix` is
+/// set. Fixing refuses while the daemon is running, since it may be midway
+/// through writing either side. Returns the mismatches found.
+pub fn fsck_cache(cache_paths: &CachePaths, fix: bool) -> Result<Vec<Mismatch>, DaemonError> {
+    if fix && is_daemon_running() {
+        return Err(DaemonError::AlreadyRunning);
+    }
+    // Don't create a database just to check it.
+    let db_path = cache_paths.root().join("ghfs.db");
+    if !db_path.exists() {
+        return Ok(backfill::mirrored_repos(cache_paths)
+            .into_iter()
+            .map(Mismatch::UntrackedMirror)
+            .collect());
+    }
+    let state = State::open(&db_path)?;
+    let mismatches = check(&state, cache_paths)?;
+    if fix {
+        for mismatch in &mismatches {
+            mismatch.fix(&state)?;
+        }
+    }
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn key(s: &str) -> RepoKey {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn finds_and_fixes_each_mismatch() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        paths.ensure_created().unwrap();
+        let state = State::open_in_memory().unwrap();
+        state.init().unwrap();
+
+        // Consistent: synced with its mirror, generations agreeing.
+        let healthy = key("octocat/hello-world");
+        state.upsert_generation(&healthy, 2, "abc123", 10).unwrap();
+        state.update_sync(&healthy, 2, "abc123").unwrap();
+        git2::Repository::init_bare(paths.mirror_dir(&healthy)).unwrap();
+        // Known but never synced; no mirror expected.
+        state.get_or_create_repo(&key("octocat/pending")).unwrap();
+        // Synced without generation rows, as every sync records it.
+        let plain = key("octocat/plain");
+        state.update_sync(&plain, 0, "abc123").unwrap();
+        git2::Repository::init_bare(paths.mirror_dir(&plain)).unwrap();
+
+        let wiped = key("octocat/spoon-knife");
+        state.update_sync(&wiped, 0, "abc123").unwrap();
+        state.upsert_generation(&wiped, 0, "abc123", 10).unwrap();
+        let stray = key("rust-lang/rust");
+        git2::Repository::init_bare(paths.mirror_dir(&stray)).unwrap();
+        let pruned = key("octocat/linguist");
+        git2::Repository::init_bare(paths.mirror_dir(&pruned)).unwrap();
+        for generation in [1, 2] {
+            state
+                .upsert_generation(&pruned, generation, "abc123", 10)
+                .unwrap();
+        }
+        state.update_sync(&pruned, 3, "def456").unwrap();
+
+        let mismatches = check(&state, &paths).unwrap();
+        assert_eq!(
+            mismatches,
+            [
+                Mismatch::MissingMirror(wiped.clone()),
+                Mismatch::UntrackedMirror(stray.clone()),
+                Mismatch::DanglingGeneration {
+                    key: pruned.clone(),
+                    current: 3,
+                    latest: 2
+                },
+            ]
+        );
+        assert_eq!(
+            mismatches[2].to_string(),
+            "octocat/linguist: current generation 3 is not recorded (latest is 2)"
+        );
+
+        for mismatch in &mismatches {
+            mismatch.fix(&state).unwrap();
+        }
+        assert_eq!(check(&state, &paths).unwrap(), []);
+        let row = state.get_or_create_repo(&wiped).unwrap();
+        assert_eq!((row.current_generation, row.last_sync_at), (None, None));
+        assert!(state.list_generations(&wiped).unwrap().is_empty());
+        assert_eq!(
+            state
+                .get_or_create_repo(&pruned)
+                .unwrap()
+                .current_generation,
+            Some(2)
+        );
+    }
+}
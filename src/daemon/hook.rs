@@ -0,0 +1,352 @@
+//! Hooks: user commands run when a repo's HEAD moves, right before the
+//! mount, and right after unmount.
+//!
+//! The hook is a command template such as
+//! `notify-indexer {owner} {repo} {commit} {path}`. It is split on whitespace
+//! into argv once, and placeholders are substituted inside each argument, so a
+//! substituted value can never become extra arguments or shell syntax. No
+//! shell is involved.
+//!
+//! Placeholders:
+//! - `{owner}`, `{repo}`: repository owner and name
+//! - `{commit}`: new HEAD commit OID
+//! - `{path}`: the repo's directory under the mount point
+//! - `{mirror}`: the blobless mirror directory
+//!
+//! Post-sync hooks run one at a time on a single [`HookRunner`] thread, fed
+//! by a bounded queue, and are killed after [`HOOK_TIMEOUT`].
+//!
+//! Mount hooks are parsed the same way, with `{mount}` (the mount point) and
+//! `{cache}` (the cache root) as their only placeholders.
+
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Sender, TrySendError, bounded};
+
+use crate::types::RepoKey;
+
+/// Environment variable holding the hook template when no flag is given.
+pub const POST_SYNC_HOOK_ENV: &str = "GHFS_POST_SYNC_HOOK";
+/// Environment variable holding the pre-mount hook when no flag is given.
+pub const PRE_MOUNT_HOOK_ENV: &str = "GHFS_PRE_MOUNT_HOOK";
+/// Environment variable holding the post-unmount hook when no flag is given.
+pub const POST_UNMOUNT_HOOK_ENV: &str = "GHFS_POST_UNMOUNT_HOOK";
+
+/// How long a post-sync hook may run before it is killed.
+pub const HOOK_TIMEOUT: Duration = Duration::from_secs(300);
+/// Post-sync hook runs that may wait for the hook thread; later ones are
+/// dropped until it catches up.
+const HOOK_QUEUE_LEN: usize = 64;
+/// How often a running hook is polled for exit.
+const HOOK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Values substituted into a hook's arguments.
+#[derive(Debug, Clone)]
+pub struct HookVars<'a> {
+    pub key: &'a RepoKey,
+    pub commit: &'a str,
+    pub mirror: &'a Path,
+}
+
+/// A parsed post-sync hook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostSyncHook {
+    argv: Vec<String>,
+    mount_point: PathBuf,
+}
+
+impl PostSyncHook {
+    /// Parse `template` into argv. Returns `None` for a blank template.
+    pub fn parse(template: &str, mount_point: PathBuf) -> Option<Self> {
+        let argv: Vec<String> = template.split_whitespace().map(str::to_string).collect();
+        if argv.is_empty() {
+            return None;
+        }
+        Some(Self { argv, mount_point })
+    }
+
+    /// Read the template from [`POST_SYNC_HOOK_ENV`], if set.
+    pub fn from_env(mount_point: PathBuf) -> Option<Self> {
+        let template = crate::config::var(POST_SYNC_HOOK_ENV)?;
+        Self::parse(&template, mount_point)
+    }
+
+    /// Substitute `vars` into each argument.
+    pub fn render(&self, vars: &HookVars<'_>) -> Vec<String> {
+        let path = self
+            .mount_point
+            .join(vars.key.owner.as_str())
+            .join(vars.key.repo.as_str());
+        let path = path.to_string_lossy();
+        let mirror = vars.mirror.to_string_lossy();
+        self.argv
+            .iter()
+            .map(|arg| {
+                arg.replace("{owner}", vars.key.owner.as_str())
+                    .replace("{repo}", vars.key.repo.as_str())
+                    .replace("{commit}", vars.commit)
+                    .replace("{path}", &path)
+                    .replace("{mirror}", &mirror)
+            })
+            .collect()
+    }
+
+    /// Run the hook with a minimal environment (only `PATH` and `HOME` are
+    /// inherited), killing it after `timeout`. Returns `None` if it was
+    /// killed.
+    pub fn run(
+        &self,
+        vars: &HookVars<'_>,
+        timeout: Duration,
+    ) -> std::io::Result<Option<ExitStatus>> {
+        let child = hardened_command(&self.render(vars))
+            .stderr(Stdio::null())
+            .spawn()?;
+        wait_or_kill(child, timeout)
+    }
+
+    /// Run the hook, logging (never returning) any failure.
+    pub fn run_logged(&self, vars: &HookVars<'_>, timeout: Duration) {
+        match self.run(vars, timeout) {
+            Ok(Some(status)) if status.success() => {
+                log::debug!("Post-sync hook succeeded for {}", vars.key);
+            }
+            Ok(Some(status)) => log::warn!(
+                "Post-sync hook for {} exited with {:?}",
+                vars.key,
+                status.code()
+            ),
+            Ok(None) => log::warn!(
+                "Post-sync hook for {} was killed after {}s",
+                vars.key,
+                timeout.as_secs()
+            ),
+            Err(e) => log::warn!("Post-sync hook for {} failed to start: {}", vars.key, e),
+        }
+    }
+}
+
+/// One queued post-sync hook run.
+struct HookRun {
+    key: RepoKey,
+    commit: String,
+    mirror: PathBuf,
+}
+
+/// Runs a [`PostSyncHook`] on its own thread, one run at a time, so a slow
+/// hook never holds up syncs and a burst of HEAD moves can't pile up
+/// processes. The thread exits once the runner is dropped and its queue is
+/// drained.
+pub struct HookRunner {
+    runs: Sender<HookRun>,
+}
+
+impl HookRunner {
+    /// Start the hook thread, killing each run after [`HOOK_TIMEOUT`].
+    pub fn spawn(hook: PostSyncHook) -> Self {
+        Self::with_timeout(hook, HOOK_TIMEOUT)
+    }
+
+    /// Start the hook thread, killing each run after `timeout`.
+    pub fn with_timeout(hook: PostSyncHook, timeout: Duration) -> Self {
+        let (runs, queued) = bounded::<HookRun>(HOOK_QUEUE_LEN);
+        thread::Builder::new()
+            .name("ghfs-hook".to_string())
+            .spawn(move || {
+                for run in queued {
+                    hook.run_logged(
+                        &HookVars {
+                            key: &run.key,
+                            commit: &run.commit,
+                            mirror: &run.mirror,
+                        },
+                        timeout,
+                    );
+                }
+            })
+            .expect("failed to spawn hook thread");
+        Self { runs }
+    }
+
+    /// Queue a run for `key` moving to `commit`. Dropped, with a warning, if
+    /// the queue is full.
+    pub fn submit(&self, key: &RepoKey, commit: &str, mirror: PathBuf) {
+        let run = HookRun {
+            key: key.clone(),
+            commit: commit.to_string(),
+            mirror,
+        };
+        if let Err(TrySendError::Full(run)) = self.runs.try_send(run) {
+            log::warn!(
+                "Post-sync hook queue is full; skipping the hook for {} at {}",
+                run.key,
+                run.commit
+            );
+        }
+    }
+}
+
+/// Wait for `child`, killing it once `timeout` passes. Returns `None` if it
+/// was killed.
+fn wait_or_kill(mut child: Child, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(HOOK_POLL_INTERVAL);
+    }
+}
+
+/// A parsed pre-mount or post-unmount hook, with its placeholders already
+/// substituted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountHook {
+    argv: Vec<String>,
+}
+
+impl MountHook {
+    /// Parse `template` into argv. Returns `None` for a blank template.
+    pub fn parse(template: &str, mount_point: &Path, cache_root: &Path) -> Option<Self> {
+        let mount = mount_point.to_string_lossy();
+        let cache = cache_root.to_string_lossy();
+        let argv: Vec<String> = template
+            .split_whitespace()
+            .map(|arg| arg.replace("{mount}", &mount).replace("{cache}", &cache))
+            .collect();
+        if argv.is_empty() {
+            return None;
+        }
+        Some(Self { argv })
+    }
+
+    /// Read the template from the environment variable `name`, if set.
+    pub fn from_env(name: &str, mount_point: &Path, cache_root: &Path) -> Option<Self> {
+        let template = crate::config::var(name)?;
+        Self::parse(&template, mount_point, cache_root)
+    }
+
+    /// The arguments the hook runs with.
+    pub fn argv(&self) -> &[String] {
+        &self.argv
+    }
+
+    /// Run the hook to completion with the same minimal environment as
+    /// [`PostSyncHook::run`]. Its stderr goes to the daemon's, so a failing
+    /// hook can say why.
+    pub fn run(&self) -> std::io::Result<ExitStatus> {
+        hardened_command(&self.argv).status()
+    }
+}
+
+/// `argv` as a command with no stdin or stdout, and an environment cleared
+/// down to `PATH` and `HOME`.
+fn hardened_command(argv: &[String]) -> Command {
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..])
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::null());
+    for name in ["PATH", "HOME"] {
+        if let Some(value) = std::env::var_os(name) {
+            cmd.env(name, value);
+        }
+    }
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars<'a>(key: &'a RepoKey, mirror: &'a Path) -> HookVars<'a> {
+        HookVars {
+            key,
+            commit: "abc123",
+            mirror,
+        }
+    }
+
+    #[test]
+    fn parse_rejects_blank_template() {
+        assert!(PostSyncHook::parse("   ", PathBuf::from("/mnt")).is_none());
+    }
+
+    #[test]
+    fn render_substitutes_within_each_argument() {
+        let hook = PostSyncHook::parse(
+            "reindex --repo={owner}/{repo} {commit} {path} {mirror}",
+            PathBuf::from("/mnt/github"),
+        )
+        .unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mirror = PathBuf::from("/cache/mirrors/octocat/hello-world.git");
+
+        assert_eq!(
+            hook.render(&vars(&key, &mirror)),
+            [
+                "reindex",
+                "--repo=octocat/hello-world",
+                "abc123",
+                "/mnt/github/octocat/hello-world",
+                "/cache/mirrors/octocat/hello-world.git",
+            ]
+        );
+    }
+
+    #[test]
+    fn values_never_split_into_extra_arguments() {
+        let hook = PostSyncHook::parse("echo {mirror}", PathBuf::from("/mnt")).unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mirror = PathBuf::from("/tmp/a b; rm -rf x");
+
+        let argv = hook.render(&vars(&key, &mirror));
+        assert_eq!(argv, ["echo", "/tmp/a b; rm -rf x"]);
+    }
+
+    #[test]
+    fn failing_hook_reports_status_without_panicking() {
+        let hook = PostSyncHook::parse("false", PathBuf::from("/mnt")).unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mirror = PathBuf::from("/nonexistent");
+
+        let status = hook.run(&vars(&key, &mirror), HOOK_TIMEOUT).unwrap();
+        assert!(!status.unwrap().success());
+        hook.run_logged(&vars(&key, &mirror), HOOK_TIMEOUT);
+
+        let missing = PostSyncHook::parse("/nonexistent/ghfs-hook", PathBuf::from("/mnt")).unwrap();
+        assert!(missing.run(&vars(&key, &mirror), HOOK_TIMEOUT).is_err());
+        missing.run_logged(&vars(&key, &mirror), HOOK_TIMEOUT);
+    }
+
+    #[test]
+    fn slow_hooks_are_killed_at_the_timeout() {
+        let hook = PostSyncHook::parse("sleep 30", PathBuf::from("/mnt")).unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mirror = PathBuf::from("/nonexistent");
+
+        let started = Instant::now();
+        let status = hook
+            .run(&vars(&key, &mirror), Duration::from_millis(100))
+            .unwrap();
+        assert!(status.is_none());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn mount_hooks_substitute_mount_and_cache() {
+        let mount = PathBuf::from("/mnt/git hub");
+        let cache = PathBuf::from("/cache");
+        let hook = MountHook::parse("unlock {mount} --state={cache}/s", &mount, &cache).unwrap();
+        assert_eq!(hook.argv(), ["unlock", "/mnt/git hub", "--state=/cache/s"]);
+        assert!(MountHook::parse(" ", &mount, &cache).is_none());
+    }
+}
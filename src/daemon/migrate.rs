@@ -0,0 +1,254 @@
+//! Migrate cache contents to the canonical repo key layout.
+//!
+//! GitHub treats owner and repo names case-insensitively and accepts a
+//! trailing `.git`, so `Octocat/Hello-World.git` and `octocat/hello-world`
+//! are one repository. Older caches could hold both spellings side by side;
+//! this renames mirrors, state rows, and lock files to
+//! [`RepoKey::canonical`] form. Running it again is a no-op.
+
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::cache::CachePaths;
+use crate::daemon::state::State;
+use crate::types::{Owner, Repo, RepoKey};
+
+/// Counts of what a migration pass changed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationStats {
+    pub mirrors_renamed: usize,
+    pub duplicates_removed: usize,
+    pub rows_renamed: usize,
+    pub locks_removed: usize,
+}
+
+/// Move every mirror, state row, and lock file to its canonical location.
+pub fn migrate_cache_layout(state: &State, cache_paths: &CachePaths) -> MigrationStats {
+    let mut stats = MigrationStats::default();
+    migrate_mirrors(cache_paths, &mut stats);
+    migrate_rows(state, &mut stats);
+    migrate_locks(cache_paths, &mut stats);
+    if stats != MigrationStats::default() {
+        log::info!("Migrated cache to canonical repo names: {stats:?}");
+    }
+    stats
+}
+
+fn migrate_mirrors(cache_paths: &CachePaths, stats: &mut MigrationStats) {
+    for owner_dir in cache_paths.owner_dirs() {
+        let Some(owner_name) = owner_dir.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Ok(repos) = std::fs::read_dir(&owner_dir) else {
+            continue;
+        };
+        for repo_entry in repos.flatten() {
+            if !repo_entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                continue;
+            }
+            let Ok(name) = repo_entry.file_name().into_string() else {
+                continue;
+            };
+            let Some(stripped) = name.strip_suffix(".git") else {
+                continue;
+            };
+            let Ok(key) = format!("{owner_name}/{stripped}").parse::<RepoKey>() else {
+                continue;
+            };
+            migrate_mirror(&repo_entry.path(), &cache_paths.mirror_dir(&key), stats);
+        }
+        if owner_name != owner_name.to_ascii_lowercase() {
+            // Only succeeds once every mirror has moved out.
+            let _ = std::fs::remove_dir(&owner_dir);
+        }
+    }
+}
+
+fn migrate_mirror(from: &Path, to: &Path, stats: &mut MigrationStats) {
+    if from == to {
+        return;
+    }
+    let result = if !to.exists() {
+        to.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::rename(from, to))
+    } else if same_file(from, to) {
+        // A case-insensitive filesystem resolves both names to one
+        // directory; go through a temporary name to change the case.
+        let tmp = from.with_extension("git.migrating");
+        std::fs::rename(from, &tmp).and_then(|()| std::fs::rename(&tmp, to))
+    } else {
+        log::warn!(
+            "Removing duplicate mirror {} (keeping {})",
+            from.display(),
+            to.display()
+        );
+        match std::fs::remove_dir_all(from) {
+            Ok(()) => {
+                stats.duplicates_removed += 1;
+                return;
+            }
+            Err(err) => Err(err),
+        }
+    };
+    match result {
+        Ok(()) => stats.mirrors_renamed += 1,
+        Err(err) => log::warn!("Failed to migrate mirror {}: {err}", from.display()),
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+fn migrate_rows(state: &State, stats: &mut MigrationStats) {
+    let repos = match state.list_repos() {
+        Ok(repos) => repos,
+        Err(err) => {
+            log::warn!("Skipping state migration: {err}");
+            return;
+        }
+    };
+    for row in repos {
+        // Only the row is renamed; nothing here touches paths or the
+        // network, so names aren't checked.
+        let key = RepoKey::new(
+            Owner::new_unchecked(row.owner),
+            Repo::new_unchecked(row.repo),
+        );
+        if key.is_canonical() {
+            continue;
+        }
+        match state.rename_repo(&key, &key.canonical()) {
+            Ok(()) => stats.rows_renamed += 1,
+            Err(err) => log::warn!("Failed to migrate state for {key}: {err}"),
+        }
+    }
+}
+
+fn migrate_locks(cache_paths: &CachePaths, stats: &mut MigrationStats) {
+    let Ok(entries) = std::fs::read_dir(cache_paths.locks_dir()) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let Some((owner, repo)) = name
+            .strip_suffix(".lock")
+            .and_then(|stem| stem.split_once("__"))
+        else {
+            continue;
+        };
+        let Ok(key) = format!("{owner}/{repo}").parse::<RepoKey>() else {
+            continue;
+        };
+        // Lock files hold no data; the canonical one is recreated on use.
+        if cache_paths.lock_path(&key) != entry.path() && std::fs::remove_file(entry.path()).is_ok()
+        {
+            stats.locks_removed += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup() -> (State, CachePaths, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path().to_path_buf());
+        paths.ensure_created().unwrap();
+        let state = State::open_in_memory().unwrap();
+        state.init().unwrap();
+        (state, paths, dir)
+    }
+
+    fn key(s: &str) -> RepoKey {
+        s.parse().unwrap()
+    }
+
+    fn row_names(state: &State) -> Vec<String> {
+        let mut names: Vec<String> = state
+            .list_repos()
+            .unwrap()
+            .into_iter()
+            .map(|r| format!("{}/{}", r.owner, r.repo))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn migrates_mixed_case_layout_without_data_loss() {
+        let (state, paths, _dir) = setup();
+        let legacy = paths.mirrors_dir().join("Octocat").join("Hello-World.git");
+        std::fs::create_dir_all(&legacy).unwrap();
+        std::fs::write(legacy.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(paths.locks_dir().join("Octocat__Hello-World.lock"), "").unwrap();
+        state
+            .update_sync(&key("Octocat/Hello-World"), 0, "abc123")
+            .unwrap();
+        state.get_or_create_repo(&key("octocat/spoon.git")).unwrap();
+
+        let stats = migrate_cache_layout(&state, &paths);
+
+        let canonical = paths.mirror_dir(&key("octocat/hello-world"));
+        assert_eq!(
+            std::fs::read_to_string(canonical.join("HEAD")).unwrap(),
+            "ref: refs/heads/main\n"
+        );
+        assert!(!paths.mirrors_dir().join("Octocat").exists());
+        assert!(!paths.locks_dir().join("Octocat__Hello-World.lock").exists());
+        assert_eq!(row_names(&state), ["octocat/hello-world", "octocat/spoon"]);
+        let rows = state.list_repos().unwrap();
+        let synced = rows.iter().find(|r| r.repo == "hello-world").unwrap();
+        assert_eq!(synced.head_commit.as_deref(), Some("abc123"));
+        assert_eq!(
+            stats,
+            MigrationStats {
+                mirrors_renamed: 1,
+                duplicates_removed: 0,
+                rows_renamed: 2,
+                locks_removed: 1,
+            }
+        );
+
+        // A second pass finds nothing left to do.
+        assert_eq!(
+            migrate_cache_layout(&state, &paths),
+            MigrationStats::default()
+        );
+    }
+
+    #[test]
+    fn duplicate_spellings_keep_the_canonical_copy() {
+        let (state, paths, _dir) = setup();
+        let canonical = paths.mirror_dir(&key("octocat/hello-world"));
+        let legacy = paths.mirrors_dir().join("OctoCat").join("hello-world.git");
+        std::fs::create_dir_all(&canonical).unwrap();
+        std::fs::create_dir_all(&legacy).unwrap();
+        std::fs::write(canonical.join("HEAD"), "canonical").unwrap();
+        state
+            .update_sync(&key("octocat/hello-world"), 0, "new")
+            .unwrap();
+        state
+            .update_sync(&key("OctoCat/hello-world"), 0, "old")
+            .unwrap();
+
+        let stats = migrate_cache_layout(&state, &paths);
+
+        assert_eq!(stats.duplicates_removed, 1);
+        assert!(!legacy.exists());
+        assert_eq!(
+            std::fs::read_to_string(canonical.join("HEAD")).unwrap(),
+            "canonical"
+        );
+        let rows = state.list_repos().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].head_commit.as_deref(), Some("new"));
+    }
+}
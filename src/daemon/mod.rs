@@ -1,16 +1,39 @@
 //! Daemon module for background sync operations.
 
 mod backfill;
+mod events;
+mod fsck;
 mod gc;
+mod hook;
+mod migrate;
+mod mounts;
+mod quota;
+mod repair;
+mod restore;
 mod scheduler;
+mod sentinel;
+mod shard;
 mod socket;
 pub mod state;
+mod submitted;
 mod worker;
 
-pub use scheduler::SchedulerHandle;
+pub use fsck::{Mismatch, fsck_cache};
+pub use hook::{
+    MountHook, POST_SYNC_HOOK_ENV, POST_UNMOUNT_HOOK_ENV, PRE_MOUNT_HOOK_ENV, PostSyncHook,
+};
+pub use mounts::STRICT_CACHE_FS_ENV;
+pub use quota::{OWNER_QUOTA_ENV, QUOTA_EXEMPT_ENV, Quotas, REPO_QUOTA_ENV, parse_size};
+pub use repair::{RepairStats, repair_db};
+pub use scheduler::{MAX_AGE_SECS, SchedulerHandle};
+pub(crate) use socket::format_timestamp;
 pub use socket::{SocketServerHandle, socket_path};
 pub use state::State;
-pub use worker::{WorkerHandle, WorkerRequest};
+pub use submitted::{MAX_TRACKED_SYNCS, SubmittedStatus};
+pub use worker::{
+    DEFAULT_SHUTDOWN_GRACE, DEFAULT_SYNC_CONCURRENCY, SHUTDOWN_GRACE_ENV, SYNC_CONCURRENCY_ENV,
+    SyncOutcome, WorkerHandle, WorkerRequest, shutdown_grace_from_env, sync_concurrency_from_env,
+};
 
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -19,8 +42,13 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use thiserror::Error;
 
 use crate::cache::CachePaths;
-use crate::fs::GhFs;
-use crate::store::Store;
+use crate::daemon::sentinel::{MountSentinel, SentinelAction};
+use crate::fs::{
+    AccessPolicy, DETERMINISTIC_INODES_ENV, GhFs, LIST_REMOTE_REPOS_ENV, OpCounters, TtlConfig,
+};
+use crate::github::ApiClient;
+use crate::store::{FOLLOW_RENAMES_ENV, REMOTE_PRECHECK_ENV, Store};
+use crate::types::RepoKey;
 
 /// Default mount point on Linux.
 #[cfg(target_os = "linux")]
@@ -34,9 +62,10 @@ pub const DEFAULT_MOUNT_POINT: &str = "/tmp/ghfs";
 #[cfg(not(any(target_os = "linux", target_os = "macos")))]
 pub const DEFAULT_MOUNT_POINT: &str = "/tmp/ghfs";
 
-/// Resolve the mount point, allowing override via GHFS_MOUNT_POINT.
+/// Resolve the mount point, allowing override via GHFS_MOUNT_POINT or the
+/// `mount_point` config setting.
 pub fn mount_point() -> PathBuf {
-    if let Some(custom) = std::env::var_os("GHFS_MOUNT_POINT") {
+    if let Some(custom) = crate::config::var("GHFS_MOUNT_POINT") {
         return PathBuf::from(custom);
     }
 
@@ -55,57 +84,88 @@ pub enum DaemonError {
     #[error("Mount failed: {0}")]
     Mount(std::io::Error),
 
+    #[error(
+        "{} is already mounted ({fstype} from {device}); unmount it or choose another mount point",
+        path.display()
+    )]
+    MountPointBusy {
+        path: PathBuf,
+        device: String,
+        fstype: String,
+    },
+
     #[error("Daemon is already running")]
     AlreadyRunning,
+
+    #[error("Pre-mount hook {command} {outcome}; not mounting")]
+    PreMountHook { command: String, outcome: String },
+
+    #[error(
+        "Cache directory {} is on a network filesystem ({fstype}), where file locks and \
+         symlink swaps aren't reliable; move it to local storage or unset {}",
+        path.display(),
+        STRICT_CACHE_FS_ENV
+    )]
+    NetworkCache { path: PathBuf, fstype: String },
+
+    #[error("Cache directory {} is not writable: {source}", path.display())]
+    CacheNotWritable {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Per-invocation overrides for the daemon's default locations.
+#[derive(Debug, Clone, Default)]
+pub struct DaemonOptions {
+    /// Mount point to use instead of [`mount_point()`].
+    pub mount_point: Option<PathBuf>,
+    /// Cache root to use instead of the default `~/.cache/ghfs`.
+    pub cache_dir: Option<PathBuf>,
+    /// Post-sync hook template to use instead of `GHFS_POST_SYNC_HOOK`.
+    pub post_sync_hook: Option<String>,
+    /// Pre-mount hook template to use instead of `GHFS_PRE_MOUNT_HOOK`.
+    pub pre_mount_hook: Option<String>,
+    /// Post-unmount hook template to use instead of
+    /// `GHFS_POST_UNMOUNT_HOOK`.
+    pub post_unmount_hook: Option<String>,
+    /// Enable FUSE `allow_other` (also enabled by `GHFS_ALLOW_OTHER`).
+    pub allow_other: bool,
+    /// Repos other users may read; overrides `GHFS_SHARED_REPOS` when set.
+    pub shared_repos: Vec<RepoKey>,
+    /// Skip refresh fetches when the remote HEAD hasn't moved (also
+    /// enabled by `GHFS_REMOTE_PRECHECK`).
+    pub remote_precheck: bool,
+    /// Cache renamed repos under their new name (also enabled by
+    /// `GHFS_FOLLOW_RENAMES`).
+    pub follow_renames: bool,
+    /// On startup, re-clone repos known to the state database whose mirror
+    /// is missing.
+    pub clone_missing: bool,
+    /// List every GitHub repo of an owner in its directory, not just cached
+    /// ones (also enabled by `GHFS_LIST_REMOTE_REPOS`).
+    pub list_remote_repos: bool,
+    /// Serve only this repo, at the mount root.
+    pub single_repo: Option<RepoKey>,
 }
 
 /// The GHFS daemon.
 pub struct Daemon {
     cache_paths: CachePaths,
     mount_point: PathBuf,
+    post_sync_hook: Option<PostSyncHook>,
+    pre_mount_hook: Option<MountHook>,
+    post_unmount_hook: Option<MountHook>,
+    access_policy: AccessPolicy,
+    remote_precheck: bool,
+    follow_renames: bool,
+    clone_missing: bool,
+    list_remote_repos: bool,
+    single_repo: Option<RepoKey>,
     state: Arc<State>,
     shutdown: Arc<AtomicBool>,
 }
 
-/// Spawn a detached thread to unmount the active filesystem backend.
-pub fn spawn_unmount(mount_point: String) {
-    std::thread::spawn(move || {
-        // Small delay to allow any response to be sent first.
-        std::thread::sleep(std::time::Duration::from_millis(100));
-
-        #[cfg(target_os = "linux")]
-        let status = std::process::Command::new("fusermount")
-            .args(["-u", &mount_point])
-            .status();
-
-        #[cfg(target_os = "macos")]
-        let status = {
-            let first = std::process::Command::new("/sbin/umount")
-                .arg(&mount_point)
-                .status();
-
-            match first {
-                Ok(exit) if exit.success() => Ok(exit),
-                _ => std::process::Command::new("/sbin/umount")
-                    .args(["-f", &mount_point])
-                    .status(),
-            }
-        };
-
-        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
-        let status: Result<std::process::ExitStatus, std::io::Error> = Err(std::io::Error::new(
-            std::io::ErrorKind::Unsupported,
-            "Unsupported platform",
-        ));
-
-        match status {
-            Ok(s) if s.success() => {}
-            Ok(s) => log::warn!("Unmount command failed with exit code: {:?}", s.code()),
-            Err(e) => log::warn!("Failed to run unmount command: {}", e),
-        }
-    });
-}
-
 #[cfg(target_os = "linux")]
 fn try_unmount_linux(mount_point: &str) -> bool {
     if std::process::Command::new("fusermount3")
@@ -133,82 +193,311 @@ fn try_unmount_linux(mount_point: &str) -> bool {
         .unwrap_or(false)
 }
 
-fn ensure_mount_point_ready(mount_point: &std::path::Path) -> std::io::Result<()> {
+/// Unmount whatever a crashed daemon left at `mount_point`. Returns whether
+/// an unmount succeeded (failure usually just means nothing was mounted).
+fn unmount_crashed(mount_point: &std::path::Path) -> bool {
     #[cfg(target_os = "linux")]
     {
-        if let Err(err) = std::fs::read_dir(mount_point) {
-            if err.raw_os_error() == Some(libc::ENOTCONN) {
+        try_unmount_linux(&mount_point.to_string_lossy())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("/sbin/umount")
+            .arg("-f")
+            .arg(mount_point)
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = mount_point;
+        false
+    }
+}
+
+/// Act on a mount sentinel left by a previous daemon: refuse to start if
+/// that daemon is alive, otherwise unmount what it left behind.
+fn recover_from_crashed_mount(sentinel_path: &std::path::Path) -> Result<(), DaemonError> {
+    let sentinel = MountSentinel::read(sentinel_path);
+    match MountSentinel::action(sentinel.as_ref(), sentinel::daemon_alive) {
+        SentinelAction::Proceed => Ok(()),
+        SentinelAction::AlreadyRunning(pid) => {
+            log::error!("ghfs daemon (PID {pid}) still has its mount up");
+            Err(DaemonError::AlreadyRunning)
+        }
+        SentinelAction::Cleanup(mount_point) => {
+            log::warn!(
+                "Previous daemon exited without unmounting {}; cleaning up",
+                mount_point.display()
+            );
+            if unmount_crashed(&mount_point) {
+                log::info!("Unmounted {}", mount_point.display());
+            }
+            remove_mount_sentinel(sentinel_path);
+            Ok(())
+        }
+    }
+}
+
+fn ensure_mount_point_ready(mount_point: &std::path::Path) -> Result<(), DaemonError> {
+    #[cfg(target_os = "linux")]
+    {
+        if let Err(err) = std::fs::read_dir(mount_point)
+            && err.raw_os_error() == Some(libc::ENOTCONN)
+        {
+            log::warn!(
+                "Mount point {} appears disconnected; attempting cleanup",
+                mount_point.display()
+            );
+
+            let mount_point_str = mount_point.to_string_lossy();
+            let cleaned = try_unmount_linux(&mount_point_str);
+            if !cleaned {
                 log::warn!(
-                    "Mount point {} appears disconnected; attempting cleanup",
+                    "Failed to unmount disconnected mount at {}",
                     mount_point.display()
                 );
-
-                let mount_point_str = mount_point.to_string_lossy();
-                let cleaned = try_unmount_linux(&mount_point_str);
-                if !cleaned {
-                    log::warn!(
-                        "Failed to unmount disconnected mount at {}",
-                        mount_point.display()
-                    );
-                }
             }
         }
     }
 
     match std::fs::create_dir_all(mount_point) {
-        Ok(()) => Ok(()),
-        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
-        Err(err) => Err(err),
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(err.into()),
     }
+
+    ensure_mount_point_free(mount_point)
 }
 
-impl Daemon {
-    /// Create a new daemon instance.
-    pub fn new() -> Result<Self, DaemonError> {
-        let cache_paths = CachePaths::default();
-        let mount_point = mount_point();
+/// Warn, or with `strict` refuse to start, when the cache directory is on a
+/// network filesystem.
+fn check_cache_filesystem(cache_root: &std::path::Path, strict: bool) -> Result<(), DaemonError> {
+    let entries = match mounts::current_mounts() {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::debug!("Skipping cache filesystem check: {err}");
+            return Ok(());
+        }
+    };
+    let root = std::fs::canonicalize(cache_root).unwrap_or_else(|_| cache_root.into());
+    let Some(entry) = mounts::mount_containing(&entries, &root) else {
+        return Ok(());
+    };
+    if !entry.is_network() {
+        return Ok(());
+    }
+    if strict {
+        return Err(DaemonError::NetworkCache {
+            path: cache_root.to_path_buf(),
+            fstype: entry.fstype.clone(),
+        });
+    }
+    log::warn!(
+        "Cache directory {} is on a network filesystem ({} from {}); repo locks and symlink \
+         swaps may not hold and mirrors can be corrupted. Set {STRICT_CACHE_FS_ENV}=1 to \
+         refuse to start in this case.",
+        cache_root.display(),
+        entry.fstype,
+        entry.device
+    );
+    Ok(())
+}
+
+/// Refuse to mount over another filesystem. A ghfs mount left behind by a
+/// daemon that died is unmounted first on Linux; the running-daemon check
+/// has already ruled out a live one.
+fn ensure_mount_point_free(mount_point: &std::path::Path) -> Result<(), DaemonError> {
+    let entries = match mounts::current_mounts() {
+        Ok(entries) => entries,
+        Err(err) => {
+            log::debug!("Skipping mount point occupancy check: {err}");
+            return Ok(());
+        }
+    };
+    // The mount table lists resolved paths (e.g. /private/tmp on macOS).
+    let target = std::fs::canonicalize(mount_point).unwrap_or_else(|_| mount_point.into());
+    let Some(entry) = mounts::mount_at(&entries, &target) else {
+        return Ok(());
+    };
+
+    #[cfg(target_os = "linux")]
+    if entry.is_ghfs() {
+        log::warn!(
+            "Found stale ghfs mount at {}; unmounting",
+            mount_point.display()
+        );
+        if try_unmount_linux(&target.to_string_lossy()) {
+            return Ok(());
+        }
+    }
+
+    Err(DaemonError::MountPointBusy {
+        path: mount_point.to_path_buf(),
+        device: entry.device.clone(),
+        fstype: entry.fstype.clone(),
+    })
+}
 
-        // Ensure cache directories exist
-        std::fs::create_dir_all(cache_paths.mirrors_dir())?;
-        std::fs::create_dir_all(cache_paths.blobs_dir())?;
-        std::fs::create_dir_all(cache_paths.locks_dir())?;
+impl Daemon {
+    /// Create a new daemon instance, applying any overrides in `options`.
+    pub fn new(options: DaemonOptions) -> Result<Self, DaemonError> {
+        let cache_paths = shard::shard_cache_layout(
+            options.cache_dir.map(CachePaths::new).unwrap_or_default(),
+            env_flag(crate::cache::SHARD_MIRRORS_ENV),
+        );
+        let mount_point = options.mount_point.unwrap_or_else(mount_point);
+        let post_sync_hook = match options.post_sync_hook {
+            Some(template) => PostSyncHook::parse(&template, mount_point.clone()),
+            None => PostSyncHook::from_env(mount_point.clone()),
+        };
+        let mount_hook = |template: Option<String>, env: &str| match template {
+            Some(template) => MountHook::parse(&template, &mount_point, cache_paths.root()),
+            None => MountHook::from_env(env, &mount_point, cache_paths.root()),
+        };
+        let pre_mount_hook = mount_hook(options.pre_mount_hook, PRE_MOUNT_HOOK_ENV);
+        let post_unmount_hook = mount_hook(options.post_unmount_hook, POST_UNMOUNT_HOOK_ENV);
+        let mut access_policy = AccessPolicy::from_env();
+        access_policy.allow_other |= options.allow_other;
+        if !options.shared_repos.is_empty() {
+            access_policy.shared_repos = options.shared_repos;
+        }
+        let remote_precheck = options.remote_precheck || env_flag(REMOTE_PRECHECK_ENV);
+        let follow_renames = options.follow_renames || env_flag(FOLLOW_RENAMES_ENV);
+        let list_remote_repos = options.list_remote_repos || env_flag(LIST_REMOTE_REPOS_ENV);
+
+        // Ensure cache directories exist and can be written to
+        cache_paths
+            .ensure_created()
+            .and_then(|()| cache_paths.validate())
+            .map_err(|source| DaemonError::CacheNotWritable {
+                path: cache_paths.root().to_path_buf(),
+                source,
+            })?;
+        check_cache_filesystem(cache_paths.root(), env_flag(STRICT_CACHE_FS_ENV))?;
 
         // Open state database
-        let db_path = cache_paths.root().join("ghfs.db");
-        let state = State::open(&db_path)?;
-        state.init()?;
+        let state = open_state(&cache_paths.root().join("ghfs.db"))?;
+        migrate::migrate_cache_layout(&state, &cache_paths);
         backfill::backfill_cache_state(&state, &cache_paths);
 
         Ok(Self {
             cache_paths,
             mount_point,
+            post_sync_hook,
+            pre_mount_hook,
+            post_unmount_hook,
+            access_policy,
+            remote_precheck,
+            follow_renames,
+            clone_missing: options.clone_missing,
+            list_remote_repos,
+            single_repo: options.single_repo,
             state: Arc::new(state),
             shutdown: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    /// Run the daemon (blocks until shutdown).
-    pub fn run(self) -> Result<(), DaemonError> {
+    /// Run the daemon (blocks until shutdown). The pre-mount hook runs
+    /// first, and the post-unmount hook once the mount is gone and the
+    /// worker drained.
+    pub fn run(mut self) -> Result<(), DaemonError> {
+        let pre = self.pre_mount_hook.take();
+        let post = self.post_unmount_hook.take();
+        with_mount_hooks(pre.as_ref(), post.as_ref(), || self.serve())
+    }
+
+    fn serve(self) -> Result<(), DaemonError> {
         log::info!("Starting ghfs daemon");
         log::info!("Mount point: {}", self.mount_point.display());
         log::info!("Cache: {}", self.cache_paths.root().display());
         log::info!("Socket: {}", socket_path().display());
 
-        // Ensure mount point exists and recover from disconnected stale mounts.
+        // Clean up after a daemon that crashed with its mount up, then ensure
+        // the mount point exists and recover from disconnected stale mounts.
+        let sentinel_path = mount_sentinel_path();
+        recover_from_crashed_mount(&sentinel_path)?;
         ensure_mount_point_ready(&self.mount_point)?;
 
         // Create the object-backed store shared by worker and fs backend.
-        let store = Store::new(self.cache_paths.clone());
+        let store = Store::new(self.cache_paths.clone())
+            .with_remote_precheck(self.remote_precheck)
+            .with_follow_renames(self.follow_renames);
+        if self.remote_precheck {
+            log::info!("Remote HEAD precheck enabled");
+        }
+        if self.follow_renames {
+            log::info!("Following renamed repos");
+        }
 
         // Spawn worker thread
-        let worker = Arc::new(WorkerHandle::spawn(store.clone()));
+        if self.post_sync_hook.is_some() {
+            log::info!("Post-sync hook enabled");
+        }
+        let quotas = Quotas::from_env();
+        if quotas.is_enabled() {
+            log::info!(
+                "Quotas enabled: repo {:?} bytes, owner {:?} bytes",
+                quotas.repo_bytes,
+                quotas.owner_bytes
+            );
+        }
+        let concurrency = sync_concurrency_from_env();
+        log::info!("Running up to {concurrency} clones/fetches at once");
+        let worker = Arc::new(WorkerHandle::spawn_with_concurrency(
+            store.clone(),
+            self.post_sync_hook,
+            quotas,
+            concurrency,
+        ));
         log::info!("Worker thread started");
 
+        if self.clone_missing {
+            match restore::queue_missing(&self.state, &self.cache_paths, &worker) {
+                Ok(queued) => log::info!("Queued {} missing mirrors for cloning", queued.len()),
+                Err(e) => log::warn!("Failed to list repos to re-clone: {e}"),
+            }
+        }
+
+        // Create the filesystem backend first so the socket server can
+        // report its table sizes.
+        let op_counters = Arc::new(OpCounters::default());
+        let fs = GhFs::new(store, Arc::clone(&worker))
+            .with_access_policy(self.access_policy)
+            .with_ttls(TtlConfig::from_env())
+            .with_io_sizes(crate::fs::IoSizes::from_env())
+            .with_op_counters(Arc::clone(&op_counters))
+            .with_access_times(crate::fs::AccessTimes::new(Arc::clone(&self.state)))
+            .with_remote_listing(self.list_remote_repos)
+            .with_deterministic_inodes(env_flag(DETERMINISTIC_INODES_ENV))
+            .with_inode_store(crate::fs::InodeStore::from_env(self.cache_paths.root()))
+            .with_meta_dir(crate::fs::meta_dir_from_env())
+            .with_meta_dir_listed(!env_flag(crate::fs::HIDE_META_DIR_ENV))
+            .with_hidden_paths(crate::fs::HiddenPaths::from_env())
+            .with_layout(match &self.single_repo {
+                Some(key) => crate::fs::Layout::Single(key.clone()),
+                None => crate::fs::Layout::from_env(),
+            });
+        if self.list_remote_repos {
+            if !ApiClient::shared().has_token() {
+                log::warn!(
+                    "Remote repo listing enabled without a GitHub token; \
+                     unauthenticated API requests are heavily rate limited"
+                );
+            }
+            log::info!("Listing owners' repos from GitHub");
+        }
+
         // Spawn socket server
         let _socket_server = SocketServerHandle::spawn(
             Arc::clone(&self.state),
             Arc::clone(&worker),
             self.cache_paths.clone(),
+            op_counters,
+            fs.stats_probe(),
             self.mount_point.to_string_lossy().to_string(),
             Arc::clone(&self.shutdown),
         )?;
@@ -222,21 +511,15 @@ impl Daemon {
         );
         log::info!("Scheduler started");
 
-        // Setup signal handler for graceful shutdown
-        #[cfg(target_os = "linux")]
-        let mount_point = self.mount_point.to_string_lossy().to_string();
+        // Setup signal handler for graceful shutdown. The mounted backend
+        // watches the flag and unmounts itself.
         let shutdown = Arc::clone(&self.shutdown);
         ctrlc::set_handler(move || {
             log::info!("Received shutdown signal");
             shutdown.store(true, Ordering::SeqCst);
-            #[cfg(target_os = "linux")]
-            spawn_unmount(mount_point.clone());
         })
         .expect("failed to set signal handler");
 
-        // Create and mount filesystem backend.
-        let fs = GhFs::new(store, Arc::clone(&worker));
-
         #[cfg(target_os = "linux")]
         log::info!("Mounting Linux FUSE filesystem");
 
@@ -246,13 +529,26 @@ impl Daemon {
         #[cfg(not(any(target_os = "linux", target_os = "macos")))]
         log::info!("Mounting filesystem backend");
 
+        if let Err(e) = MountSentinel::current(&self.mount_point).write(&sentinel_path) {
+            log::warn!(
+                "Failed to write mount sentinel {}: {e}",
+                sentinel_path.display()
+            );
+        }
+
         // This blocks until unmount
-        if let Err(e) = fs.mount(&self.mount_point, Arc::clone(&self.shutdown)) {
+        let mounted = fs.mount(&self.mount_point, Arc::clone(&self.shutdown));
+        remove_mount_sentinel(&sentinel_path);
+        if let Err(e) = mounted {
             log::error!("Mount failed: {}", e);
             return Err(DaemonError::Mount(e));
         }
 
         log::info!("Filesystem unmounted, shutting down");
+        let grace = shutdown_grace_from_env();
+        if worker.drain(grace) {
+            log::info!("Worker drained");
+        }
 
         // Let the Stop RPC response flush before socket teardown.
         std::thread::sleep(std::time::Duration::from_millis(100));
@@ -264,6 +560,68 @@ impl Daemon {
     }
 }
 
+/// Run `pre`, then `body` if `pre` succeeded, then `post` once `body`
+/// returns, whether or not it succeeded. A failing `post` is only logged.
+fn with_mount_hooks(
+    pre: Option<&MountHook>,
+    post: Option<&MountHook>,
+    body: impl FnOnce() -> Result<(), DaemonError>,
+) -> Result<(), DaemonError> {
+    if let Some(hook) = pre {
+        log::info!("Running pre-mount hook: {}", hook.argv().join(" "));
+        let outcome = match hook.run() {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(match status.code() {
+                Some(code) => format!("exited with {code}"),
+                None => "was killed by a signal".to_string(),
+            }),
+            Err(e) => Some(format!("failed to start: {e}")),
+        };
+        if let Some(outcome) = outcome {
+            return Err(DaemonError::PreMountHook {
+                command: hook.argv()[0].clone(),
+                outcome,
+            });
+        }
+    }
+    let result = body();
+    if let Some(hook) = post {
+        log::info!("Running post-unmount hook: {}", hook.argv().join(" "));
+        match hook.run() {
+            Ok(status) if status.success() => {}
+            Ok(status) => log::warn!("Post-unmount hook exited with {:?}", status.code()),
+            Err(e) => log::warn!("Post-unmount hook failed to start: {e}"),
+        }
+    }
+    result
+}
+
+/// Whether the boolean environment variable `name` (or the config setting
+/// it mirrors) is set to `1`, `true`, or `yes`.
+fn env_flag(name: &str) -> bool {
+    crate::config::var(name).is_some_and(|v| matches!(v.trim(), "1" | "true" | "yes"))
+}
+
+/// Open and initialize the state database at `db_path`. If it can't be used
+/// (locked, corrupt, or on a filesystem without SQLite locking), fall back
+/// to an in-memory database: sync history is lost on restart, but serving
+/// files never depends on it.
+fn open_state(db_path: &std::path::Path) -> Result<State, DaemonError> {
+    let opened = State::open(db_path).and_then(|state| state.init().map(|()| state));
+    match opened {
+        Ok(state) => Ok(state),
+        Err(e) => {
+            log::warn!(
+                "State database {} is unavailable ({e}); continuing with in-memory state",
+                db_path.display()
+            );
+            let state = State::open_in_memory()?;
+            state.init()?;
+            Ok(state)
+        }
+    }
+}
+
 /// Check if a daemon is already running.
 pub fn is_daemon_running() -> bool {
     let path = socket_path();
@@ -291,14 +649,24 @@ fn remove_pid_file() {
     let _ = std::fs::remove_file(pid_file_path());
 }
 
+/// Get the mount sentinel path.
+fn mount_sentinel_path() -> PathBuf {
+    socket_path().with_extension("mount")
+}
+
+/// Remove the mount sentinel.
+fn remove_mount_sentinel(path: &std::path::Path) {
+    let _ = std::fs::remove_file(path);
+}
+
 /// Start the daemon (blocks until shutdown).
-pub fn start() -> Result<(), DaemonError> {
+pub fn start(options: DaemonOptions) -> Result<(), DaemonError> {
     if is_daemon_running() {
         return Err(DaemonError::AlreadyRunning);
     }
 
     // Create daemon first, then write PID file on success
-    let daemon = Daemon::new()?;
+    let daemon = Daemon::new(options)?;
 
     write_pid_file()?;
 
@@ -308,3 +676,136 @@ pub fn start() -> Result<(), DaemonError> {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn new_rejects_unwritable_cache_dir() {
+        let dir = tempdir().unwrap();
+        // A regular file where the cache root should be can't be created
+        // as a directory, even by root.
+        let cache_dir = dir.path().join("cache");
+        std::fs::write(&cache_dir, b"").unwrap();
+
+        let err = Daemon::new(DaemonOptions {
+            cache_dir: Some(cache_dir.clone()),
+            mount_point: Some(dir.path().join("mnt")),
+            ..Default::default()
+        })
+        .err()
+        .unwrap();
+        assert!(matches!(err, DaemonError::CacheNotWritable { path, .. } if path == cache_dir));
+    }
+
+    #[test]
+    fn new_falls_back_to_in_memory_state_when_db_is_unusable() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        // A directory where the database file should be can never open.
+        std::fs::create_dir_all(cache_dir.join("ghfs.db")).unwrap();
+
+        let daemon = Daemon::new(DaemonOptions {
+            cache_dir: Some(cache_dir),
+            mount_point: Some(dir.path().join("mnt")),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        daemon.state.update_sync(&key, 0, "abc123").unwrap();
+        assert_eq!(daemon.state.list_repos().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn new_applies_mount_point_and_cache_dir_overrides() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let mount = dir.path().join("mnt");
+
+        let daemon = Daemon::new(DaemonOptions {
+            mount_point: Some(mount.clone()),
+            cache_dir: Some(cache_dir.clone()),
+            post_sync_hook: Some("reindex {owner}/{repo}".to_string()),
+            pre_mount_hook: Some("unlock {mount}".to_string()),
+            post_unmount_hook: None,
+            allow_other: true,
+            shared_repos: vec!["octocat/hello-world".parse().unwrap()],
+            remote_precheck: true,
+            follow_renames: true,
+            clone_missing: true,
+            list_remote_repos: true,
+            single_repo: Some("octocat/hello-world".parse().unwrap()),
+        })
+        .unwrap();
+
+        assert_eq!(daemon.mount_point, mount);
+        assert_eq!(daemon.cache_paths.root(), cache_dir);
+        assert!(cache_dir.join("ghfs.db").exists());
+        assert!(daemon.cache_paths.mirrors_dir().is_dir());
+        assert!(daemon.access_policy.allow_other);
+        assert!(daemon.remote_precheck);
+        assert!(daemon.follow_renames);
+        assert!(daemon.clone_missing);
+        assert!(daemon.list_remote_repos);
+        assert!(daemon.single_repo.is_some());
+        assert_eq!(
+            daemon.access_policy.shared_repos,
+            vec!["octocat/hello-world".parse::<RepoKey>().unwrap()]
+        );
+        assert_eq!(
+            daemon.pre_mount_hook,
+            MountHook::parse("unlock {mount}", &mount, &cache_dir)
+        );
+        assert_eq!(
+            daemon.post_sync_hook,
+            PostSyncHook::parse("reindex {owner}/{repo}", mount)
+        );
+    }
+
+    #[test]
+    fn failing_pre_mount_hook_prevents_mounting() {
+        let dir = tempdir().unwrap();
+        let (mount, cache) = (dir.path().join("mnt"), dir.path().join("cache"));
+        let marker = dir.path().join("unmounted");
+        let post = MountHook::parse(&format!("touch {}", marker.display()), &mount, &cache);
+
+        for pre in ["false", "/nonexistent/ghfs-hook"] {
+            let pre = MountHook::parse(pre, &mount, &cache);
+            let mut mounted = false;
+            let result = with_mount_hooks(pre.as_ref(), post.as_ref(), || {
+                mounted = true;
+                Ok(())
+            });
+            assert!(matches!(result, Err(DaemonError::PreMountHook { .. })));
+            assert!(!mounted);
+            assert!(!marker.exists());
+        }
+    }
+
+    #[test]
+    fn post_unmount_hook_runs_after_the_mount_loop() {
+        let dir = tempdir().unwrap();
+        let (mount, cache) = (dir.path().join("mnt"), dir.path().join("cache"));
+        let marker = dir.path().join("unmounted");
+        let pre = MountHook::parse("true", &mount, &cache);
+        let post = MountHook::parse(&format!("touch {}", marker.display()), &mount, &cache);
+
+        let result = with_mount_hooks(pre.as_ref(), post.as_ref(), || {
+            assert!(!marker.exists(), "post-unmount hook ran while mounted");
+            Ok(())
+        });
+        assert!(result.is_ok());
+        assert!(marker.exists());
+
+        // It also runs when mounting fails, and the mount's error wins.
+        std::fs::remove_file(&marker).unwrap();
+        let result = with_mount_hooks(None, post.as_ref(), || {
+            Err(DaemonError::Mount(std::io::Error::other("no fuse")))
+        });
+        assert!(matches!(result, Err(DaemonError::Mount(_))));
+        assert!(marker.exists());
+    }
+}
@@ -0,0 +1,261 @@
+//! Mount table lookups for the pre-mount occupancy check.
+//!
+//! Mounting over an existing filesystem fails (or silently shadows it) with
+//! an error that doesn't say what is in the way. Before mounting, the daemon
+//! reads the mount table (`/proc/mounts` on Linux, `mount` output on macOS)
+//! and looks for an entry at the target.
+//!
+//! The same table tells whether the cache directory sits on a network
+//! filesystem, where the advisory locks behind `RepoLock` and atomic
+//! symlink swaps can't be relied on.
+
+use std::path::{Path, PathBuf};
+
+/// Environment variable (`1` or `true`) making the daemon refuse to start
+/// with its cache on a network filesystem instead of only warning.
+pub const STRICT_CACHE_FS_ENV: &str = "GHFS_STRICT_CACHE_FS";
+
+/// Filesystem types whose locking and rename semantics depend on a server.
+/// FUSE types are matched without their `fuse.` prefix.
+const NETWORK_FSTYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb",
+    "smb3",
+    "smbfs",
+    "afpfs",
+    "webdav",
+    "davfs",
+    "9p",
+    "afs",
+    "ncpfs",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "gpfs",
+    "sshfs",
+    "s3fs",
+    "rclone",
+];
+
+/// One entry of the mount table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountEntry {
+    pub device: String,
+    pub target: PathBuf,
+    pub fstype: String,
+}
+
+impl MountEntry {
+    /// Whether this looks like a ghfs mount: the FUSE backend's fsname on
+    /// Linux, or the localhost NFS export on macOS.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn is_ghfs(&self) -> bool {
+        (self.device == "ghfs" && self.fstype.starts_with("fuse"))
+            || (self.device == "127.0.0.1:/" && self.fstype == "nfs")
+    }
+
+    /// Whether this filesystem is served over the network.
+    pub fn is_network(&self) -> bool {
+        let fstype = self.fstype.to_ascii_lowercase();
+        let fstype = fstype
+            .strip_prefix("fuse.")
+            .or_else(|| fstype.strip_prefix("fuseblk."))
+            .unwrap_or(&fstype);
+        NETWORK_FSTYPES.contains(&fstype)
+    }
+}
+
+/// Parse `/proc/mounts` (or `/proc/self/mounts`) contents.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub fn parse_proc_mounts(contents: &str) -> Vec<MountEntry> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_ascii_whitespace();
+            let device = unescape_octal(fields.next()?);
+            let target = unescape_octal(fields.next()?);
+            let fstype = unescape_octal(fields.next()?);
+            Some(MountEntry {
+                device,
+                target: PathBuf::from(target),
+                fstype,
+            })
+        })
+        .collect()
+}
+
+/// Parse the output of macOS `mount`, whose lines look like
+/// `127.0.0.1:/ on /private/tmp/ghfs (nfs, nodev, nosuid, mounted by me)`.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+pub fn parse_mount_output(output: &str) -> Vec<MountEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (device, rest) = line.split_once(" on ")?;
+            let (target, details) = rest.rsplit_once(" (")?;
+            let fstype = details
+                .trim_end_matches(')')
+                .split(',')
+                .next()?
+                .trim()
+                .to_string();
+            Some(MountEntry {
+                device: device.to_string(),
+                target: PathBuf::from(target),
+                fstype,
+            })
+        })
+        .collect()
+}
+
+/// `/proc/mounts` escapes space, tab, newline, and backslash as `\ooo`.
+fn unescape_octal(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && let Some(digits) = bytes.get(i + 1..i + 4)
+            && digits.iter().all(|b| (b'0'..=b'7').contains(b))
+        {
+            let value = digits
+                .iter()
+                .fold(0u32, |acc, b| acc * 8 + u32::from(b - b'0'));
+            if let Ok(byte) = u8::try_from(value) {
+                out.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The topmost mount at `target`, if any. Later entries are mounted over
+/// earlier ones, so the last match wins.
+pub fn mount_at<'a>(entries: &'a [MountEntry], target: &Path) -> Option<&'a MountEntry> {
+    entries.iter().rev().find(|entry| entry.target == target)
+}
+
+/// The mount `path` lives on: the entry with the longest target that
+/// contains it, the last one winning among mounts stacked at that target.
+pub fn mount_containing<'a>(entries: &'a [MountEntry], path: &Path) -> Option<&'a MountEntry> {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| path.starts_with(&entry.target))
+        .max_by_key(|(i, entry)| (entry.target.components().count(), *i))
+        .map(|(_, entry)| entry)
+}
+
+/// Read the system mount table.
+pub fn current_mounts() -> std::io::Result<Vec<MountEntry>> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/self/mounts").map(|contents| parse_proc_mounts(&contents))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let output = std::process::Command::new("/sbin/mount").output()?;
+        Ok(parse_mount_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROC_MOUNTS: &str = "\
+sysfs /sys sysfs rw,nosuid,nodev,noexec,relatime 0 0
+/dev/nvme0n1p2 / ext4 rw,relatime 0 0
+ghfs /mnt/github fuse rw,nosuid,nodev,relatime,user_id=1000,group_id=1000 0 0
+//nas/share /mnt/with\\040space cifs rw,relatime 0 0
+tmpfs /mnt/stacked tmpfs rw 0 0
+ghfs /mnt/stacked fuse.ghfs ro 0 0
+";
+
+    #[test]
+    fn parses_proc_mounts_and_finds_occupant() {
+        let entries = parse_proc_mounts(PROC_MOUNTS);
+        assert_eq!(entries.len(), 6);
+
+        let github = mount_at(&entries, Path::new("/mnt/github")).unwrap();
+        assert_eq!(github.fstype, "fuse");
+        assert!(github.is_ghfs());
+
+        let root = mount_at(&entries, Path::new("/")).unwrap();
+        assert_eq!(root.device, "/dev/nvme0n1p2");
+        assert!(!root.is_ghfs());
+
+        assert!(mount_at(&entries, Path::new("/mnt/empty")).is_none());
+        assert!(mount_at(&entries, Path::new("/mnt")).is_none());
+    }
+
+    #[test]
+    fn proc_mounts_unescapes_octal_and_prefers_top_mount() {
+        let entries = parse_proc_mounts(PROC_MOUNTS);
+
+        let share = mount_at(&entries, Path::new("/mnt/with space")).unwrap();
+        assert_eq!(share.device, "//nas/share");
+        assert!(!share.is_ghfs());
+
+        let stacked = mount_at(&entries, Path::new("/mnt/stacked")).unwrap();
+        assert_eq!(stacked.fstype, "fuse.ghfs");
+        assert_eq!(unescape_octal(r"a\134b\011c\9"), "a\\b\tc\\9");
+    }
+
+    #[test]
+    fn finds_network_filesystems_under_paths() {
+        let entries = parse_proc_mounts(
+            "\
+/dev/sda1 / ext4 rw 0 0
+nas:/export/home /home nfs4 rw,relatime 0 0
+/dev/sdb1 /home/me/local xfs rw 0 0
+me@host:/srv /srv/remote fuse.sshfs rw 0 0
+ghfs /mnt/github fuse ro 0 0
+",
+        );
+        let on = |path: &str| mount_containing(&entries, Path::new(path)).unwrap();
+
+        assert!(on("/home/me/.cache/ghfs").is_network());
+        assert_eq!(on("/home/me/.cache/ghfs").fstype, "nfs4");
+        assert!(!on("/home/me/local/ghfs").is_network());
+        assert!(!on("/var/cache/ghfs").is_network());
+        assert!(on("/srv/remote/cache").is_network());
+        assert!(!on("/mnt/github").is_network());
+        // Prefix matching is by component, not by string.
+        assert_eq!(on("/homework").target, Path::new("/"));
+
+        let mac = parse_mount_output(
+            "\
+/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)
+//me@nas/cache on /Volumes/cache (smbfs, nodev, nosuid, mounted by me)
+",
+        );
+        let cache = mount_containing(&mac, Path::new("/Volumes/cache/ghfs")).unwrap();
+        assert!(cache.is_network());
+    }
+
+    #[test]
+    fn parses_macos_mount_output() {
+        let output = "\
+/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)
+127.0.0.1:/ on /private/tmp/ghfs (nfs, nodev, nosuid, read-only, mounted by me)
+/dev/disk5s1 on /Volumes/My Disk (hfs, local, nodev, nosuid)
+";
+        let entries = parse_mount_output(output);
+        assert_eq!(entries.len(), 3);
+
+        let ghfs = mount_at(&entries, Path::new("/private/tmp/ghfs")).unwrap();
+        assert!(ghfs.is_ghfs());
+
+        let disk = mount_at(&entries, Path::new("/Volumes/My Disk")).unwrap();
+        assert_eq!(disk.fstype, "hfs");
+        assert!(!disk.is_ghfs());
+    }
+}
@@ -0,0 +1,336 @@
+//! Per-owner and per-repo disk quotas on mirror size.
+//!
+//! Checked by the worker before any clone or fetch. A repo whose mirror, or
+//! whose owner's mirrors together, already meet their quota is refused with
+//! [`StoreError::QuotaExceeded`], which the filesystem reports as `ENOSPC`.
+//! Exempt repos only log a warning. Hydrated blobs are shared across repos
+//! and don't count toward either quota.
+//!
+//! Sizes come from a [`QuotaUsage`] cache rather than a walk of the owner's
+//! directory on every check: each mirror is measured when first seen and
+//! again after a fetch grows it.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::cache::CachePaths;
+use crate::daemon::repair::dir_size;
+use crate::store::StoreError;
+use crate::types::RepoKey;
+
+/// Environment variable holding the per-repo quota, e.g. `2G`.
+pub const REPO_QUOTA_ENV: &str = "GHFS_REPO_QUOTA";
+/// Environment variable holding the per-owner quota, e.g. `20G`.
+pub const OWNER_QUOTA_ENV: &str = "GHFS_OWNER_QUOTA";
+/// Environment variable listing comma-separated `owner/repo`s exempt from
+/// quotas.
+pub const QUOTA_EXEMPT_ENV: &str = "GHFS_QUOTA_EXEMPT";
+
+/// Configured quotas. Both limits are off by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Quotas {
+    pub repo_bytes: Option<u64>,
+    pub owner_bytes: Option<u64>,
+    pub exempt: Vec<RepoKey>,
+}
+
+/// Outcome of checking a repo against its quotas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuotaDecision {
+    Allow,
+    /// Over quota but exempt: proceed, logging the reason.
+    Warn(String),
+    /// Over quota: don't grow the repo.
+    Refuse(String),
+}
+
+impl Quotas {
+    /// Read quotas from [`REPO_QUOTA_ENV`], [`OWNER_QUOTA_ENV`], and
+    /// [`QUOTA_EXEMPT_ENV`]. Unparseable values are logged and ignored.
+    pub fn from_env() -> Self {
+        Self::from_lookup(crate::config::var)
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let bytes = |name: &str| {
+            let value = lookup(name)?;
+            let parsed = parse_size(&value);
+            if parsed.is_none() {
+                log::warn!("Ignoring invalid {name}={value:?}; expected a size like 500M or 2G");
+            }
+            parsed
+        };
+        let exempt = lookup(QUOTA_EXEMPT_ENV)
+            .map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .filter_map(|s| match s.parse::<RepoKey>() {
+                        Ok(key) => Some(key.canonical()),
+                        Err(e) => {
+                            log::warn!("Ignoring invalid {QUOTA_EXEMPT_ENV} entry {s:?}: {e}");
+                            None
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            repo_bytes: bytes(REPO_QUOTA_ENV),
+            owner_bytes: bytes(OWNER_QUOTA_ENV),
+            exempt,
+        }
+    }
+
+    /// Whether any limit is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.repo_bytes.is_some() || self.owner_bytes.is_some()
+    }
+
+    /// Decide whether `key` may grow, given its mirror size and the total
+    /// mirror size of its owner.
+    pub fn decide(&self, key: &RepoKey, repo_usage: u64, owner_usage: u64) -> QuotaDecision {
+        let reason = if let Some(limit) = self.repo_bytes.filter(|&l| repo_usage >= l) {
+            format!("{key} uses {repo_usage} bytes, repo quota is {limit}")
+        } else if let Some(limit) = self.owner_bytes.filter(|&l| owner_usage >= l) {
+            format!(
+                "{} uses {owner_usage} bytes, owner quota is {limit}",
+                key.owner
+            )
+        } else {
+            return QuotaDecision::Allow;
+        };
+        if self.exempt.contains(&key.canonical()) {
+            QuotaDecision::Warn(reason)
+        } else {
+            QuotaDecision::Refuse(reason)
+        }
+    }
+
+    /// Check `key` against the mirrors on disk before a clone or fetch.
+    pub fn check(
+        &self,
+        usage: &QuotaUsage,
+        paths: &CachePaths,
+        key: &RepoKey,
+    ) -> Result<(), StoreError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let (repo_usage, owner_usage) = usage.get(paths, key);
+        match self.decide(key, repo_usage, owner_usage) {
+            QuotaDecision::Allow => Ok(()),
+            QuotaDecision::Warn(reason) => {
+                log::warn!("Quota exceeded for exempt repo: {reason}");
+                Ok(())
+            }
+            QuotaDecision::Refuse(reason) => Err(StoreError::QuotaExceeded(reason)),
+        }
+    }
+}
+
+/// Measured mirror sizes, per owner directory, with a running total.
+#[derive(Debug, Default)]
+pub struct QuotaUsage {
+    owners: Mutex<HashMap<PathBuf, OwnerUsage>>,
+}
+
+#[derive(Debug, Default)]
+struct OwnerUsage {
+    /// Bytes per entry of the owner directory.
+    entries: HashMap<PathBuf, u64>,
+    total: u64,
+}
+
+impl OwnerUsage {
+    fn set(&mut self, path: PathBuf, bytes: u64) {
+        let old = self.entries.insert(path, bytes).unwrap_or(0);
+        self.total = self.total - old + bytes;
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some(old) = self.entries.remove(path) {
+            self.total -= old;
+        }
+    }
+}
+
+impl QuotaUsage {
+    /// `(repo bytes, owner bytes)` for `key`. Lists the owner directory to
+    /// pick up mirrors cloned or removed since the last call, measuring
+    /// only the new ones.
+    fn get(&self, paths: &CachePaths, key: &RepoKey) -> (u64, u64) {
+        let mirror = paths.mirror_dir(key);
+        let owner_dir = mirror.parent().unwrap_or(Path::new("")).to_path_buf();
+        let present: HashSet<PathBuf> = match std::fs::read_dir(&owner_dir) {
+            Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+            Err(_) => HashSet::new(),
+        };
+
+        let mut owners = self.owners.lock().expect("quota usage poisoned");
+        let owner = owners.entry(owner_dir).or_default();
+        let gone: Vec<PathBuf> = owner
+            .entries
+            .keys()
+            .filter(|path| !present.contains(*path))
+            .cloned()
+            .collect();
+        for path in gone {
+            owner.remove(&path);
+        }
+        for path in present {
+            if !owner.entries.contains_key(&path) {
+                let bytes = dir_size(&path);
+                owner.set(path, bytes);
+            }
+        }
+        let repo = owner.entries.get(&mirror).copied().unwrap_or(0);
+        (repo, owner.total)
+    }
+
+    /// Measure `key`'s mirror again after a fetch. Owners no check has
+    /// looked at yet are left for their first check to measure.
+    pub fn record(&self, paths: &CachePaths, key: &RepoKey) {
+        let mirror = paths.mirror_dir(key);
+        let Some(owner_dir) = mirror.parent() else {
+            return;
+        };
+        let mut owners = self.owners.lock().expect("quota usage poisoned");
+        let Some(owner) = owners.get_mut(owner_dir) else {
+            return;
+        };
+        if mirror.exists() {
+            let bytes = dir_size(&mirror);
+            owner.set(mirror, bytes);
+        } else {
+            owner.remove(&mirror);
+        }
+    }
+}
+
+/// Parse a byte count with an optional `K`, `M`, `G`, or `T` suffix
+/// (binary multiples).
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (digits, shift) = match value.char_indices().last()? {
+        (i, 'k' | 'K') => (&value[..i], 10),
+        (i, 'm' | 'M') => (&value[..i], 20),
+        (i, 'g' | 'G') => (&value[..i], 30),
+        (i, 't' | 'T') => (&value[..i], 40),
+        _ => (value, 0),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(s: &str) -> RepoKey {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_size_accepts_suffixes() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("2K"), Some(2048));
+        assert_eq!(parse_size(" 3g "), Some(3 << 30));
+        assert_eq!(parse_size("lots"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn decide_against_synthetic_sizes() {
+        let quotas = Quotas {
+            repo_bytes: Some(100),
+            owner_bytes: Some(250),
+            exempt: vec![key("octocat/linux")],
+        };
+        let repo = key("octocat/hello-world");
+
+        assert_eq!(quotas.decide(&repo, 99, 249), QuotaDecision::Allow);
+        assert!(matches!(
+            quotas.decide(&repo, 100, 100),
+            QuotaDecision::Refuse(reason) if reason.contains("repo quota")
+        ));
+        assert!(matches!(
+            quotas.decide(&repo, 10, 250),
+            QuotaDecision::Refuse(reason) if reason.contains("owner quota")
+        ));
+        assert!(matches!(
+            quotas.decide(&key("Octocat/Linux"), 500, 500),
+            QuotaDecision::Warn(_)
+        ));
+        assert_eq!(
+            Quotas::default().decide(&repo, u64::MAX, u64::MAX),
+            QuotaDecision::Allow
+        );
+    }
+
+    #[test]
+    fn check_measures_mirrors_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let big = paths.mirror_dir(&key("octocat/big"));
+        std::fs::create_dir_all(&big).unwrap();
+        std::fs::write(big.join("pack"), vec![0u8; 300]).unwrap();
+
+        let quotas = Quotas {
+            owner_bytes: Some(256),
+            ..Quotas::default()
+        };
+        let usage = QuotaUsage::default();
+        // A new repo for a full owner is refused; another owner is fine.
+        assert!(matches!(
+            quotas.check(&usage, &paths, &key("octocat/new")),
+            Err(StoreError::QuotaExceeded(_))
+        ));
+        assert!(quotas.check(&usage, &paths, &key("rust-lang/rust")).is_ok());
+    }
+
+    #[test]
+    fn usage_is_measured_once_and_updated_after_fetches() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let small = key("octocat/small");
+        let mirror = paths.mirror_dir(&small);
+        std::fs::create_dir_all(&mirror).unwrap();
+        std::fs::write(mirror.join("pack"), vec![0u8; 100]).unwrap();
+
+        let usage = QuotaUsage::default();
+        assert_eq!(usage.get(&paths, &small), (100, 100));
+
+        // Growth isn't seen until the fetch is recorded.
+        std::fs::write(mirror.join("pack2"), vec![0u8; 50]).unwrap();
+        assert_eq!(usage.get(&paths, &small), (100, 100));
+        usage.record(&paths, &small);
+        assert_eq!(usage.get(&paths, &small), (150, 150));
+
+        // New and removed mirrors are picked up on the next check.
+        let other = paths.mirror_dir(&key("octocat/other"));
+        std::fs::create_dir_all(&other).unwrap();
+        std::fs::write(other.join("pack"), vec![0u8; 10]).unwrap();
+        assert_eq!(usage.get(&paths, &small), (150, 160));
+        std::fs::remove_dir_all(&mirror).unwrap();
+        assert_eq!(usage.get(&paths, &small), (0, 10));
+    }
+
+    #[test]
+    fn env_configures_quotas() {
+        let quotas = Quotas::from_lookup(|name| match name {
+            REPO_QUOTA_ENV => Some("1G".to_string()),
+            OWNER_QUOTA_ENV => Some("huge".to_string()),
+            QUOTA_EXEMPT_ENV => Some("Octocat/Linux, bad".to_string()),
+            _ => None,
+        });
+        assert_eq!(
+            quotas,
+            Quotas {
+                repo_bytes: Some(1 << 30),
+                owner_bytes: None,
+                exempt: vec![key("octocat/linux")],
+            }
+        );
+    }
+}
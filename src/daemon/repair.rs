@@ -0,0 +1,218 @@
+//! Rebuild the state database from the on-disk cache.
+//!
+//! Unlike [`super::backfill`], which only adds missing rows on daemon start,
+//! repair discards `ghfs.db` entirely and repopulates every column it can
+//! recover from the mirrors: HEAD commit, last fetch time, and mirror size.
+
+use std::collections::HashSet;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::cache::CachePaths;
+use crate::daemon::state::State;
+use crate::daemon::{DaemonError, is_daemon_running};
+use crate::store::git;
+
+use super::backfill;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RepairStats {
+    pub repos_restored: u64,
+    pub heads_resolved: u64,
+}
+
+/// Delete and rebuild `ghfs.db` under `cache_paths`. Refuses while the
+/// daemon is running, since it holds the database open.
+pub fn repair_db(cache_paths: &CachePaths) -> Result<RepairStats, DaemonError> {
+    if is_daemon_running() {
+        return Err(DaemonError::AlreadyRunning);
+    }
+
+    let db_path = cache_paths.root().join("ghfs.db");
+    for suffix in ["", "-journal", "-wal", "-shm"] {
+        let mut path = db_path.clone().into_os_string();
+        path.push(suffix);
+        match std::fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    std::fs::create_dir_all(cache_paths.root())?;
+    let state = State::open(&db_path)?;
+    state.init()?;
+    Ok(repopulate(&state, cache_paths)?)
+}
+
+/// Fill an empty `state` from the mirrors under `cache_paths`.
+fn repopulate(state: &State, cache_paths: &CachePaths) -> Result<RepairStats, rusqlite::Error> {
+    backfill::backfill_cache_state(state, cache_paths);
+
+    let mut stats = RepairStats::default();
+    for repo in state.list_repos()? {
+        let key = match format!("{}/{}", repo.owner, repo.repo).parse() {
+            Ok(key) => key,
+            Err(_) => continue,
+        };
+        stats.repos_restored += 1;
+
+        let mirror = cache_paths.mirror_dir(&key);
+        state.update_mirror_size(&key, dir_size(&mirror))?;
+
+        let Ok(handle) = git::open_repository(&mirror) else {
+            log::warn!("repair: cannot open mirror for {key}");
+            continue;
+        };
+        let head = match git::resolve_head(&handle) {
+            Ok(Some(head)) => head,
+            Ok(None) => {
+                log::debug!("repair: {key} has no commits yet");
+                continue;
+            }
+            Err(_) => {
+                log::warn!("repair: cannot resolve HEAD for {key}");
+                continue;
+            }
+        };
+        state.update_sync_at(&key, 0, &head.to_string(), last_fetch_at(&mirror))?;
+        stats.heads_resolved += 1;
+    }
+
+    Ok(stats)
+}
+
+/// [`git::last_fetch_time`] as Unix seconds, 0 if unknown.
+fn last_fetch_at(mirror: &Path) -> i64 {
+    git::last_fetch_time(mirror)
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// How deep [`dir_size`] descends before giving up on a branch. Mirrors are
+/// a few levels deep; this only stops pathological trees.
+const MAX_WALK_DEPTH: usize = 64;
+
+/// Total size of regular files under `path`, without following symlinks.
+/// A directory reached twice (a bind mount of an ancestor, say) is counted
+/// once and logged.
+pub(super) fn dir_size(path: &Path) -> u64 {
+    let mut walk = SizeWalk::default();
+    let size = walk.size(path, 0);
+    for cycle in &walk.cycles {
+        log::warn!(
+            "Not descending into {}: already visited, or over {MAX_WALK_DEPTH} levels deep",
+            cycle.display()
+        );
+    }
+    size
+}
+
+/// Directories a [`dir_size`] walk has entered, by device and inode.
+#[derive(Default)]
+struct SizeWalk {
+    visited: HashSet<(u64, u64)>,
+    cycles: Vec<PathBuf>,
+}
+
+impl SizeWalk {
+    fn size(&mut self, dir: &Path, depth: usize) -> u64 {
+        let Ok(meta) = std::fs::symlink_metadata(dir) else {
+            return 0;
+        };
+        if depth > MAX_WALK_DEPTH || !self.visited.insert((meta.dev(), meta.ino())) {
+            self.cycles.push(dir.to_path_buf());
+            return 0;
+        }
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return 0;
+        };
+        entries
+            .flatten()
+            .map(|entry| match entry.file_type() {
+                Ok(t) if t.is_dir() => self.size(&entry.path(), depth + 1),
+                Ok(t) if t.is_file() => entry.metadata().map_or(0, |m| m.len()),
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_fixtures::mirror_with_commit;
+    use crate::types::RepoKey;
+    use tempfile::tempdir;
+
+    #[test]
+    fn repopulate_rebuilds_rows_from_mirrors() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let synced: RepoKey = "octocat/hello-world".parse().unwrap();
+        let empty: RepoKey = "octocat/empty".parse().unwrap();
+        let commit = mirror_with_commit(&paths, &synced);
+        // A mirror with no commits gets a row but no HEAD.
+        git2::Repository::init_bare(paths.mirror_dir(&empty)).unwrap();
+
+        let state = State::open(&dir.path().join("ghfs.db")).unwrap();
+        state.init().unwrap();
+        let stats = repopulate(&state, &paths).unwrap();
+
+        assert_eq!(
+            stats,
+            RepairStats {
+                repos_restored: 2,
+                heads_resolved: 1,
+            }
+        );
+
+        let repos = state.list_repos_with_stats().unwrap();
+        let row = repos.iter().find(|r| r.repo == "hello-world").unwrap();
+        assert_eq!(row.head_commit, Some(commit.to_string()));
+        assert_eq!(row.current_generation, Some(0));
+        assert!(row.last_sync_at.is_some());
+        assert!(row.total_size_bytes > 0);
+
+        let row = repos.iter().find(|r| r.repo == "empty").unwrap();
+        assert!(row.head_commit.is_none());
+        assert!(row.last_sync_at.is_none());
+    }
+
+    #[test]
+    fn dir_size_sums_nested_files() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::fs::write(dir.path().join("x"), [0u8; 10]).unwrap();
+        std::fs::write(dir.path().join("a/b/y"), [0u8; 5]).unwrap();
+        assert_eq!(dir_size(dir.path()), 15);
+    }
+
+    #[test]
+    fn dir_size_terminates_on_cycles() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::fs::write(dir.path().join("a/b/y"), [0u8; 5]).unwrap();
+        std::os::unix::fs::symlink(".", dir.path().join("a/self")).unwrap();
+        std::os::unix::fs::symlink("../..", dir.path().join("a/b/up")).unwrap();
+        assert_eq!(dir_size(dir.path()), 5);
+
+        // A directory reached a second time, as through a looping bind
+        // mount, is reported and not descended into again.
+        let mut walk = SizeWalk::default();
+        assert_eq!(walk.size(dir.path(), 0), 5);
+        assert_eq!(walk.size(&dir.path().join("a"), 0), 0);
+        assert_eq!(walk.cycles, [dir.path().join("a")]);
+
+        let mut deep = dir.path().to_path_buf();
+        for _ in 0..=MAX_WALK_DEPTH {
+            deep.push("d");
+        }
+        std::fs::create_dir_all(&deep).unwrap();
+        std::fs::write(deep.join("z"), [0u8; 3]).unwrap();
+        let mut walk = SizeWalk::default();
+        assert_eq!(walk.size(dir.path(), 0), 5);
+        assert_eq!(walk.cycles, [deep]);
+    }
+}
@@ -0,0 +1,79 @@
+//! Re-clone repos the state database knows about but whose mirrors are gone.
+//!
+//! Wiping `mirrors/` (to reclaim space, or after disk trouble) leaves the
+//! rows in `ghfs.db` behind, and each repo only comes back when something
+//! browses it. This queues a background refresh, which clones a missing
+//! mirror, for every such repo instead. Repos whose mirror is still there
+//! already serve from it and are left alone.
+
+use crate::cache::CachePaths;
+use crate::daemon::state::State;
+use crate::daemon::worker::WorkerHandle;
+use crate::types::RepoKey;
+
+/// Repos with a state row but no mirror on disk.
+pub fn missing_mirrors(
+    state: &State,
+    cache_paths: &CachePaths,
+) -> Result<Vec<RepoKey>, rusqlite::Error> {
+    let mut missing: Vec<RepoKey> = state
+        .list_repos()?
+        .into_iter()
+        // The names become paths and clone URLs, so rows that don't parse
+        // (say, edited by hand) are skipped.
+        .filter_map(|row| match format!("{}/{}", row.owner, row.repo).parse() {
+            Ok(key) => Some(key),
+            Err(e) => {
+                log::warn!("Skipping invalid repo row {}/{}: {e}", row.owner, row.repo);
+                None
+            }
+        })
+        .filter(|key| !cache_paths.mirror_dir(key).exists())
+        .collect();
+    missing.sort_by_key(|key| key.to_string());
+    Ok(missing)
+}
+
+/// Queue a clone for every repo in [`missing_mirrors`], returning them.
+pub fn queue_missing(
+    state: &State,
+    cache_paths: &CachePaths,
+    worker: &WorkerHandle,
+) -> Result<Vec<RepoKey>, rusqlite::Error> {
+    let missing = missing_mirrors(state, cache_paths)?;
+    for key in &missing {
+        log::info!("Re-cloning {key}: known to state but its mirror is missing");
+        worker.refresh(key.clone());
+    }
+    Ok(missing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_rows_whose_mirror_is_gone() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = CachePaths::new(dir.path().to_path_buf());
+        paths.ensure_created().unwrap();
+        let state = State::open_in_memory().unwrap();
+        state.init().unwrap();
+
+        let kept: RepoKey = "octocat/hello-world".parse().unwrap();
+        let wiped: RepoKey = "octocat/spoon-knife".parse().unwrap();
+        let never: RepoKey = "rust-lang/rust".parse().unwrap();
+        for key in [&kept, &wiped, &never] {
+            state.update_sync(key, 0, "abc123").unwrap();
+        }
+        std::fs::create_dir_all(paths.mirror_dir(&kept)).unwrap();
+        // A row no valid name could have produced.
+        let bad = RepoKey::new(
+            crate::types::Owner::new_unchecked(".."),
+            crate::types::Repo::new_unchecked("etc"),
+        );
+        state.update_sync(&bad, 0, "abc123").unwrap();
+
+        assert_eq!(missing_mirrors(&state, &paths).unwrap(), [wiped, never]);
+    }
+}
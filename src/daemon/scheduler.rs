@@ -2,6 +2,11 @@
 //!
 //! This module provides a scheduler that periodically checks for stale repositories
 //! and triggers background refreshes via the worker thread.
+//!
+//! Owners watched with `ghfs watch --recursive` are re-listed through the
+//! GitHub API on each check, and repos that appeared since are added to
+//! state and cloned. Listing needs a token, so without one owner watches
+//! are skipped.
 
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -10,15 +15,92 @@ use std::time::Duration;
 
 use crossbeam_channel::Sender;
 
-use crate::daemon::state::State;
+use crate::daemon::state::{RepoState, State};
 use crate::daemon::worker::WorkerRequest;
+use crate::fs::fetch_repo_names;
+use crate::github::ApiClient;
 use crate::types::RepoKey;
 
 /// How often to check for stale repos.
-const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60); // 5 minutes
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60); // 5 minutes
 
 /// Max age before a repo is considered stale.
-const MAX_AGE_SECS: i64 = 24 * 60 * 60; // 24 hours
+pub const MAX_AGE_SECS: i64 = 24 * 60 * 60; // 24 hours
+
+/// A repo's place in the scheduler's refresh plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanEntry {
+    pub owner: String,
+    pub repo: String,
+    pub last_sync_at: Option<i64>,
+    /// When the repo becomes stale. `None` if it has never synced.
+    pub next_due_at: Option<i64>,
+    /// Whether the next scheduler check would queue a refresh.
+    pub eligible: bool,
+}
+
+/// Compute the scheduler's view of `repos` at `now` without scheduling anything.
+///
+/// Entries are ordered by when they fall due, never-synced repos first.
+/// Repos with their own `max_age_secs` use it instead of the default.
+pub fn plan(repos: &[RepoState], now: i64, max_age_secs: i64) -> Vec<PlanEntry> {
+    let mut entries: Vec<PlanEntry> = repos
+        .iter()
+        .map(|repo| {
+            let max_age_secs = repo.max_age_secs.unwrap_or(max_age_secs);
+            let next_due_at = repo.last_sync_at.map(|ts| ts.saturating_add(max_age_secs));
+            PlanEntry {
+                owner: repo.owner.clone(),
+                repo: repo.repo.clone(),
+                last_sync_at: repo.last_sync_at,
+                next_due_at,
+                eligible: next_due_at.is_none_or(|due| now > due),
+            }
+        })
+        .collect();
+
+    entries.sort_by(|a, b| {
+        a.next_due_at
+            .cmp(&b.next_due_at)
+            .then_with(|| a.owner.cmp(&b.owner))
+            .then_with(|| a.repo.cmp(&b.repo))
+    });
+    entries
+}
+
+/// Repos GitHub lists for a watched `owner` that `known` doesn't track yet.
+///
+/// Names are lowercased to match the keys mirrors live under, and names that
+/// aren't valid repo names are dropped.
+pub fn owner_additions(owner: &str, known: &[RepoState], listed: &[String]) -> Vec<RepoKey> {
+    let mut additions: Vec<RepoKey> = listed
+        .iter()
+        .map(|name| name.to_ascii_lowercase())
+        .filter(|name| {
+            !known
+                .iter()
+                .any(|r| r.owner.eq_ignore_ascii_case(owner) && r.repo == *name)
+        })
+        .filter_map(|name| format!("{owner}/{name}").parse().ok())
+        .collect();
+    additions.sort_by_key(|key: &RepoKey| key.to_string());
+    additions.dedup();
+    additions
+}
+
+/// Record the repos in `listed` that `owner`'s watch hasn't picked up yet,
+/// returning them so the caller can queue their clones.
+pub fn add_owner_repos(
+    state: &State,
+    owner: &str,
+    listed: &[String],
+) -> Result<Vec<RepoKey>, rusqlite::Error> {
+    let additions = owner_additions(owner, &state.list_repos()?, listed);
+    for key in &additions {
+        state.get_or_create_repo(key)?;
+    }
+    Ok(additions)
+}
 
 /// Background scheduler that periodically checks for stale repos.
 pub struct Scheduler {
@@ -60,10 +142,58 @@ impl Scheduler {
                 return;
             }
 
+            self.check_owner_watches();
             self.check_and_refresh();
         }
     }
 
+    /// Re-list watched owners and queue clones for repos new since the last
+    /// check.
+    fn check_owner_watches(&self) {
+        let owners = match self.state.list_owner_watches() {
+            Ok(o) => o,
+            Err(e) => {
+                log::warn!("Failed to list owner watches: {}", e);
+                return;
+            }
+        };
+        if owners.is_empty() {
+            return;
+        }
+        if !ApiClient::shared().has_token() {
+            log::warn!("Skipping owner watches: listing repos needs a GitHub token");
+            return;
+        }
+
+        for owner in owners {
+            let listed = match fetch_repo_names(&owner) {
+                Ok(names) => names,
+                Err(e) => {
+                    log::warn!("Failed to list repos for watched owner {}: {}", owner, e);
+                    continue;
+                }
+            };
+            let additions = match add_owner_repos(&self.state, &owner, &listed) {
+                Ok(a) => a,
+                Err(e) => {
+                    log::warn!("Failed to record new repos for {}: {}", owner, e);
+                    continue;
+                }
+            };
+            for key in additions {
+                log::info!("Watching new repo {}", key);
+                if self
+                    .worker_tx
+                    .send(WorkerRequest::Refresh { repo: key })
+                    .is_err()
+                {
+                    log::warn!("Worker channel closed, stopping scheduler");
+                    return;
+                }
+            }
+        }
+    }
+
     /// Check all repos and schedule refreshes for stale ones.
     fn check_and_refresh(&self) {
         log::debug!("Scheduler checking for stale repos");
@@ -81,14 +211,8 @@ impl Scheduler {
             .unwrap()
             .as_secs() as i64;
 
-        for repo in repos {
-            // Check if stale
-            let is_stale = match repo.last_sync_at {
-                Some(ts) => now - ts > MAX_AGE_SECS,
-                None => true, // Never synced
-            };
-
-            if is_stale {
+        for repo in plan(&repos, now, MAX_AGE_SECS) {
+            if repo.eligible {
                 let key_str = format!("{}/{}", repo.owner, repo.repo);
                 let key: RepoKey = match key_str.parse() {
                     Ok(k) => k,
@@ -153,3 +277,139 @@ impl Drop for SchedulerHandle {
         self.shutdown();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo_state(owner: &str, repo: &str, last_sync_at: Option<i64>) -> RepoState {
+        RepoState {
+            id: 0,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            current_generation: None,
+            head_commit: None,
+            last_access_at: None,
+            last_sync_at,
+            max_age_secs: None,
+        }
+    }
+
+    #[test]
+    fn plan_orders_by_next_due_with_never_synced_first() {
+        let now = 10_000;
+        let repos = vec![
+            repo_state("a", "fresh", Some(now - 10)),
+            repo_state("b", "stale", Some(now - 500)),
+            repo_state("c", "never", None),
+        ];
+
+        let entries = plan(&repos, now, 100);
+        let names: Vec<&str> = entries.iter().map(|e| e.repo.as_str()).collect();
+        assert_eq!(names, ["never", "stale", "fresh"]);
+
+        assert_eq!(entries[0].next_due_at, None);
+        assert!(entries[0].eligible);
+        assert_eq!(entries[1].next_due_at, Some(now - 400));
+        assert!(entries[1].eligible);
+        assert_eq!(entries[2].next_due_at, Some(now + 90));
+        assert!(!entries[2].eligible);
+    }
+
+    #[test]
+    fn plan_eligibility_depends_on_interval() {
+        let now = 100_000;
+        let repos = vec![repo_state("o", "r", Some(now - 3_600))];
+
+        let hourly = plan(&repos, now, 1_800);
+        assert!(hourly[0].eligible);
+        assert_eq!(hourly[0].next_due_at, Some(now - 1_800));
+
+        let daily = plan(&repos, now, MAX_AGE_SECS);
+        assert!(!daily[0].eligible);
+        assert_eq!(daily[0].next_due_at, Some(now - 3_600 + MAX_AGE_SECS));
+    }
+
+    #[test]
+    fn plan_is_not_due_exactly_at_boundary() {
+        let now = 5_000;
+        let repos = vec![repo_state("o", "r", Some(now - 100))];
+        assert!(!plan(&repos, now, 100)[0].eligible);
+        assert!(plan(&repos, now + 1, 100)[0].eligible);
+    }
+
+    #[test]
+    fn plan_honors_per_repo_max_age() {
+        let now = 100_000;
+        let mut hourly = repo_state("o", "hourly", Some(now - 3_600 - 1));
+        hourly.max_age_secs = Some(3_600);
+        let mut weekly = repo_state("o", "weekly", Some(now - 2 * MAX_AGE_SECS));
+        weekly.max_age_secs = Some(7 * MAX_AGE_SECS);
+        let default = repo_state("o", "default", Some(now - 3_600 - 1));
+        let repos = vec![hourly, weekly, default];
+
+        let entries = plan(&repos, now, MAX_AGE_SECS);
+        let eligible: Vec<(&str, bool)> = entries
+            .iter()
+            .map(|e| (e.repo.as_str(), e.eligible))
+            .collect();
+        assert_eq!(
+            eligible,
+            [("hourly", true), ("default", false), ("weekly", false)]
+        );
+        assert_eq!(entries[2].next_due_at, Some(now + 5 * MAX_AGE_SECS));
+
+        let mut never = repo_state("o", "never", Some(now));
+        never.max_age_secs = Some(i64::MAX);
+        assert_eq!(
+            plan(&[never], now, MAX_AGE_SECS)[0].next_due_at,
+            Some(i64::MAX)
+        );
+    }
+
+    #[test]
+    fn owner_additions_are_listed_repos_not_yet_known() {
+        let known = vec![
+            repo_state("octo-org", "api", Some(1)),
+            repo_state("octo-org", "web", None),
+            repo_state("other", "new-thing", Some(1)),
+        ];
+        let listed = vec![
+            "api".to_string(),
+            "Web".to_string(),
+            "New-Thing".to_string(),
+            "docs".to_string(),
+            "docs".to_string(),
+            "not valid".to_string(),
+        ];
+
+        let additions: Vec<String> = owner_additions("octo-org", &known, &listed)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(additions, ["octo-org/docs", "octo-org/new-thing"]);
+
+        // Nothing new once the additions are known.
+        assert!(owner_additions("octo-org", &known, &listed[..2]).is_empty());
+    }
+
+    #[test]
+    fn added_owner_repos_are_recorded_once() {
+        let state = State::open_in_memory().unwrap();
+        state.init().unwrap();
+        let listed = vec!["api".to_string(), "web".to_string()];
+
+        let added = add_owner_repos(&state, "octo-org", &listed).unwrap();
+        assert_eq!(added.len(), 2);
+        assert_eq!(state.list_repos().unwrap().len(), 2);
+
+        let listed = vec!["api".to_string(), "web".to_string(), "cli".to_string()];
+        let added = add_owner_repos(&state, "octo-org", &listed).unwrap();
+        assert_eq!(added, ["octo-org/cli".parse::<RepoKey>().unwrap()]);
+        assert!(
+            add_owner_repos(&state, "octo-org", &listed)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
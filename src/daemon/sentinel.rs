@@ -0,0 +1,164 @@
+//! Mount sentinel: a positive marker that a daemon has a mount up.
+//!
+//! The daemon writes the sentinel (its PID and mount point) right before
+//! mounting and removes it after a clean unmount. A sentinel found at
+//! startup therefore means the previous daemon either is still running or
+//! died without unmounting, e.g. from SIGKILL, which leaves a disconnected
+//! FUSE mount behind. The PID tells the two apart.
+
+use std::path::{Path, PathBuf};
+
+/// Contents of the sentinel file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountSentinel {
+    pub pid: u32,
+    pub mount_point: PathBuf,
+}
+
+/// What to do about a sentinel found at startup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SentinelAction {
+    /// No sentinel, or an unreadable one: mount as usual.
+    Proceed,
+    /// The daemon that wrote it is gone; unmount what it left behind first.
+    Cleanup(PathBuf),
+    /// The daemon that wrote it is still running.
+    AlreadyRunning(u32),
+}
+
+impl MountSentinel {
+    /// A sentinel for the current process mounting at `mount_point`.
+    pub fn current(mount_point: &Path) -> Self {
+        Self {
+            pid: std::process::id(),
+            mount_point: mount_point.to_path_buf(),
+        }
+    }
+
+    /// Parse sentinel contents: the PID on the first line, the mount point
+    /// on the second.
+    pub fn parse(contents: &str) -> Option<Self> {
+        let (pid, mount_point) = contents.split_once('\n')?;
+        let mount_point = mount_point.strip_suffix('\n').unwrap_or(mount_point);
+        if mount_point.is_empty() {
+            return None;
+        }
+        Some(Self {
+            pid: pid.trim().parse().ok()?,
+            mount_point: PathBuf::from(mount_point),
+        })
+    }
+
+    fn contents(&self) -> String {
+        format!("{}\n{}\n", self.pid, self.mount_point.display())
+    }
+
+    /// Read the sentinel at `path`, if there is a valid one.
+    pub fn read(path: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let sentinel = Self::parse(&contents);
+        if sentinel.is_none() {
+            log::warn!("Ignoring malformed mount sentinel {}", path.display());
+        }
+        sentinel
+    }
+
+    /// Write the sentinel to `path`.
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.contents())
+    }
+
+    /// Decide what to do about `sentinel` given whether a PID belongs to a
+    /// live daemon. A sentinel carrying our own PID is stale by definition.
+    pub fn action(sentinel: Option<&Self>, daemon_alive: impl Fn(u32) -> bool) -> SentinelAction {
+        match sentinel {
+            None => SentinelAction::Proceed,
+            Some(s) if s.pid != std::process::id() && daemon_alive(s.pid) => {
+                SentinelAction::AlreadyRunning(s.pid)
+            }
+            Some(s) => SentinelAction::Cleanup(s.mount_point.clone()),
+        }
+    }
+}
+
+/// Whether `pid` is a running ghfs process. On Linux the process name is
+/// checked too, so a PID reused by something else after a crash doesn't
+/// look like a live daemon.
+pub fn daemon_alive(pid: u32) -> bool {
+    let Ok(raw) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if raw <= 0 {
+        return false;
+    }
+    let exists = unsafe { libc::kill(raw, 0) } == 0
+        || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+    if !exists {
+        return false;
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Ok(comm) = std::fs::read_to_string(format!("/proc/{pid}/comm")) {
+        return comm.trim().starts_with("ghfs");
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn sentinel_round_trips_through_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("ghfs.mount");
+        assert_eq!(MountSentinel::read(&path), None);
+
+        let sentinel = MountSentinel {
+            pid: 4242,
+            mount_point: PathBuf::from("/mnt/git hub"),
+        };
+        sentinel.write(&path).unwrap();
+        assert_eq!(MountSentinel::read(&path), Some(sentinel));
+
+        std::fs::write(&path, "not a pid\n/mnt/github\n").unwrap();
+        assert_eq!(MountSentinel::read(&path), None);
+        assert_eq!(MountSentinel::parse("4242\n"), None);
+    }
+
+    #[test]
+    fn dead_pid_cleans_up_and_live_pid_is_already_running() {
+        let sentinel = MountSentinel {
+            pid: 4242,
+            mount_point: PathBuf::from("/mnt/github"),
+        };
+
+        assert_eq!(
+            MountSentinel::action(None, |_| true),
+            SentinelAction::Proceed
+        );
+        assert_eq!(
+            MountSentinel::action(Some(&sentinel), |_| false),
+            SentinelAction::Cleanup(PathBuf::from("/mnt/github"))
+        );
+        assert_eq!(
+            MountSentinel::action(Some(&sentinel), |pid| pid == 4242),
+            SentinelAction::AlreadyRunning(4242)
+        );
+
+        // Our own PID left over from a previous run is never "running".
+        let ours = MountSentinel::current(Path::new("/mnt/github"));
+        assert_eq!(
+            MountSentinel::action(Some(&ours), |_| true),
+            SentinelAction::Cleanup(PathBuf::from("/mnt/github"))
+        );
+    }
+
+    #[test]
+    fn daemon_alive_rejects_invalid_pids() {
+        assert!(!daemon_alive(0));
+        assert!(!daemon_alive(u32::MAX));
+    }
+}
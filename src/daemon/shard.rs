@@ -0,0 +1,220 @@
+//! Move a flat `mirrors/` into the sharded layout.
+//!
+//! `mirrors/` is first renamed aside to `mirrors.flat`, so shard
+//! directories never collide with owners still waiting to move. Then the
+//! `.sharded` marker is written and each owner directory is moved under its
+//! shard. An owner whose shard directory already exists (say, a repo
+//! cloned into the new layout before a pass resumed) is merged into it
+//! repo by repo; a repo present in both stays in `mirrors.flat` and is
+//! logged. A daemon that dies partway finds `mirrors.flat` at the next
+//! startup and carries on. Aliases of renamed repos are relinked at the
+//! end, since their relative targets gain a level.
+
+use std::path::{Path, PathBuf};
+
+use crate::cache::CachePaths;
+use crate::types::RepoKey;
+
+/// Where the flat `mirrors/` waits while its owners move into shards.
+fn staging_dir(paths: &CachePaths) -> PathBuf {
+    paths.root().join("mirrors.flat")
+}
+
+/// Shard `paths`' mirrors if `enabled` and they aren't yet, or finish an
+/// interrupted pass. Returns the paths for the resulting layout; failures
+/// are logged and leave the cache serving whatever layout it has.
+pub fn shard_cache_layout(paths: CachePaths, enabled: bool) -> CachePaths {
+    let resuming = staging_dir(&paths).exists();
+    if !resuming && (!enabled || paths.is_sharded()) {
+        if !enabled && paths.is_sharded() {
+            log::debug!("Mirrors stay sharded; the layout isn't converted back");
+        }
+        return paths;
+    }
+    match shard_mirrors(&paths) {
+        Ok(moved) => log::info!("Moved {moved} owner directories into mirror shards"),
+        Err(err) => log::warn!("Failed to shard mirrors: {err}"),
+    }
+    CachePaths::new(paths.root())
+}
+
+/// Move every owner directory into its shard. Returns how many moved.
+fn shard_mirrors(paths: &CachePaths) -> std::io::Result<usize> {
+    let staging = staging_dir(paths);
+    if !staging.exists() && paths.mirrors_dir().exists() {
+        std::fs::rename(paths.mirrors_dir(), &staging)?;
+    }
+    std::fs::create_dir_all(paths.mirrors_dir())?;
+    std::fs::write(paths.sharded_marker(), b"")?;
+    let sharded = CachePaths::new(paths.root());
+
+    let mut moved = 0;
+    if let Ok(owners) = std::fs::read_dir(&staging) {
+        for owner in owners.flatten() {
+            let Ok(name) = owner.file_name().into_string() else {
+                continue;
+            };
+            if !owner.file_type().is_ok_and(|t| t.is_dir()) {
+                continue;
+            }
+            let target = sharded.owner_dir(&name);
+            if target.exists() {
+                if merge_owner(&owner.path(), &target)? {
+                    moved += 1;
+                }
+                continue;
+            }
+            if let Some(shard) = target.parent() {
+                std::fs::create_dir_all(shard)?;
+            }
+            std::fs::rename(owner.path(), &target)?;
+            moved += 1;
+        }
+    }
+    if let Err(err) = std::fs::remove_dir(&staging)
+        && err.kind() != std::io::ErrorKind::NotFound
+    {
+        log::warn!("Leaving {}: {err}", staging.display());
+    }
+
+    relink_aliases(&sharded);
+    Ok(moved)
+}
+
+/// Move each repo of the staged owner directory `from` into its existing
+/// shard directory `into`, leaving (and logging) repos `into` already has.
+/// Returns whether `from` was emptied and removed.
+fn merge_owner(from: &Path, into: &Path) -> std::io::Result<bool> {
+    let mut conflicts = 0;
+    for repo in std::fs::read_dir(from)?.flatten() {
+        let target = into.join(repo.file_name());
+        if target.symlink_metadata().is_ok() {
+            log::warn!(
+                "Not sharding {}: {} exists",
+                repo.path().display(),
+                target.display()
+            );
+            conflicts += 1;
+            continue;
+        }
+        std::fs::rename(repo.path(), &target)?;
+    }
+    if conflicts > 0 {
+        return Ok(false);
+    }
+    std::fs::remove_dir(from)?;
+    Ok(true)
+}
+
+/// Recreate every alias symlink with a target relative to its new depth.
+fn relink_aliases(paths: &CachePaths) {
+    for owner_dir in paths.owner_dirs() {
+        let Some(owner) = owner_dir.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Ok(repos) = std::fs::read_dir(&owner_dir) else {
+            continue;
+        };
+        for repo in repos.flatten() {
+            if !repo.path().is_symlink() {
+                continue;
+            }
+            let file_name = repo.file_name();
+            let Some(name) = file_name.to_str().and_then(|n| n.strip_suffix(".git")) else {
+                continue;
+            };
+            let Ok(from) = format!("{owner}/{name}").parse::<RepoKey>() else {
+                continue;
+            };
+            if let Some(to) = paths.alias_target(&from)
+                && let Err(err) = paths.alias(&from, &to)
+            {
+                log::warn!("Failed to relink {from} to {to}: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn key(s: &str) -> RepoKey {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn flat_mirrors_move_into_shards() {
+        let dir = tempdir().unwrap();
+        let flat = CachePaths::new(dir.path());
+        for repo in ["octocat/hello-world", "oc/tools", "rust-lang/rust"] {
+            git2::Repository::init_bare(flat.mirror_dir(&key(repo))).unwrap();
+        }
+        flat.alias(&key("octocat/old-name"), &key("rust-lang/rust"))
+            .unwrap();
+
+        // Disabled leaves the flat layout alone.
+        let paths = shard_cache_layout(flat, false);
+        assert!(!paths.is_sharded());
+
+        let paths = shard_cache_layout(paths, true);
+        assert!(paths.is_sharded());
+        assert!(!dir.path().join("mirrors.flat").exists());
+        // An owner named like a shard lands inside its own shard.
+        for repo in ["octocat/hello-world", "oc/tools", "rust-lang/rust"] {
+            let mirror = paths.mirror_dir(&key(repo));
+            assert!(mirror.starts_with(dir.path().join("mirrors").join(&repo[..2])));
+            assert!(mirror.join("HEAD").exists(), "{repo} not moved");
+        }
+        assert_eq!(
+            paths.alias_target(&key("octocat/old-name")),
+            Some(key("rust-lang/rust"))
+        );
+        assert!(
+            paths
+                .mirror_dir(&key("octocat/old-name"))
+                .join("HEAD")
+                .exists()
+        );
+
+        // Running again, enabled or not, changes nothing.
+        let paths = shard_cache_layout(paths, true);
+        let paths = shard_cache_layout(paths, false);
+        assert!(paths.is_sharded());
+        assert_eq!(paths.owner_dirs().len(), 3);
+    }
+
+    #[test]
+    fn owners_already_in_a_shard_are_merged() {
+        let dir = tempdir().unwrap();
+        let flat = CachePaths::new(dir.path());
+        for repo in ["octocat/hello-world", "octocat/spoon-knife"] {
+            git2::Repository::init_bare(flat.mirror_dir(&key(repo))).unwrap();
+        }
+        // A pass interrupted after the marker, with a clone into the new
+        // layout since.
+        std::fs::rename(flat.mirrors_dir(), dir.path().join("mirrors.flat")).unwrap();
+        std::fs::create_dir(flat.mirrors_dir()).unwrap();
+        std::fs::write(flat.sharded_marker(), b"").unwrap();
+        let sharded = CachePaths::new(dir.path());
+        git2::Repository::init_bare(sharded.mirror_dir(&key("octocat/hello-world"))).unwrap();
+        git2::Repository::init_bare(sharded.mirror_dir(&key("octocat/octo-app"))).unwrap();
+
+        let paths = shard_cache_layout(sharded, true);
+        for repo in [
+            "octocat/hello-world",
+            "octocat/spoon-knife",
+            "octocat/octo-app",
+        ] {
+            assert!(
+                paths.mirror_dir(&key(repo)).join("HEAD").exists(),
+                "{repo} missing"
+            );
+        }
+        // The repo in both layouts is left where it was, not overwritten.
+        let staged = dir.path().join("mirrors.flat/octocat");
+        assert!(staged.join("hello-world.git/HEAD").exists());
+        assert!(!staged.join("spoon-knife.git").exists());
+    }
+}
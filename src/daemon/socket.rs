@@ -1,22 +1,36 @@
 //! Unix socket server for handling JSON-RPC requests from the CLI.
 
-use std::io::BufReader;
+use std::collections::HashMap;
+use std::io::{BufReader, Write};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread::{self, JoinHandle};
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
 
 use crate::cache::CachePaths;
 use crate::daemon::gc;
+use crate::daemon::repair;
+use crate::daemon::restore;
+use crate::daemon::scheduler;
 use crate::daemon::state::State;
-use crate::daemon::worker::WorkerHandle;
+use crate::daemon::submitted::SubmittedStatus;
+use crate::daemon::worker::{SyncOutcome, WorkerHandle};
+use crate::fs::{FsStatsProbe, OpCounters};
+use crate::github::ApiClient;
 use crate::protocol::{
-    GcResult, ListResult, RepoInfo, Request, Response, RpcError, RpcErrorResponse, RpcResponse,
-    StatusResult, SyncResult, VersionResult, read_request, write_message,
+    CloneMissingResult, FsStatsResult, GcResult, ListResult, OpStatsResult, PruneResult,
+    RemoteCheck, RemoteStatus, RemoteStatusResult, RepoInfo, Request, Response, RpcError,
+    RpcErrorResponse, RpcResponse, ScheduleEntry, ScheduleResult, StatusResult, SubmitSyncResult,
+    SyncEvent, SyncResult, SyncState, SyncStatusResult, VersionResult, WatchResult, WatchesResult,
+    read_request, write_message,
 };
-use crate::types::RepoKey;
+use crate::store::{GitError, StoreError};
+use crate::time::unix_now;
+use crate::types::{Owner, RepoKey};
 
 /// Get the socket path for the daemon.
 ///
@@ -36,6 +50,8 @@ pub struct Context {
     pub state: Arc<State>,
     pub worker: Arc<WorkerHandle>,
     pub cache_paths: CachePaths,
+    pub op_counters: Arc<OpCounters>,
+    pub fs_stats: FsStatsProbe,
     pub start_time: Instant,
     pub mount_point: String,
     pub shutdown: Arc<AtomicBool>,
@@ -61,17 +77,120 @@ fn handle_request(ctx: &Context, request: Request) -> Result<Response, RpcError>
             }))
         }
 
-        Request::Sync { repo } => {
-            let key: RepoKey = repo
-                .parse()
-                .map_err(|e| RpcError::invalid_params(format!("invalid repo: {}", e)))?;
+        Request::Sync {
+            repo,
+            commit,
+            if_older_than_secs,
+        } => {
+            let key = parse_sync_repo(&repo, &commit, if_older_than_secs)?;
+            let outcome = ctx
+                .worker
+                .sync_outcome(key, commit, if_older_than_secs.map(Duration::from_secs))
+                .map_err(sync_error_to_rpc)?;
+
+            Ok(Response::Sync(SyncResult {
+                commit: outcome.commit().to_string(),
+                fresh: matches!(outcome, SyncOutcome::Fresh(_)),
+                changed: outcome.changed(),
+            }))
+        }
+
+        Request::SubmitSync {
+            repo,
+            commit,
+            if_older_than_secs,
+        } => {
+            let key = parse_sync_repo(&repo, &commit, if_older_than_secs)?;
+            let sync_id = ctx
+                .worker
+                .submit_sync(key, commit, if_older_than_secs.map(Duration::from_secs))
+                .map_err(sync_error_to_rpc)?;
+            Ok(Response::SubmitSync(SubmitSyncResult { sync_id }))
+        }
+
+        Request::SyncStatus { sync_id } => {
+            let (repo, status) = ctx.worker.submitted_status(sync_id).ok_or_else(|| {
+                RpcError::not_found(format!("no sync with id {sync_id} (it may be too old)"))
+            })?;
+            let mut result = SyncStatusResult {
+                sync_id,
+                repo: repo.to_string(),
+                state: SyncState::Pending,
+                commit: None,
+                fresh: false,
+                changed: false,
+                error: None,
+            };
+            match status {
+                SubmittedStatus::Pending => {}
+                SubmittedStatus::Done(outcome) => {
+                    result.state = SyncState::Done;
+                    result.fresh = matches!(outcome, SyncOutcome::Fresh(_));
+                    result.changed = outcome.changed();
+                    result.commit = Some(outcome.commit().to_string());
+                }
+                SubmittedStatus::Failed(error) => {
+                    result.state = SyncState::Failed;
+                    result.error = Some(error);
+                }
+            }
+            Ok(Response::SyncStatus(result))
+        }
 
-            let commit = ctx
+        Request::Remove { repo } => {
+            // State rows are keyed by the canonical name, like the mirrors.
+            let key = repo
+                .parse::<RepoKey>()
+                .map_err(|e| RpcError::invalid_params(format!("invalid repo: {}", e)))?
+                .canonical();
+
+            // Checked by the worker under the repo lock, against the
+            // mount's own table of open handles.
+            let probe = ctx.fs_stats.clone();
+            let checked = key.clone();
+            let had_mirror = ctx
                 .worker
-                .sync(key)
+                .remove(key.clone(), move || match probe.open_in(&checked) {
+                    0 => None,
+                    n => Some(format!("{checked} has {n} open files on the mount")),
+                })
+                .map_err(|e| match e {
+                    StoreError::Busy(reason) => RpcError::busy(reason),
+                    e => RpcError::internal(e.to_string()),
+                })?;
+            let repos = ctx
+                .state
+                .list_repos()
                 .map_err(|e| RpcError::internal(e.to_string()))?;
+            let known = repos
+                .iter()
+                .any(|r| r.owner == key.owner.as_str() && r.repo == key.repo.as_str());
+            if !had_mirror && !known {
+                return Err(RpcError::not_found(format!("{} is not cached", key)));
+            }
+
+            // Generations cascade via the foreign key. The mirror is already
+            // gone, so a state failure is only logged; gc reconciles later.
+            if let Err(e) = ctx.state.delete_repo(&key) {
+                log::warn!("Failed to delete state for {key}: {e}");
+            }
 
-            Ok(Response::Sync(SyncResult { commit }))
+            Ok(Response::Ok(()))
+        }
+
+        Request::Prune { repo } => {
+            let key = repo
+                .parse::<RepoKey>()
+                .map_err(|e| RpcError::invalid_params(format!("invalid repo: {}", e)))?
+                .canonical();
+
+            let mirror = ctx.cache_paths.mirror_dir(&key);
+            let before = repair::dir_size(&mirror);
+            ctx.worker.prune(key.clone()).map_err(sync_error_to_rpc)?;
+
+            Ok(Response::Prune(PruneResult {
+                freed_bytes: before.saturating_sub(repair::dir_size(&mirror)),
+            }))
         }
 
         Request::Gc => {
@@ -93,6 +212,11 @@ fn handle_request(ctx: &Context, request: Request) -> Result<Response, RpcError>
             let infos: Vec<RepoInfo> = repos
                 .into_iter()
                 .map(|r| RepoInfo {
+                    renamed_to: format!("{}/{}", r.owner, r.repo)
+                        .parse::<RepoKey>()
+                        .ok()
+                        .and_then(|key| ctx.cache_paths.alias_target(&key))
+                        .map(|key| key.to_string()),
                     owner: r.owner,
                     repo: r.repo,
                     commit: r.head_commit,
@@ -105,41 +229,229 @@ fn handle_request(ctx: &Context, request: Request) -> Result<Response, RpcError>
             Ok(Response::List(ListResult { repos: infos }))
         }
 
+        Request::RemoteStatus => {
+            let repos = ctx
+                .state
+                .list_repos()
+                .map_err(|e| RpcError::internal(e.to_string()))?;
+            let repo_key =
+                |owner: &str, repo: &str| format!("{owner}/{repo}").parse::<RepoKey>().ok();
+
+            // Only synced repos have a commit to compare.
+            let synced: Vec<RepoKey> = repos
+                .iter()
+                .filter(|r| r.head_commit.is_some())
+                .filter_map(|r| repo_key(&r.owner, &r.repo))
+                .collect();
+            let mut heads: HashMap<RepoKey, String> = synced
+                .clone()
+                .into_iter()
+                .zip(ctx.worker.remote_heads(synced))
+                .filter_map(|(key, head)| match head {
+                    Ok(head) => Some((key, head)),
+                    Err(e) => {
+                        log::debug!("Checking the remote head of {key} failed: {e}");
+                        None
+                    }
+                })
+                .collect();
+
+            let checks = repos
+                .into_iter()
+                .map(|r| {
+                    let remote_commit =
+                        repo_key(&r.owner, &r.repo).and_then(|key| heads.remove(&key));
+                    RemoteCheck {
+                        status: RemoteStatus::compare(
+                            r.head_commit.as_deref(),
+                            remote_commit.as_deref(),
+                        ),
+                        owner: r.owner,
+                        repo: r.repo,
+                        commit: r.head_commit,
+                        remote_commit,
+                    }
+                })
+                .collect();
+            Ok(Response::RemoteStatus(RemoteStatusResult { checks }))
+        }
+
+        Request::Schedule => {
+            let repos = ctx
+                .state
+                .list_repos()
+                .map_err(|e| RpcError::internal(e.to_string()))?;
+
+            let entries = scheduler::plan(&repos, unix_now(), scheduler::MAX_AGE_SECS)
+                .into_iter()
+                .map(|e| ScheduleEntry {
+                    owner: e.owner,
+                    repo: e.repo,
+                    last_sync: e.last_sync_at.map(format_timestamp),
+                    next_due: e.next_due_at.map(format_timestamp),
+                    eligible: e.eligible,
+                })
+                .collect();
+
+            Ok(Response::Schedule(ScheduleResult {
+                check_interval_secs: scheduler::CHECK_INTERVAL.as_secs(),
+                max_age_secs: scheduler::MAX_AGE_SECS as u64,
+                entries,
+            }))
+        }
+
+        Request::OpStats => {
+            let counts = ctx.op_counters.snapshot();
+            Ok(Response::OpStats(OpStatsResult {
+                uptime_ms: ctx.start_time.elapsed().as_millis() as u64,
+                lookup: counts.lookup,
+                getattr: counts.getattr,
+                readdir: counts.readdir,
+                read: counts.read,
+                materialize: counts.materialize,
+            }))
+        }
+
+        Request::FsStats => {
+            let stats = ctx.fs_stats.snapshot();
+            Ok(Response::FsStats(FsStatsResult {
+                passthrough_inodes: stats.passthrough_inodes as u64,
+                virtual_inodes: stats.virtual_inodes as u64,
+                open_files: stats.open_files as u64,
+                cached_trees: stats.cached_trees as u64,
+                cached_blobs: stats.cached_blobs as u64,
+            }))
+        }
+
+        Request::CloneMissing => {
+            let queued = restore::queue_missing(&ctx.state, &ctx.cache_paths, &ctx.worker)
+                .map_err(|e| RpcError::internal(e.to_string()))?;
+            Ok(Response::CloneMissing(CloneMissingResult {
+                queued: queued.iter().map(ToString::to_string).collect(),
+            }))
+        }
+
+        Request::Watch { owner } => {
+            let owner: Owner = owner
+                .parse()
+                .map_err(|e| RpcError::invalid_params(format!("invalid owner: {}", e)))?;
+            if !ApiClient::shared().has_token() {
+                return Err(RpcError::invalid_params(
+                    "Watching an owner lists its repos through the GitHub API, which needs a token",
+                ));
+            }
+            let name = owner.as_str().to_ascii_lowercase();
+            let listed = crate::fs::fetch_repo_names(&name)
+                .map_err(|e| RpcError::network(format!("listing repos for {name}: {e}")))?;
+
+            let watched_since = ctx
+                .state
+                .add_owner_watch(&owner)
+                .map_err(|e| RpcError::internal(e.to_string()))?;
+            let added = scheduler::add_owner_repos(&ctx.state, &name, &listed)
+                .map_err(|e| RpcError::internal(e.to_string()))?;
+            for key in &added {
+                ctx.worker.refresh(key.clone());
+            }
+            Ok(Response::Watch(WatchResult {
+                added: added.iter().map(ToString::to_string).collect(),
+                watched_since,
+            }))
+        }
+
+        Request::Unwatch { owner } => {
+            let owner: Owner = owner
+                .parse()
+                .map_err(|e| RpcError::invalid_params(format!("invalid owner: {}", e)))?;
+            let watched_since = ctx
+                .state
+                .remove_owner_watch(&owner)
+                .map_err(|e| RpcError::internal(e.to_string()))?;
+            Ok(Response::Watch(WatchResult {
+                added: Vec::new(),
+                watched_since,
+            }))
+        }
+
+        Request::Watches => {
+            let owners = ctx
+                .state
+                .list_owner_watches()
+                .map_err(|e| RpcError::internal(e.to_string()))?;
+            Ok(Response::Watches(WatchesResult { owners }))
+        }
+
+        // Takes over the connection, so handle_client deals with it.
+        Request::Subscribe => Err(RpcError::internal("subscribe is not a call")),
+
         Request::Version => Ok(Response::Version(VersionResult {
             version: env!("CARGO_PKG_VERSION").to_string(),
             pid: std::process::id(),
         })),
 
         Request::Stop => {
+            // Both backends watch the flag: the FUSE session unmounts itself,
+            // and the macOS NFS handle unmounts via Drop.
             ctx.shutdown.store(true, Ordering::SeqCst);
-            // FUSE blocks in fuser, so Linux still needs a host-side unmount.
-            // The macOS NFS handle observes shutdown and unmounts via Drop.
-            #[cfg(target_os = "linux")]
-            super::spawn_unmount(ctx.mount_point.clone());
             Ok(Response::Ok(()))
         }
     }
 }
 
+/// Classify a sync failure so the CLI can report a distinct exit code.
+/// Validate a sync's repo and its mutually exclusive options.
+fn parse_sync_repo(
+    repo: &str,
+    commit: &Option<String>,
+    if_older_than_secs: Option<u64>,
+) -> Result<RepoKey, RpcError> {
+    let key: RepoKey = repo
+        .parse()
+        .map_err(|e| RpcError::invalid_params(format!("invalid repo: {}", e)))?;
+    if commit.is_some() && if_older_than_secs.is_some() {
+        return Err(RpcError::invalid_params(
+            "commit and if_older_than_secs are mutually exclusive",
+        ));
+    }
+    Ok(key)
+}
+
+fn sync_error_to_rpc(err: StoreError) -> RpcError {
+    match &err {
+        StoreError::RepoNotFound(_) | StoreError::Git(GitError::NotFound(_)) => {
+            RpcError::not_found(err.to_string())
+        }
+        StoreError::Git(GitError::CloneError(_) | GitError::FetchError(_)) => {
+            RpcError::network(err.to_string())
+        }
+        StoreError::Git(GitError::Unavailable(_)) => RpcError::unavailable(err.to_string()),
+        StoreError::Git(GitError::InvalidInput(_)) => RpcError::invalid_params(err.to_string()),
+        _ => RpcError::internal(err.to_string()),
+    }
+}
+
 /// Format a Unix timestamp as a human-readable relative time.
-fn format_timestamp(ts: i64) -> String {
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
+pub(crate) fn format_timestamp(ts: i64) -> String {
+    format_relative(unix_now() - ts)
+}
+
+/// Format a signed age in seconds: positive is past, negative is future.
+fn format_relative(diff: i64) -> String {
+    let secs = diff.unsigned_abs();
+    let span = if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h", secs / 3600)
+    } else {
+        format!("{}d", secs / 86400)
+    };
 
-    let diff = now - ts;
     if diff < 0 {
-        return format!("in {}s", -diff);
-    }
-    if diff < 60 {
-        format!("{}s ago", diff)
-    } else if diff < 3600 {
-        format!("{}m ago", diff / 60)
-    } else if diff < 86400 {
-        format!("{}h ago", diff / 3600)
+        format!("in {}", span)
     } else {
-        format!("{}d ago", diff / 86400)
+        format!("{} ago", span)
     }
 }
 
@@ -160,6 +472,17 @@ fn handle_client(ctx: &Context, stream: UnixStream) {
 
         let id = rpc_req.id.clone();
 
+        if let Request::Subscribe = rpc_req.request {
+            let events = ctx.worker.events().subscribe();
+            let ack = RpcResponse::new(Response::Ok(()), id);
+            if let Err(e) = write_message(&mut writer, &ack)
+                .and_then(|()| stream_events(&events, &mut writer, &ctx.shutdown))
+            {
+                log::debug!("Event subscriber went away: {}", e);
+            }
+            break;
+        }
+
         match handle_request(ctx, rpc_req.request) {
             Ok(result) => {
                 let response = RpcResponse::new(result, id);
@@ -179,6 +502,22 @@ fn handle_client(ctx: &Context, stream: UnixStream) {
     }
 }
 
+/// Write each event as a line until the subscriber disconnects or the daemon
+/// shuts down.
+fn stream_events<W: Write>(
+    events: &Receiver<SyncEvent>,
+    writer: &mut W,
+    shutdown: &AtomicBool,
+) -> std::io::Result<()> {
+    loop {
+        match events.recv_timeout(Duration::from_secs(1)) {
+            Ok(event) => write_message(writer, &event)?,
+            Err(RecvTimeoutError::Timeout) if !shutdown.load(Ordering::SeqCst) => {}
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
 /// Handle for managing the socket server thread.
 pub struct SocketServerHandle {
     thread: Option<JoinHandle<()>>,
@@ -191,6 +530,8 @@ impl SocketServerHandle {
         state: Arc<State>,
         worker: Arc<WorkerHandle>,
         cache_paths: CachePaths,
+        op_counters: Arc<OpCounters>,
+        fs_stats: FsStatsProbe,
         mount_point: String,
         shutdown: Arc<AtomicBool>,
     ) -> std::io::Result<Self> {
@@ -208,6 +549,8 @@ impl SocketServerHandle {
             state,
             worker,
             cache_paths,
+            op_counters,
+            fs_stats,
             start_time: Instant::now(),
             mount_point,
             shutdown: Arc::clone(&shutdown),
@@ -1,17 +1,39 @@
 //! SQLite-based state persistence for tracking repos and sync state.
 
-use rusqlite::{Connection, params, params_from_iter};
+use rusqlite::{Connection, ErrorCode, params, params_from_iter};
 use std::path::Path;
 use std::sync::Mutex;
+use std::time::Duration;
 
-use crate::types::RepoKey;
+use crate::time::unix_now;
+use crate::types::{Owner, RepoKey};
 
-/// Returns the current Unix timestamp in seconds.
-fn now_unix() -> i64 {
-    std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64
+/// How long SQLite waits on a lock held by another connection before
+/// returning `SQLITE_BUSY`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Extra attempts for a write that still hit a lock after [`BUSY_TIMEOUT`].
+const WRITE_RETRIES: u32 = 3;
+
+/// Whether `err` is a lock conflict worth retrying.
+fn is_busy(err: &rusqlite::Error) -> bool {
+    matches!(
+        err.sqlite_error_code(),
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked)
+    )
+}
+
+/// When the lowercased `owner` was first watched, if it is.
+fn watched_since(conn: &Connection, owner: &str) -> Result<Option<i64>, rusqlite::Error> {
+    match conn.query_row(
+        "SELECT created_at FROM owner_watches WHERE owner = ?1",
+        params![owner],
+        |row| row.get(0),
+    ) {
+        Ok(since) => Ok(Some(since)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(err) => Err(err),
+    }
 }
 
 /// Convert Option<i64> to Option<u64> for generation IDs.
@@ -35,6 +57,8 @@ pub struct RepoState {
     pub head_commit: Option<String>,
     pub last_access_at: Option<i64>,
     pub last_sync_at: Option<i64>,
+    /// Refresh interval overriding the scheduler's default, if set.
+    pub max_age_secs: Option<i64>,
 }
 
 /// Repo state with aggregated generation stats.
@@ -46,20 +70,63 @@ pub struct RepoStats {
     pub head_commit: Option<String>,
     pub last_access_at: Option<i64>,
     pub last_sync_at: Option<i64>,
+    pub max_age_secs: Option<i64>,
     pub generation_count: u64,
     pub commit_count: u64,
+    pub mirror_size_bytes: u64,
     pub total_size_bytes: u64,
 }
 
+/// A generation row in the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenerationState {
+    pub generation: u64,
+    pub commit: String,
+    pub size_bytes: u64,
+    pub created_at: i64,
+}
+
 impl State {
-    /// Open or create the state database at the given path.
+    /// Open or create the state database at the given path. Lock conflicts
+    /// with other connections wait up to [`BUSY_TIMEOUT`].
     pub fn open(path: &Path) -> Result<Self, rusqlite::Error> {
         let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
         Ok(Self {
             conn: Mutex::new(conn),
         })
     }
 
+    /// Open a private in-memory database. Used when the on-disk database is
+    /// unusable so the daemon can keep serving without persistence.
+    pub fn open_in_memory() -> Result<Self, rusqlite::Error> {
+        Ok(Self {
+            conn: Mutex::new(Connection::open_in_memory()?),
+        })
+    }
+
+    /// Run a write, retrying with backoff while another process holds the
+    /// database lock past the busy timeout. The connection is released
+    /// between attempts so readers in this process aren't stuck behind the
+    /// backoff.
+    fn write<T>(
+        &self,
+        op: impl Fn(&Connection) -> Result<T, rusqlite::Error>,
+    ) -> Result<T, rusqlite::Error> {
+        let mut attempt = 0;
+        loop {
+            let result = op(&self.conn.lock().unwrap());
+            match result {
+                Err(err) if is_busy(&err) && attempt < WRITE_RETRIES => {
+                    attempt += 1;
+                    log::warn!("State database busy, retrying write ({attempt}/{WRITE_RETRIES})");
+                    std::thread::sleep(Duration::from_millis(100) * attempt);
+                }
+                result => return result,
+            }
+        }
+    }
+
     /// Initialize the database schema. This is idempotent.
     pub fn init(&self) -> Result<(), rusqlite::Error> {
         let conn = self.conn.lock().unwrap();
@@ -75,6 +142,7 @@ impl State {
                 last_access_at INTEGER,
                 last_sync_at INTEGER,
                 mirror_size_bytes INTEGER DEFAULT 0,
+                max_age_secs INTEGER,
                 UNIQUE(owner, repo)
             );
 
@@ -89,10 +157,22 @@ impl State {
                 FOREIGN KEY(repo_id) REFERENCES repos(id) ON DELETE CASCADE
             );
 
+            CREATE TABLE IF NOT EXISTS owner_watches (
+                owner TEXT PRIMARY KEY,
+                created_at INTEGER NOT NULL
+            );
+
             CREATE INDEX IF NOT EXISTS idx_repos_sync ON repos(last_sync_at);
             CREATE INDEX IF NOT EXISTS idx_generations_repo ON generations(repo_id);
             ",
         )?;
+        // Databases from before per-repo refresh intervals lack the column.
+        let has_max_age = conn
+            .prepare("SELECT 1 FROM pragma_table_info('repos') WHERE name = 'max_age_secs'")?
+            .exists([])?;
+        if !has_max_age {
+            conn.execute_batch("ALTER TABLE repos ADD COLUMN max_age_secs INTEGER")?;
+        }
         Ok(())
     }
 
@@ -100,18 +180,18 @@ impl State {
     pub fn get_or_create_repo_id(&self, key: &RepoKey) -> Result<i64, rusqlite::Error> {
         let owner = key.owner.as_str();
         let repo = key.repo.as_str();
-        let conn = self.conn.lock().unwrap();
-
-        conn.execute(
-            "INSERT OR IGNORE INTO repos (owner, repo) VALUES (?1, ?2)",
-            params![owner, repo],
-        )?;
+        self.write(|conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO repos (owner, repo) VALUES (?1, ?2)",
+                params![owner, repo],
+            )?;
 
-        conn.query_row(
-            "SELECT id FROM repos WHERE owner = ?1 AND repo = ?2",
-            params![owner, repo],
-            |row| row.get(0),
-        )
+            conn.query_row(
+                "SELECT id FROM repos WHERE owner = ?1 AND repo = ?2",
+                params![owner, repo],
+                |row| row.get(0),
+            )
+        })
     }
 
     /// Get or create a repo record, returning the repo state.
@@ -120,31 +200,33 @@ impl State {
     pub fn get_or_create_repo(&self, key: &RepoKey) -> Result<RepoState, rusqlite::Error> {
         let owner = key.owner.as_str();
         let repo = key.repo.as_str();
-        let conn = self.conn.lock().unwrap();
-
-        // Insert if not exists
-        conn.execute(
-            "INSERT OR IGNORE INTO repos (owner, repo) VALUES (?1, ?2)",
-            params![owner, repo],
-        )?;
+        self.write(|conn| {
+            // Insert if not exists
+            conn.execute(
+                "INSERT OR IGNORE INTO repos (owner, repo) VALUES (?1, ?2)",
+                params![owner, repo],
+            )?;
 
-        // Select the record
-        conn.query_row(
-            "SELECT id, owner, repo, current_generation, head_commit, last_access_at, last_sync_at
-             FROM repos WHERE owner = ?1 AND repo = ?2",
-            params![owner, repo],
-            |row| {
-                Ok(RepoState {
-                    id: row.get(0)?,
-                    owner: row.get(1)?,
-                    repo: row.get(2)?,
-                    current_generation: i64_to_u64_opt(row.get(3)?),
-                    head_commit: row.get(4)?,
-                    last_access_at: row.get(5)?,
-                    last_sync_at: row.get(6)?,
-                })
-            },
-        )
+            // Select the record
+            conn.query_row(
+                "SELECT id, owner, repo, current_generation, head_commit, last_access_at, last_sync_at,
+                    max_age_secs
+                 FROM repos WHERE owner = ?1 AND repo = ?2",
+                params![owner, repo],
+                |row| {
+                    Ok(RepoState {
+                        id: row.get(0)?,
+                        owner: row.get(1)?,
+                        repo: row.get(2)?,
+                        current_generation: i64_to_u64_opt(row.get(3)?),
+                        head_commit: row.get(4)?,
+                        last_access_at: row.get(5)?,
+                        last_sync_at: row.get(6)?,
+                        max_age_secs: row.get(7)?,
+                    })
+                },
+            )
+        })
     }
 
     /// Update repo after a successful sync.
@@ -156,7 +238,7 @@ impl State {
         generation: u64,
         commit: &str,
     ) -> Result<(), rusqlite::Error> {
-        let now = now_unix();
+        let now = unix_now();
         self.update_sync_at(key, generation, commit, now)
     }
 
@@ -171,44 +253,80 @@ impl State {
         let owner = key.owner.as_str();
         let repo = key.repo.as_str();
         let _ = self.get_or_create_repo_id(key)?;
-        let conn = self.conn.lock().unwrap();
+        self.write(|conn| {
+            conn.execute(
+                "UPDATE repos SET current_generation = ?1, head_commit = ?2, last_sync_at = ?3
+                 WHERE owner = ?4 AND repo = ?5",
+                params![generation as i64, commit, ts, owner, repo],
+            )?;
+            Ok(())
+        })
+    }
 
-        conn.execute(
-            "UPDATE repos SET current_generation = ?1, head_commit = ?2, last_sync_at = ?3
-             WHERE owner = ?4 AND repo = ?5",
-            params![generation as i64, commit, ts, owner, repo],
-        )?;
-        Ok(())
+    /// Point a repo's current generation at `generation`.
+    pub fn set_current_generation(
+        &self,
+        key: &RepoKey,
+        generation: u64,
+    ) -> Result<(), rusqlite::Error> {
+        let owner = key.owner.as_str();
+        let repo = key.repo.as_str();
+        self.write(|conn| {
+            conn.execute(
+                "UPDATE repos SET current_generation = ?1 WHERE owner = ?2 AND repo = ?3",
+                params![generation as i64, owner, repo],
+            )?;
+            Ok(())
+        })
     }
 
     /// Clear sync metadata for a repository.
     pub fn clear_sync(&self, key: &RepoKey) -> Result<(), rusqlite::Error> {
         let owner = key.owner.as_str();
         let repo = key.repo.as_str();
-        let conn = self.conn.lock().unwrap();
-
-        conn.execute(
-            "UPDATE repos
-             SET current_generation = NULL, head_commit = NULL, last_sync_at = NULL
-             WHERE owner = ?1 AND repo = ?2",
-            params![owner, repo],
-        )?;
-        Ok(())
+        self.write(|conn| {
+            conn.execute(
+                "UPDATE repos
+                 SET current_generation = NULL, head_commit = NULL, last_sync_at = NULL
+                 WHERE owner = ?1 AND repo = ?2",
+                params![owner, repo],
+            )?;
+            Ok(())
+        })
     }
 
     /// Record an access time for GC decisions.
     pub fn touch_access(&self, key: &RepoKey) -> Result<(), rusqlite::Error> {
         let owner = key.owner.as_str();
         let repo = key.repo.as_str();
-        let now = now_unix();
+        let now = unix_now();
         let _ = self.get_or_create_repo_id(key)?;
-        let conn = self.conn.lock().unwrap();
+        self.write(|conn| {
+            conn.execute(
+                "UPDATE repos SET last_access_at = ?1 WHERE owner = ?2 AND repo = ?3",
+                params![now, owner, repo],
+            )?;
+            Ok(())
+        })
+    }
 
-        conn.execute(
-            "UPDATE repos SET last_access_at = ?1 WHERE owner = ?2 AND repo = ?3",
-            params![now, owner, repo],
-        )?;
-        Ok(())
+    /// Refresh `key` every `max_age_secs` instead of the scheduler's
+    /// default, or go back to the default with `None`.
+    /// Returns false if `key` has no row.
+    pub fn set_max_age(
+        &self,
+        key: &RepoKey,
+        max_age_secs: Option<i64>,
+    ) -> Result<bool, rusqlite::Error> {
+        let owner = key.owner.as_str();
+        let repo = key.repo.as_str();
+        self.write(|conn| {
+            let rows_affected = conn.execute(
+                "UPDATE repos SET max_age_secs = ?1 WHERE owner = ?2 AND repo = ?3",
+                params![max_age_secs, owner, repo],
+            )?;
+            Ok(rows_affected > 0)
+        })
     }
 
     /// Get all repos ordered by staleness.
@@ -216,7 +334,8 @@ impl State {
         let conn = self.conn.lock().unwrap();
 
         let mut stmt = conn.prepare(
-            "SELECT id, owner, repo, current_generation, head_commit, last_access_at, last_sync_at
+            "SELECT id, owner, repo, current_generation, head_commit, last_access_at, last_sync_at,
+                    max_age_secs
              FROM repos
              ORDER BY COALESCE(last_sync_at, 0)",
         )?;
@@ -230,6 +349,7 @@ impl State {
                 head_commit: row.get(4)?,
                 last_access_at: row.get(5)?,
                 last_sync_at: row.get(6)?,
+                max_age_secs: row.get(7)?,
             })
         })?;
 
@@ -242,8 +362,10 @@ impl State {
 
         let mut stmt = conn.prepare(
             "SELECT r.owner, r.repo, r.current_generation, r.head_commit, r.last_access_at, r.last_sync_at,
+                    r.max_age_secs,
                     COALESCE(g.gen_count, 0) AS gen_count,
                     COALESCE(g.commit_count, 0) AS commit_count,
+                    COALESCE(r.mirror_size_bytes, 0) AS mirror_size,
                     COALESCE(g.total_size, 0) + COALESCE(r.mirror_size_bytes, 0) AS total_size
              FROM repos r
              LEFT JOIN (
@@ -265,15 +387,25 @@ impl State {
                 head_commit: row.get(3)?,
                 last_access_at: row.get(4)?,
                 last_sync_at: row.get(5)?,
-                generation_count: row.get::<_, i64>(6)? as u64,
-                commit_count: row.get::<_, i64>(7)? as u64,
-                total_size_bytes: row.get::<_, i64>(8)? as u64,
+                max_age_secs: row.get(6)?,
+                generation_count: row.get::<_, i64>(7)? as u64,
+                commit_count: row.get::<_, i64>(8)? as u64,
+                mirror_size_bytes: row.get::<_, i64>(9)? as u64,
+                total_size_bytes: row.get::<_, i64>(10)? as u64,
             })
         })?;
 
         rows.collect()
     }
 
+    /// Aggregated stats for a single repo, if it has a row.
+    pub fn repo_stats(&self, key: &RepoKey) -> Result<Option<RepoStats>, rusqlite::Error> {
+        Ok(self
+            .list_repos_with_stats()?
+            .into_iter()
+            .find(|r| r.owner == key.owner.as_str() && r.repo == key.repo.as_str()))
+    }
+
     /// Insert or update a generation record for a repo.
     pub fn upsert_generation(
         &self,
@@ -294,20 +426,44 @@ impl State {
         commit: &str,
         size_bytes: u64,
     ) -> Result<(), rusqlite::Error> {
-        let now = now_unix();
+        let now = unix_now();
+        self.write(|conn| {
+            conn.execute(
+                "INSERT INTO generations (repo_id, generation, commit_sha, size_bytes, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(repo_id, generation) DO UPDATE SET
+                    commit_sha = excluded.commit_sha,
+                    size_bytes = excluded.size_bytes,
+                    created_at = excluded.created_at",
+                params![repo_id, generation as i64, commit, size_bytes as i64, now],
+            )?;
+
+            Ok(())
+        })
+    }
+
+    /// A repo's generation rows, oldest first. Empty for unknown repos.
+    pub fn list_generations(&self, key: &RepoKey) -> Result<Vec<GenerationState>, rusqlite::Error> {
         let conn = self.conn.lock().unwrap();
 
-        conn.execute(
-            "INSERT INTO generations (repo_id, generation, commit_sha, size_bytes, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5)
-             ON CONFLICT(repo_id, generation) DO UPDATE SET
-                commit_sha = excluded.commit_sha,
-                size_bytes = excluded.size_bytes,
-                created_at = excluded.created_at",
-            params![repo_id, generation as i64, commit, size_bytes as i64, now],
+        let mut stmt = conn.prepare(
+            "SELECT g.generation, g.commit_sha, g.size_bytes, g.created_at
+             FROM generations g
+             JOIN repos r ON r.id = g.repo_id
+             WHERE r.owner = ?1 AND r.repo = ?2
+             ORDER BY g.generation",
         )?;
 
-        Ok(())
+        let rows = stmt.query_map(params![key.owner.as_str(), key.repo.as_str()], |row| {
+            Ok(GenerationState {
+                generation: row.get::<_, i64>(0)? as u64,
+                commit: row.get(1)?,
+                size_bytes: row.get::<_, i64>(2)? as u64,
+                created_at: row.get(3)?,
+            })
+        })?;
+
+        rows.collect()
     }
 
     /// Delete generation rows for a repo, keeping only the specified generation numbers.
@@ -317,33 +473,33 @@ impl State {
         keep_generations: &[u64],
     ) -> Result<(), rusqlite::Error> {
         let repo_id = self.get_or_create_repo_id(key)?;
-        let conn = self.conn.lock().unwrap();
-
-        if keep_generations.is_empty() {
-            conn.execute(
-                "DELETE FROM generations WHERE repo_id = ?1",
-                params![repo_id],
-            )?;
-            return Ok(());
-        }
-
-        let placeholders = (0..keep_generations.len())
-            .map(|idx| format!("?{}", idx + 2))
-            .collect::<Vec<_>>()
-            .join(", ");
-        let sql = format!(
-            "DELETE FROM generations WHERE repo_id = ?1 AND generation NOT IN ({})",
-            placeholders
-        );
+        self.write(|conn| {
+            if keep_generations.is_empty() {
+                conn.execute(
+                    "DELETE FROM generations WHERE repo_id = ?1",
+                    params![repo_id],
+                )?;
+                return Ok(());
+            }
+
+            let placeholders = (0..keep_generations.len())
+                .map(|idx| format!("?{}", idx + 2))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let sql = format!(
+                "DELETE FROM generations WHERE repo_id = ?1 AND generation NOT IN ({})",
+                placeholders
+            );
 
-        let mut values = Vec::with_capacity(1 + keep_generations.len());
-        values.push(rusqlite::types::Value::Integer(repo_id));
-        for generation in keep_generations {
-            values.push(rusqlite::types::Value::Integer(*generation as i64));
-        }
+            let mut values = Vec::with_capacity(1 + keep_generations.len());
+            values.push(rusqlite::types::Value::Integer(repo_id));
+            for generation in keep_generations {
+                values.push(rusqlite::types::Value::Integer(*generation as i64));
+            }
 
-        conn.execute(&sql, params_from_iter(values))?;
-        Ok(())
+            conn.execute(&sql, params_from_iter(values.iter()))?;
+            Ok(())
+        })
     }
 
     /// Update the stored mirror size for a repo.
@@ -355,26 +511,89 @@ impl State {
         let owner = key.owner.as_str();
         let repo = key.repo.as_str();
         let _ = self.get_or_create_repo_id(key)?;
-        let conn = self.conn.lock().unwrap();
-
-        conn.execute(
-            "UPDATE repos SET mirror_size_bytes = ?1 WHERE owner = ?2 AND repo = ?3",
-            params![size_bytes as i64, owner, repo],
-        )?;
-        Ok(())
+        self.write(|conn| {
+            conn.execute(
+                "UPDATE repos SET mirror_size_bytes = ?1 WHERE owner = ?2 AND repo = ?3",
+                params![size_bytes as i64, owner, repo],
+            )?;
+            Ok(())
+        })
     }
 
     /// Delete a repo record.
     pub fn delete_repo(&self, key: &RepoKey) -> Result<(), rusqlite::Error> {
         let owner = key.owner.as_str();
         let repo = key.repo.as_str();
-        let conn = self.conn.lock().unwrap();
+        self.write(|conn| {
+            conn.execute(
+                "DELETE FROM repos WHERE owner = ?1 AND repo = ?2",
+                params![owner, repo],
+            )?;
+            Ok(())
+        })
+    }
 
-        conn.execute(
-            "DELETE FROM repos WHERE owner = ?1 AND repo = ?2",
-            params![owner, repo],
-        )?;
-        Ok(())
+    /// Move a repo record to a new key. When `to` already has a record the
+    /// `from` record is dropped instead, keeping the existing one.
+    pub fn rename_repo(&self, from: &RepoKey, to: &RepoKey) -> Result<(), rusqlite::Error> {
+        let (from_owner, from_repo) = (from.owner.as_str(), from.repo.as_str());
+        let (to_owner, to_repo) = (to.owner.as_str(), to.repo.as_str());
+        self.write(|conn| {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM repos WHERE owner = ?1 AND repo = ?2)",
+                params![to_owner, to_repo],
+                |row| row.get(0),
+            )?;
+            if exists {
+                conn.execute(
+                    "DELETE FROM repos WHERE owner = ?1 AND repo = ?2",
+                    params![from_owner, from_repo],
+                )?;
+            } else {
+                conn.execute(
+                    "UPDATE repos SET owner = ?1, repo = ?2 WHERE owner = ?3 AND repo = ?4",
+                    params![to_owner, to_repo, from_owner, from_repo],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Watch every repo of `owner`, including ones created later. Returns
+    /// when the owner was first watched if it already was, leaving that
+    /// unchanged, or `None` for a new watch.
+    pub fn add_owner_watch(&self, owner: &Owner) -> Result<Option<i64>, rusqlite::Error> {
+        let owner = owner.as_str().to_ascii_lowercase();
+        let now = unix_now();
+        self.write(|conn| {
+            let since = watched_since(conn, &owner)?;
+            if since.is_none() {
+                conn.execute(
+                    "INSERT INTO owner_watches (owner, created_at) VALUES (?1, ?2)",
+                    params![owner, now],
+                )?;
+            }
+            Ok(since)
+        })
+    }
+
+    /// Stop watching `owner`. Its repos stay known. Returns when the owner
+    /// had been watched since, or `None` if it wasn't watched.
+    pub fn remove_owner_watch(&self, owner: &Owner) -> Result<Option<i64>, rusqlite::Error> {
+        let owner = owner.as_str().to_ascii_lowercase();
+        self.write(|conn| {
+            let since = watched_since(conn, &owner)?;
+            conn.execute("DELETE FROM owner_watches WHERE owner = ?1", params![owner])?;
+            Ok(since)
+        })
+    }
+
+    /// Watched owners, sorted by name.
+    pub fn list_owner_watches(&self) -> Result<Vec<String>, rusqlite::Error> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT owner FROM owner_watches ORDER BY owner")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect()
     }
 
     /// Delete a repo only if it was never successfully synced.
@@ -385,14 +604,14 @@ impl State {
     pub fn delete_repo_if_never_synced(&self, key: &RepoKey) -> Result<bool, rusqlite::Error> {
         let owner = key.owner.as_str();
         let repo = key.repo.as_str();
-        let conn = self.conn.lock().unwrap();
-
-        let rows_affected = conn.execute(
-            "DELETE FROM repos WHERE owner = ?1 AND repo = ?2
-             AND current_generation IS NULL AND last_sync_at IS NULL",
-            params![owner, repo],
-        )?;
-        Ok(rows_affected > 0)
+        self.write(|conn| {
+            let rows_affected = conn.execute(
+                "DELETE FROM repos WHERE owner = ?1 AND repo = ?2
+                 AND current_generation IS NULL AND last_sync_at IS NULL",
+                params![owner, repo],
+            )?;
+            Ok(rows_affected > 0)
+        })
     }
 }
 
@@ -409,6 +628,40 @@ mod tests {
         (state, dir)
     }
 
+    #[test]
+    fn write_waits_out_a_transient_lock() {
+        let (state, dir) = create_test_state();
+        let key = make_repo_key("octocat", "hello-world");
+
+        let other = Connection::open(dir.path().join("test.db")).unwrap();
+        other.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let holder = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            other.execute_batch("COMMIT").unwrap();
+        });
+
+        state.update_sync(&key, 1, "abc123").unwrap();
+        holder.join().unwrap();
+        assert_eq!(
+            state
+                .get_or_create_repo(&key)
+                .unwrap()
+                .head_commit
+                .as_deref(),
+            Some("abc123")
+        );
+    }
+
+    #[test]
+    fn is_busy_matches_only_lock_errors() {
+        let busy = rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_BUSY),
+            None,
+        );
+        assert!(is_busy(&busy));
+        assert!(!is_busy(&rusqlite::Error::QueryReturnedNoRows));
+    }
+
     fn make_repo_key(owner: &str, repo: &str) -> RepoKey {
         RepoKey {
             owner: owner.parse().unwrap(),
@@ -457,7 +710,7 @@ mod tests {
         assert!(repo.last_sync_at.is_some());
 
         // Verify timestamp is recent (within last 5 seconds)
-        let now = now_unix();
+        let now = unix_now();
         let sync_time = repo.last_sync_at.unwrap();
         assert!(now - sync_time < 5);
     }
@@ -478,6 +731,48 @@ mod tests {
         assert!(repo.last_sync_at.is_none());
     }
 
+    #[test]
+    fn max_age_is_set_cleared_and_added_to_old_databases() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("test.db");
+        // A repos table from before the column existed.
+        Connection::open(&db_path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE repos (
+                    id INTEGER PRIMARY KEY,
+                    owner TEXT NOT NULL,
+                    repo TEXT NOT NULL,
+                    current_generation INTEGER,
+                    head_commit TEXT,
+                    last_access_at INTEGER,
+                    last_sync_at INTEGER,
+                    mirror_size_bytes INTEGER DEFAULT 0,
+                    UNIQUE(owner, repo)
+                );
+                INSERT INTO repos (owner, repo) VALUES ('rust-lang', 'rust');",
+            )
+            .unwrap();
+        let state = State::open(&db_path).unwrap();
+        state.init().unwrap();
+        state.init().unwrap();
+
+        let key = make_repo_key("rust-lang", "rust");
+        assert_eq!(state.get_or_create_repo(&key).unwrap().max_age_secs, None);
+        assert!(state.set_max_age(&key, Some(600)).unwrap());
+        assert_eq!(state.list_repos().unwrap()[0].max_age_secs, Some(600));
+        assert_eq!(
+            state.repo_stats(&key).unwrap().unwrap().max_age_secs,
+            Some(600)
+        );
+        assert!(state.set_max_age(&key, None).unwrap());
+        assert_eq!(state.get_or_create_repo(&key).unwrap().max_age_secs, None);
+
+        let unknown = make_repo_key("octocat", "missing");
+        assert!(!state.set_max_age(&unknown, Some(600)).unwrap());
+        assert!(state.repo_stats(&unknown).unwrap().is_none());
+    }
+
     #[test]
     fn test_delete_repo_if_never_synced() {
         let (state, _dir) = create_test_state();
@@ -512,6 +807,25 @@ mod tests {
         assert_eq!(repos[0].owner, "real");
     }
 
+    #[test]
+    fn test_delete_repo_cascades_generations() {
+        let (state, _dir) = create_test_state();
+        let key = make_repo_key("octocat", "hello-world");
+
+        state.update_sync(&key, 2, "commit2").unwrap();
+        state.upsert_generation(&key, 1, "commit1", 100).unwrap();
+        state.upsert_generation(&key, 2, "commit2", 200).unwrap();
+
+        state.delete_repo(&key).unwrap();
+
+        assert!(state.list_repos().unwrap().is_empty());
+        let conn = state.conn.lock().unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM generations", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
     #[test]
     fn test_delete_generations_except() {
         let (state, _dir) = create_test_state();
@@ -571,4 +885,44 @@ mod tests {
             .unwrap();
         assert_eq!(count, 0);
     }
+
+    #[test]
+    fn test_list_generations_in_order() {
+        let (state, _dir) = create_test_state();
+        let key = make_repo_key("octocat", "hello-world");
+
+        assert!(state.list_generations(&key).unwrap().is_empty());
+        state.upsert_generation(&key, 2, "commit2", 200).unwrap();
+        state.upsert_generation(&key, 1, "commit1", 100).unwrap();
+        state
+            .upsert_generation(&make_repo_key("octocat", "other"), 3, "commit3", 300)
+            .unwrap();
+
+        let generations = state.list_generations(&key).unwrap();
+        let listed: Vec<(u64, &str, u64)> = generations
+            .iter()
+            .map(|g| (g.generation, g.commit.as_str(), g.size_bytes))
+            .collect();
+        assert_eq!(listed, [(1, "commit1", 100), (2, "commit2", 200)]);
+    }
+
+    #[test]
+    fn owner_watches_add_and_remove_without_touching_repos() {
+        let (state, _dir) = create_test_state();
+        let key = make_repo_key("octo-org", "api");
+        state.get_or_create_repo(&key).unwrap();
+
+        let owner: Owner = "Octo-Org".parse().unwrap();
+        assert_eq!(state.add_owner_watch(&owner).unwrap(), None);
+        let since = state.add_owner_watch(&owner).unwrap();
+        assert!(since.is_some_and(|since| since > 0));
+        // Watching again keeps the original time.
+        assert_eq!(state.add_owner_watch(&owner).unwrap(), since);
+        assert_eq!(state.list_owner_watches().unwrap(), ["octo-org"]);
+
+        assert_eq!(state.remove_owner_watch(&owner).unwrap(), since);
+        assert_eq!(state.remove_owner_watch(&owner).unwrap(), None);
+        assert!(state.list_owner_watches().unwrap().is_empty());
+        assert_eq!(state.list_repos().unwrap().len(), 1);
+    }
 }
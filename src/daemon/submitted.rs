@@ -0,0 +1,144 @@
+//! Syncs submitted without waiting for them (`ghfs sync --async`).
+//!
+//! Each submitted sync gets an id, and its reply channel is kept under that
+//! id until someone asks for its status. Nothing polls the channels in the
+//! background: a status request reads whatever reply has arrived by then.
+//! Only the latest [`MAX_TRACKED_SYNCS`] ids are kept.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::mpsc as oneshot;
+
+use super::worker::SyncOutcome;
+use crate::store::StoreError;
+use crate::types::RepoKey;
+
+/// How many submitted syncs are remembered; older ids are forgotten.
+pub const MAX_TRACKED_SYNCS: usize = 1000;
+
+/// Where a submitted sync has got to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmittedStatus {
+    /// Queued or running.
+    Pending,
+    /// Finished; see [`SyncOutcome`].
+    Done(SyncOutcome),
+    /// Finished with this error.
+    Failed(String),
+}
+
+enum Tracked {
+    Waiting(oneshot::Receiver<Result<SyncOutcome, StoreError>>),
+    Finished(SubmittedStatus),
+}
+
+/// Submitted syncs by id.
+#[derive(Default)]
+pub struct SubmittedSyncs {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    last_id: u64,
+    syncs: BTreeMap<u64, (RepoKey, Tracked)>,
+}
+
+impl SubmittedSyncs {
+    /// Track a sync of `repo` whose result arrives on `reply`. Returns its
+    /// id, counting up from 1.
+    pub fn track(
+        &self,
+        repo: RepoKey,
+        reply: oneshot::Receiver<Result<SyncOutcome, StoreError>>,
+    ) -> u64 {
+        let mut inner = self.inner.lock().expect("submitted syncs poisoned");
+        inner.last_id += 1;
+        let id = inner.last_id;
+        inner.syncs.insert(id, (repo, Tracked::Waiting(reply)));
+        while inner.syncs.len() > MAX_TRACKED_SYNCS {
+            inner.syncs.pop_first();
+        }
+        id
+    }
+
+    /// The repo and status of sync `id`, or `None` if it's unknown or
+    /// forgotten.
+    pub fn status(&self, id: u64) -> Option<(RepoKey, SubmittedStatus)> {
+        let mut inner = self.inner.lock().expect("submitted syncs poisoned");
+        let (repo, tracked) = inner.syncs.get_mut(&id)?;
+        if let Tracked::Waiting(reply) = tracked {
+            let finished = match reply.try_recv() {
+                Ok(Ok(outcome)) => SubmittedStatus::Done(outcome),
+                Ok(Err(err)) => SubmittedStatus::Failed(err.to_string()),
+                Err(oneshot::TryRecvError::Empty) => {
+                    return Some((repo.clone(), SubmittedStatus::Pending));
+                }
+                Err(oneshot::TryRecvError::Disconnected) => {
+                    SubmittedStatus::Failed("the worker dropped the sync".to_string())
+                }
+            };
+            *tracked = Tracked::Finished(finished);
+        }
+        match tracked {
+            Tracked::Finished(status) => Some((repo.clone(), status.clone())),
+            Tracked::Waiting(_) => unreachable!("waiting syncs were resolved above"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_moves_from_pending_to_finished() {
+        let syncs = SubmittedSyncs::default();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+
+        let (done_tx, done_rx) = oneshot::channel();
+        let (failed_tx, failed_rx) = oneshot::channel();
+        let done = syncs.track(key.clone(), done_rx);
+        let failed = syncs.track(key.clone(), failed_rx);
+        assert_eq!((done, failed), (1, 2));
+        assert_eq!(
+            syncs.status(done),
+            Some((key.clone(), SubmittedStatus::Pending))
+        );
+
+        done_tx
+            .send(Ok(SyncOutcome::Synced("abc".to_string())))
+            .unwrap();
+        let expected = SubmittedStatus::Done(SyncOutcome::Synced("abc".to_string()));
+        assert_eq!(syncs.status(done), Some((key.clone(), expected.clone())));
+        // The result stays readable after the first status request.
+        assert_eq!(syncs.status(done), Some((key.clone(), expected)));
+
+        failed_tx.send(Err(StoreError::LockFailed)).unwrap();
+        assert!(matches!(
+            syncs.status(failed),
+            Some((_, SubmittedStatus::Failed(_)))
+        ));
+        assert_eq!(syncs.status(3), None);
+    }
+
+    #[test]
+    fn old_ids_are_forgotten() {
+        let syncs = SubmittedSyncs::default();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mut senders = Vec::new();
+        for _ in 0..=MAX_TRACKED_SYNCS {
+            let (tx, rx) = oneshot::channel();
+            senders.push(tx);
+            syncs.track(key.clone(), rx);
+        }
+        assert_eq!(syncs.status(1), None);
+        assert!(syncs.status(2).is_some());
+        // A sync whose reply was dropped counts as failed.
+        drop(senders);
+        assert!(matches!(
+            syncs.status(2),
+            Some((_, SubmittedStatus::Failed(_)))
+        ));
+    }
+}
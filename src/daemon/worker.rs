@@ -3,15 +3,45 @@
 //! Offloads network operations (blobless clone/fetch, ref resolution) from
 //! the mount backend thread so filesystem operations don't block on the
 //! promisor. Operates entirely against the [`crate::store::Store`].
+//!
+//! Requests that may touch the network run on their own threads, at most
+//! [`SYNC_CONCURRENCY_ENV`] at a time; the rest wait in the queue. The store
+//! serializes work on any one repo, so only different repos overlap.
+//!
+//! Queued requests are served by [`Priority`]: a filesystem lookup waiting
+//! on a clone goes ahead of queued refreshes and syncs, and a background
+//! request waiting for a free slot is put back when one arrives. Jobs that
+//! are already running aren't interrupted.
+//!
+//! At shutdown, [`WorkerHandle::drain`] stops taking requests, drops the
+//! queued ones without starting them, and gives running jobs
+//! [`SHUTDOWN_GRACE_ENV`] to finish. Clones and fetches still
+//! running after that are aborted; an aborted clone removes its temporary
+//! directory, so no half-written mirror is left behind.
 
 use crossbeam_channel::{Receiver, Sender, bounded};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc as oneshot;
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::cache::NegativeCache;
-use crate::store::{Store, StoreError};
+use git2::Oid;
+
+use super::events::EventBus;
+use super::hook::{HookRunner, PostSyncHook};
+use super::quota::{QuotaUsage, Quotas};
+use super::submitted::{SubmittedStatus, SubmittedSyncs};
+use crate::cache::{Absence, NegativeCache};
+use crate::protocol::SyncEvent;
+use crate::store::{GitError, GitProgress, Store, StoreError};
+use crate::time::unix_now;
 use crate::types::RepoKey;
 
+/// Asked under the repo lock before a removal; `Some(reason)` refuses it.
+pub type InUseCheck = Box<dyn FnOnce() -> Option<String> + Send>;
+
 /// Requests the worker can handle.
 pub enum WorkerRequest {
     /// Ensure the repo mirror exists and resolve the default-branch (HEAD)
@@ -32,8 +62,33 @@ pub enum WorkerRequest {
     /// Background refresh (fire and forget): re-fetch the mirror's refs.
     Refresh { repo: RepoKey },
 
-    /// Force sync (from CLI): re-fetch and return the HEAD commit.
+    /// Force sync (from CLI): re-fetch and return the HEAD commit, or serve
+    /// `commit` as HEAD until the next refresh if given. With `max_age`, a
+    /// mirror fetched more recently than that is left alone.
     Sync {
+        repo: RepoKey,
+        commit: Option<String>,
+        max_age: Option<Duration>,
+        reply: oneshot::Sender<Result<SyncOutcome, StoreError>>,
+    },
+
+    /// Delete the repo's mirror and lock, unless `in_use` gives a reason not
+    /// to. Returns whether a mirror existed.
+    Remove {
+        repo: RepoKey,
+        in_use: InUseCheck,
+        reply: oneshot::Sender<Result<bool, StoreError>>,
+    },
+
+    /// Repack the repo's mirror and delete unreachable objects now.
+    Prune {
+        repo: RepoKey,
+        reply: oneshot::Sender<Result<(), StoreError>>,
+    },
+
+    /// Ask the remote which commit the repo's served ref points at,
+    /// answering from a recent check when there is one.
+    RemoteHead {
         repo: RepoKey,
         reply: oneshot::Sender<Result<String, StoreError>>,
     },
@@ -42,96 +97,721 @@ pub enum WorkerRequest {
     Shutdown,
 }
 
+/// How urgently a queued [`WorkerRequest`] is served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// A filesystem operation is blocked on the reply.
+    Interactive,
+    /// Refreshes, CLI syncs, and maintenance.
+    Background,
+    /// The worker is stopping; taken ahead of everything else.
+    Shutdown,
+}
+
+impl WorkerRequest {
+    pub fn priority(&self) -> Priority {
+        match self {
+            Self::Materialize { .. } | Self::Resolve { .. } => Priority::Interactive,
+            Self::Shutdown => Priority::Shutdown,
+            _ => Priority::Background,
+        }
+    }
+
+    /// Whether the request runs on the job limiter rather than inline.
+    fn needs_job_slot(&self) -> bool {
+        !matches!(self, Self::Shutdown)
+    }
+}
+
+/// How many requests the dispatcher takes off the channel before leaving
+/// the rest there, so senders wait again instead of the queue growing.
+const MAX_QUEUED: usize = 1000;
+
+/// Requests received but not yet dispatched: a shutdown first, then
+/// interactive ones, each class in arrival order.
+#[derive(Default)]
+struct RequestQueue {
+    interactive: VecDeque<WorkerRequest>,
+    background: VecDeque<WorkerRequest>,
+}
+
+impl RequestQueue {
+    fn class(&mut self, priority: Priority) -> &mut VecDeque<WorkerRequest> {
+        match priority {
+            Priority::Interactive | Priority::Shutdown => &mut self.interactive,
+            Priority::Background => &mut self.background,
+        }
+    }
+
+    fn push(&mut self, request: WorkerRequest) {
+        match request.priority() {
+            Priority::Shutdown => self.interactive.push_front(request),
+            priority => self.class(priority).push_back(request),
+        }
+    }
+
+    /// Put a request that was popped but not dispatched back at the head of
+    /// its class.
+    fn push_front(&mut self, request: WorkerRequest) {
+        self.class(request.priority()).push_front(request);
+    }
+
+    fn pop(&mut self) -> Option<WorkerRequest> {
+        self.interactive
+            .pop_front()
+            .or_else(|| self.background.pop_front())
+    }
+
+    fn len(&self) -> usize {
+        self.interactive.len() + self.background.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.interactive.is_empty() && self.background.is_empty()
+    }
+
+    fn has_interactive(&self) -> bool {
+        !self.interactive.is_empty()
+    }
+}
+
+/// How long a remote head answers [`WorkerRequest::RemoteHead`] before
+/// the remote is asked again.
+pub const REMOTE_HEAD_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How often a background request waiting for a job slot checks for
+/// interactive requests to let ahead.
+const PREEMPT_POLL: Duration = Duration::from_millis(20);
+
+/// How often an interruptible wait for the worker checks whether its
+/// caller gave up.
+const INTERRUPT_POLL: Duration = Duration::from_millis(100);
+
+/// Wait for a reply on `rx`, returning [`StoreError::Interrupted`] as soon
+/// as `interrupted` says the caller gave up. The request itself carries
+/// on; its reply is dropped.
+fn wait_interruptible<T>(
+    rx: &oneshot::Receiver<Result<T, StoreError>>,
+    interrupted: &dyn Fn() -> bool,
+) -> Result<T, StoreError> {
+    loop {
+        match rx.recv_timeout(INTERRUPT_POLL) {
+            Ok(result) => return result,
+            Err(oneshot::RecvTimeoutError::Timeout) if interrupted() => {
+                return Err(StoreError::Interrupted);
+            }
+            Err(oneshot::RecvTimeoutError::Timeout) => {}
+            Err(oneshot::RecvTimeoutError::Disconnected) => return Err(StoreError::LockFailed),
+        }
+    }
+}
+
+/// What a [`WorkerRequest::Sync`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncOutcome {
+    /// The mirror was fetched (or advanced) and HEAD moved to this commit.
+    Synced(String),
+    /// The mirror was fetched but HEAD is still this commit.
+    Unchanged(String),
+    /// The mirror was younger than the requested max age and still serves
+    /// this commit; nothing was fetched.
+    Fresh(String),
+}
+
+impl SyncOutcome {
+    pub fn commit(&self) -> &str {
+        match self {
+            Self::Synced(commit) | Self::Unchanged(commit) | Self::Fresh(commit) => commit,
+        }
+    }
+
+    /// Whether HEAD moved; a first clone counts as moving.
+    pub fn changed(&self) -> bool {
+        matches!(self, Self::Synced(_))
+    }
+}
+
+/// Whether a mirror last fetched at `last_synced` is younger than `max_age`
+/// at `now`. Never-fetched mirrors are never fresh.
+pub fn is_fresh(last_synced: Option<SystemTime>, now: SystemTime, max_age: Duration) -> bool {
+    last_synced.is_some_and(|at| now.duration_since(at).unwrap_or_default() < max_age)
+}
+
+/// Environment variable capping how many clones and fetches run at once.
+pub const SYNC_CONCURRENCY_ENV: &str = "GHFS_SYNC_CONCURRENCY";
+
+/// Default for [`SYNC_CONCURRENCY_ENV`].
+pub const DEFAULT_SYNC_CONCURRENCY: usize = 4;
+
+/// The concurrency limit from [`SYNC_CONCURRENCY_ENV`], or
+/// [`DEFAULT_SYNC_CONCURRENCY`]. Zero and unparseable values are logged and
+/// ignored.
+pub fn sync_concurrency_from_env() -> usize {
+    sync_concurrency_from_lookup(crate::config::var)
+}
+
+fn sync_concurrency_from_lookup(lookup: impl Fn(&str) -> Option<String>) -> usize {
+    match lookup(SYNC_CONCURRENCY_ENV) {
+        None => DEFAULT_SYNC_CONCURRENCY,
+        Some(value) => match value.trim().parse::<usize>() {
+            Ok(limit) if limit > 0 => limit,
+            _ => {
+                log::warn!(
+                    "Ignoring invalid {SYNC_CONCURRENCY_ENV}={value:?}; expected a positive number"
+                );
+                DEFAULT_SYNC_CONCURRENCY
+            }
+        },
+    }
+}
+
+/// Environment variable setting how many seconds running clones and
+/// fetches get to finish at shutdown before they're aborted.
+pub const SHUTDOWN_GRACE_ENV: &str = "GHFS_SHUTDOWN_GRACE";
+
+/// Default for [`SHUTDOWN_GRACE_ENV`].
+pub const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(30);
+
+/// How long aborted jobs get to clean up after themselves.
+const ABORT_GRACE: Duration = Duration::from_secs(5);
+
+/// The grace period from [`SHUTDOWN_GRACE_ENV`], or
+/// [`DEFAULT_SHUTDOWN_GRACE`]. Unparseable values are logged and ignored.
+pub fn shutdown_grace_from_env() -> Duration {
+    shutdown_grace_from_lookup(crate::config::var)
+}
+
+fn shutdown_grace_from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Duration {
+    match lookup(SHUTDOWN_GRACE_ENV) {
+        None => DEFAULT_SHUTDOWN_GRACE,
+        Some(value) => match value.trim().parse::<u64>() {
+            Ok(secs) => Duration::from_secs(secs),
+            Err(_) => {
+                log::warn!(
+                    "Ignoring invalid {SHUTDOWN_GRACE_ENV}={value:?}; expected a number of seconds"
+                );
+                DEFAULT_SHUTDOWN_GRACE
+            }
+        },
+    }
+}
+
+/// Counting semaphore that runs each job on its own thread, at most `limit`
+/// at a time.
+struct JobLimiter {
+    limit: usize,
+    running: Mutex<usize>,
+    changed: Condvar,
+    /// Set by [`JobLimiter::close`]; no job starts afterwards.
+    closed: AtomicBool,
+}
+
+/// A running job's slot, released on drop (even if the job panics).
+struct Permit(Arc<JobLimiter>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        *self.0.running.lock().expect("job limiter poisoned") -= 1;
+        self.0.changed.notify_all();
+    }
+}
+
+impl JobLimiter {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            running: Mutex::new(0),
+            changed: Condvar::new(),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Run `job` on a new thread once a slot is free, blocking until then.
+    /// After [`JobLimiter::close`], `job` is dropped instead, and its reply
+    /// sender with it.
+    fn spawn(self: &Arc<Self>, job: impl FnOnce() + Send + 'static) {
+        let mut running = self.running.lock().expect("job limiter poisoned");
+        while *running >= self.limit && !self.is_closed() {
+            running = self.changed.wait(running).expect("job limiter poisoned");
+        }
+        if self.is_closed() {
+            return;
+        }
+        *running += 1;
+        drop(running);
+
+        let permit = Permit(Arc::clone(self));
+        let spawned = thread::Builder::new()
+            .name("ghfs-sync".to_string())
+            .spawn(move || {
+                let _permit = permit;
+                job();
+            });
+        if let Err(e) = spawned {
+            // The closure (and its permit) is dropped, freeing the slot; the
+            // job's reply sender goes with it, so callers see an error.
+            log::error!("Failed to spawn sync thread: {e}");
+        }
+    }
+
+    /// Start no more jobs, waking a dispatcher waiting for a slot. Running
+    /// jobs carry on.
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _running = self.running.lock().expect("job limiter poisoned");
+        self.changed.notify_all();
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Wait up to `timeout` for a free slot; returns whether one is free.
+    /// Only the dispatching thread takes slots, so a free one stays free
+    /// until it calls [`JobLimiter::spawn`].
+    fn wait_for_slot(&self, timeout: Duration) -> bool {
+        let running = self.running.lock().expect("job limiter poisoned");
+        let (running, _) = self
+            .changed
+            .wait_timeout_while(running, timeout, |running| *running >= self.limit)
+            .expect("job limiter poisoned");
+        *running < self.limit
+    }
+
+    /// Block until every running job has finished.
+    fn wait_idle(&self) {
+        let mut running = self.running.lock().expect("job limiter poisoned");
+        while *running > 0 {
+            running = self.changed.wait(running).expect("job limiter poisoned");
+        }
+    }
+
+    /// Wait up to `timeout` for every running job to finish. Returns how
+    /// many are still running.
+    fn wait_idle_timeout(&self, timeout: Duration) -> usize {
+        let running = self.running.lock().expect("job limiter poisoned");
+        let (running, _) = self
+            .changed
+            .wait_timeout_while(running, timeout, |running| *running > 0)
+            .expect("job limiter poisoned");
+        *running
+    }
+}
+
 /// Background worker that processes git operations.
 pub struct Worker {
     receiver: Receiver<WorkerRequest>,
     store: Store,
     negative_cache: NegativeCache,
+    post_sync_hook: Option<HookRunner>,
+    quotas: Quotas,
+    quota_usage: QuotaUsage,
+    events: Arc<EventBus>,
+    jobs: Arc<JobLimiter>,
+    /// Remote heads from recent `ls-remote`s, with when they were asked.
+    remote_heads: Mutex<HashMap<RepoKey, (Instant, String)>>,
 }
 
 impl Worker {
-    pub fn new(receiver: Receiver<WorkerRequest>, store: Store) -> Self {
+    pub fn new(
+        receiver: Receiver<WorkerRequest>,
+        store: Store,
+        post_sync_hook: Option<PostSyncHook>,
+        quotas: Quotas,
+        events: Arc<EventBus>,
+        concurrency: usize,
+    ) -> Self {
+        // Progress goes out as events; `ghfs sync` renders it for its repo.
+        let progress = Arc::clone(&events);
+        let store = store.with_progress(Arc::new(move |repo: &RepoKey, p: GitProgress| {
+            progress.publish(SyncEvent::Progress {
+                repo: repo.to_string(),
+                phase: p.phase,
+                percent: p.percent,
+            })
+        }));
         Self {
             receiver,
             store,
             negative_cache: NegativeCache::new(),
+            post_sync_hook: post_sync_hook.map(HookRunner::spawn),
+            quotas,
+            quota_usage: QuotaUsage::default(),
+            events,
+            jobs: Arc::new(JobLimiter::new(concurrency)),
+            remote_heads: Mutex::new(HashMap::new()),
         }
     }
 
-    /// Run the worker loop (blocks until Shutdown).
+    /// HEAD of an already-cloned mirror, without triggering a clone.
+    fn cached_head(&self, repo: &RepoKey) -> Option<Oid> {
+        if !self.store.paths().mirror_dir(repo).exists() {
+            return None;
+        }
+        self.store.resolve_head(repo).ok()
+    }
+
+    /// HEAD of `repo` if its mirror fetched within `max_age`.
+    fn fresh_head(&self, repo: &RepoKey, max_age: Duration) -> Option<Oid> {
+        if !is_fresh(self.store.last_synced(repo), SystemTime::now(), max_age) {
+            return None;
+        }
+        self.cached_head(repo)
+    }
+
+    /// Resolving against a missing mirror clones it, which counts toward the
+    /// quotas; an existing mirror doesn't grow on reads.
+    fn check_clone_quota(&self, repo: &RepoKey) -> Result<(), StoreError> {
+        if self.store.paths().mirror_dir(repo).exists() {
+            return Ok(());
+        }
+        self.quotas
+            .check(&self.quota_usage, self.store.paths(), repo)
+    }
+
+    /// Refresh `repo` (or advance it to `commit`), then publish an event
+    /// and fire the post-sync hook if HEAD moved. Failures are published too.
+    fn sync_and_notify(
+        &self,
+        repo: &RepoKey,
+        commit: Option<&str>,
+    ) -> Result<SyncOutcome, StoreError> {
+        let previous = self.cached_head(repo);
+        let synced = self
+            .quotas
+            .check(&self.quota_usage, self.store.paths(), repo)
+            .and_then(|()| match commit {
+                Some(sha) => self.store.advance_to(repo, sha),
+                None => self
+                    .store
+                    .refresh(repo)
+                    .and_then(|()| self.store.resolve_head(repo)),
+            });
+        if self.quotas.is_enabled() {
+            self.quota_usage.record(self.store.paths(), repo);
+        }
+        // Whatever the remote said before this sync may be older than the
+        // head just fetched.
+        self.remote_heads
+            .lock()
+            .expect("remote heads poisoned")
+            .remove(repo);
+        let head = match synced {
+            Ok(head) => head,
+            Err(e) => {
+                self.events.publish(SyncEvent::Failed {
+                    repo: repo.to_string(),
+                    error: e.to_string(),
+                    at: unix_now(),
+                });
+                return Err(e);
+            }
+        };
+        if previous == Some(head) {
+            return Ok(SyncOutcome::Unchanged(head.to_string()));
+        }
+        self.events.publish(SyncEvent::Synced {
+            repo: repo.to_string(),
+            commit: head.to_string(),
+            previous: previous.map(|oid| oid.to_string()),
+            at: unix_now(),
+        });
+        if let Some(hook) = &self.post_sync_hook {
+            // Runs on the hook thread; its outcome never reaches the sync
+            // result.
+            hook.submit(repo, &head.to_string(), self.store.paths().mirror_dir(repo));
+        }
+        Ok(SyncOutcome::Synced(head.to_string()))
+    }
+
+    /// The error for a repo in the negative cache, if it is.
+    fn cached_failure(&self, repo: &RepoKey) -> Option<StoreError> {
+        self.negative_cache.get(repo).map(|absence| match absence {
+            Absence::NotFound => StoreError::RepoNotFound(repo.to_string()),
+            Absence::Unavailable(message) => GitError::Unavailable(message).into(),
+        })
+    }
+
+    /// Negative-cache a repo GitHub refused to serve, so it isn't retried.
+    fn remember_unavailable(&self, repo: &RepoKey, err: &StoreError) {
+        if let StoreError::Git(GitError::Unavailable(message)) = err {
+            self.negative_cache
+                .insert_unavailable(repo, message.clone());
+        }
+    }
+
+    fn materialize(&self, repo: RepoKey, reply: oneshot::Sender<Result<String, StoreError>>) {
+        if let Some(err) = self.cached_failure(&repo) {
+            let _ = reply.send(Err(err));
+            return;
+        }
+        let result = self
+            .check_clone_quota(&repo)
+            .and_then(|()| self.store.resolve_head(&repo))
+            .map(|oid| oid.to_string());
+        match &result {
+            // Confirm via the API before caching; the error is
+            // returned to the caller either way.
+            Err(StoreError::Git(GitError::CloneError(_))) => {
+                self.negative_cache.insert_if_not_exists(&repo);
+            }
+            Err(e) => {
+                log_disk_full(&repo, e);
+                self.remember_unavailable(&repo, e);
+            }
+            Ok(_) => {}
+        }
+        let _ = reply.send(result);
+    }
+
+    fn refresh(&self, repo: RepoKey) {
+        if self.negative_cache.contains(&repo) {
+            return;
+        }
+        if let Err(e) = self.sync_and_notify(&repo, None) {
+            log_disk_full(&repo, &e);
+            self.remember_unavailable(&repo, &e);
+            log::warn!("Background refresh failed for {repo}: {e}");
+        }
+    }
+
+    /// The remote's head for `repo`, from a check made within
+    /// [`REMOTE_HEAD_TTL`] if there was one.
+    fn remote_head(&self, repo: &RepoKey) -> Result<String, StoreError> {
+        if let Some((at, head)) = self
+            .remote_heads
+            .lock()
+            .expect("remote heads poisoned")
+            .get(repo)
+            && at.elapsed() < REMOTE_HEAD_TTL
+        {
+            return Ok(head.clone());
+        }
+        let head = self.store.remote_head(repo)?.to_string();
+        self.remote_heads
+            .lock()
+            .expect("remote heads poisoned")
+            .insert(repo.clone(), (Instant::now(), head.clone()));
+        Ok(head)
+    }
+
+    fn sync(
+        &self,
+        repo: RepoKey,
+        commit: Option<String>,
+        max_age: Option<Duration>,
+        reply: oneshot::Sender<Result<SyncOutcome, StoreError>>,
+    ) {
+        if let Some(head) = max_age.and_then(|age| self.fresh_head(&repo, age)) {
+            log::debug!("{repo} fetched within {max_age:?}, not syncing");
+            let _ = reply.send(Ok(SyncOutcome::Fresh(head.to_string())));
+            return;
+        }
+        // Missing repos get another try on an explicit sync; blocked ones
+        // would only be refused again.
+        if let Some(Absence::Unavailable(message)) = self.negative_cache.get(&repo) {
+            let _ = reply.send(Err(GitError::Unavailable(message).into()));
+            return;
+        }
+        let mut result = self.sync_and_notify(&repo, commit.as_deref());
+        // A failed clone is only "not found" once the API agrees;
+        // otherwise it stays a clone (network) error.
+        if let Err(StoreError::Git(GitError::CloneError(_))) = &result
+            && self.negative_cache.insert_if_not_exists(&repo)
+        {
+            result = Err(StoreError::RepoNotFound(repo.to_string()));
+        } else if let Err(e) = &result {
+            self.remember_unavailable(&repo, e);
+        }
+        let _ = reply.send(result);
+    }
+
+    /// Run the worker loop (blocks until Shutdown). Requests that may clone
+    /// or fetch are handed to the job limiter; the loop blocks while it is
+    /// full, leaving later requests queued. Interactive requests are taken
+    /// first, and a background request waiting for a slot yields to them.
     pub fn run(self) {
         log::info!("Worker thread started");
+        let receiver = self.receiver.clone();
+        let worker = Arc::new(self);
+        let mut queue = RequestQueue::default();
         loop {
-            match self.receiver.recv() {
-                Ok(WorkerRequest::Materialize { repo, reply }) => {
-                    if self.negative_cache.contains(&repo) {
-                        let _ = reply.send(Err(StoreError::RepoNotFound(repo.to_string())));
-                        continue;
-                    }
-                    let result = self.store.resolve_head(&repo).map(|oid| oid.to_string());
-                    if let Err(StoreError::Git(crate::store::GitError::CloneError(_))) = &result {
-                        if self.negative_cache.insert_if_not_exists(&repo) {
-                            // confirmed not found; error already returned
-                        }
+            if worker.jobs.is_closed() {
+                queue_pending(&receiver, &mut queue);
+                if !queue.is_empty() {
+                    // Dropping the requests drops their reply senders,
+                    // so waiting callers see an error.
+                    log::info!("Dropping {} queued requests", queue.len());
+                }
+                log::info!("Worker thread shutting down");
+                break;
+            }
+            if queue.is_empty() {
+                match receiver.recv() {
+                    Ok(request) => queue.push(request),
+                    Err(_) => {
+                        log::info!("Worker channel closed, exiting");
+                        break;
                     }
-                    let _ = reply.send(result);
                 }
-                Ok(WorkerRequest::Resolve {
+            }
+            queue_pending(&receiver, &mut queue);
+            let request = queue.pop().expect("queue is non-empty");
+            if request.priority() == Priority::Background
+                && request.needs_job_slot()
+                && !wait_for_slot_or_interactive(&worker.jobs, &receiver, &mut queue)
+            {
+                queue.push_front(request);
+                continue;
+            }
+
+            let this = Arc::clone(&worker);
+            match request {
+                WorkerRequest::Materialize { repo, reply } => {
+                    worker.jobs.spawn(move || this.materialize(repo, reply));
+                }
+                WorkerRequest::Resolve {
                     repo,
                     selector,
                     reply,
-                }) => {
-                    let _ = reply.send(
-                        self.store
-                            .resolve_revision(&repo, &selector)
-                            .map(|oid| oid.to_string()),
-                    );
+                } => {
+                    worker.jobs.spawn(move || {
+                        let _ = reply.send(
+                            this.check_clone_quota(&repo)
+                                .and_then(|()| this.store.resolve_revision(&repo, &selector))
+                                .map(|oid| oid.to_string()),
+                        );
+                    });
                 }
-                Ok(WorkerRequest::Refresh { repo }) => {
-                    if self.negative_cache.contains(&repo) {
-                        continue;
-                    }
-                    if let Err(e) = self.store.refresh(&repo) {
-                        log::warn!("Background refresh failed for {repo}: {e}");
-                    }
+                WorkerRequest::Refresh { repo } => {
+                    worker.jobs.spawn(move || this.refresh(repo));
                 }
-                Ok(WorkerRequest::Sync { repo, reply }) => {
-                    let _ = reply.send(match self.store.refresh(&repo) {
-                        Ok(()) => self.store.resolve_head(&repo).map(|oid| oid.to_string()),
-                        Err(e) => Err(e),
-                    });
+                WorkerRequest::Sync {
+                    repo,
+                    commit,
+                    max_age,
+                    reply,
+                } => {
+                    worker
+                        .jobs
+                        .spawn(move || this.sync(repo, commit, max_age, reply));
+                }
+                WorkerRequest::Remove {
+                    repo,
+                    in_use,
+                    reply,
+                } => {
+                    // Off the dispatcher: removal waits on the repo lock
+                    // while a refresh of the repo finishes.
+                    worker
+                        .jobs
+                        .spawn(move || drop(reply.send(this.store.remove_unless(&repo, in_use))));
                 }
-                Ok(WorkerRequest::Shutdown) => {
-                    log::info!("Worker thread shutting down");
-                    break;
+                WorkerRequest::Prune { repo, reply } => {
+                    worker
+                        .jobs
+                        .spawn(move || drop(reply.send(this.store.prune(&repo))));
                 }
-                Err(_) => {
-                    log::info!("Worker channel closed, exiting");
-                    break;
+                WorkerRequest::RemoteHead { repo, reply } => {
+                    worker
+                        .jobs
+                        .spawn(move || drop(reply.send(this.remote_head(&repo))));
                 }
+                WorkerRequest::Shutdown => worker.jobs.close(),
             }
         }
     }
 }
 
+/// Move what was already sent to the worker into `queue`, up to
+/// [`MAX_QUEUED`] requests in all.
+fn queue_pending(receiver: &Receiver<WorkerRequest>, queue: &mut RequestQueue) {
+    while queue.len() < MAX_QUEUED {
+        match receiver.try_recv() {
+            Ok(request) => queue.push(request),
+            Err(_) => break,
+        }
+    }
+}
+
+/// Wait for a free job slot, returning false instead if an interactive
+/// request arrives first.
+fn wait_for_slot_or_interactive(
+    jobs: &JobLimiter,
+    receiver: &Receiver<WorkerRequest>,
+    queue: &mut RequestQueue,
+) -> bool {
+    loop {
+        queue_pending(receiver, queue);
+        if queue.has_interactive() || jobs.is_closed() {
+            return false;
+        }
+        if jobs.wait_for_slot(PREEMPT_POLL) {
+            return true;
+        }
+    }
+}
+
+/// Point at the fix when `repo` failed because the cache's disk is full,
+/// which otherwise reads like any other git failure.
+fn log_disk_full(repo: &RepoKey, err: &StoreError) {
+    if let StoreError::Git(GitError::DiskFull(_)) = err {
+        log::error!(
+            "{repo}: the cache's disk is full; free space there, e.g. with `ghfs gc` or `ghfs rm <owner/repo>`"
+        );
+    }
+}
+
 /// Handle for managing the worker thread from the main daemon.
 pub struct WorkerHandle {
     sender: Sender<WorkerRequest>,
-    thread: Option<JoinHandle<()>>,
+    events: Arc<EventBus>,
+    submitted: SubmittedSyncs,
+    jobs: Arc<JobLimiter>,
+    /// Set once [`WorkerHandle::drain`] starts; later requests are refused.
+    closing: AtomicBool,
+    thread: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl WorkerHandle {
-    /// Spawn the worker thread.
-    pub fn spawn(store: Store) -> Self {
+    /// Spawn the worker thread. `post_sync_hook` runs whenever a sync or
+    /// refresh moves a repo's HEAD; clones and fetches that would exceed
+    /// `quotas` are refused. At most [`DEFAULT_SYNC_CONCURRENCY`] clones and
+    /// fetches run at once.
+    pub fn spawn(store: Store, post_sync_hook: Option<PostSyncHook>, quotas: Quotas) -> Self {
+        Self::spawn_with_concurrency(store, post_sync_hook, quotas, DEFAULT_SYNC_CONCURRENCY)
+    }
+
+    /// Like [`WorkerHandle::spawn`], running at most `concurrency` clones
+    /// and fetches at once.
+    pub fn spawn_with_concurrency(
+        store: Store,
+        post_sync_hook: Option<PostSyncHook>,
+        quotas: Quotas,
+        concurrency: usize,
+    ) -> Self {
         let (sender, receiver) = bounded(100);
-        let worker = Worker::new(receiver, store);
+        let events = Arc::new(EventBus::default());
+        let worker = Worker::new(
+            receiver,
+            store,
+            post_sync_hook,
+            quotas,
+            Arc::clone(&events),
+            concurrency,
+        );
+        let jobs = Arc::clone(&worker.jobs);
         let thread = thread::Builder::new()
             .name("ghfs-worker".to_string())
             .spawn(move || worker.run())
             .expect("failed to spawn worker thread");
         Self {
             sender,
-            thread: Some(thread),
+            events,
+            submitted: SubmittedSyncs::default(),
+            jobs,
+            closing: AtomicBool::new(false),
+            thread: Mutex::new(Some(thread)),
         }
     }
 
@@ -139,45 +819,675 @@ impl WorkerHandle {
         self.sender.clone()
     }
 
+    /// Sync events published as the worker completes syncs and refreshes.
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
     /// Ensure the mirror exists and resolve HEAD.
     pub fn materialize(&self, repo: RepoKey) -> Result<String, StoreError> {
         let (tx, rx) = oneshot::channel();
-        self.sender
-            .send(WorkerRequest::Materialize { repo, reply: tx })
-            .map_err(|_| StoreError::LockFailed)?;
+        self.send(WorkerRequest::Materialize { repo, reply: tx })?;
         rx.recv().map_err(|_| StoreError::LockFailed)?
     }
 
+    /// [`WorkerHandle::materialize`], giving up with
+    /// [`StoreError::Interrupted`] once `interrupted` returns true. A clone
+    /// already under way finishes for the next caller.
+    pub fn materialize_interruptible(
+        &self,
+        repo: RepoKey,
+        interrupted: &dyn Fn() -> bool,
+    ) -> Result<String, StoreError> {
+        let (tx, rx) = oneshot::channel();
+        self.send(WorkerRequest::Materialize { repo, reply: tx })?;
+        wait_interruptible(&rx, interrupted)
+    }
+
     /// Ensure the mirror exists and resolve a ref selector.
     pub fn resolve(&self, repo: RepoKey, selector: String) -> Result<String, StoreError> {
         let (tx, rx) = oneshot::channel();
-        self.sender
-            .send(WorkerRequest::Resolve {
-                repo,
-                selector,
-                reply: tx,
-            })
-            .map_err(|_| StoreError::LockFailed)?;
+        self.send(WorkerRequest::Resolve {
+            repo,
+            selector,
+            reply: tx,
+        })?;
         rx.recv().map_err(|_| StoreError::LockFailed)?
     }
 
     pub fn refresh(&self, repo: RepoKey) {
-        let _ = self.sender.send(WorkerRequest::Refresh { repo });
+        let _ = self.send(WorkerRequest::Refresh { repo });
+    }
+
+    /// Force refresh and return the HEAD commit, or advance to `commit`.
+    pub fn sync(&self, repo: RepoKey, commit: Option<String>) -> Result<String, StoreError> {
+        self.request_sync(repo, commit, None)
+            .map(|outcome| outcome.commit().to_string())
+    }
+
+    /// Refresh only if the mirror last fetched more than `max_age` ago.
+    pub fn sync_if_older_than(
+        &self,
+        repo: RepoKey,
+        max_age: Duration,
+    ) -> Result<SyncOutcome, StoreError> {
+        self.request_sync(repo, None, Some(max_age))
+    }
+
+    /// [`WorkerHandle::sync`] (or, with `max_age`,
+    /// [`WorkerHandle::sync_if_older_than`]), reporting whether HEAD moved.
+    pub fn sync_outcome(
+        &self,
+        repo: RepoKey,
+        commit: Option<String>,
+        max_age: Option<Duration>,
+    ) -> Result<SyncOutcome, StoreError> {
+        self.request_sync(repo, commit, max_age)
+    }
+
+    /// Queue a sync like [`WorkerHandle::sync`] (or, with `max_age`,
+    /// [`WorkerHandle::sync_if_older_than`]) without waiting for it.
+    /// Returns an id for [`WorkerHandle::submitted_status`].
+    pub fn submit_sync(
+        &self,
+        repo: RepoKey,
+        commit: Option<String>,
+        max_age: Option<Duration>,
+    ) -> Result<u64, StoreError> {
+        let rx = self.send_sync(repo.clone(), commit, max_age)?;
+        Ok(self.submitted.track(repo, rx))
+    }
+
+    /// The repo and status of a sync queued by [`WorkerHandle::submit_sync`],
+    /// or `None` if `id` is unknown or too old to be remembered.
+    pub fn submitted_status(&self, id: u64) -> Option<(RepoKey, SubmittedStatus)> {
+        self.submitted.status(id)
+    }
+
+    fn request_sync(
+        &self,
+        repo: RepoKey,
+        commit: Option<String>,
+        max_age: Option<Duration>,
+    ) -> Result<SyncOutcome, StoreError> {
+        let rx = self.send_sync(repo, commit, max_age)?;
+        rx.recv().map_err(|_| StoreError::LockFailed)?
     }
 
-    /// Force refresh and return the HEAD commit.
-    pub fn sync(&self, repo: RepoKey) -> Result<String, StoreError> {
+    fn send_sync(
+        &self,
+        repo: RepoKey,
+        commit: Option<String>,
+        max_age: Option<Duration>,
+    ) -> Result<oneshot::Receiver<Result<SyncOutcome, StoreError>>, StoreError> {
         let (tx, rx) = oneshot::channel();
-        self.sender
-            .send(WorkerRequest::Sync { repo, reply: tx })
-            .map_err(|_| StoreError::LockFailed)?;
+        self.send(WorkerRequest::Sync {
+            repo,
+            commit,
+            max_age,
+            reply: tx,
+        })?;
+        Ok(rx)
+    }
+
+    /// Delete the repo's cached mirror and lock, unless `in_use` (asked
+    /// under the repo lock) gives a reason to keep it.
+    pub fn remove(
+        &self,
+        repo: RepoKey,
+        in_use: impl FnOnce() -> Option<String> + Send + 'static,
+    ) -> Result<bool, StoreError> {
+        let (tx, rx) = oneshot::channel();
+        self.send(WorkerRequest::Remove {
+            repo,
+            in_use: Box::new(in_use),
+            reply: tx,
+        })?;
         rx.recv().map_err(|_| StoreError::LockFailed)?
     }
 
+    /// Repack the repo's mirror and delete unreachable objects now.
+    pub fn prune(&self, repo: RepoKey) -> Result<(), StoreError> {
+        let (tx, rx) = oneshot::channel();
+        self.send(WorkerRequest::Prune { repo, reply: tx })?;
+        rx.recv().map_err(|_| StoreError::LockFailed)?
+    }
+
+    /// The remote's head for each of `repos`, in order. All the checks are
+    /// queued before waiting on any, so they run in parallel.
+    pub fn remote_heads(&self, repos: Vec<RepoKey>) -> Vec<Result<String, StoreError>> {
+        let replies: Vec<_> = repos
+            .into_iter()
+            .map(|repo| {
+                let (tx, rx) = oneshot::channel();
+                self.send(WorkerRequest::RemoteHead { repo, reply: tx })
+                    .map(|()| rx)
+            })
+            .collect();
+        replies
+            .into_iter()
+            .map(|rx| rx?.recv().map_err(|_| StoreError::LockFailed)?)
+            .collect()
+    }
+
+    /// Queue `request`, unless the worker is shutting down.
+    fn send(&self, request: WorkerRequest) -> Result<(), StoreError> {
+        if self.closing.load(Ordering::SeqCst) {
+            return Err(StoreError::ShuttingDown);
+        }
+        self.sender
+            .send(request)
+            .map_err(|_| StoreError::LockFailed)
+    }
+
+    /// Stop taking requests and give running jobs up to `grace` to finish.
+    /// Queued requests that haven't started are dropped. Clones and fetches
+    /// still running after `grace` are aborted and get a few seconds to
+    /// clean up. Returns whether every job finished.
+    pub fn drain(&self, grace: Duration) -> bool {
+        if self.closing.swap(true, Ordering::SeqCst) {
+            return self.jobs.wait_idle_timeout(Duration::ZERO) == 0;
+        }
+        self.stop_dispatching();
+        let running = self.jobs.wait_idle_timeout(grace);
+        if running == 0 {
+            return true;
+        }
+        let aborted = crate::store::git::abort_running();
+        log::warn!(
+            "{running} jobs still running after {}s; aborted {aborted} git processes",
+            grace.as_secs()
+        );
+        let running = self.jobs.wait_idle_timeout(ABORT_GRACE);
+        if running > 0 {
+            log::warn!("Exiting with {running} jobs still running");
+        }
+        running == 0
+    }
+
+    /// Stop taking requests, drop queued ones, and wait for running jobs.
     pub fn shutdown(&mut self) {
-        let _ = self.sender.send(WorkerRequest::Shutdown);
-        if let Some(thread) = self.thread.take() {
+        self.closing.store(true, Ordering::SeqCst);
+        self.stop_dispatching();
+        self.jobs.wait_idle();
+    }
+
+    /// Close the job limiter and wait for the dispatcher to exit, so no
+    /// job starts after this returns. Queued requests are dropped.
+    fn stop_dispatching(&self) {
+        self.jobs.close();
+        // Wakes a dispatcher waiting for requests. If the channel is full
+        // the dispatcher isn't waiting, and sees the limiter closed.
+        let _ = self.sender.try_send(WorkerRequest::Shutdown);
+        let thread = self.thread.lock().expect("worker thread poisoned").take();
+        if let Some(thread) = thread {
             let _ = thread.join();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CachePaths;
+    use crate::git_fixtures::{commit, init_bare};
+    use std::path::{Path, PathBuf};
+    use tempfile::tempdir;
+
+    /// A bare "upstream" plus a mirror of it whose `origin` is the upstream
+    /// path, so refreshes fetch locally.
+    fn mirror_of_local_upstream(root: &Path, key: &RepoKey) -> (git2::Repository, CachePaths) {
+        let upstream = init_bare(&root.join("upstream.git"));
+        commit(&upstream, "init", &[]);
+
+        let paths = CachePaths::new(root.join("cache"));
+        let mirror = init_bare(&paths.mirror_dir(key));
+        mirror
+            .remote("origin", upstream.path().to_str().unwrap())
+            .unwrap();
+        (upstream, paths)
+    }
+
+    #[test]
+    fn failing_hook_does_not_fail_sync() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (upstream, paths) = mirror_of_local_upstream(dir.path(), &key);
+        // Leaves a marker per run, then fails.
+        let script = dir.path().join("hook.sh");
+        std::fs::write(&script, "#!/bin/sh\ntouch \"$1\"\nexit 1\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let ran = |commit: &str| dir.path().join(format!("ran-{commit}"));
+        let hook = PostSyncHook::parse(
+            &format!(
+                "{} {}/ran-{{commit}}",
+                script.display(),
+                dir.path().display()
+            ),
+            PathBuf::from("/mnt"),
+        );
+
+        let mut worker = WorkerHandle::spawn(Store::new(paths), hook, Quotas::default());
+        let head = worker.sync(key.clone(), None).unwrap();
+        assert_eq!(
+            head,
+            upstream
+                .refname_to_id("refs/heads/main")
+                .unwrap()
+                .to_string()
+        );
+
+        let first = head;
+
+        let head = commit(&upstream, "second", &[]);
+        assert_eq!(worker.sync(key.clone(), None).unwrap(), head.to_string());
+        worker.shutdown();
+
+        // Hooks run off the worker thread; give them a moment.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !(ran(&first).exists() && ran(&head.to_string()).exists()) {
+            assert!(Instant::now() < deadline, "the hook never ran");
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn sync_to_commit_lasts_until_next_sync() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (upstream, paths) = mirror_of_local_upstream(dir.path(), &key);
+        let first = upstream.refname_to_id("refs/heads/main").unwrap();
+        let second = commit(&upstream, "second", &[]);
+
+        let store = Store::new(paths);
+        let mut worker = WorkerHandle::spawn(store.clone(), None, Quotas::default());
+        assert_eq!(worker.sync(key.clone(), None).unwrap(), second.to_string());
+
+        let pinned = worker.sync(key.clone(), Some(first.to_string())).unwrap();
+        assert_eq!(pinned, first.to_string());
+        assert_eq!(store.resolve_head(&key).unwrap(), first);
+
+        // The override is one-off: a plain sync returns to the remote HEAD.
+        assert_eq!(worker.sync(key.clone(), None).unwrap(), second.to_string());
+        worker.shutdown();
+    }
+
+    #[test]
+    fn submitted_sync_reports_pending_then_done() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (upstream, paths) = mirror_of_local_upstream(dir.path(), &key);
+        let head = upstream.refname_to_id("refs/heads/main").unwrap();
+
+        // Hold the repo's lock so the sync stays queued until released.
+        let store = Store::new(paths);
+        let lock = crate::cache::lock::RepoLock::acquire(&store.paths().lock_path(&key)).unwrap();
+        let mut worker = WorkerHandle::spawn(store, None, Quotas::default());
+        let id = worker.submit_sync(key.clone(), None, None).unwrap();
+        assert_eq!(
+            worker.submitted_status(id),
+            Some((key.clone(), SubmittedStatus::Pending))
+        );
+        assert_eq!(worker.submitted_status(id + 1), None);
+
+        drop(lock);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let status = loop {
+            match worker.submitted_status(id) {
+                Some((_, SubmittedStatus::Pending)) if Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                other => break other,
+            }
+        };
+        assert_eq!(
+            status,
+            Some((
+                key,
+                SubmittedStatus::Done(SyncOutcome::Synced(head.to_string()))
+            ))
+        );
+        worker.shutdown();
+    }
+
+    #[test]
+    fn drain_lets_running_syncs_finish_and_refuses_new_ones() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (upstream, paths) = mirror_of_local_upstream(dir.path(), &key);
+        let head = upstream.refname_to_id("refs/heads/main").unwrap();
+
+        // The sync blocks on the repo's lock until it's released, partway
+        // through the grace period.
+        let store = Store::new(paths);
+        let lock = crate::cache::lock::RepoLock::acquire(&store.paths().lock_path(&key)).unwrap();
+        let worker = WorkerHandle::spawn(store, None, Quotas::default());
+        let id = worker.submit_sync(key.clone(), None, None).unwrap();
+        while worker.jobs.wait_idle_timeout(Duration::ZERO) == 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            drop(lock);
+        });
+
+        assert!(worker.drain(Duration::from_secs(10)));
+        release.join().unwrap();
+        assert_eq!(
+            worker.submitted_status(id),
+            Some((
+                key.clone(),
+                SubmittedStatus::Done(SyncOutcome::Synced(head.to_string()))
+            ))
+        );
+        assert!(matches!(
+            worker.sync(key, None),
+            Err(StoreError::ShuttingDown)
+        ));
+    }
+
+    #[test]
+    fn shutdown_grace_comes_from_env() {
+        assert_eq!(shutdown_grace_from_lookup(|_| None), DEFAULT_SHUTDOWN_GRACE);
+        assert_eq!(
+            shutdown_grace_from_lookup(|_| Some("5".to_string())),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            shutdown_grace_from_lookup(|_| Some("soon".to_string())),
+            DEFAULT_SHUTDOWN_GRACE
+        );
+    }
+
+    #[test]
+    fn freshness_compares_last_fetch_with_max_age() {
+        let now = SystemTime::now();
+        let hour = Duration::from_secs(3600);
+        assert!(is_fresh(Some(now - hour / 2), now, hour));
+        assert!(!is_fresh(Some(now - hour * 2), now, hour));
+        assert!(!is_fresh(Some(now), now, Duration::ZERO));
+        assert!(!is_fresh(None, now, hour));
+        // Clock skew (an mtime in the future) counts as just fetched.
+        assert!(is_fresh(Some(now + hour), now, hour));
+    }
+
+    #[test]
+    fn sync_if_older_than_skips_recent_mirrors() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (upstream, paths) = mirror_of_local_upstream(dir.path(), &key);
+        let first = upstream.refname_to_id("refs/heads/main").unwrap();
+
+        let mut worker = WorkerHandle::spawn(Store::new(paths), None, Quotas::default());
+        worker.sync(key.clone(), None).unwrap();
+        let second = commit(&upstream, "second", &[]);
+
+        let hour = Duration::from_secs(3600);
+        assert_eq!(
+            worker.sync_if_older_than(key.clone(), hour).unwrap(),
+            SyncOutcome::Fresh(first.to_string())
+        );
+        assert_eq!(
+            worker
+                .sync_if_older_than(key.clone(), Duration::ZERO)
+                .unwrap(),
+            SyncOutcome::Synced(second.to_string())
+        );
+        worker.shutdown();
+    }
+
+    #[test]
+    fn sync_reports_whether_head_moved() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (upstream, paths) = mirror_of_local_upstream(dir.path(), &key);
+        let first = upstream.refname_to_id("refs/heads/main").unwrap();
+
+        let mut worker = WorkerHandle::spawn(Store::new(paths), None, Quotas::default());
+        let outcome = worker.sync_outcome(key.clone(), None, None).unwrap();
+        assert_eq!(outcome, SyncOutcome::Synced(first.to_string()));
+        assert!(outcome.changed());
+
+        let outcome = worker.sync_outcome(key.clone(), None, None).unwrap();
+        assert_eq!(outcome, SyncOutcome::Unchanged(first.to_string()));
+        assert!(!outcome.changed());
+
+        let second = commit(&upstream, "second", &[]);
+        let outcome = worker.sync_outcome(key.clone(), None, None).unwrap();
+        assert_eq!(outcome, SyncOutcome::Synced(second.to_string()));
+        assert!(outcome.changed());
+        worker.shutdown();
+    }
+
+    #[test]
+    fn job_limiter_never_exceeds_its_limit() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let limit = 3;
+        let jobs = Arc::new(JobLimiter::new(limit));
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..12 {
+            let (running, peak, done) =
+                (Arc::clone(&running), Arc::clone(&peak), Arc::clone(&done));
+            // A stub "clone" that records how many run at once.
+            jobs.spawn(move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        jobs.wait_idle();
+
+        assert_eq!(done.load(Ordering::SeqCst), 12);
+        let peak = peak.load(Ordering::SeqCst);
+        assert!(peak <= limit, "{peak} jobs ran at once");
+        assert!(peak > 1, "jobs never overlapped");
+    }
+
+    #[test]
+    fn interrupted_wait_returns_while_the_request_runs_on() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // A stub worker holding the reply until after the caller gives up.
+        let (tx, rx) = oneshot::channel::<Result<String, StoreError>>();
+        let checks = AtomicUsize::new(0);
+        let started = Instant::now();
+        let result = wait_interruptible(&rx, &|| checks.fetch_add(1, Ordering::SeqCst) == 1);
+        assert!(matches!(result, Err(StoreError::Interrupted)));
+        assert!(started.elapsed() < INTERRUPT_POLL * 5);
+        assert_eq!(checks.load(Ordering::SeqCst), 2);
+        // The worker's late reply goes nowhere instead of failing.
+        drop(rx);
+        assert!(tx.send(Ok("abc123".to_string())).is_err());
+
+        let (tx, rx) = oneshot::channel();
+        tx.send(Ok("abc123".to_string())).unwrap();
+        assert_eq!(wait_interruptible(&rx, &|| true).unwrap(), "abc123");
+    }
+
+    #[test]
+    fn drain_starts_nothing_that_was_still_queued() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (_upstream, paths) = mirror_of_local_upstream(dir.path(), &key);
+
+        // One slot, held by a sync blocked on the repo's lock, with a
+        // second sync queued behind it.
+        let store = Store::new(paths);
+        let lock = crate::cache::lock::RepoLock::acquire(&store.paths().lock_path(&key)).unwrap();
+        let worker = WorkerHandle::spawn_with_concurrency(store, None, Quotas::default(), 1);
+        let running = worker.submit_sync(key.clone(), None, None).unwrap();
+        while worker.jobs.wait_idle_timeout(Duration::ZERO) == 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let queued = worker.submit_sync(key.clone(), None, None).unwrap();
+        let release = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            drop(lock);
+        });
+
+        assert!(worker.drain(Duration::from_secs(10)));
+        release.join().unwrap();
+        assert!(matches!(
+            worker.submitted_status(running),
+            Some((_, SubmittedStatus::Done(_)))
+        ));
+        assert!(matches!(
+            worker.submitted_status(queued),
+            Some((_, SubmittedStatus::Failed(_)))
+        ));
+        assert_eq!(worker.jobs.wait_idle_timeout(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn interactive_requests_are_served_before_queued_background_ones() {
+        let key = |name: &str| -> RepoKey { format!("octocat/{name}").parse().unwrap() };
+        let mut queue = RequestQueue::default();
+        queue.push(WorkerRequest::Refresh { repo: key("a") });
+        queue.push(WorkerRequest::Refresh { repo: key("b") });
+        let (reply, _rx) = oneshot::channel();
+        queue.push(WorkerRequest::Materialize {
+            repo: key("c"),
+            reply,
+        });
+
+        let order: Vec<String> = std::iter::from_fn(|| queue.pop())
+            .map(|request| match request {
+                WorkerRequest::Refresh { repo } | WorkerRequest::Materialize { repo, .. } => {
+                    repo.repo.as_str().to_string()
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(order, ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn shutdown_goes_ahead_and_the_queue_is_capped() {
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (sender, receiver) = bounded(MAX_QUEUED + 10);
+        for _ in 0..MAX_QUEUED + 5 {
+            sender
+                .send(WorkerRequest::Refresh { repo: key.clone() })
+                .unwrap();
+        }
+        let mut queue = RequestQueue::default();
+        queue_pending(&receiver, &mut queue);
+        assert_eq!(queue.len(), MAX_QUEUED);
+        assert_eq!(receiver.len(), 5);
+
+        let (reply, _rx) = oneshot::channel();
+        queue.push(WorkerRequest::Materialize { repo: key, reply });
+        queue.push(WorkerRequest::Shutdown);
+        assert!(matches!(queue.pop(), Some(WorkerRequest::Shutdown)));
+        assert!(matches!(
+            queue.pop(),
+            Some(WorkerRequest::Materialize { .. })
+        ));
+    }
+
+    #[test]
+    fn background_request_waiting_for_a_slot_yields_to_interactive() {
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (sender, receiver) = bounded(10);
+        let jobs = Arc::new(JobLimiter::new(1));
+        // A stub refresh holding the only slot until released.
+        let (release, hold) = oneshot::channel::<()>();
+        jobs.spawn(move || {
+            let _ = hold.recv();
+        });
+
+        let mut queue = RequestQueue::default();
+        let (reply, _rx) = oneshot::channel();
+        let sent = thread::spawn(move || {
+            thread::sleep(PREEMPT_POLL * 2);
+            sender
+                .send(WorkerRequest::Materialize { repo: key, reply })
+                .unwrap();
+        });
+        assert!(!wait_for_slot_or_interactive(&jobs, &receiver, &mut queue));
+        assert!(queue.has_interactive());
+        sent.join().unwrap();
+
+        // With nothing interactive queued, it waits for the slot.
+        queue.pop();
+        release.send(()).unwrap();
+        assert!(wait_for_slot_or_interactive(&jobs, &receiver, &mut queue));
+    }
+
+    #[test]
+    fn sync_concurrency_reads_env_with_fallback() {
+        let lookup = |value: Option<&'static str>| {
+            move |name: &str| {
+                assert_eq!(name, SYNC_CONCURRENCY_ENV);
+                value.map(str::to_string)
+            }
+        };
+        assert_eq!(
+            sync_concurrency_from_lookup(lookup(None)),
+            DEFAULT_SYNC_CONCURRENCY
+        );
+        assert_eq!(sync_concurrency_from_lookup(lookup(Some(" 8 "))), 8);
+        assert_eq!(
+            sync_concurrency_from_lookup(lookup(Some("0"))),
+            DEFAULT_SYNC_CONCURRENCY
+        );
+        assert_eq!(
+            sync_concurrency_from_lookup(lookup(Some("lots"))),
+            DEFAULT_SYNC_CONCURRENCY
+        );
+    }
+
+    #[test]
+    fn sync_publishes_moves_and_failures() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (upstream, paths) = mirror_of_local_upstream(dir.path(), &key);
+        let first = upstream.refname_to_id("refs/heads/main").unwrap();
+
+        let mut worker = WorkerHandle::spawn(Store::new(paths), None, Quotas::default());
+        let events = worker.events().subscribe();
+        worker.sync(key.clone(), None).unwrap();
+        let second = commit(&upstream, "second", &[]);
+        worker.sync(key.clone(), None).unwrap();
+        // HEAD didn't move, so nothing is published.
+        worker.sync(key.clone(), None).unwrap();
+        assert!(worker.sync(key.clone(), Some("nope".to_string())).is_err());
+        worker.shutdown();
+
+        let received: Vec<SyncEvent> = events
+            .try_iter()
+            .filter(|event| !matches!(event, SyncEvent::Progress { .. }))
+            .collect();
+        assert_eq!(received.len(), 3);
+        match &received[1] {
+            SyncEvent::Synced {
+                repo,
+                commit,
+                previous,
+                ..
+            } => {
+                assert_eq!(repo, "octocat/hello-world");
+                assert_eq!(commit, &second.to_string());
+                assert_eq!(previous.as_deref(), Some(first.to_string().as_str()));
+            }
+            other => panic!("Expected Synced event, got {other:?}"),
+        }
+        assert!(
+            matches!(&received[2], SyncEvent::Failed { repo, .. } if repo == "octocat/hello-world")
+        );
+
+        // Each event is one line of JSON on the wire.
+        let line = serde_json::to_string(&received[1]).unwrap();
+        assert!(!line.contains('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["event"], "synced");
+        assert_eq!(json["commit"], second.to_string());
+    }
+}
@@ -0,0 +1,157 @@
+//! Visibility of mounted repos to users other than the mount owner.
+//!
+//! A single FUSE mount can't carry per-directory mount options, so sharing
+//! is expressed through the modes ghfs reports: with `allow_other` on, repos
+//! not listed as shared drop their group/other bits. The mount then asks
+//! the kernel to enforce those modes (`default_permissions`), and `access`,
+//! `lookup`, `readdir` and `open` check the requesting uid against them too.
+
+use crate::types::RepoKey;
+
+/// Environment variable enabling `allow_other` (`1` or `true`).
+pub const ALLOW_OTHER_ENV: &str = "GHFS_ALLOW_OTHER";
+/// Environment variable listing shared repos, comma-separated `owner/repo`.
+pub const SHARED_REPOS_ENV: &str = "GHFS_SHARED_REPOS";
+
+/// Which repos users other than the mount owner may read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessPolicy {
+    /// Mount with FUSE `allow_other` so other users can reach the mount.
+    pub allow_other: bool,
+    /// Repos readable by other users. Empty shares every repo.
+    pub shared_repos: Vec<RepoKey>,
+}
+
+impl AccessPolicy {
+    /// Build a policy from [`ALLOW_OTHER_ENV`] and [`SHARED_REPOS_ENV`].
+    /// Unparseable repo entries are logged and skipped.
+    pub fn from_env() -> Self {
+        let allow_other = crate::config::var(ALLOW_OTHER_ENV)
+            .is_some_and(|v| matches!(v.trim(), "1" | "true" | "yes"));
+        let shared_repos = crate::config::var(SHARED_REPOS_ENV)
+            .map(|v| parse_repo_list(&v))
+            .unwrap_or_default();
+        Self {
+            allow_other,
+            shared_repos,
+        }
+    }
+
+    /// Whether other users may read `repo` (`None` for the owner/ref
+    /// discovery directories, which stay traversable).
+    pub fn is_shared(&self, repo: Option<&RepoKey>) -> bool {
+        match repo {
+            None => true,
+            Some(_) if !self.allow_other || self.shared_repos.is_empty() => true,
+            Some(key) => self.shared_repos.contains(key),
+        }
+    }
+
+    /// Permission bits to report for a node in `repo` whose natural mode is
+    /// `base`: unchanged when shared, owner-only otherwise.
+    pub fn mode(&self, repo: Option<&RepoKey>, base: u16) -> u16 {
+        if self.is_shared(repo) {
+            base
+        } else {
+            base & 0o700
+        }
+    }
+}
+
+/// Split a comma-separated `owner/repo` list.
+fn parse_repo_list(value: &str) -> Vec<RepoKey> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(|s| match s.parse() {
+            Ok(key) => Some(key),
+            Err(e) => {
+                log::warn!("Ignoring invalid shared repo '{}': {}", s, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// `access(2)` check of `mask` (`R_OK`/`W_OK`/`X_OK` bits) for `req_uid`
+/// against `perm`, for a mount owned by `owner_uid`. Root is always allowed.
+pub fn access_allowed(owner_uid: u32, req_uid: u32, perm: u16, mask: i32) -> bool {
+    if req_uid == 0 {
+        return true;
+    }
+    let bits = if req_uid == owner_uid {
+        (perm >> 6) & 0o7
+    } else {
+        perm & 0o7
+    };
+    let wanted = (mask & 0o7) as u16;
+    bits & wanted == wanted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: u32 = 1000;
+    const OTHER: u32 = 1001;
+    const R_OK: i32 = 4;
+    const X_OK: i32 = 1;
+
+    fn key(s: &str) -> RepoKey {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn default_policy_keeps_modes() {
+        let policy = AccessPolicy::default();
+        assert_eq!(policy.mode(Some(&key("a/b")), 0o755), 0o755);
+        assert_eq!(policy.mode(None, 0o755), 0o755);
+    }
+
+    #[test]
+    fn allow_other_with_list_hides_unlisted_repos() {
+        let policy = AccessPolicy {
+            allow_other: true,
+            shared_repos: vec![key("octocat/public")],
+        };
+        assert_eq!(policy.mode(None, 0o755), 0o755);
+        assert_eq!(policy.mode(Some(&key("octocat/public")), 0o644), 0o644);
+        assert_eq!(policy.mode(Some(&key("octocat/private")), 0o755), 0o700);
+        assert_eq!(policy.mode(Some(&key("octocat/private")), 0o644), 0o600);
+    }
+
+    #[test]
+    fn allow_other_without_list_shares_everything() {
+        let policy = AccessPolicy {
+            allow_other: true,
+            shared_repos: vec![],
+        };
+        assert!(policy.is_shared(Some(&key("any/repo"))));
+    }
+
+    #[test]
+    fn access_checks_requesting_uid() {
+        let policy = AccessPolicy {
+            allow_other: true,
+            shared_repos: vec![key("octocat/public")],
+        };
+        let shared = policy.mode(Some(&key("octocat/public")), 0o755);
+        let private = policy.mode(Some(&key("octocat/private")), 0o755);
+
+        assert!(access_allowed(OWNER, OTHER, shared, R_OK | X_OK));
+        assert!(!access_allowed(OWNER, OTHER, private, R_OK));
+        assert!(access_allowed(OWNER, OTHER, private, 0));
+        assert!(access_allowed(OWNER, OWNER, private, R_OK | X_OK));
+        assert!(access_allowed(OWNER, 0, private, R_OK));
+        assert!(!access_allowed(OWNER, OWNER, 0o644, X_OK));
+    }
+
+    #[test]
+    fn repo_list_skips_invalid_entries() {
+        assert_eq!(
+            parse_repo_list("a/b, bad ,c/d,"),
+            vec![key("a/b"), key("c/d")]
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Recording reads in each repo's `last_access_at`.
+//!
+//! Every file open (every read, on macOS) would otherwise be a database
+//! write, so a repo's access time is written at most once per
+//! [`ACCESS_TOUCH_INTERVAL`]; reads in between only check an in-memory
+//! timestamp. The writes themselves happen on a background thread, so an
+//! open never waits on SQLite.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Sender, bounded};
+use dashmap::DashMap;
+
+use crate::daemon::State;
+use crate::types::RepoKey;
+
+/// How long after recording a repo's access further reads go unrecorded.
+pub const ACCESS_TOUCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Pending writes before further touches are dropped.
+const MAX_PENDING_WRITES: usize = 256;
+
+enum Write {
+    Touch(RepoKey),
+    #[cfg(test)]
+    Flush(Sender<()>),
+}
+
+/// Writes repos' access times to the state database, debounced per repo.
+pub struct AccessTimes {
+    interval: Duration,
+    touched: DashMap<RepoKey, Instant>,
+    writes: Sender<Write>,
+}
+
+impl AccessTimes {
+    /// Record into `state`, at most once per [`ACCESS_TOUCH_INTERVAL`].
+    pub fn new(state: Arc<State>) -> Self {
+        Self::with_interval(state, ACCESS_TOUCH_INTERVAL)
+    }
+
+    /// Record into `state`, at most once per `interval`.
+    pub fn with_interval(state: Arc<State>, interval: Duration) -> Self {
+        let (writes, pending) = bounded(MAX_PENDING_WRITES);
+        thread::Builder::new()
+            .name("ghfs-access-times".to_string())
+            .spawn(move || {
+                for write in pending {
+                    match write {
+                        Write::Touch(key) => {
+                            if let Err(err) = state.touch_access(&key) {
+                                log::debug!("Failed to record access to {key}: {err}");
+                            }
+                        }
+                        #[cfg(test)]
+                        Write::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn access time thread");
+        Self {
+            interval,
+            touched: DashMap::new(),
+            writes,
+        }
+    }
+
+    /// Note a read under `repo`. Returns whether a write was queued, which
+    /// it isn't if the last one was within the interval or the writer is
+    /// backed up.
+    pub fn touch(&self, repo: &RepoKey) -> bool {
+        let key = repo.canonical();
+        let now = Instant::now();
+        match self.touched.entry(key.clone()) {
+            dashmap::mapref::entry::Entry::Occupied(mut entry) => {
+                if now.duration_since(*entry.get()) < self.interval {
+                    return false;
+                }
+                entry.insert(now);
+            }
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                entry.insert(now);
+            }
+        }
+        if let Err(err) = self.writes.try_send(Write::Touch(key)) {
+            log::debug!("Dropped an access time write: {err}");
+            return false;
+        }
+        true
+    }
+
+    /// Wait for every write queued so far to land.
+    #[cfg(test)]
+    pub(crate) fn flush(&self) {
+        let (done, wait) = bounded(1);
+        self.writes.send(Write::Flush(done)).unwrap();
+        wait.recv().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access_time(state: &State, key: &RepoKey) -> Option<i64> {
+        state
+            .list_repos()
+            .unwrap()
+            .into_iter()
+            .find(|row| row.owner == key.owner.as_str() && row.repo == key.repo.as_str())
+            .and_then(|row| row.last_access_at)
+    }
+
+    #[test]
+    fn touches_are_debounced_per_repo() {
+        let state = Arc::new(State::open_in_memory().unwrap());
+        state.init().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let other: RepoKey = "rust-lang/rust".parse().unwrap();
+
+        let times = AccessTimes::new(Arc::clone(&state));
+        assert!(times.touch(&key));
+        times.flush();
+        assert!(access_time(&state, &key).is_some());
+        // Another spelling of the same repo shares its window.
+        assert!(!times.touch(&"OctoCat/Hello-World".parse().unwrap()));
+        assert!(times.touch(&other));
+
+        let times = AccessTimes::with_interval(Arc::clone(&state), Duration::ZERO);
+        assert!(times.touch(&key));
+        assert!(times.touch(&key));
+    }
+}
@@ -0,0 +1,185 @@
+//! In-memory content cache for small, frequently-read files.
+//!
+//! Tooling re-reads the same handful of config files (`Cargo.toml`,
+//! `package.json`, `.editorconfig`) constantly. Blobs are keyed by OID, and
+//! git objects are immutable, so a cached copy never goes stale; entries only
+//! leave via LRU eviction.
+//!
+//! [`BlobSizes`] does the same for file sizes, so the `getattr` that follows
+//! a `lookup` doesn't stat the hydrated blob a second time.
+
+use git2::Oid;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+/// Largest blob (in bytes) kept in memory.
+pub const MAX_CACHED_FILE_SIZE: u64 = 64 * 1024;
+
+/// Number of blobs kept. Bounds memory at `MAX_CACHED_FILES * MAX_CACHED_FILE_SIZE`.
+const MAX_CACHED_FILES: usize = 512;
+
+/// Number of blob sizes kept by [`BlobSizes`].
+const MAX_CACHED_SIZES: usize = 64 * 1024;
+
+/// Bounded LRU of small blob contents keyed by blob OID.
+pub struct ContentCache {
+    inner: Mutex<lru::LruCache<Oid, Arc<[u8]>>>,
+}
+
+impl ContentCache {
+    /// Create an empty cache sized to [`MAX_CACHED_FILES`].
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(lru::LruCache::new(
+                NonZeroUsize::new(MAX_CACHED_FILES).expect("nonzero cap"),
+            )),
+        }
+    }
+
+    /// Number of blobs currently cached.
+    pub fn entry_count(&self) -> usize {
+        self.inner.lock().expect("content cache poisoned").len()
+    }
+
+    /// Return the cached contents of `oid`, or call `load` and cache its
+    /// result. `load` returns `None` for blobs that shouldn't be cached; those
+    /// and anything over [`MAX_CACHED_FILE_SIZE`] are passed over.
+    pub fn get_or_load<E>(
+        &self,
+        oid: Oid,
+        load: impl FnOnce() -> Result<Option<Vec<u8>>, E>,
+    ) -> Result<Option<Arc<[u8]>>, E> {
+        {
+            let mut guard = self.inner.lock().expect("content cache poisoned");
+            if let Some(data) = guard.get(&oid) {
+                return Ok(Some(Arc::clone(data)));
+            }
+        }
+
+        // Miss: read outside the lock, then insert.
+        let Some(data) = load()? else {
+            return Ok(None);
+        };
+        if data.len() as u64 > MAX_CACHED_FILE_SIZE {
+            return Ok(None);
+        }
+        let data: Arc<[u8]> = data.into();
+        self.inner
+            .lock()
+            .expect("content cache poisoned")
+            .put(oid, Arc::clone(&data));
+        Ok(Some(data))
+    }
+}
+
+impl Default for ContentCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Bounded LRU of blob sizes keyed by blob OID.
+pub struct BlobSizes {
+    inner: Mutex<lru::LruCache<Oid, u64>>,
+}
+
+impl BlobSizes {
+    /// Create an empty cache sized to [`MAX_CACHED_SIZES`].
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(lru::LruCache::new(
+                NonZeroUsize::new(MAX_CACHED_SIZES).expect("nonzero cap"),
+            )),
+        }
+    }
+
+    /// Return the cached size of `oid`, or call `load` and cache its result.
+    pub fn get_or_load<E>(
+        &self,
+        oid: Oid,
+        load: impl FnOnce() -> Result<u64, E>,
+    ) -> Result<u64, E> {
+        if let Some(size) = self.inner.lock().expect("blob sizes poisoned").get(&oid) {
+            return Ok(*size);
+        }
+        let size = load()?;
+        self.inner
+            .lock()
+            .expect("blob sizes poisoned")
+            .put(oid, size);
+        Ok(size)
+    }
+}
+
+impl Default for BlobSizes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn oid(byte: u8) -> Oid {
+        Oid::from_bytes(&[byte; 20]).unwrap()
+    }
+
+    #[test]
+    fn second_read_hits_cache() {
+        let cache = ContentCache::new();
+        let reads = Cell::new(0);
+        let load = || {
+            reads.set(reads.get() + 1);
+            Ok::<_, ()>(Some(b"[package]\n".to_vec()))
+        };
+
+        let first = cache.get_or_load(oid(1), load).unwrap().unwrap();
+        let second = cache.get_or_load(oid(1), load).unwrap().unwrap();
+
+        assert_eq!(&*first, b"[package]\n");
+        assert_eq!(first, second);
+        assert_eq!(reads.get(), 1);
+    }
+
+    #[test]
+    fn oversized_blobs_are_not_cached() {
+        let cache = ContentCache::new();
+        let reads = Cell::new(0);
+        let load = || {
+            reads.set(reads.get() + 1);
+            Ok::<_, ()>(Some(vec![0u8; MAX_CACHED_FILE_SIZE as usize + 1]))
+        };
+
+        assert!(cache.get_or_load(oid(2), load).unwrap().is_none());
+        assert!(cache.get_or_load(oid(2), load).unwrap().is_none());
+        assert_eq!(reads.get(), 2);
+    }
+
+    #[test]
+    fn blob_sizes_load_once() {
+        let sizes = BlobSizes::new();
+        let loads = Cell::new(0);
+        let load = || {
+            loads.set(loads.get() + 1);
+            Ok::<_, ()>(42)
+        };
+
+        assert_eq!(sizes.get_or_load(oid(4), load), Ok(42));
+        assert_eq!(sizes.get_or_load(oid(4), load), Ok(42));
+        assert_eq!(loads.get(), 1);
+        assert_eq!(sizes.get_or_load(oid(5), || Err("boom")), Err("boom"));
+        assert_eq!(sizes.get_or_load(oid(5), load), Ok(42));
+    }
+
+    #[test]
+    fn loader_errors_propagate_and_are_not_cached() {
+        let cache = ContentCache::new();
+        assert_eq!(cache.get_or_load(oid(3), || Err("boom")), Err("boom"));
+        let data = cache
+            .get_or_load(oid(3), || Ok::<_, &str>(Some(b"ok".to_vec())))
+            .unwrap();
+        assert_eq!(data.as_deref(), Some(&b"ok"[..]));
+    }
+}
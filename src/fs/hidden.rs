@@ -0,0 +1,127 @@
+//! Paths hidden from the mounted view of particular repos.
+//!
+//! Each rule pairs a repo with a glob matched against paths relative to the
+//! commit root. A hidden path is missing from listings and fails lookup with
+//! `ENOENT`, so nothing below a hidden directory is reachable either. The
+//! mirror itself is untouched.
+//!
+//! Globs match whole path segments: `*` and `?` stay within a segment and
+//! `**` spans any number of them, so `vendor/**` hides everything below
+//! `vendor` and `**/*.min.js` hides minified scripts anywhere.
+
+use std::collections::HashMap;
+
+use crate::types::RepoKey;
+
+/// Environment variable listing hidden paths as comma-separated
+/// `owner/repo:glob` rules.
+pub const HIDDEN_PATHS_ENV: &str = "GHFS_HIDDEN_PATHS";
+
+/// Per-repo globs of paths to hide.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HiddenPaths {
+    rules: HashMap<RepoKey, Vec<Vec<u8>>>,
+}
+
+impl HiddenPaths {
+    /// Rules from [`HIDDEN_PATHS_ENV`] (or its config setting). Invalid
+    /// rules are logged and skipped.
+    pub fn from_env() -> Self {
+        crate::config::var(HIDDEN_PATHS_ENV)
+            .map(|v| Self::parse(&v))
+            .unwrap_or_default()
+    }
+
+    /// Parse comma-separated `owner/repo:glob` rules.
+    pub fn parse(value: &str) -> Self {
+        let mut hidden = Self::default();
+        for rule in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let parsed = rule.split_once(':').and_then(|(repo, glob)| {
+                let glob = glob.trim().trim_matches('/');
+                let key = repo.trim().parse::<RepoKey>().ok()?;
+                (!glob.is_empty()).then_some((key, glob))
+            });
+            match parsed {
+                Some((key, glob)) => hidden.hide(key, glob),
+                None => log::warn!("Ignoring invalid hidden path rule '{rule}'"),
+            }
+        }
+        hidden
+    }
+
+    /// Hide paths of `repo` matching `glob`.
+    pub fn hide(&mut self, repo: RepoKey, glob: &str) {
+        self.rules
+            .entry(repo)
+            .or_default()
+            .push(glob.as_bytes().to_vec());
+    }
+
+    /// Whether `name` in the directory at `prefix` of `repo` is hidden.
+    pub fn hides(&self, repo: &RepoKey, prefix: &[u8], name: &[u8]) -> bool {
+        let Some(globs) = self.rules.get(repo) else {
+            return false;
+        };
+        let path = if prefix.is_empty() {
+            name.to_vec()
+        } else {
+            [prefix, &b"/"[..], name].concat()
+        };
+        let segments: Vec<&[u8]> = path.split(|&b| b == b'/').collect();
+        globs.iter().any(|glob| {
+            let pattern: Vec<&[u8]> = glob.split(|&b| b == b'/').collect();
+            match_segments(&pattern, &segments)
+        })
+    }
+}
+
+/// Match path segments against glob segments, `**` spanning any number.
+fn match_segments(pattern: &[&[u8]], path: &[&[u8]]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&b"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(segment, tail)| {
+            match_segment(first, segment) && match_segments(rest, tail)
+        }),
+    }
+}
+
+/// Match one segment against a glob of literals, `*`, and `?`.
+fn match_segment(pattern: &[u8], segment: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => segment.is_empty(),
+        Some((b'*', rest)) => (0..=segment.len()).any(|skip| match_segment(rest, &segment[skip..])),
+        Some((b'?', rest)) => !segment.is_empty() && match_segment(rest, &segment[1..]),
+        Some((c, rest)) => segment.first() == Some(c) && match_segment(rest, &segment[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_paths_relative_to_the_root() {
+        let hidden = HiddenPaths::parse(
+            "octocat/hello-world:.github/workflows, octocat/hello-world:vendor/**/,\
+             octocat/hello-world:**/*.min.js, rust-lang/rust:src/?.rs, bad-rule, x/y:",
+        );
+        let repo: RepoKey = "octocat/hello-world".parse().unwrap();
+        let hides =
+            |prefix: &str, name: &str| hidden.hides(&repo, prefix.as_bytes(), name.as_bytes());
+
+        assert!(hides(".github", "workflows"));
+        assert!(!hides(".github", "CODEOWNERS"));
+        assert!(!hides("", ".github"));
+        assert!(hides("", "vendor"));
+        assert!(hides("vendor/lib", "a.c"));
+        assert!(hides("", "app.min.js"));
+        assert!(hides("static/js", "app.min.js"));
+        assert!(!hides("static/js", "app.js"));
+
+        let other: RepoKey = "rust-lang/rust".parse().unwrap();
+        assert!(hidden.hides(&other, b"src", b"a.rs"));
+        assert!(!hidden.hides(&other, b"src", b"ab.rs"));
+        assert!(!hidden.hides(&other, b"", b"vendor"));
+    }
+}
@@ -8,12 +8,28 @@
 //! - Path range (allocated from `PASSTHROUGH_INO_START` upward): one inode
 //!   per `(repo, commit_oid, repo-relative path)`. Directories carry their
 //!   git tree OID so descending is a single `tree_entry` lookup; files carry
-//!   their blob OID for hydration.
+//!   their blob OID for hydration. The synthetic `.ghfs` metadata directory
+//!   under each commit root is allocated from this range too.
+//!
+//! Path inodes normally come from a counter, so their numbers depend on
+//! traversal order. [`InodeTable::deterministic`] instead derives them from
+//! a hash of the path's identity (probing past collisions), so the same
+//! file gets the same inode in every fresh table.
+//!
+//! Either way, a table can also [remember](InodeTable::remember) the inodes
+//! an earlier mount handed out (see `inode_store.rs`), so a file looked up again
+//! after a remount keeps its number. NFS clients rely on that: a fileid that
+//! changes under them surfaces as a stale handle.
 
 use crate::store::EntryKind;
+use crate::time::unix_now;
 use crate::types::{Owner, RepoKey};
 use dashmap::DashMap;
-use std::sync::atomic::{AtomicU64, Ordering};
+use dashmap::mapref::entry::Entry;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
 /// Reserved inode for the filesystem root.
 pub const ROOT_INO: u64 = 1;
@@ -26,6 +42,14 @@ pub const VIRTUAL_INO_END: u64 = 1000;
 /// First inode allocated to real repository paths.
 pub const PASSTHROUGH_INO_START: u64 = 1001;
 
+/// How stale a remembered path's `last_used` may get before a lookup
+/// refreshes it. Eviction only needs a rough order.
+const TOUCH_INTERVAL_SECS: u64 = 60;
+
+/// Environment variable selecting [`InodeTable::deterministic`] for the
+/// mount (`1` or `true`).
+pub const DETERMINISTIC_INODES_ENV: &str = "GHFS_DETERMINISTIC_INODES";
+
 /// Identity of a resolved repository path inode: `(repo, commit, path)`.
 /// Immutability of git objects makes this a stable, content-defined key.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -35,6 +59,15 @@ pub struct PathKey {
     pub path: Vec<u8>,
 }
 
+/// A path inode to hand out again for `key`, and when it was last used
+/// (Unix seconds).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RememberedInode {
+    pub key: PathKey,
+    pub ino: u64,
+    pub last_used: u64,
+}
+
 /// Data stored per inode in the forward table.
 #[derive(Debug, Clone)]
 pub enum InodeData {
@@ -70,6 +103,18 @@ pub enum InodeData {
         kind: EntryKind,
         parent: u64,
     },
+    /// `<commit root>/.ghfs`: synthetic metadata directory.
+    MetaDir {
+        repo: RepoKey,
+        commit: String,
+        parent: u64,
+    },
+    /// `<commit root>/.ghfs/head`: the commit OID the root serves.
+    MetaHead {
+        repo: RepoKey,
+        commit: String,
+        parent: u64,
+    },
 }
 
 impl InodeData {
@@ -90,23 +135,100 @@ impl InodeData {
         }
     }
 
+    /// Inode generation reported to the kernel alongside the inode number.
+    ///
+    /// Derived from the leading 64 bits of the commit OID, so entries
+    /// served from different commits never share a `(ino, generation)`
+    /// pair. Virtual nodes are commit-independent and report `0`.
+    pub fn generation(&self) -> u64 {
+        match self {
+            Self::Repo { commit, .. }
+            | Self::Path { commit, .. }
+            | Self::MetaDir { commit, .. }
+            | Self::MetaHead { commit, .. } => commit_generation(commit),
+            _ => 0,
+        }
+    }
+
+    /// The repository this node belongs to, if any.
+    pub fn repo(&self) -> Option<&RepoKey> {
+        match self {
+            Self::RefRepo(key)
+            | Self::Repo { key, .. }
+            | Self::Path { repo: key, .. }
+            | Self::MetaDir { repo: key, .. }
+            | Self::MetaHead { repo: key, .. } => Some(key),
+            _ => None,
+        }
+    }
+
     /// [`EntryKind`] for attribute synthesis. Virtual dirs report Tree.
     pub fn kind(&self) -> EntryKind {
         match self {
             Self::Path { kind, .. } => *kind,
+            Self::MetaHead { .. } => EntryKind::Blob,
             _ => EntryKind::Tree,
         }
     }
 }
 
+/// Stable 64-bit FNV-1a over `parts`, each terminated by a NUL so
+/// boundaries between parts can't shift. Unlike `std`'s hasher its output
+/// is fixed across builds and Rust versions.
+fn stable_hash(parts: &[&[u8]]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let mut hash = OFFSET;
+    for part in parts {
+        for &byte in part.iter().chain(&[0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+/// Map a hash into the path inode range.
+fn hashed_ino(hash: u64) -> u64 {
+    PASSTHROUGH_INO_START + hash % (u64::MAX - PASSTHROUGH_INO_START)
+}
+
+/// Fold a hex commit OID into a generation number.
+fn commit_generation(commit: &str) -> u64 {
+    commit
+        .get(..16)
+        .and_then(|prefix| u64::from_str_radix(prefix, 16).ok())
+        .unwrap_or(0)
+}
+
 pub struct InodeTable {
     next_virtual: AtomicU64,
     next_path: AtomicU64,
+    /// Derive path inodes from a hash of their identity instead of
+    /// `next_path`.
+    deterministic: bool,
     forward: DashMap<u64, InodeData>,
     /// `(parent_ino, name_utf8)` → virtual child inode (for stable discovery).
     virtual_children: DashMap<(u64, String), u64>,
     /// `(repo, commit, path)` → path inode.
     path_reverse: DashMap<PathKey, u64>,
+    /// `(parent_ino, commit, name)` → synthetic metadata inode.
+    meta_children: DashMap<(u64, String, String), u64>,
+    /// Reclaimed virtual inodes, reused before `next_virtual` advances.
+    free_virtual: Mutex<Vec<u64>>,
+    /// Outstanding kernel lookups per dynamic virtual inode. An inode with
+    /// lookups left is never reclaimed, since the kernel may still use it.
+    virtual_lookups: DashMap<u64, u64>,
+    /// How many times each virtual inode number has been reclaimed. Folded
+    /// into its generation so a reused number never repeats an old
+    /// `(ino, generation)` pair.
+    virtual_reuses: DashMap<u64, u64>,
+    /// Path inodes to keep across remounts, as `key → (ino, last_used)`.
+    /// Unlike `path_reverse`, entries outlive `forget`.
+    remembered: DashMap<PathKey, (u64, u64)>,
+    /// How many `remembered` entries to keep; 0 until [`Self::remember`]
+    /// turns remembering on.
+    remember_limit: AtomicUsize,
 }
 
 impl InodeTable {
@@ -117,9 +239,26 @@ impl InodeTable {
         Self {
             next_virtual: AtomicU64::new(VIRTUAL_INO_START),
             next_path: AtomicU64::new(PASSTHROUGH_INO_START),
+            deterministic: false,
             forward,
             virtual_children: DashMap::new(),
             path_reverse: DashMap::new(),
+            meta_children: DashMap::new(),
+            free_virtual: Mutex::new(Vec::new()),
+            virtual_lookups: DashMap::new(),
+            virtual_reuses: DashMap::new(),
+            remembered: DashMap::new(),
+            remember_limit: AtomicUsize::new(0),
+        }
+    }
+
+    /// A table whose path inodes are derived from `(repo, commit, path)`
+    /// rather than allocation order, so they're stable across fresh tables
+    /// (and remounts). Virtual inodes are still allocated in order.
+    pub fn deterministic() -> Self {
+        Self {
+            deterministic: true,
+            ..Self::new()
         }
     }
 
@@ -133,6 +272,33 @@ impl InodeTable {
         ino < PASSTHROUGH_INO_START
     }
 
+    /// Generation to report for `ino`: the node's own
+    /// [generation](InodeData::generation), offset by the number of times a
+    /// virtual inode number has been reclaimed and handed out again.
+    pub fn generation(&self, ino: u64) -> u64 {
+        let base = self.forward.get(&ino).map_or(0, |data| data.generation());
+        let reuses = self.virtual_reuses.get(&ino).map_or(0, |count| *count);
+        base.wrapping_add(reuses)
+    }
+
+    /// Record that the kernel was handed `ino` by a lookup. Only dynamic
+    /// virtual inodes are counted; see [`Self::reclaim_virtual`].
+    pub fn record_lookup(&self, ino: u64) {
+        if (VIRTUAL_INO_START..PASSTHROUGH_INO_START).contains(&ino) {
+            *self.virtual_lookups.entry(ino).or_insert(0) += 1;
+        }
+    }
+
+    /// Drop `nlookup` kernel lookups of `ino` (FUSE `forget`).
+    pub fn forget_lookups(&self, ino: u64, nlookup: u64) {
+        if let Entry::Occupied(mut count) = self.virtual_lookups.entry(ino) {
+            *count.get_mut() = count.get().saturating_sub(nlookup);
+            if *count.get() == 0 {
+                count.remove();
+            }
+        }
+    }
+
     /// Get or create a virtual child inode of `parent` named `name`, storing
     /// `data`. Returns the inode number. Allocation from the virtual range.
     pub fn get_or_alloc_virtual(
@@ -160,41 +326,281 @@ impl InodeTable {
         parent: u64,
     ) -> u64 {
         if let Some(ino) = self.path_reverse.get(&key) {
-            return *ino;
+            let ino = *ino;
+            self.touch(&key, ino);
+            return ino;
         }
-        let ino = self.next_path.fetch_add(1, Ordering::Relaxed);
-        self.forward.insert(
-            ino,
+        let data = InodeData::Path {
+            repo: key.repo.clone(),
+            commit: key.commit.clone(),
+            path: key.path.clone(),
+            oid,
+            kind,
+            parent,
+        };
+        let same = |existing: &InodeData| match existing {
             InodeData::Path {
-                repo: key.repo.clone(),
-                commit: key.commit.clone(),
-                path: key.path.clone(),
-                oid,
-                kind,
-                parent,
-            },
-        );
+                repo, commit, path, ..
+            } => *repo == key.repo && *commit == key.commit && *path == key.path,
+            _ => false,
+        };
+        let remembered = self.remembered.get(&key).map(|entry| entry.0);
+        let ino = if let Some(ino) = remembered {
+            self.claim_from(ino, data, same)
+        } else if self.deterministic {
+            let hash = stable_hash(&[
+                key.repo.to_string().as_bytes(),
+                key.commit.as_bytes(),
+                &key.path,
+            ]);
+            self.claim_from(hashed_ino(hash), data, same)
+        } else {
+            // Remembered inodes may sit past the counter, so claim rather
+            // than overwrite.
+            let ino = self.next_path.fetch_add(1, Ordering::Relaxed);
+            self.claim_from(ino, data, |_| false)
+        };
+        self.touch(&key, ino);
         self.path_reverse.insert(key, ino);
         ino
     }
 
+    /// Keep handing out the inodes in `entries` for their paths, and
+    /// remember up to `limit` path inodes from now on for
+    /// [`Self::remembered`]. Entries that clash with an inode already in use
+    /// are dropped.
+    pub fn remember(&self, entries: Vec<RememberedInode>, limit: usize) {
+        self.remember_limit.store(limit, Ordering::Relaxed);
+        let mut highest = 0;
+        for entry in entries {
+            if entry.ino < PASSTHROUGH_INO_START || self.forward.contains_key(&entry.ino) {
+                continue;
+            }
+            highest = highest.max(entry.ino);
+            self.remembered
+                .entry(entry.key)
+                .or_insert((entry.ino, entry.last_used));
+        }
+        // Counter allocation starts past the remembered inodes, so it
+        // rarely has to probe around them.
+        if !self.deterministic && highest < u64::MAX {
+            self.next_path.fetch_max(highest + 1, Ordering::Relaxed);
+        }
+        self.evict_cold(limit);
+    }
+
+    /// The path inodes worth keeping for the next mount, most recently used
+    /// first and at most the limit given to [`Self::remember`].
+    pub fn remembered(&self) -> Vec<RememberedInode> {
+        let mut entries: Vec<RememberedInode> = self
+            .remembered
+            .iter()
+            .map(|entry| RememberedInode {
+                key: entry.key().clone(),
+                ino: entry.value().0,
+                last_used: entry.value().1,
+            })
+            .collect();
+        entries.sort_by(|a, b| b.last_used.cmp(&a.last_used).then(a.ino.cmp(&b.ino)));
+        entries.truncate(self.remember_limit.load(Ordering::Relaxed));
+        entries
+    }
+
+    /// Record that `key` was served as `ino` just now, if remembering.
+    /// A path already recorded as `ino` within [`TOUCH_INTERVAL_SECS`] is
+    /// left alone, so repeated lookups don't write the map.
+    fn touch(&self, key: &PathKey, ino: u64) {
+        let limit = self.remember_limit.load(Ordering::Relaxed);
+        if limit == 0 {
+            return;
+        }
+        let now = unix_now() as u64;
+        if let Some(mut entry) = self.remembered.get_mut(key) {
+            let (seen_ino, last_used) = *entry;
+            if seen_ino != ino || now.saturating_sub(last_used) >= TOUCH_INTERVAL_SECS {
+                *entry = (ino, now);
+            }
+            return;
+        }
+        self.remembered.insert(key.clone(), (ino, now));
+        // Let the map overshoot before trimming, so eviction's sort runs
+        // once per `limit` new paths rather than on every one.
+        if self.remembered.len() > limit.saturating_mul(2) {
+            self.evict_cold(limit);
+        }
+    }
+
+    /// Drop all but the `limit` most recently used remembered inodes.
+    fn evict_cold(&self, limit: usize) {
+        if self.remembered.len() <= limit {
+            return;
+        }
+        let mut by_use: Vec<(u64, PathKey)> = self
+            .remembered
+            .iter()
+            .map(|entry| (entry.value().1, entry.key().clone()))
+            .collect();
+        by_use.sort_unstable_by_key(|entry| Reverse(entry.0));
+        for (_, key) in by_use.into_iter().skip(limit) {
+            self.remembered.remove(&key);
+        }
+    }
+
+    /// Claim the first free path inode at or after `start`, storing `data`.
+    /// An inode already holding the same node (per `same`, e.g. inserted by
+    /// a racing lookup) is returned as is.
+    fn claim_from(&self, start: u64, data: InodeData, same: impl Fn(&InodeData) -> bool) -> u64 {
+        let mut ino = start;
+        loop {
+            match self.forward.entry(ino) {
+                Entry::Vacant(slot) => {
+                    slot.insert(data);
+                    return ino;
+                }
+                Entry::Occupied(slot) if same(slot.get()) => return ino,
+                Entry::Occupied(_) => {}
+            }
+            ino = if ino == u64::MAX {
+                PASSTHROUGH_INO_START
+            } else {
+                ino + 1
+            };
+        }
+    }
+
+    /// Get or create the synthetic metadata inode named `name` below
+    /// `parent` for `commit`. `data` must be a `MetaDir` or `MetaHead`.
+    pub fn get_or_alloc_meta(&self, parent: u64, name: &str, data: InodeData) -> u64 {
+        let commit = match &data {
+            InodeData::MetaDir { commit, .. } | InodeData::MetaHead { commit, .. } => {
+                commit.clone()
+            }
+            _ => unreachable!("get_or_alloc_meta called with non-metadata node"),
+        };
+        let key = (parent, commit, name.to_string());
+        if let Some(ino) = self.meta_children.get(&key) {
+            return *ino;
+        }
+        let ino = if self.deterministic {
+            let repo = match &data {
+                InodeData::MetaDir { repo, .. } | InodeData::MetaHead { repo, .. } => {
+                    repo.to_string()
+                }
+                _ => unreachable!(),
+            };
+            let hash = stable_hash(&[b"meta", repo.as_bytes(), key.1.as_bytes(), name.as_bytes()]);
+            // Metadata nodes are only looked up via `meta_children`, so any
+            // occupied inode is a collision.
+            self.claim_from(hashed_ino(hash), data, |_| false)
+        } else {
+            let ino = self.next_path.fetch_add(1, Ordering::Relaxed);
+            self.forward.insert(ino, data);
+            ino
+        };
+        self.meta_children.insert(key, ino);
+        ino
+    }
+
     /// Remove an inode by number (used on forget for path inodes).
     pub fn forget(&self, ino: u64) {
-        if let Some((_, data)) = self.forward.remove(&ino) {
-            if let InodeData::Path {
-                repo, commit, path, ..
-            } = &data
-            {
-                self.path_reverse.remove(&PathKey {
-                    repo: repo.clone(),
-                    commit: commit.clone(),
-                    path: path.clone(),
-                });
+        match self.forward.remove(&ino) {
+            Some((
+                _,
+                InodeData::Path {
+                    repo, commit, path, ..
+                },
+            )) => {
+                self.path_reverse.remove(&PathKey { repo, commit, path });
+            }
+            Some((_, InodeData::MetaDir { .. } | InodeData::MetaHead { .. })) => {
+                self.meta_children.retain(|_, child| *child != ino);
+            }
+            _ => {}
+        }
+    }
+
+    /// Number of live inodes as `(virtual, passthrough)`, counting the
+    /// reserved roots as virtual.
+    pub fn counts(&self) -> (usize, usize) {
+        self.forward.iter().fold((0, 0), |(v, p), entry| {
+            if Self::is_virtual_ino(*entry.key()) {
+                (v + 1, p)
+            } else {
+                (v, p + 1)
             }
+        })
+    }
+
+    /// Free every dynamically-allocated virtual inode whose data matches
+    /// `stale` and that the kernel has forgotten, making its number
+    /// available for reuse. Path and metadata inodes of a freed commit root
+    /// go with it. The reserved root inodes are never freed. Returns the
+    /// number of virtual inodes freed.
+    ///
+    /// NFS clients never forget, so an inode handed out over NFS stays
+    /// allocated for the life of the mount.
+    pub fn reclaim_virtual(&self, stale: impl Fn(&InodeData) -> bool) -> usize {
+        let doomed: HashSet<u64> = self
+            .forward
+            .iter()
+            .filter(|entry| {
+                let ino = *entry.key();
+                (VIRTUAL_INO_START..PASSTHROUGH_INO_START).contains(&ino)
+                    && !self.virtual_lookups.contains_key(&ino)
+                    && stale(entry.value())
+            })
+            .map(|entry| *entry.key())
+            .collect();
+        if doomed.is_empty() {
+            return 0;
+        }
+
+        self.virtual_children
+            .retain(|(parent, _), ino| !doomed.contains(ino) && !doomed.contains(parent));
+        let roots: HashSet<(RepoKey, String)> = doomed
+            .iter()
+            .filter_map(|ino| match self.forward.remove(ino)? {
+                (_, InodeData::Repo { key, commit, .. }) => Some((key, commit)),
+                _ => None,
+            })
+            .collect();
+        if !roots.is_empty() {
+            let orphans: Vec<u64> = self
+                .forward
+                .iter()
+                .filter(|entry| match entry.value() {
+                    InodeData::Path { repo, commit, .. }
+                    | InodeData::MetaDir { repo, commit, .. }
+                    | InodeData::MetaHead { repo, commit, .. } => {
+                        roots.contains(&(repo.clone(), commit.clone()))
+                    }
+                    _ => false,
+                })
+                .map(|entry| *entry.key())
+                .collect();
+            for ino in orphans {
+                self.forget(ino);
+            }
+        }
+        for ino in &doomed {
+            *self.virtual_reuses.entry(*ino).or_insert(0) += 1;
         }
+        self.free_virtual
+            .lock()
+            .expect("virtual free list poisoned")
+            .extend(&doomed);
+        doomed.len()
     }
 
     fn alloc_virtual(&self) -> Result<u64, i32> {
+        if let Some(ino) = self
+            .free_virtual
+            .lock()
+            .expect("virtual free list poisoned")
+            .pop()
+        {
+            return Ok(ino);
+        }
         self.next_virtual
             .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |cur| {
                 if cur >= VIRTUAL_INO_END {
@@ -212,3 +618,182 @@ impl Default for InodeTable {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path_data(commit: &str) -> InodeData {
+        InodeData::Path {
+            repo: "octocat/hello-world".parse().unwrap(),
+            commit: commit.to_string(),
+            path: b"README".to_vec(),
+            oid: "0".repeat(40),
+            kind: EntryKind::Blob,
+            parent: ROOT_INO,
+        }
+    }
+
+    #[test]
+    fn path_entries_across_commits_have_distinct_generations() {
+        let old = path_data("1111111111111111aaaaaaaaaaaaaaaaaaaaaaaa");
+        let new = path_data("2222222222222222aaaaaaaaaaaaaaaaaaaaaaaa");
+        assert_eq!(old.generation(), 0x1111111111111111);
+        assert_eq!(new.generation(), 0x2222222222222222);
+        assert_ne!(old.generation(), new.generation());
+    }
+
+    #[test]
+    fn reclaimed_virtual_inodes_are_reused() {
+        let table = InodeTable::new();
+        let gone: Owner = "gone".parse().unwrap();
+        let kept: Owner = "kept".parse().unwrap();
+
+        let gone_ino = table
+            .get_or_alloc_virtual(ROOT_INO, "gone", InodeData::Owner(gone.clone()))
+            .unwrap();
+        let kept_ino = table
+            .get_or_alloc_virtual(ROOT_INO, "kept", InodeData::Owner(kept))
+            .unwrap();
+
+        let freed = table.reclaim_virtual(|data| matches!(data, InodeData::Owner(o) if *o == gone));
+        assert_eq!(freed, 1);
+        assert!(table.get(gone_ino).is_none());
+        assert!(table.get(kept_ino).is_some());
+
+        let reused = table
+            .get_or_alloc_virtual(ROOT_INO, "new", InodeData::Owner("new".parse().unwrap()))
+            .unwrap();
+        assert_eq!(reused, gone_ino);
+        assert_ne!(table.generation(reused), 0);
+    }
+
+    #[test]
+    fn reclaim_waits_for_the_kernel_to_forget() {
+        let table = InodeTable::new();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let root = table
+            .get_or_alloc_virtual(
+                ROOT_INO,
+                "hello-world",
+                InodeData::Repo {
+                    key: key.clone(),
+                    selector: None,
+                    commit: "aaaa".to_string(),
+                    root_tree: "bbbb".to_string(),
+                },
+            )
+            .unwrap();
+        let file = table.get_or_alloc_path(
+            PathKey {
+                repo: key,
+                commit: "aaaa".to_string(),
+                path: b"README".to_vec(),
+            },
+            "cccc".to_string(),
+            EntryKind::Blob,
+            root,
+        );
+
+        table.record_lookup(root);
+        table.record_lookup(root);
+        assert_eq!(table.reclaim_virtual(|_| true), 0);
+        table.forget_lookups(root, 1);
+        assert_eq!(table.reclaim_virtual(|_| true), 0);
+        table.forget_lookups(root, 1);
+
+        assert_eq!(table.reclaim_virtual(|_| true), 1);
+        assert!(table.get(root).is_none());
+        assert!(table.get(file).is_none());
+    }
+
+    #[test]
+    fn meta_inodes_are_stable_per_commit() {
+        let table = InodeTable::new();
+        let meta = |commit: &str| InodeData::MetaDir {
+            repo: "octocat/hello-world".parse().unwrap(),
+            commit: commit.to_string(),
+            parent: 5,
+        };
+
+        let first = table.get_or_alloc_meta(5, ".ghfs", meta("aaaa"));
+        assert!(first >= PASSTHROUGH_INO_START);
+        assert_eq!(table.get_or_alloc_meta(5, ".ghfs", meta("aaaa")), first);
+        assert_ne!(table.get_or_alloc_meta(5, ".ghfs", meta("bbbb")), first);
+
+        table.forget(first);
+        assert!(table.get(first).is_none());
+        assert_ne!(table.get_or_alloc_meta(5, ".ghfs", meta("aaaa")), first);
+    }
+
+    #[test]
+    fn reclaim_never_frees_reserved_roots() {
+        let table = InodeTable::new();
+        assert_eq!(table.reclaim_virtual(|_| true), 0);
+        assert!(table.get(ROOT_INO).is_some());
+        assert!(table.get(BY_REF_INO).is_some());
+    }
+
+    #[test]
+    fn virtual_entries_use_generation_zero() {
+        assert_eq!(InodeData::Root.generation(), 0);
+        assert_eq!(InodeData::ByRefRoot.generation(), 0);
+    }
+
+    fn path_key(path: &str) -> PathKey {
+        PathKey {
+            repo: "octocat/hello-world".parse().unwrap(),
+            commit: "1111111111111111aaaaaaaaaaaaaaaaaaaaaaaa".to_string(),
+            path: path.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn deterministic_inodes_ignore_access_order() {
+        let alloc = |table: &InodeTable, path: &str| {
+            table.get_or_alloc_path(path_key(path), "0".repeat(40), EntryKind::Blob, ROOT_INO)
+        };
+
+        let first = InodeTable::deterministic();
+        let readme = alloc(&first, "README");
+        let src = alloc(&first, "src/lib.rs");
+
+        let second = InodeTable::deterministic();
+        assert_eq!(alloc(&second, "src/lib.rs"), src);
+        assert_eq!(alloc(&second, "README"), readme);
+        assert_ne!(readme, src);
+        assert!(readme >= PASSTHROUGH_INO_START && src >= PASSTHROUGH_INO_START);
+
+        // Counter mode depends on order.
+        let counter = InodeTable::new();
+        assert_eq!(alloc(&counter, "src/lib.rs"), PASSTHROUGH_INO_START);
+    }
+
+    #[test]
+    fn deterministic_collisions_probe_to_the_next_free_inode() {
+        let table = InodeTable::deterministic();
+        let readme = table.get_or_alloc_path(
+            path_key("README"),
+            "0".repeat(40),
+            EntryKind::Blob,
+            ROOT_INO,
+        );
+
+        // Another node hashing to the same inode moves past it...
+        let other = table.claim_from(readme, path_data("2222222222222222"), |_| false);
+        assert_eq!(other, readme + 1);
+        // ...while the same node (a racing insert) reuses it.
+        let again = table.claim_from(
+            readme,
+            path_data("1111111111111111"),
+            |d| matches!(d, InodeData::Path { path, .. } if path == b"README"),
+        );
+        assert_eq!(again, readme);
+
+        // Probing wraps around to the start of the path range.
+        table.forward.insert(u64::MAX, InodeData::Root);
+        table.forward.insert(PASSTHROUGH_INO_START, InodeData::Root);
+        let wrapped = table.claim_from(u64::MAX, path_data("3333333333333333"), |_| false);
+        assert_eq!(wrapped, PASSTHROUGH_INO_START + 1);
+    }
+}
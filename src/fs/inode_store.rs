@@ -0,0 +1,237 @@
+//! Path inodes kept across remounts.
+//!
+//! On unmount the table's [remembered](InodeTable::remembered) inodes are
+//! written to `inodes` in the cache root, one per line:
+//!
+//! ```text
+//! <ino> <last used> <owner/repo> <commit> <hex path>
+//! ```
+//!
+//! and the next mount hands the same numbers out again. The file is only a
+//! hint: a missing or damaged file, or a line that no longer fits, just
+//! means those paths get fresh inodes.
+
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use super::inode::{InodeTable, PathKey, RememberedInode};
+
+/// Environment variable setting how many path inodes are kept across
+/// remounts.
+pub const PERSISTED_INODES_ENV: &str = "GHFS_PERSISTED_INODES";
+/// Path inodes kept when none is configured.
+pub const DEFAULT_PERSISTED_INODES: usize = 100_000;
+
+/// First line of the file; anything else is ignored rather than misread.
+const HEADER: &str = "ghfs-inodes 1";
+
+/// Where a mount's path inodes are saved, and how many.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InodeStore {
+    path: PathBuf,
+    limit: usize,
+}
+
+impl InodeStore {
+    /// Keep up to `limit` inodes in `inodes` under `cache_root`.
+    pub fn new(cache_root: &Path, limit: usize) -> Self {
+        Self {
+            path: cache_root.join("inodes"),
+            limit,
+        }
+    }
+
+    /// [`Self::new`] with the limit from [`PERSISTED_INODES_ENV`].
+    pub fn from_env(cache_root: &Path) -> Self {
+        Self::new(cache_root, limit_from_lookup(crate::config::var))
+    }
+
+    /// Seed `table` with the saved inodes. Returns how many were read.
+    pub fn load(&self, table: &InodeTable) -> usize {
+        let entries = match std::fs::read_to_string(&self.path) {
+            Ok(text) => parse(&text),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    log::warn!("Failed to read {}: {err}", self.path.display());
+                }
+                Vec::new()
+            }
+        };
+        let loaded = entries.len();
+        table.remember(entries, self.limit);
+        loaded
+    }
+
+    /// Write `table`'s remembered inodes, replacing the file atomically.
+    /// Returns how many were written.
+    pub fn save(&self, table: &InodeTable) -> std::io::Result<usize> {
+        let entries = table.remembered();
+        let temporary = self
+            .path
+            .with_extension(format!("tmp.{}", std::process::id()));
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&temporary)?);
+        writeln!(file, "{HEADER}")?;
+        for entry in &entries {
+            writeln!(
+                file,
+                "{} {} {} {} {}",
+                entry.ino,
+                entry.last_used,
+                entry.key.repo,
+                entry.key.commit,
+                hex_encode(&entry.key.path)
+            )?;
+        }
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&temporary, &self.path)?;
+        Ok(entries.len())
+    }
+}
+
+fn limit_from_lookup(lookup: impl Fn(&str) -> Option<String>) -> usize {
+    match lookup(PERSISTED_INODES_ENV) {
+        None => DEFAULT_PERSISTED_INODES,
+        Some(value) => match value.trim().parse::<usize>() {
+            Ok(limit) if limit > 0 => limit,
+            _ => {
+                log::warn!(
+                    "Ignoring invalid {PERSISTED_INODES_ENV}={value:?}; expected a positive number"
+                );
+                DEFAULT_PERSISTED_INODES
+            }
+        },
+    }
+}
+
+/// Entries of a saved file, skipping lines that don't parse.
+fn parse(text: &str) -> Vec<RememberedInode> {
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Vec::new();
+    }
+    lines.filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<RememberedInode> {
+    let mut fields = line.split(' ');
+    let ino = fields.next()?.parse().ok()?;
+    let last_used = fields.next()?.parse().ok()?;
+    let repo = fields.next()?.parse().ok()?;
+    let commit = fields.next()?.to_string();
+    let path = hex_decode(fields.next()?)?;
+    if fields.next().is_some() || path.is_empty() {
+        return None;
+    }
+    Some(RememberedInode {
+        key: PathKey { repo, commit, path },
+        ino,
+        last_used,
+    })
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::inode::PASSTHROUGH_INO_START;
+    use crate::store::EntryKind;
+    use tempfile::tempdir;
+
+    fn alloc(table: &InodeTable, path: &str) -> u64 {
+        let key = PathKey {
+            repo: "octocat/hello-world".parse().unwrap(),
+            commit: "1111111111111111aaaaaaaaaaaaaaaaaaaaaaaa".to_string(),
+            path: path.as_bytes().to_vec(),
+        };
+        table.get_or_alloc_path(key, "0".repeat(40), EntryKind::Blob, 5)
+    }
+
+    #[test]
+    fn inodes_survive_a_table_reload() {
+        let dir = tempdir().unwrap();
+        let store = InodeStore::new(dir.path(), 100);
+
+        let first = InodeTable::new();
+        assert_eq!(store.load(&first), 0);
+        let readme = alloc(&first, "README");
+        let lib = alloc(&first, "src/lib.rs");
+        // Forgotten inodes are still remembered for the next mount.
+        first.forget(lib);
+        assert_eq!(store.save(&first).unwrap(), 2);
+
+        // A fresh table looking paths up in another order gets the same
+        // numbers, and new paths don't collide with them.
+        let second = InodeTable::new();
+        assert_eq!(store.load(&second), 2);
+        let other = alloc(&second, "Cargo.toml");
+        assert_eq!(alloc(&second, "src/lib.rs"), lib);
+        assert_eq!(alloc(&second, "README"), readme);
+        assert!(other > lib.max(readme));
+
+        // The same holds for deterministic tables.
+        let third = InodeTable::deterministic();
+        store.load(&third);
+        assert_eq!(alloc(&third, "src/lib.rs"), lib);
+    }
+
+    #[test]
+    fn only_the_most_recently_used_inodes_are_kept() {
+        let dir = tempdir().unwrap();
+        let path = |n: u64| format!("file-{n}");
+        let key = |n: u64| PathKey {
+            repo: "octocat/hello-world".parse().unwrap(),
+            commit: "1111".to_string(),
+            path: path(n).into_bytes(),
+        };
+        let table = InodeTable::new();
+        table.remember(
+            (0..10)
+                .map(|n| RememberedInode {
+                    key: key(n),
+                    ino: PASSTHROUGH_INO_START + n,
+                    last_used: n,
+                })
+                .collect(),
+            3,
+        );
+        let kept: Vec<u64> = table.remembered().iter().map(|e| e.last_used).collect();
+        assert_eq!(kept, [9, 8, 7]);
+
+        let store = InodeStore::new(dir.path(), 3);
+        store.save(&table).unwrap();
+        let text = std::fs::read_to_string(dir.path().join("inodes")).unwrap();
+        assert_eq!(text.lines().count(), 4, "{text}");
+        assert!(text.contains(&hex_encode(b"file-9")));
+    }
+
+    #[test]
+    fn damaged_files_and_lines_are_skipped() {
+        let good = "1001 5 octocat/hello-world 1111 524541444d45";
+        let entries = parse(&format!(
+            "{HEADER}\n{good}\nnot a line\n1002 5 octocat/hello-world 1111 zz\n"
+        ));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key.path, b"README");
+        assert!(parse(&format!("ghfs-inodes 0\n{good}\n")).is_empty());
+
+        assert_eq!(limit_from_lookup(|_| None), DEFAULT_PERSISTED_INODES);
+        assert_eq!(
+            limit_from_lookup(|_| Some("0".into())),
+            DEFAULT_PERSISTED_INODES
+        );
+        assert_eq!(limit_from_lookup(|_| Some("50".into())), 50);
+    }
+}
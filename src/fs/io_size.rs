@@ -0,0 +1,99 @@
+//! Block size reported in attributes and the largest FUSE read request.
+//!
+//! Every node reports the same `st_blksize`, which tools such as `cp` use to
+//! size their reads. `max_read` caps the size of a single FUSE read; left
+//! unset, the kernel picks its own limit (typically 128 KiB). Raising both
+//! helps throughput on large files at the cost of larger buffers per read.
+
+use crate::daemon::parse_size;
+
+/// Environment variable overriding [`IoSizes::block_size`], in bytes with an
+/// optional `K` or `M` suffix.
+pub const BLOCK_SIZE_ENV: &str = "GHFS_BLOCK_SIZE";
+/// Environment variable setting [`IoSizes::max_read`], in bytes with an
+/// optional `K` or `M` suffix.
+pub const MAX_READ_ENV: &str = "GHFS_MAX_READ";
+/// Block size reported when none is configured.
+pub const DEFAULT_BLOCK_SIZE: u32 = 4096;
+
+/// Smallest accepted block size and `max_read`.
+const MIN_SIZE: u64 = 512;
+/// Largest accepted block size and `max_read`.
+const MAX_SIZE: u64 = 16 << 20;
+
+/// I/O sizes the mount advertises.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoSizes {
+    /// `st_blksize` of every node. A power of two.
+    pub block_size: u32,
+    /// FUSE `max_read` mount option; `None` leaves the kernel default.
+    pub max_read: Option<u32>,
+}
+
+impl Default for IoSizes {
+    fn default() -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            max_read: None,
+        }
+    }
+}
+
+impl IoSizes {
+    /// Defaults overridden by [`BLOCK_SIZE_ENV`] and [`MAX_READ_ENV`].
+    /// Values out of range (512 bytes to 16 MiB, and a power of two for the
+    /// block size) are logged and ignored.
+    pub fn from_env() -> Self {
+        Self::from_lookup(crate::config::var)
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let size = |name: &str, power_of_two: bool| {
+            let value = lookup(name)?;
+            match parse_size(&value) {
+                Some(size)
+                    if (MIN_SIZE..=MAX_SIZE).contains(&size)
+                        && (!power_of_two || size.is_power_of_two()) =>
+                {
+                    Some(size as u32)
+                }
+                _ => {
+                    log::warn!("Ignoring invalid {name}={value:?}");
+                    None
+                }
+            }
+        };
+        Self {
+            block_size: size(BLOCK_SIZE_ENV, true).unwrap_or(DEFAULT_BLOCK_SIZE),
+            max_read: size(MAX_READ_ENV, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_overrides_defaults_and_skips_bad_values() {
+        let sizes = |block: &'static str, max_read: &'static str| {
+            IoSizes::from_lookup(move |name| match name {
+                BLOCK_SIZE_ENV => Some(block.to_string()),
+                MAX_READ_ENV => Some(max_read.to_string()),
+                _ => None,
+            })
+        };
+        assert_eq!(IoSizes::from_lookup(|_| None), IoSizes::default());
+        assert_eq!(
+            sizes("64K", "1M"),
+            IoSizes {
+                block_size: 65536,
+                max_read: Some(1 << 20)
+            }
+        );
+        // Not a power of two, and too large.
+        assert_eq!(sizes("3000", "1G"), IoSizes::default());
+        assert_eq!(sizes("256", "100000").max_read, Some(100_000));
+        assert_eq!(sizes("256", "x").block_size, DEFAULT_BLOCK_SIZE);
+    }
+}
@@ -0,0 +1,167 @@
+//! How repos are arranged under the mount root.
+//!
+//! The default, nested layout serves `/<owner>/<repo>`. The flat layout
+//! serves each repo as a single root entry, `/<owner><delimiter><repo>`, for
+//! tools that don't cope with auto-created intermediate directories. The
+//! `by-ref` namespace stays nested in both layouts. The single-repo layout
+//! serves one repo's default-branch tree as the root itself, with no
+//! discovery directories or `by-ref` namespace at all.
+//!
+//! A flat name is split at the first occurrence of the delimiter, so the
+//! delimiter must contain a character owner names can't, and the owner part
+//! therefore never contains it.
+
+use std::path::{Path, PathBuf};
+
+use crate::types::RepoKey;
+
+/// Environment variable selecting the layout: `nested` (default) or `flat`.
+pub const LAYOUT_ENV: &str = "GHFS_LAYOUT";
+/// Environment variable overriding [`DEFAULT_FLAT_DELIMITER`].
+pub const FLAT_DELIMITER_ENV: &str = "GHFS_FLAT_DELIMITER";
+/// Delimiter between owner and repo in flat names. `+` appears in neither
+/// owner nor repo names.
+pub const DEFAULT_FLAT_DELIMITER: &str = "+";
+
+/// Arrangement of repos under the mount root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Layout {
+    /// `/<owner>/<repo>`.
+    #[default]
+    Nested,
+    /// `/<owner><delimiter><repo>`.
+    Flat { delimiter: String },
+    /// The repo's tree at `/`.
+    Single(RepoKey),
+}
+
+impl Layout {
+    /// The flat layout with `delimiter`, if it is usable: non-empty, not
+    /// containing `/` or NUL, and containing a character owner names can't.
+    pub fn flat(delimiter: &str) -> Option<Self> {
+        let usable = !delimiter.contains(['/', '\0'])
+            && delimiter
+                .chars()
+                .any(|c| !c.is_ascii_alphanumeric() && c != '-');
+        usable.then(|| Self::Flat {
+            delimiter: delimiter.to_string(),
+        })
+    }
+
+    /// The layout from [`LAYOUT_ENV`] and [`FLAT_DELIMITER_ENV`] (or their
+    /// config settings). Invalid values are logged and ignored.
+    pub fn from_env() -> Self {
+        Self::from_lookup(crate::config::var)
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        match lookup(LAYOUT_ENV).as_deref().map(str::trim) {
+            None | Some("nested") => Self::Nested,
+            Some("flat") => {
+                let delimiter =
+                    lookup(FLAT_DELIMITER_ENV).unwrap_or_else(|| DEFAULT_FLAT_DELIMITER.into());
+                Self::flat(&delimiter).unwrap_or_else(|| {
+                    log::warn!(
+                        "Ignoring invalid {FLAT_DELIMITER_ENV}={delimiter:?}; it must include \
+                         a character other than letters, digits, and '-'"
+                    );
+                    Self::flat(DEFAULT_FLAT_DELIMITER).expect("default delimiter is valid")
+                })
+            }
+            Some(other) => {
+                log::warn!("Ignoring invalid {LAYOUT_ENV}={other:?}; expected nested or flat");
+                Self::Nested
+            }
+        }
+    }
+
+    /// Where `key`'s default-branch tree appears under `mount_point`.
+    pub fn repo_path(&self, mount_point: &Path, key: &RepoKey) -> PathBuf {
+        match self {
+            Self::Nested => mount_point.join(key.owner.as_str()).join(key.repo.as_str()),
+            Self::Flat { delimiter } => mount_point.join(flat_name(key, delimiter)),
+            Self::Single(_) => mount_point.to_path_buf(),
+        }
+    }
+}
+
+/// The root entry name for `key` in the flat layout.
+pub fn flat_name(key: &RepoKey, delimiter: &str) -> String {
+    format!("{}{delimiter}{}", key.owner, key.repo)
+}
+
+/// Parse a flat root entry name back into the repo it names.
+pub fn parse_flat_name(name: &str, delimiter: &str) -> Option<RepoKey> {
+    let (owner, repo) = name.split_once(delimiter)?;
+    format!("{owner}/{repo}").parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_names_parse_back_into_repo_keys() {
+        let key: RepoKey = "rust-lang/rust.vim".parse().unwrap();
+        let name = flat_name(&key, "+");
+        assert_eq!(name, "rust-lang+rust.vim");
+        assert_eq!(parse_flat_name(&name, "+"), Some(key.clone()));
+
+        // Repo names may contain the delimiter's characters after the
+        // first occurrence, which still splits at the owner.
+        let key: RepoKey = "octo-org/a--b".parse().unwrap();
+        assert_eq!(parse_flat_name(&flat_name(&key, "__"), "__"), Some(key));
+
+        assert_eq!(parse_flat_name("octocat", "+"), None);
+        assert_eq!(parse_flat_name("+hello-world", "+"), None);
+        assert_eq!(parse_flat_name("octocat+", "+"), None);
+        assert_eq!(parse_flat_name("octo cat+repo", "+"), None);
+    }
+
+    #[test]
+    fn delimiters_must_not_fit_in_owner_names() {
+        assert!(Layout::flat("+").is_some());
+        assert!(Layout::flat("__").is_some());
+        assert!(Layout::flat("--").is_none());
+        assert!(Layout::flat("x").is_none());
+        assert!(Layout::flat("/").is_none());
+        assert!(Layout::flat("").is_none());
+    }
+
+    #[test]
+    fn layout_from_lookup() {
+        let env = |layout: Option<&'static str>, delimiter: Option<&'static str>| {
+            move |name: &str| match name {
+                LAYOUT_ENV => layout.map(str::to_string),
+                FLAT_DELIMITER_ENV => delimiter.map(str::to_string),
+                _ => None,
+            }
+        };
+        assert_eq!(Layout::from_lookup(env(None, None)), Layout::Nested);
+        assert_eq!(
+            Layout::from_lookup(env(Some("flat"), None)),
+            Layout::flat("+").unwrap()
+        );
+        assert_eq!(
+            Layout::from_lookup(env(Some("flat"), Some("~"))),
+            Layout::flat("~").unwrap()
+        );
+        assert_eq!(
+            Layout::from_lookup(env(Some("flat"), Some("--"))),
+            Layout::flat("+").unwrap()
+        );
+        assert_eq!(Layout::from_lookup(env(Some("tree"), None)), Layout::Nested);
+
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mount = Path::new("/mnt/github");
+        assert_eq!(
+            Layout::Nested.repo_path(mount, &key),
+            mount.join("octocat").join("hello-world")
+        );
+        assert_eq!(
+            Layout::flat("+").unwrap().repo_path(mount, &key),
+            mount.join("octocat+hello-world")
+        );
+        assert_eq!(Layout::Single(key.clone()).repo_path(mount, &key), mount);
+    }
+}
@@ -9,7 +9,7 @@
 use crate::cache::CachePaths;
 use crate::daemon::WorkerHandle;
 use crate::store::git::MIN_OID_LEN;
-use crate::store::ref_selector::{BY_REF_ROOT, decode_ref, encode_ref};
+use crate::store::ref_selector::{BY_REF_ROOT, decode_ref, encode_ref, tag_name};
 use crate::store::{EntryKind, Store, StoreError};
 use crate::types::{Owner, Repo, RepoKey};
 #[cfg(target_os = "linux")]
@@ -21,8 +21,10 @@ use std::path::Path;
 use std::sync::Arc;
 #[cfg(target_os = "linux")]
 use std::sync::Mutex;
+#[cfg(target_os = "linux")]
+use std::sync::atomic::AtomicU32;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use std::os::unix::ffi::{OsStrExt, OsStringExt};
@@ -33,27 +35,87 @@ use fuser::{
     ReplyEntry, ReplyOpen, ReplyXattr, Request,
 };
 
+mod access;
+mod access_times;
+mod content_cache;
+mod hidden;
 mod inode;
+mod inode_store;
+mod io_size;
+mod layout;
 #[cfg(target_os = "macos")]
 mod nfs;
+mod remote;
+mod stats;
+mod ttl;
+
+use content_cache::{BlobSizes, ContentCache, MAX_CACHED_FILE_SIZE};
+use remote::{LISTING_TTL, RemoteRepos, merge_repo_names};
 
+pub use access::{ALLOW_OTHER_ENV, AccessPolicy, SHARED_REPOS_ENV, access_allowed};
+pub use access_times::{ACCESS_TOUCH_INTERVAL, AccessTimes};
+pub use hidden::{HIDDEN_PATHS_ENV, HiddenPaths};
 pub use inode::{
-    BY_REF_INO, InodeData, InodeTable, PASSTHROUGH_INO_START, PathKey, ROOT_INO, VIRTUAL_INO_END,
-    VIRTUAL_INO_START,
+    BY_REF_INO, DETERMINISTIC_INODES_ENV, InodeData, InodeTable, PASSTHROUGH_INO_START, PathKey,
+    ROOT_INO, RememberedInode, VIRTUAL_INO_END, VIRTUAL_INO_START,
 };
+pub use inode_store::{DEFAULT_PERSISTED_INODES, InodeStore, PERSISTED_INODES_ENV};
+pub use io_size::{BLOCK_SIZE_ENV, DEFAULT_BLOCK_SIZE, IoSizes, MAX_READ_ENV};
+pub use layout::{
+    DEFAULT_FLAT_DELIMITER, FLAT_DELIMITER_ENV, LAYOUT_ENV, Layout, flat_name, parse_flat_name,
+};
+pub use remote::{LIST_REMOTE_REPOS_ENV, fetch_repo_names};
+pub use stats::{FsStats, FsStatsProbe, Op, OpCounters, OpCounts};
+pub use ttl::{COMMIT_TTL_ENV, REF_TTL_ENV, TtlConfig, VIRTUAL_TTL_ENV};
+
+/// Default name of the synthetic metadata directory served at every commit
+/// root. A real entry of the same name in the tree takes precedence.
+pub const META_DIR: &str = ".ghfs";
+/// Environment variable renaming the metadata directory, for repos whose
+/// own `.ghfs` would hide it.
+pub const META_DIR_ENV: &str = "GHFS_META_DIR";
+/// Environment variable that, when truthy, leaves the metadata directory
+/// out of commit-root listings. It still resolves when looked up by name.
+pub const HIDE_META_DIR_ENV: &str = "GHFS_HIDE_META_DIR";
+/// File under [`META_DIR`] holding the commit OID the root serves, so tools
+/// can read it without a working `.git`.
+pub const META_HEAD: &str = "head";
+
+/// The metadata directory name from [`META_DIR_ENV`], or [`META_DIR`].
+/// Names that aren't a single path component are logged and ignored.
+pub fn meta_dir_from_env() -> String {
+    match crate::config::var(META_DIR_ENV) {
+        None => META_DIR.to_string(),
+        Some(name) if is_single_component(&name) => name,
+        Some(name) => {
+            log::warn!("Ignoring invalid {META_DIR_ENV}={name:?}; expected a file name");
+            META_DIR.to_string()
+        }
+    }
+}
 
-/// TTL for virtual discovery nodes (root, owners, by-ref roots, ref-repo
-/// directory listings).
-#[cfg(target_os = "linux")]
-const VIRTUAL_TTL: Duration = Duration::from_secs(60);
-/// TTL for mutable ref resolution (default-branch alias and named ref
-/// selectors). Short so a moved branch re-resolves promptly.
-#[cfg(target_os = "linux")]
-const REF_TTL: Duration = Duration::from_secs(5);
-/// TTL for content pinned to an immutable commit (commit-OID selectors and
-/// all repository path nodes). Long, since git objects never change.
+/// Whether `name` is usable as a directory entry name.
+pub fn is_single_component(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\0'])
+}
+
+/// Whether a `/proc/<pid>/status` shows the process exiting or holding an
+/// unblocked pending signal.
 #[cfg(target_os = "linux")]
-const COMMIT_TTL: Duration = Duration::from_secs(3600);
+fn status_shows_signal(status: &str) -> bool {
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(str::trim)
+    };
+    let mask = |name: &str| field(name).and_then(|v| u64::from_str_radix(v, 16).ok());
+    if field("State:").is_some_and(|state| state.starts_with(['Z', 'X'])) {
+        return true;
+    }
+    let pending = mask("SigPnd:").unwrap_or(0) | mask("ShdPnd:").unwrap_or(0);
+    pending & !mask("SigBlk:").unwrap_or(0) != 0
+}
 
 #[cfg(target_os = "linux")]
 const FINDER_INFO_XATTR: &str = "com.apple.FinderInfo";
@@ -61,6 +123,66 @@ const FINDER_INFO_XATTR: &str = "com.apple.FinderInfo";
 const FINDER_INFO_XATTR_LIST: &[u8] = b"com.apple.FinderInfo\0";
 #[cfg(target_os = "linux")]
 const FINDER_INFO_SIZE: usize = 32;
+/// Extended attribute holding a file's git mode in octal, e.g. `100755`.
+#[cfg(target_os = "linux")]
+const GIT_MODE_XATTR: &str = "user.ghfs.mode";
+/// Extended attribute holding `1` for executable files and `0` otherwise.
+#[cfg(target_os = "linux")]
+const EXECUTABLE_XATTR: &str = "user.ghfs.executable";
+
+/// The value of extended attribute `name` on the node `data`. Every node
+/// carries an empty FinderInfo; files also carry their git mode.
+#[cfg(target_os = "linux")]
+fn xattr_value(data: &InodeData, name: &str) -> Option<Vec<u8>> {
+    if name == FINDER_INFO_XATTR {
+        return Some(vec![0; FINDER_INFO_SIZE]);
+    }
+    let kind = match data {
+        InodeData::Path { kind, .. } if *kind != EntryKind::Tree => *kind,
+        _ => return None,
+    };
+    match name {
+        GIT_MODE_XATTR => Some(format!("{:06o}", kind.git_mode()).into_bytes()),
+        EXECUTABLE_XATTR => Some(if kind == EntryKind::Executable {
+            b"1".to_vec()
+        } else {
+            b"0".to_vec()
+        }),
+        _ => None,
+    }
+}
+
+/// The NUL-separated extended attribute names of the node `data`.
+#[cfg(target_os = "linux")]
+fn xattr_names(data: &InodeData) -> Vec<u8> {
+    let mut names = FINDER_INFO_XATTR_LIST.to_vec();
+    if matches!(data, InodeData::Path { kind, .. } if *kind != EntryKind::Tree) {
+        for name in [GIT_MODE_XATTR, EXECUTABLE_XATTR] {
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+    }
+    names
+}
+
+/// Reply to a `getxattr` or `listxattr` of `value`: its size when the
+/// caller asks with `size` 0, `ERANGE` when its buffer is too small.
+#[cfg(target_os = "linux")]
+fn reply_xattr(reply: ReplyXattr, size: u32, value: &[u8]) {
+    if size == 0 {
+        reply.size(value.len() as u32);
+    } else if size < value.len() as u32 {
+        reply.error(libc::ERANGE);
+    } else {
+        reply.data(value);
+    }
+}
+
+/// Free inodes `statfs` reports on top of the live ones. Inodes are
+/// allocated on demand, so there is no real limit; this just keeps tools
+/// that check for free inodes before copying from seeing a full disk.
+#[cfg(target_os = "linux")]
+const STATFS_INODE_HEADROOM: u64 = 1 << 20;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum FsKind {
@@ -106,6 +228,8 @@ fn store_err_errno(err: &StoreError) -> i32 {
             crate::store::GitError::RefNotFound(_) => libc::ENOENT,
             crate::store::GitError::AmbiguousRef(_) => libc::EINVAL,
             crate::store::GitError::InvalidInput(_) => libc::EINVAL,
+            crate::store::GitError::Unavailable(_) => libc::EACCES,
+            crate::store::GitError::DiskFull(_) => libc::ENOSPC,
             _ => libc::EIO,
         },
         StoreError::Tree(t) => match t {
@@ -115,10 +239,16 @@ fn store_err_errno(err: &StoreError) -> i32 {
         },
         StoreError::Blob(b) => match b {
             crate::store::BlobError::BlobNotFound(_) => libc::ENOENT,
+            crate::store::BlobError::Git(crate::store::GitError::DiskFull(_)) => libc::ENOSPC,
             _ => libc::EIO,
         },
         StoreError::RepoNotFound(_) => libc::ENOENT,
         StoreError::LockFailed => libc::EIO,
+        StoreError::QuotaExceeded(_) => libc::ENOSPC,
+        StoreError::Busy(_) => libc::EBUSY,
+        StoreError::NoCommits(_) => libc::ENOENT,
+        StoreError::Interrupted => libc::EINTR,
+        StoreError::ShuttingDown => libc::EIO,
         StoreError::Io(e) => io_errno(std::io::Error::from(e.kind()), libc::EIO),
     }
 }
@@ -140,6 +270,23 @@ fn entry_mode(kind: EntryKind) -> u16 {
     }
 }
 
+/// Why `open` with `flags` fails on an inode of `kind`, if it does.
+/// Directories go through `opendir`, so opening one here is `EISDIR`.
+/// Symlinks are otherwise opened as their target text, but `O_NOFOLLOW`
+/// refuses them with `ELOOP` as open(2) does.
+#[cfg(target_os = "linux")]
+fn open_flags_errno(kind: EntryKind, flags: i32) -> Option<i32> {
+    if flags & libc::O_ACCMODE != libc::O_RDONLY {
+        return Some(libc::EROFS);
+    }
+    match kind {
+        EntryKind::Tree => Some(libc::EISDIR),
+        _ if flags & libc::O_DIRECTORY != 0 => Some(libc::ENOTDIR),
+        EntryKind::Symlink if flags & libc::O_NOFOLLOW != 0 => Some(libc::ELOOP),
+        _ => None,
+    }
+}
+
 #[cfg(target_os = "linux")]
 fn kind_to_fuse(kind: FsKind) -> FileType {
     match kind {
@@ -172,6 +319,23 @@ impl NodeAttr {
     }
 }
 
+/// An open file handle: either a small blob served from [`ContentCache`], or
+/// the hydrated blob file (with its size, captured at open) for everything
+/// else.
+#[cfg(target_os = "linux")]
+enum OpenFile {
+    Cached(Arc<[u8]>),
+    File { file: File, size: u64 },
+}
+
+/// An [`OpenFile`] with the (canonical) repo it was opened under, so
+/// removing a repo can refuse while it has open handles.
+#[cfg(target_os = "linux")]
+struct OpenHandle {
+    repo: Option<RepoKey>,
+    file: OpenFile,
+}
+
 /// The GHFS filesystem. Backend-agnostic; both FUSE and NFS adapters delegate
 /// to the same store-backed methods.
 #[cfg_attr(target_os = "macos", allow(dead_code))]
@@ -179,11 +343,43 @@ pub struct GhFs {
     store: Store,
     worker: Arc<WorkerHandle>,
     cache_paths: CachePaths,
-    inodes: InodeTable,
+    inodes: Arc<InodeTable>,
+    /// Where path inodes are saved on unmount for the next mount, if
+    /// anywhere.
+    inode_store: Option<InodeStore>,
+    content_cache: Arc<ContentCache>,
+    /// Sizes of hydrated file blobs, so `getattr` after `lookup` doesn't
+    /// stat the blob again.
+    blob_sizes: BlobSizes,
+    access_policy: AccessPolicy,
+    /// Where reads are recorded as repo access times, if anywhere.
+    access_times: Option<AccessTimes>,
+    op_counters: Arc<OpCounters>,
+    ttls: TtlConfig,
+    io_sizes: IoSizes,
+    /// GitHub-backed owner listings; `None` lists cached repos only.
+    remote_repos: Option<RemoteRepos>,
+    /// Name of the synthetic metadata directory at each commit root.
+    meta_dir: String,
+    /// Whether the metadata directory appears in commit-root listings.
+    list_meta_dir: bool,
+    /// Whether repos sit under owner directories or directly at the root.
+    layout: Layout,
+    /// Paths left out of particular repos' trees.
+    hidden_paths: HiddenPaths,
+    /// Repos already warned about a real entry hiding the metadata dir.
+    meta_collisions: dashmap::DashSet<RepoKey>,
+    /// HEAD each repo last materialized to, reused for [`TtlConfig::ref_ttl`]
+    /// so a recursive listing doesn't ask the worker once per lookup.
+    recent_heads: dashmap::DashMap<RepoKey, (Instant, String)>,
     uid: u32,
     gid: u32,
+    /// PID of the process whose FUSE request is being served, or 0. Waits
+    /// on the worker give up once it exits or is signalled.
     #[cfg(target_os = "linux")]
-    open_files: Mutex<HashMap<u64, File>>,
+    caller: AtomicU32,
+    #[cfg(target_os = "linux")]
+    open_files: Arc<Mutex<HashMap<u64, OpenHandle>>>,
     #[cfg(target_os = "linux")]
     next_fh: AtomicU64,
 }
@@ -192,20 +388,166 @@ impl GhFs {
     /// Create a new filesystem instance.
     pub fn new(store: Store, worker: Arc<WorkerHandle>) -> Self {
         let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let cache_paths = store.paths().clone();
         Self {
             store,
             worker,
-            cache_paths: CachePaths::default(),
-            inodes: InodeTable::new(),
+            cache_paths,
+            inodes: Arc::new(InodeTable::new()),
+            inode_store: None,
+            content_cache: Arc::new(ContentCache::new()),
+            blob_sizes: BlobSizes::new(),
+            access_policy: AccessPolicy::default(),
+            access_times: None,
+            op_counters: Arc::new(OpCounters::default()),
+            ttls: TtlConfig::default(),
+            io_sizes: IoSizes::default(),
+            remote_repos: None,
+            meta_dir: META_DIR.to_string(),
+            list_meta_dir: true,
+            layout: Layout::Nested,
+            hidden_paths: HiddenPaths::default(),
+            meta_collisions: dashmap::DashSet::new(),
+            recent_heads: dashmap::DashMap::new(),
             uid,
             gid,
             #[cfg(target_os = "linux")]
-            open_files: Mutex::new(HashMap::new()),
+            caller: AtomicU32::new(0),
+            #[cfg(target_os = "linux")]
+            open_files: Arc::new(Mutex::new(HashMap::new())),
             #[cfg(target_os = "linux")]
             next_fh: AtomicU64::new(1),
         }
     }
 
+    /// Apply `policy` to the modes reported for every node.
+    pub fn with_access_policy(mut self, policy: AccessPolicy) -> Self {
+        self.access_policy = policy;
+        self
+    }
+
+    /// Record opening a file as an access to its repo in `times`.
+    pub fn with_access_times(mut self, times: AccessTimes) -> Self {
+        self.access_times = Some(times);
+        self
+    }
+
+    /// Leave the paths in `hidden` out of listings and lookups.
+    pub fn with_hidden_paths(mut self, hidden: HiddenPaths) -> Self {
+        self.hidden_paths = hidden;
+        self
+    }
+
+    /// Report `sizes.block_size` as every node's block size, and mount with
+    /// `sizes.max_read` when set.
+    pub fn with_io_sizes(mut self, sizes: IoSizes) -> Self {
+        self.io_sizes = sizes;
+        self
+    }
+
+    /// Report `ttls` to the kernel instead of the defaults.
+    pub fn with_ttls(mut self, ttls: TtlConfig) -> Self {
+        self.ttls = ttls;
+        self
+    }
+
+    /// Derive path inode numbers from the path's identity instead of
+    /// access order (see [`InodeTable::deterministic`]). Call before the
+    /// filesystem serves anything; it replaces the inode table.
+    pub fn with_deterministic_inodes(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.inodes = Arc::new(InodeTable::deterministic());
+        }
+        self
+    }
+
+    /// Hand out the path inodes `store` saved at the last unmount, and
+    /// save this mount's on unmount. Call after
+    /// [`Self::with_deterministic_inodes`], which replaces the table.
+    pub fn with_inode_store(mut self, store: InodeStore) -> Self {
+        let loaded = store.load(&self.inodes);
+        if loaded > 0 {
+            log::info!("Restored {loaded} inodes from the last mount");
+        }
+        self.inode_store = Some(store);
+        self
+    }
+
+    /// Save the remembered path inodes, if an [`InodeStore`] is set.
+    fn save_inodes(store: Option<InodeStore>, inodes: &InodeTable) {
+        if let Some(store) = store {
+            match store.save(inodes) {
+                Ok(saved) => log::debug!("Saved {saved} inodes for the next mount"),
+                Err(err) => log::warn!("Failed to save inodes: {err}"),
+            }
+        }
+    }
+
+    /// Arrange repos under the root according to `layout`.
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Serve the synthetic metadata directory as `name` instead of
+    /// [`META_DIR`].
+    pub fn with_meta_dir(mut self, name: impl Into<String>) -> Self {
+        self.meta_dir = name.into();
+        self
+    }
+
+    /// Leave the metadata directory out of commit-root listings, so they
+    /// show only the tree's own entries. Lookups by name still find it.
+    pub fn with_meta_dir_listed(mut self, listed: bool) -> Self {
+        self.list_meta_dir = listed;
+        self
+    }
+
+    /// Note that `key` has a real entry named like the metadata dir, which
+    /// is served instead. Warns once per repo.
+    fn meta_dir_hidden(&self, key: &RepoKey) {
+        if self.meta_collisions.insert(key.canonical()) {
+            log::warn!(
+                "{key} has its own {}; serving it instead of ghfs metadata \
+                 (set {META_DIR_ENV} to rename the metadata dir)",
+                self.meta_dir
+            );
+        }
+    }
+
+    /// List every repo GitHub reports for an owner in its directory, not
+    /// just the cached ones. Listed repos are cloned on first traversal.
+    pub fn with_remote_listing(mut self, enabled: bool) -> Self {
+        self.remote_repos = enabled.then(|| RemoteRepos::new(LISTING_TTL));
+        self
+    }
+
+    /// Record operations into `counters` (shared with the socket server
+    /// for `ghfs top`).
+    pub fn with_op_counters(mut self, counters: Arc<OpCounters>) -> Self {
+        self.op_counters = counters;
+        self
+    }
+
+    /// A handle the socket server keeps to report table sizes for
+    /// `ghfs fsstats` after `self` has moved into the mount.
+    pub fn stats_probe(&self) -> FsStatsProbe {
+        FsStatsProbe {
+            store: self.store.clone(),
+            inodes: Arc::clone(&self.inodes),
+            content_cache: Arc::clone(&self.content_cache),
+            #[cfg(target_os = "linux")]
+            open_files: Arc::clone(&self.open_files),
+        }
+    }
+
+    /// `base` permission bits adjusted for the visibility of `ino`'s repo.
+    fn perm_for(&self, ino: u64, base: u16) -> u16 {
+        let data = self.inodes.get(ino);
+        self.access_policy
+            .mode(data.as_ref().and_then(InodeData::repo), base)
+    }
+
     fn virtual_dir_attr(&self, ino: u64) -> NodeAttr {
         NodeAttr {
             ino,
@@ -215,12 +557,12 @@ impl GhFs {
             mtime: UNIX_EPOCH,
             ctime: UNIX_EPOCH,
             kind: FsKind::Directory,
-            perm: 0o755,
+            perm: self.perm_for(ino, 0o755),
             nlink: 2,
             uid: self.uid,
             gid: self.gid,
             rdev: 0,
-            blksize: 4096,
+            blksize: self.io_sizes.block_size,
         }
     }
 
@@ -229,17 +571,17 @@ impl GhFs {
         NodeAttr {
             ino,
             size,
-            blocks: (size + 511) / 512,
+            blocks: size.div_ceil(512),
             atime: UNIX_EPOCH,
             mtime: UNIX_EPOCH,
             ctime: UNIX_EPOCH,
             kind: fskind,
-            perm: entry_mode(kind) & 0o7777,
+            perm: self.perm_for(ino, entry_mode(kind) & 0o7777),
             nlink: 1,
             uid: self.uid,
             gid: self.gid,
             rdev: 0,
-            blksize: 4096,
+            blksize: self.io_sizes.block_size,
         }
     }
 
@@ -257,24 +599,27 @@ impl GhFs {
         name.len() >= MIN_OID_LEN && name.chars().all(|c| c.is_ascii_hexdigit())
     }
 
+    /// Whether a decoded selector names content that never changes: a
+    /// commit OID or a full `refs/tags/<name>` tag.
+    fn is_immutable_selector(raw: &str) -> bool {
+        Self::is_commit_oid_selector(raw) || tag_name(raw).is_some()
+    }
+
     fn list_cached_owners(&self) -> Vec<String> {
-        let mut owners = Vec::new();
-        if let Ok(entries) = std::fs::read_dir(self.cache_paths.mirrors_dir()) {
-            for entry in entries.flatten() {
-                if entry.file_type().map(|t| t.is_dir()).unwrap_or(false)
-                    && let Some(name) = entry.file_name().to_str()
-                    && Self::is_valid_owner(name)
-                {
-                    owners.push(name.to_string());
-                }
-            }
-        }
+        let mut owners: Vec<String> = self
+            .cache_paths
+            .owner_dirs()
+            .iter()
+            .filter_map(|dir| dir.file_name()?.to_str())
+            .filter(|name| Self::is_valid_owner(name))
+            .map(str::to_string)
+            .collect();
         owners.sort();
         owners
     }
 
     fn list_cached_repos(&self, owner: &str) -> Vec<String> {
-        let dir = self.cache_paths.mirrors_dir().join(owner);
+        let dir = self.cache_paths.owner_dir(owner);
         let mut repos = Vec::new();
         if let Ok(entries) = std::fs::read_dir(&dir) {
             for entry in entries.flatten() {
@@ -293,14 +638,69 @@ impl GhFs {
         repos
     }
 
+    /// Repos shown in an owner directory: the cached ones, plus the
+    /// owner's GitHub repos when remote listing is enabled.
+    fn list_owner_repos(&self, owner: &str) -> Vec<String> {
+        let cached = self.list_cached_repos(owner);
+        match &self.remote_repos {
+            Some(remote) => merge_repo_names(cached, &remote.list(owner)),
+            None => cached,
+        }
+    }
+
     /// Ensure a repo's mirror exists and return its default-branch (HEAD)
     /// commit. Off-loaded to the worker so the mount thread isn't the one
     /// performing a network clone.
-    fn materialize_head(&self, key: &RepoKey) -> Result<String, i32> {
-        self.worker.materialize(key.clone()).map_err(|e| {
-            log::error!("materialize {key} failed: {e}");
-            store_err_errno(&e)
-        })
+    ///
+    /// A HEAD materialized within the last [`TtlConfig::ref_ttl`] is reused,
+    /// the same staleness the kernel already allows for the repo's entry.
+    /// `None` if the repo has no commits yet.
+    fn materialize_head(&self, key: &RepoKey) -> Result<Option<String>, i32> {
+        let window = self.ttls.ref_ttl;
+        if let Some(recent) = self.recent_heads.get(key)
+            && recent.0.elapsed() < window
+        {
+            return Ok(Some(recent.1.clone()));
+        }
+        self.op_counters.record(Op::Materialize);
+        let commit = match self
+            .worker
+            .materialize_interruptible(key.clone(), &|| self.caller_gave_up())
+        {
+            Ok(commit) => commit,
+            Err(StoreError::NoCommits(_)) => return Ok(None),
+            Err(e) => {
+                match e {
+                    StoreError::Interrupted => {
+                        log::debug!("materialize {key} abandoned by its caller")
+                    }
+                    _ => log::error!("materialize {key} failed: {e}"),
+                }
+                return Err(store_err_errno(&e));
+            }
+        };
+        if !window.is_zero() {
+            self.recent_heads.retain(|_, (at, _)| at.elapsed() < window);
+            self.recent_heads
+                .insert(key.clone(), (Instant::now(), commit.clone()));
+        }
+        Ok(Some(commit))
+    }
+
+    /// Whether the process behind the FUSE request being served has exited
+    /// or been signalled since. fuser doesn't pass FUSE_INTERRUPT on, so
+    /// this is how a slow clone notices a Ctrl-C'd `ls`.
+    fn caller_gave_up(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        if let pid @ 1.. = self.caller.load(Ordering::Relaxed) {
+            return match std::fs::read_to_string(format!("/proc/{pid}/status")) {
+                Ok(status) => status_shows_signal(&status),
+                // Gone. Other errors (`hidepid`, another user's process)
+                // say nothing about the caller, so keep waiting.
+                Err(err) => err.kind() == std::io::ErrorKind::NotFound,
+            };
+        }
+        false
     }
 
     /// Ensure a repo mirror exists and resolve a ref selector to a commit OID.
@@ -313,59 +713,127 @@ impl GhFs {
             })
     }
 
+    /// Allocate a virtual inode, reclaiming nodes for owners and repos that
+    /// are no longer cached if the virtual range is exhausted.
+    fn get_or_alloc_virtual(&self, parent: u64, name: &str, data: InodeData) -> Result<u64, i32> {
+        match self.inodes.get_or_alloc_virtual(parent, name, data.clone()) {
+            Err(libc::ENOSPC) if self.reclaim_virtual_inodes() > 0 => {
+                self.inodes.get_or_alloc_virtual(parent, name, data)
+            }
+            result => result,
+        }
+    }
+
+    /// Free virtual inodes whose owner or repo no longer has a mirror in the
+    /// cache (e.g. after `ghfs rm` or gc). Returns the number freed.
+    pub fn reclaim_virtual_inodes(&self) -> usize {
+        let paths = self.store.paths();
+        let freed = self.inodes.reclaim_virtual(|data| match data {
+            InodeData::Owner(owner) | InodeData::RefOwner(owner) => {
+                !paths.owner_dir(owner.as_str()).is_dir()
+            }
+            InodeData::RefRepo(key) | InodeData::Repo { key, .. } => {
+                !paths.mirror_dir(key).exists()
+            }
+            _ => false,
+        });
+        if freed > 0 {
+            log::info!("Reclaimed {freed} virtual inodes for uncached repos");
+        }
+        freed
+    }
+
     fn lookup_inode(&self, parent: u64, name: &OsStr) -> Result<u64, i32> {
-        let name_str = name.to_str().ok_or(libc::ENOENT)?;
+        // The single-repo root follows the repo's default branch.
+        if parent == ROOT_INO
+            && let Layout::Single(key) = &self.layout
+        {
+            let (commit, root_tree) = self.head_tree(key)?;
+            return self.lookup_commit_root_child(parent, key, &commit, &root_tree, name);
+        }
+        let parent_data = self.inodes.get(parent).ok_or(libc::ENOENT)?;
+
+        // ---- commit-pinned path descent ----
+        // Names are matched as raw bytes against tree entries, so non-UTF-8
+        // filenames resolve like any other.
+        match parent_data {
+            InodeData::Repo {
+                ref key,
+                ref commit,
+                ref root_tree,
+                ..
+            } => return self.lookup_commit_root_child(parent, key, commit, root_tree, name),
+            InodeData::MetaDir { repo, commit, .. } => {
+                if name != META_HEAD {
+                    return Err(libc::ENOENT);
+                }
+                return Ok(self.inodes.get_or_alloc_meta(
+                    parent,
+                    META_HEAD,
+                    InodeData::MetaHead {
+                        repo,
+                        commit,
+                        parent,
+                    },
+                ));
+            }
+            InodeData::MetaHead { .. } => return Err(libc::ENOTDIR),
+            InodeData::Path {
+                ref repo,
+                ref commit,
+                ref path,
+                ref oid,
+                kind,
+                ..
+            } => {
+                if kind != EntryKind::Tree {
+                    return Err(libc::ENOTDIR);
+                }
+                let tree_oid = parse_oid(oid)?;
+                return self.lookup_path_child(parent, repo, commit, tree_oid, path, name);
+            }
+            _ => {}
+        }
 
         // ---- virtual discovery hierarchy ----
+        // Owners, repos, and encoded ref selectors are all ASCII.
+        let name_str = name.to_str().ok_or(libc::ENOENT)?;
         if parent == ROOT_INO {
             if name_str == BY_REF_ROOT {
                 return Ok(BY_REF_INO);
             }
+            if let Layout::Flat { delimiter } = &self.layout {
+                let key = parse_flat_name(name_str, delimiter).ok_or(libc::ENOENT)?;
+                return self.repo_node(parent, name_str, key);
+            }
             if !Self::is_valid_owner(name_str) {
                 return Err(libc::ENOENT);
             }
-            return Ok(self.inodes.get_or_alloc_virtual(
+            return self.get_or_alloc_virtual(
                 parent,
                 name_str,
                 InodeData::Owner(name_str.parse::<Owner>().unwrap()),
-            )?);
+            );
         }
 
         if parent == BY_REF_INO {
             if !Self::is_valid_owner(name_str) {
                 return Err(libc::ENOENT);
             }
-            return Ok(self.inodes.get_or_alloc_virtual(
+            return self.get_or_alloc_virtual(
                 parent,
                 name_str,
                 InodeData::RefOwner(name_str.parse::<Owner>().unwrap()),
-            )?);
+            );
         }
 
-        let parent_data = self.inodes.get(parent).ok_or(libc::ENOENT)?;
-
         match parent_data {
             InodeData::Owner(owner) => {
                 if !Self::is_valid_repo(name_str) {
                     return Err(libc::ENOENT);
                 }
                 let repo: Repo = name_str.parse().unwrap();
-                let key = RepoKey::new(owner, repo);
-                let commit = self.materialize_head(&key)?;
-                let root_tree = self
-                    .store
-                    .root_tree(&key, parse_oid(&commit)?)
-                    .map_err(|e| store_err_errno(&e))?;
-                Ok(self.inodes.get_or_alloc_virtual(
-                    parent,
-                    name_str,
-                    InodeData::Repo {
-                        key,
-                        selector: None,
-                        commit,
-                        root_tree: root_tree.to_string(),
-                    },
-                )?)
+                self.repo_node(parent, name_str, RepoKey::new(owner, repo))
             }
             InodeData::RefOwner(owner) => {
                 if !Self::is_valid_repo(name_str) {
@@ -373,9 +841,7 @@ impl GhFs {
                 }
                 let repo: Repo = name_str.parse().unwrap();
                 let key = RepoKey::new(owner, repo);
-                Ok(self
-                    .inodes
-                    .get_or_alloc_virtual(parent, name_str, InodeData::RefRepo(key))?)
+                Ok(self.get_or_alloc_virtual(parent, name_str, InodeData::RefRepo(key))?)
             }
             InodeData::RefRepo(key) => {
                 // Child is an encoded ref selector.
@@ -385,7 +851,7 @@ impl GhFs {
                     .store
                     .root_tree(&key, parse_oid(&commit)?)
                     .map_err(|e| store_err_errno(&e))?;
-                Ok(self.inodes.get_or_alloc_virtual(
+                Ok(self.get_or_alloc_virtual(
                     parent,
                     name_str,
                     InodeData::Repo {
@@ -396,36 +862,112 @@ impl GhFs {
                     },
                 )?)
             }
-            // ---- commit-pinned path descent ----
+            // Root and ByRefRoot are handled above, Repo and Path by the
+            // byte-wise descent; nothing else should reach here.
+            _ => Err(libc::ENOENT),
+        }
+    }
+
+    /// The node serving `key`'s default-branch tree as `name` under
+    /// `parent`, cloning the repo first if needed.
+    fn repo_node(&self, parent: u64, name: &str, key: RepoKey) -> Result<u64, i32> {
+        let (commit, root_tree) = self.head_tree(&key)?;
+        self.get_or_alloc_virtual(
+            parent,
+            name,
             InodeData::Repo {
                 key,
+                selector: None,
                 commit,
                 root_tree,
-                ..
-            } => {
-                let tree_oid = parse_oid(&root_tree)?;
-                self.lookup_path_child(parent, &key, &commit, tree_oid, &[], name)
+            },
+        )
+    }
+
+    /// `key`'s default-branch commit and its root tree, cloning the repo
+    /// first if needed. A repo with no commits yet serves the empty tree
+    /// under the all-zero commit, so it mounts as an empty directory.
+    fn head_tree(&self, key: &RepoKey) -> Result<(String, String), i32> {
+        let (commit, root_tree) = match self.materialize_head(key)? {
+            Some(commit) => {
+                let root_tree = self.store.root_tree(key, parse_oid(&commit)?);
+                (commit, root_tree)
             }
-            InodeData::Path {
-                repo,
-                commit,
-                path,
-                oid,
-                kind,
-                ..
-            } => {
-                if kind != EntryKind::Tree {
-                    return Err(libc::ENOTDIR);
-                }
-                let tree_oid = parse_oid(&oid)?;
-                self.lookup_path_child(parent, &repo, &commit, tree_oid, &path, name)
+            None => (git2::Oid::zero().to_string(), self.store.empty_tree(key)),
+        };
+        let root_tree = root_tree.map_err(|e| store_err_errno(&e))?;
+        Ok((commit, root_tree.to_string()))
+    }
+
+    /// Look up a named child of the directory `parent` serving `commit`'s
+    /// root tree, including the synthetic metadata dir.
+    fn lookup_commit_root_child(
+        &self,
+        parent: u64,
+        key: &RepoKey,
+        commit: &str,
+        root_tree: &str,
+        name: &OsStr,
+    ) -> Result<u64, i32> {
+        let tree_oid = parse_oid(root_tree)?;
+        if name != self.meta_dir.as_str() {
+            return self.lookup_path_child(parent, key, commit, tree_oid, &[], name);
+        }
+        // A real entry wins over the synthetic metadata dir.
+        match self.lookup_path_child(parent, key, commit, tree_oid, &[], name) {
+            Err(libc::ENOENT) => Ok(self.inodes.get_or_alloc_meta(
+                parent,
+                &self.meta_dir,
+                InodeData::MetaDir {
+                    repo: key.clone(),
+                    commit: commit.to_string(),
+                    parent,
+                },
+            )),
+            Ok(ino) => {
+                self.meta_dir_hidden(key);
+                Ok(ino)
             }
-            // Root, ByRefRoot, RefOwner are handled by explicit arms above;
-            // nothing else should reach here.
-            _ => Err(libc::ENOENT),
+            Err(e) => Err(e),
         }
     }
 
+    /// Entries of the directory `ino` serving `commit`'s root tree,
+    /// including the synthetic metadata dir unless it's unlisted.
+    fn list_commit_root_children(
+        &self,
+        ino: u64,
+        key: RepoKey,
+        commit: String,
+        root_tree: &str,
+    ) -> Result<Vec<DirEntryInfo>, i32> {
+        let tree_oid = parse_oid(root_tree)?;
+        let mut out = self.list_tree_children(ino, &key, &commit, tree_oid, &[])?;
+        // A real entry wins over the synthetic metadata dir.
+        if out.iter().any(|entry| entry.name == self.meta_dir.as_str()) {
+            self.meta_dir_hidden(&key);
+            return Ok(out);
+        }
+        if !self.list_meta_dir {
+            return Ok(out);
+        }
+        let meta = self.inodes.get_or_alloc_meta(
+            ino,
+            &self.meta_dir,
+            InodeData::MetaDir {
+                repo: key,
+                commit,
+                parent: ino,
+            },
+        );
+        out.push(DirEntryInfo {
+            ino: meta,
+            kind: FsKind::Directory,
+            name: OsString::from(&self.meta_dir),
+        });
+        Ok(out)
+    }
+
     /// Look up a named child of a directory identified by its tree OID.
     /// `prefix` is the parent path (repo-relative) of the directory.
     fn lookup_path_child(
@@ -437,6 +979,9 @@ impl GhFs {
         prefix: &[u8],
         name: &OsStr,
     ) -> Result<u64, i32> {
+        if self.hidden_paths.hides(repo, prefix, name.as_bytes()) {
+            return Err(libc::ENOENT);
+        }
         let entry = self
             .store
             .tree_entry(repo, tree_oid, name.as_bytes())
@@ -461,7 +1006,12 @@ impl GhFs {
             | InodeData::Owner(_)
             | InodeData::RefOwner(_)
             | InodeData::RefRepo(_) => Ok(self.virtual_dir_attr(ino)),
-            InodeData::Repo { .. } => Ok(self.virtual_dir_attr(ino)),
+            InodeData::Repo { .. } | InodeData::MetaDir { .. } => Ok(self.virtual_dir_attr(ino)),
+            InodeData::MetaHead { commit, .. } => Ok(self.file_attr(
+                ino,
+                EntryKind::Blob,
+                meta_head_contents(&commit).len() as u64,
+            )),
             InodeData::Path {
                 kind, oid, repo, ..
             } => {
@@ -474,16 +1024,24 @@ impl GhFs {
                 // File or symlink: hydrate to learn the size (one-time,
                 // content-addressed and cached thereafter).
                 let blob_oid = parse_oid(&oid)?;
-                let (_path, size) = self
-                    .store
-                    .hydrate_blob(&repo, blob_oid)
-                    .map_err(|e| store_err_errno(&e))?;
+                let size = self.blob_sizes.get_or_load(blob_oid, || {
+                    self.store
+                        .hydrate_blob(&repo, blob_oid)
+                        .map(|(_path, size)| size)
+                        .map_err(|e| store_err_errno(&e))
+                })?;
                 Ok(self.file_attr(ino, kind, size))
             }
         }
     }
 
     fn list_children(&self, ino: u64) -> Result<Vec<DirEntryInfo>, i32> {
+        if ino == ROOT_INO
+            && let Layout::Single(key) = &self.layout
+        {
+            let (commit, root_tree) = self.head_tree(key)?;
+            return self.list_commit_root_children(ino, key.clone(), commit, &root_tree);
+        }
         let data = self.inodes.get(ino).ok_or(libc::ENOENT)?;
         match data {
             InodeData::Root => {
@@ -492,6 +1050,30 @@ impl GhFs {
                     kind: FsKind::Directory,
                     name: OsString::from(BY_REF_ROOT),
                 }];
+                if let Layout::Flat { delimiter } = &self.layout {
+                    for owner in self.list_cached_owners() {
+                        for repo in self.list_cached_repos(&owner) {
+                            let key = RepoKey::new(
+                                owner.parse::<Owner>().unwrap(),
+                                repo.parse::<Repo>().unwrap(),
+                            );
+                            let name = flat_name(&key, delimiter);
+                            // Only cached mirrors are listed, so this
+                            // resolves HEAD without cloning.
+                            let repo_ino = match self.repo_node(ino, &name, key) {
+                                Ok(repo_ino) => repo_ino,
+                                Err(libc::ENOSPC) => return Err(libc::ENOSPC),
+                                Err(_) => continue,
+                            };
+                            out.push(DirEntryInfo {
+                                ino: repo_ino,
+                                kind: FsKind::Directory,
+                                name: OsString::from(name),
+                            });
+                        }
+                    }
+                    return Ok(out);
+                }
                 // `by-ref` is the synthetic namespace at the root. An owner
                 // with that name remains available below `/by-ref/by-ref`.
                 for owner in self
@@ -499,7 +1081,7 @@ impl GhFs {
                     .into_iter()
                     .filter(|owner| owner != BY_REF_ROOT)
                 {
-                    let owner_ino = self.inodes.get_or_alloc_virtual(
+                    let owner_ino = self.get_or_alloc_virtual(
                         ino,
                         &owner,
                         InodeData::Owner(owner.parse::<Owner>().unwrap()),
@@ -515,7 +1097,7 @@ impl GhFs {
             InodeData::ByRefRoot => {
                 let mut out = Vec::new();
                 for owner in self.list_cached_owners() {
-                    let owner_ino = self.inodes.get_or_alloc_virtual(
+                    let owner_ino = self.get_or_alloc_virtual(
                         ino,
                         &owner,
                         InodeData::RefOwner(owner.parse::<Owner>().unwrap()),
@@ -530,14 +1112,13 @@ impl GhFs {
             }
             InodeData::Owner(owner) => {
                 let mut out = Vec::new();
-                for repo in self.list_cached_repos(owner.as_str()) {
+                for repo in self.list_owner_repos(owner.as_str()) {
                     let name = repo.clone();
                     let key = RepoKey::new(owner.clone(), name.parse::<Repo>().unwrap());
-                    // Don't clone on a mere listing of cached repos; only show
-                    // repos whose mirror already exists (list_cached_repos
-                    // scans the mirror dir, so this holds).
+                    // Don't clone on a mere listing: remote-only repos are
+                    // virtual nodes until a lookup inside them materializes
+                    // the mirror.
                     let ino = self
-                        .inodes
                         .get_or_alloc_virtual(ino, &repo, InodeData::RefRepo(key))
                         .map_err(|_| libc::EIO)?; // shouldn't run out of vnodes for small lists
                     out.push(DirEntryInfo {
@@ -550,11 +1131,9 @@ impl GhFs {
             }
             InodeData::RefOwner(owner) => {
                 let mut out = Vec::new();
-                for repo in self.list_cached_repos(owner.as_str()) {
+                for repo in self.list_owner_repos(owner.as_str()) {
                     let key = RepoKey::new(owner.clone(), repo.parse::<Repo>().unwrap());
-                    let ino =
-                        self.inodes
-                            .get_or_alloc_virtual(ino, &repo, InodeData::RefRepo(key))?;
+                    let ino = self.get_or_alloc_virtual(ino, &repo, InodeData::RefRepo(key))?;
                     out.push(DirEntryInfo {
                         ino,
                         kind: FsKind::Directory,
@@ -594,7 +1173,7 @@ impl GhFs {
                             continue;
                         }
                     };
-                    let ino = self.inodes.get_or_alloc_virtual(
+                    let ino = self.get_or_alloc_virtual(
                         ino,
                         &enc,
                         InodeData::Repo {
@@ -617,10 +1196,24 @@ impl GhFs {
                 commit,
                 root_tree,
                 ..
-            } => {
-                let tree_oid = parse_oid(&root_tree)?;
-                self.list_tree_children(ino, &key, &commit, tree_oid, &[])
+            } => self.list_commit_root_children(ino, key, commit, &root_tree),
+            InodeData::MetaDir { repo, commit, .. } => {
+                let head = self.inodes.get_or_alloc_meta(
+                    ino,
+                    META_HEAD,
+                    InodeData::MetaHead {
+                        repo,
+                        commit,
+                        parent: ino,
+                    },
+                );
+                Ok(vec![DirEntryInfo {
+                    ino: head,
+                    kind: FsKind::RegularFile,
+                    name: OsString::from(META_HEAD),
+                }])
             }
+            InodeData::MetaHead { .. } => Err(libc::ENOTDIR),
             InodeData::Path {
                 repo,
                 commit,
@@ -646,34 +1239,80 @@ impl GhFs {
         tree_oid: git2::Oid,
         prefix: &[u8],
     ) -> Result<Vec<DirEntryInfo>, i32> {
+        Ok(self
+            .tree_children_from(
+                parent_ino,
+                repo.clone(),
+                commit.to_string(),
+                tree_oid,
+                prefix.to_vec(),
+                0,
+            )?
+            .collect())
+    }
+
+    /// A tree's visible children in name order, starting at the `skip`th.
+    /// Inodes are allocated as the iterator advances, so a `readdir` page
+    /// into a huge directory only touches the entries it returns.
+    fn tree_children_from(
+        &self,
+        parent_ino: u64,
+        repo: RepoKey,
+        commit: String,
+        tree_oid: git2::Oid,
+        prefix: Vec<u8>,
+        skip: usize,
+    ) -> Result<impl Iterator<Item = DirEntryInfo> + '_, i32> {
         let entries = self
             .store
-            .tree_entries(repo, tree_oid)
+            .tree_entries(&repo, tree_oid)
             .map_err(|e| store_err_errno(&e))?;
-        let mut out = Vec::with_capacity(entries.len());
-        for entry in entries.iter() {
-            let child_path = join_path(prefix, &entry.name);
+        let visible = {
+            let (entries, repo, prefix) = (Arc::clone(&entries), repo.clone(), prefix.clone());
+            move |i: &usize| !self.hidden_paths.hides(&repo, &prefix, &entries[*i].name)
+        };
+        Ok((0..entries.len()).filter(visible).skip(skip).map(move |i| {
+            let entry = &entries[i];
             let key = PathKey {
                 repo: repo.clone(),
-                commit: commit.to_string(),
-                path: child_path,
+                commit: commit.clone(),
+                path: join_path(&prefix, &entry.name),
             };
             let ino =
                 self.inodes
                     .get_or_alloc_path(key, entry.oid.to_string(), entry.kind, parent_ino);
-            out.push(DirEntryInfo {
+            DirEntryInfo {
                 ino,
                 kind: entry_kind_to_fs(entry.kind),
                 name: OsString::from_vec(entry.name.clone()),
-            });
+            }
+        }))
+    }
+
+    /// `ino`'s children from the `skip`th on. Subdirectories of a commit are
+    /// streamed straight from the tree; other directories are small and
+    /// listed whole.
+    #[cfg(target_os = "linux")]
+    fn children_from(
+        &self,
+        ino: u64,
+        skip: usize,
+    ) -> Result<Box<dyn Iterator<Item = DirEntryInfo> + '_>, i32> {
+        if let Some(InodeData::Path {
+            repo,
+            commit,
+            path,
+            oid,
+            kind: EntryKind::Tree,
+            ..
+        }) = self.inodes.get(ino)
+        {
+            let tree_oid = parse_oid(&oid)?;
+            return Ok(Box::new(
+                self.tree_children_from(ino, repo, commit, tree_oid, path, skip)?,
+            ));
         }
-        out.sort_by(|a, b| {
-            a.name
-                .as_os_str()
-                .as_bytes()
-                .cmp(b.name.as_os_str().as_bytes())
-        });
-        Ok(out)
+        Ok(Box::new(self.list_children(ino)?.into_iter().skip(skip)))
     }
 
     fn parent_inode(&self, ino: u64) -> u64 {
@@ -699,7 +1338,11 @@ impl GhFs {
                 // tolerable. We store parent on Path nodes precisely.
                 ROOT_INO
             }
-            Some(InodeData::Path { parent, .. }) => parent,
+            Some(
+                InodeData::Path { parent, .. }
+                | InodeData::MetaDir { parent, .. }
+                | InodeData::MetaHead { parent, .. },
+            ) => parent,
             _ => ROOT_INO,
         }
     }
@@ -708,8 +1351,11 @@ impl GhFs {
         let data = self.inodes.get(ino).ok_or(libc::ENOENT)?;
         match data {
             InodeData::Path {
-                kind, oid, repo, ..
-            } if kind == EntryKind::Symlink => {
+                kind: EntryKind::Symlink,
+                oid,
+                repo,
+                ..
+            } => {
                 let blob_oid = parse_oid(&oid)?;
                 let (path, _size) = self
                     .store
@@ -721,8 +1367,61 @@ impl GhFs {
         }
     }
 
-    /// Hydrate (if needed) and open a file's cached blob for offset reads.
-    fn open_blob(&self, ino: u64) -> Result<File, i32> {
+    /// Contents of a small regular file from the in-memory cache, loading it
+    /// on first use. `None` for anything that isn't a blob under
+    /// [`MAX_CACHED_FILE_SIZE`].
+    fn cached_blob(&self, ino: u64) -> Result<Option<Arc<[u8]>>, i32> {
+        let (kind, oid, repo) = match self.inodes.get(ino) {
+            Some(InodeData::Path {
+                kind, oid, repo, ..
+            }) => {
+                if let Some(times) = &self.access_times {
+                    times.touch(&repo);
+                }
+                (kind, oid, repo)
+            }
+            Some(InodeData::MetaHead { commit, .. }) => {
+                return Ok(Some(Arc::from(meta_head_contents(&commit).into_bytes())));
+            }
+            _ => return Ok(None),
+        };
+        if kind != EntryKind::Blob && kind != EntryKind::Executable {
+            return Ok(None);
+        }
+        let blob_oid = parse_oid(&oid)?;
+        // Check the size first (usually known from the lookup's stat), so a
+        // large file isn't hydrated here only to be opened by `open_blob`.
+        let mut hydrated = None;
+        let size = self.blob_sizes.get_or_load::<i32>(blob_oid, || {
+            let (path, size) = self
+                .store
+                .hydrate_blob(&repo, blob_oid)
+                .map_err(|e| store_err_errno(&e))?;
+            hydrated = Some(path);
+            Ok(size)
+        })?;
+        if size > MAX_CACHED_FILE_SIZE {
+            return Ok(None);
+        }
+        self.content_cache.get_or_load(blob_oid, || {
+            let path = match hydrated {
+                Some(path) => path,
+                None => {
+                    self.store
+                        .hydrate_blob(&repo, blob_oid)
+                        .map_err(|e| store_err_errno(&e))?
+                        .0
+                }
+            };
+            std::fs::read(&path)
+                .map(Some)
+                .map_err(|e| io_errno(e, libc::EIO))
+        })
+    }
+
+    /// Hydrate (if needed) and open a file's cached blob for offset reads,
+    /// returning it with the blob's size.
+    fn open_blob(&self, ino: u64) -> Result<(File, u64), i32> {
         let data = self.inodes.get(ino).ok_or(libc::ENOENT)?;
         match data {
             InodeData::Path {
@@ -732,18 +1431,20 @@ impl GhFs {
                 || kind == EntryKind::Symlink =>
             {
                 let blob_oid = parse_oid(&oid)?;
-                let (path, _size) = self
+                let (path, size) = self
                     .store
                     .hydrate_blob(&repo, blob_oid)
                     .map_err(|e| store_err_errno(&e))?;
-                File::open(&path).map_err(|e| io_errno(e, libc::EIO))
+                let file = File::open(&path).map_err(|e| io_errno(e, libc::EIO))?;
+                Ok((file, size))
             }
             InodeData::Path {
                 kind: EntryKind::Gitlink,
                 ..
             } => {
                 // Submodule gitlink: serve as an empty file.
-                File::open("/dev/null").map_err(|e| io_errno(e, libc::EIO))
+                let file = File::open("/dev/null").map_err(|e| io_errno(e, libc::EIO))?;
+                Ok((file, 0))
             }
             _ => Err(libc::EISDIR),
         }
@@ -754,65 +1455,179 @@ impl GhFs {
         if InodeTable::is_virtual_ino(ino) {
             return Err(libc::EISDIR);
         }
-        let mut file = self.open_blob(ino)?;
-        file.seek(SeekFrom::Start(offset))
-            .map_err(|e| io_errno(e, libc::EIO))?;
-        let mut buf = vec![0u8; size as usize];
-        let n = file.read(&mut buf).map_err(|e| io_errno(e, libc::EIO))?;
-        buf.truncate(n);
-        Ok((buf, n < size as usize))
+        if let Some(data) = self.cached_blob(ino)? {
+            let (chunk, eof) = slice_range(&data, offset, size);
+            return Ok((chunk.to_vec(), eof));
+        }
+        let (mut file, file_size) = self.open_blob(ino)?;
+        read_at(&mut file, file_size, offset, size).map_err(|e| io_errno(e, libc::EIO))
+    }
+
+    /// Total and free inode counts for `statfs`: the live inodes plus
+    /// [`STATFS_INODE_HEADROOM`], all of the headroom free.
+    #[cfg(target_os = "linux")]
+    fn statfs_files(&self) -> (u64, u64) {
+        let (virtual_inodes, passthrough) = self.inodes.counts();
+        let live = (virtual_inodes + passthrough) as u64;
+        (live + STATFS_INODE_HEADROOM, STATFS_INODE_HEADROOM)
     }
 
     #[cfg(target_os = "linux")]
     fn ttl_for_inode(&self, ino: u64) -> Duration {
         match self.inodes.get(ino) {
             Some(InodeData::Repo { selector, .. }) => {
-                // Commit-pinned content is immutable.
-                if selector
-                    .as_ref()
-                    .is_some_and(|s| Self::is_commit_oid_selector(s))
-                    || selector.is_none()
-                {
-                    // default HEAD alias is mutable → short; named ref short;
-                    // commit-OID selector long.
-                    if selector
-                        .as_ref()
-                        .is_some_and(|s| Self::is_commit_oid_selector(s))
-                    {
-                        COMMIT_TTL
-                    } else {
-                        REF_TTL
-                    }
+                // The default HEAD alias and named refs move; commit OIDs
+                // and tags don't.
+                if selector.as_deref().is_some_and(Self::is_immutable_selector) {
+                    self.ttls.commit_ttl
                 } else {
-                    REF_TTL
+                    self.ttls.ref_ttl
                 }
             }
-            Some(InodeData::Path { .. }) => COMMIT_TTL,
-            _ => VIRTUAL_TTL,
+            Some(InodeData::Path { .. }) => self.ttls.commit_ttl,
+            _ => self.ttls.virtual_ttl,
         }
     }
 
     /// TTL for a lookup reply, based on what was resolved.
     #[cfg(target_os = "linux")]
     fn lookup_ttl(&self, parent: u64, name: &str) -> Duration {
-        // Default-branch repo node (under Owner): short.
-        // Ref selector under RefRepo: short unless it's a commit OID.
+        // Default-branch repo node (under Owner, or the root when flat) or
+        // an entry of the single-repo root: short. Ref selector under RefRepo: short unless it's a commit OID
+        // or tag.
         // Everything else discovery: virtual.
         let parent_data = self.inodes.get(parent);
-        match parent_data.as_ref().map(|d| d) {
-            Some(InodeData::Owner(_)) => REF_TTL,
+        match parent_data.as_ref() {
+            Some(InodeData::Owner(_)) => self.ttls.ref_ttl,
+            Some(InodeData::Root) if matches!(self.layout, Layout::Single(_)) => self.ttls.ref_ttl,
+            Some(InodeData::Root) if name != BY_REF_ROOT && self.layout != Layout::Nested => {
+                self.ttls.ref_ttl
+            }
             Some(InodeData::RefRepo(_)) => {
-                if Self::is_commit_oid_selector(name) {
-                    COMMIT_TTL
+                if decode_ref(name.as_bytes()).is_some_and(|raw| Self::is_immutable_selector(&raw))
+                {
+                    self.ttls.commit_ttl
                 } else {
-                    REF_TTL
+                    self.ttls.ref_ttl
                 }
             }
-            _ => VIRTUAL_TTL,
+            _ => self.ttls.virtual_ttl,
+        }
+    }
+
+    /// How long the kernel may cache a failed lookup under `parent`.
+    ///
+    /// Directories pinned to a commit (paths below a commit root, `.ghfs`,
+    /// and commit-OID or tag selector roots) never gain entries, so a missing
+    /// name stays missing. Anything at or above a mutable repo boundary (the
+    /// default-branch alias, named refs, discovery directories) can gain
+    /// entries on the next sync or clone, so misses there aren't cached.
+    #[cfg(target_os = "linux")]
+    fn negative_lookup_ttl(&self, parent: u64) -> Duration {
+        match self.inodes.get(parent) {
+            Some(InodeData::Path { .. } | InodeData::MetaDir { .. }) => self.ttls.commit_ttl,
+            Some(InodeData::Repo {
+                selector: Some(selector),
+                ..
+            }) if Self::is_immutable_selector(&selector) => self.ttls.commit_ttl,
+            _ => Duration::ZERO,
         }
     }
 }
 
+/// Attributes for a negative lookup reply: the kernel caches an entry with
+/// inode 0 as "does not exist" for the reply's TTL.
+#[cfg(target_os = "linux")]
+fn negative_entry_attr() -> FileAttr {
+    FileAttr {
+        ino: 0,
+        size: 0,
+        blocks: 0,
+        atime: UNIX_EPOCH,
+        mtime: UNIX_EPOCH,
+        ctime: UNIX_EPOCH,
+        crtime: UNIX_EPOCH,
+        kind: FileType::RegularFile,
+        perm: 0,
+        nlink: 0,
+        uid: 0,
+        gid: 0,
+        rdev: 0,
+        blksize: 0,
+        flags: 0,
+    }
+}
+
+/// Contents of `.ghfs/head` for a commit root serving `commit`.
+fn meta_head_contents(commit: &str) -> String {
+    format!("{commit}\n")
+}
+
+/// The `size`-byte window of `data` starting at `offset`, and whether it
+/// reaches the end of `data`.
+fn slice_range(data: &[u8], offset: u64, size: u32) -> (&[u8], bool) {
+    let start = (offset.min(data.len() as u64)) as usize;
+    let end = start.saturating_add(size as usize).min(data.len());
+    (&data[start..end], end == data.len())
+}
+
+/// Read up to `size` bytes at `offset` from a file of `file_size` bytes, and
+/// whether the read reaches the end. Reads at or past the end return empty
+/// without touching `file`, so clients that over-read cost no syscalls.
+///
+/// Only the bytes that exist are allocated, so a large read near the end
+/// returns a short buffer. See [`read_at_into`] for the details.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+fn read_at<R: Read + Seek>(
+    file: &mut R,
+    file_size: u64,
+    offset: u64,
+    size: u32,
+) -> std::io::Result<(Vec<u8>, bool)> {
+    let mut buf = Vec::new();
+    let eof = read_at_into(file, file_size, offset, size, &mut buf)?;
+    Ok((buf, eof))
+}
+
+/// [`read_at`] into `buf`, replacing its contents but keeping its
+/// allocation, so a caller reusing one buffer allocates only when a read
+/// outgrows every earlier one.
+///
+/// The underlying file is read until the request is filled or it hits EOF:
+/// FUSE treats a short reply as end of file, so a partial `read` must not
+/// end the request early. Holes in sparse files read as zeros like any
+/// other bytes.
+fn read_at_into<R: Read + Seek>(
+    file: &mut R,
+    file_size: u64,
+    offset: u64,
+    size: u32,
+    buf: &mut Vec<u8>,
+) -> std::io::Result<bool> {
+    buf.clear();
+    if offset >= file_size {
+        return Ok(true);
+    }
+    let want = u64::from(size).min(file_size - offset);
+    file.seek(SeekFrom::Start(offset))?;
+    buf.reserve_exact(want as usize);
+    file.by_ref().take(want).read_to_end(buf)?;
+    Ok(offset + buf.len() as u64 >= file_size)
+}
+
+#[cfg(target_os = "linux")]
+thread_local! {
+    /// Buffer FUSE reads on this thread are served from. It grows to the
+    /// largest read seen (bounded by `max_read`) and is reused after that.
+    static READ_BUFFER: std::cell::RefCell<Vec<u8>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Run `f` with this thread's read buffer.
+#[cfg(target_os = "linux")]
+fn with_read_buffer<T>(f: impl FnOnce(&mut Vec<u8>) -> T) -> T {
+    READ_BUFFER.with(|buf| f(&mut buf.borrow_mut()))
+}
+
 fn join_path(prefix: &[u8], name: &[u8]) -> Vec<u8> {
     if prefix.is_empty() {
         return name.to_vec();
@@ -830,12 +1645,123 @@ fn parse_oid(s: &str) -> Result<git2::Oid, i32> {
 
 #[cfg(target_os = "linux")]
 impl GhFs {
-    /// Mount the filesystem at the given path.
-    pub fn mount(self, mountpoint: &Path, _shutdown: Arc<AtomicBool>) -> std::io::Result<()> {
-        let options = vec![MountOption::FSName("ghfs".to_string()), MountOption::RO];
-        fuser::mount2(self, mountpoint, &options)?;
-        Ok(())
+    /// Mount the filesystem at the given path, blocking until it is
+    /// unmounted. Setting `shutdown` unmounts it.
+    pub fn mount(self, mountpoint: &Path, shutdown: Arc<AtomicBool>) -> std::io::Result<()> {
+        let (store, inodes) = (self.inode_store.clone(), Arc::clone(&self.inodes));
+        let mut session = MountSession::new(self, mountpoint)?;
+        session.unmount_on(shutdown);
+        let result = session.run();
+        Self::save_inodes(store, &inodes);
+        result
+    }
+
+    /// Refuse `req_uid` with `EACCES` unless the mode reported for `ino`
+    /// grants it `mask`. Without `allow_other` only the mount owner (and
+    /// root) reach the mount, so there's nothing to check.
+    fn check_access(&self, req_uid: u32, ino: u64, mask: i32) -> Result<(), i32> {
+        if !self.access_policy.allow_other {
+            return Ok(());
+        }
+        let attr = self.stat_inode(ino)?;
+        if access_allowed(self.uid, req_uid, attr.perm, mask) {
+            Ok(())
+        } else {
+            Err(libc::EACCES)
+        }
     }
+
+    /// Open `ino` with `flags` for `req_uid`.
+    fn open_file(&self, req_uid: u32, ino: u64, flags: i32) -> Result<OpenFile, i32> {
+        let kind = if InodeTable::is_virtual_ino(ino) {
+            EntryKind::Tree
+        } else {
+            self.inodes.get(ino).ok_or(libc::ENOENT)?.kind()
+        };
+        if let Some(errno) = open_flags_errno(kind, flags) {
+            return Err(errno);
+        }
+        self.check_access(req_uid, ino, libc::R_OK)?;
+        match self.cached_blob(ino)? {
+            Some(data) => Ok(OpenFile::Cached(data)),
+            None => self
+                .open_blob(ino)
+                .map(|(file, size)| OpenFile::File { file, size }),
+        }
+    }
+}
+
+/// A live FUSE mount whose session stays in our hands, so the kernel's
+/// caches can be invalidated through [`MountSession::notifier`] and the
+/// mount torn down without shelling out to `fusermount`.
+#[cfg(target_os = "linux")]
+pub struct MountSession {
+    session: fuser::Session<GhFs>,
+    done: Arc<AtomicBool>,
+}
+
+#[cfg(target_os = "linux")]
+impl MountSession {
+    /// Mount `fs` at `mountpoint`. Requests are served once
+    /// [`MountSession::run`] is called.
+    pub fn new(fs: GhFs, mountpoint: &Path) -> std::io::Result<Self> {
+        let mut options = vec![MountOption::FSName("ghfs".to_string()), MountOption::RO];
+        if fs.access_policy.allow_other {
+            // Have the kernel enforce the modes the sharing policy reports,
+            // on top of the checks in lookup, readdir and open.
+            options.push(MountOption::AllowOther);
+            options.push(MountOption::DefaultPermissions);
+        }
+        if let Some(max_read) = fs.io_sizes.max_read {
+            options.push(MountOption::CUSTOM(format!("max_read={max_read}")));
+        }
+        Ok(Self {
+            session: fuser::Session::new(fs, mountpoint, &options)?,
+            done: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Handle for pushing entry/inode invalidations to the kernel.
+    pub fn notifier(&self) -> fuser::Notifier {
+        self.session.notifier()
+    }
+
+    /// Unmount cleanly once `shutdown` is set.
+    pub fn unmount_on(&mut self, shutdown: Arc<AtomicBool>) {
+        let mut unmounter = self.session.unmount_callable();
+        spawn_shutdown_watcher(shutdown, Arc::clone(&self.done), move || {
+            if let Err(e) = unmounter.unmount() {
+                log::warn!("Failed to unmount FUSE session: {}", e);
+            }
+        });
+    }
+
+    /// Serve requests until the filesystem is unmounted.
+    pub fn run(mut self) -> std::io::Result<()> {
+        let result = self.session.run();
+        self.done.store(true, Ordering::SeqCst);
+        result
+    }
+}
+
+/// Poll `shutdown` on a helper thread and call `on_shutdown` once it is set.
+/// The thread exits without calling it if `done` is set first (the session
+/// ended on its own, e.g. an external `umount`).
+#[cfg(target_os = "linux")]
+fn spawn_shutdown_watcher(
+    shutdown: Arc<AtomicBool>,
+    done: Arc<AtomicBool>,
+    on_shutdown: impl FnOnce() + Send + 'static,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        while !done.load(Ordering::SeqCst) {
+            if shutdown.load(Ordering::SeqCst) {
+                on_shutdown();
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    })
 }
 
 #[cfg(target_os = "macos")]
@@ -849,53 +1775,80 @@ impl GhFs {
             .build()
             .map_err(|e| std::io::Error::other(format!("failed to start tokio runtime: {e}")))?;
 
-        runtime.block_on(async move {
+        let (store, inodes) = (self.inode_store.clone(), Arc::clone(&self.inodes));
+        let result = runtime.block_on(async move {
             let handle = nfs::mount_nfs(self, opts).await?;
             while !shutdown.load(Ordering::SeqCst) && nfs::is_mount_active(handle.mountpoint()) {
                 tokio::time::sleep(Duration::from_millis(100)).await;
             }
             drop(handle);
             Ok(())
-        })
+        });
+        Self::save_inodes(store, &inodes);
+        result
     }
 }
 
 #[cfg(target_os = "linux")]
 impl Filesystem for GhFs {
     fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        self.op_counters.record(Op::Getattr);
         match self.stat_inode(ino) {
             Ok(attr) => reply.attr(&self.ttl_for_inode(ino), &attr.to_fuse_attr()),
             Err(err) => reply.error(err),
         }
     }
 
-    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
-        match self.lookup_inode(parent, name) {
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        self.op_counters.record(Op::Lookup);
+        if let Err(err) = self.check_access(req.uid(), parent, libc::X_OK) {
+            reply.error(err);
+            return;
+        }
+        self.caller.store(req.pid(), Ordering::Relaxed);
+        let found = self.lookup_inode(parent, name);
+        self.caller.store(0, Ordering::Relaxed);
+        match found {
             Ok(ino) => match self.stat_inode(ino) {
                 Ok(attr) => {
                     let ttl = self.lookup_ttl(parent, name.to_str().unwrap_or(""));
-                    reply.entry(&ttl, &attr.to_fuse_attr(), 0)
+                    self.inodes.record_lookup(ino);
+                    reply.entry(&ttl, &attr.to_fuse_attr(), self.inodes.generation(ino))
                 }
                 Err(err) => reply.error(err),
             },
+            Err(libc::ENOENT) => match self.negative_lookup_ttl(parent) {
+                Duration::ZERO => reply.error(libc::ENOENT),
+                ttl => reply.entry(&ttl, &negative_entry_attr(), 0),
+            },
             Err(err) => reply.error(err),
         }
     }
 
+    fn forget(&mut self, _req: &Request<'_>, ino: u64, nlookup: u64) {
+        self.inodes.forget_lookups(ino, nlookup);
+    }
+
     fn readdir(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         _fh: u64,
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
+        self.op_counters.record(Op::Readdir);
         if offset < 0 {
             reply.error(libc::EINVAL);
             return;
         }
+        if let Err(err) = self.check_access(req.uid(), ino, libc::R_OK) {
+            reply.error(err);
+            return;
+        }
+        let offset = offset as usize;
         let parent = self.parent_inode(ino);
-        let mut entries = vec![
+        let dots = [
             DirEntryInfo {
                 ino,
                 kind: FsKind::Directory,
@@ -907,14 +1860,18 @@ impl Filesystem for GhFs {
                 name: OsString::from(".."),
             },
         ];
-        match self.list_children(ino) {
-            Ok(mut children) => entries.append(&mut children),
+        self.caller.store(req.pid(), Ordering::Relaxed);
+        let children = self.children_from(ino, offset.saturating_sub(dots.len()));
+        self.caller.store(0, Ordering::Relaxed);
+        let children = match children {
+            Ok(children) => children,
             Err(err) => {
                 reply.error(err);
                 return;
             }
-        }
-        for (i, entry) in entries.into_iter().enumerate().skip(offset as usize) {
+        };
+        let entries = dots.into_iter().skip(offset).chain(children);
+        for (i, entry) in (offset..).zip(entries) {
             if reply.add(
                 entry.ino,
                 (i + 1) as i64,
@@ -927,21 +1884,17 @@ impl Filesystem for GhFs {
         reply.ok();
     }
 
-    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
-        if flags & libc::O_ACCMODE != libc::O_RDONLY {
-            reply.error(libc::EROFS);
-            return;
-        }
-        if InodeTable::is_virtual_ino(ino) {
-            reply.error(libc::EISDIR);
-            return;
-        }
-        match self.open_blob(ino) {
+    fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        match self.open_file(req.uid(), ino, flags) {
             Ok(file) => {
                 let fh = self.next_fh.fetch_add(1, Ordering::Relaxed);
+                let repo = self
+                    .inodes
+                    .get(ino)
+                    .and_then(|data| data.repo().map(RepoKey::canonical));
                 match self.open_files.lock() {
                     Ok(mut files) => {
-                        files.insert(fh, file);
+                        files.insert(fh, OpenHandle { repo, file });
                         reply.opened(fh, 0);
                     }
                     Err(_) => reply.error(libc::EIO),
@@ -962,6 +1915,7 @@ impl Filesystem for GhFs {
         _lock_owner: Option<u64>,
         reply: ReplyData,
     ) {
+        self.op_counters.record(Op::Read);
         if offset < 0 {
             reply.error(libc::EINVAL);
             return;
@@ -973,21 +1927,17 @@ impl Filesystem for GhFs {
                 return;
             }
         };
-        let file = match files.get_mut(&fh) {
-            Some(file) => file,
-            None => {
-                reply.error(libc::EBADF);
-                return;
+        match files.get_mut(&fh).map(|handle| &mut handle.file) {
+            Some(OpenFile::File { file, size: len }) => {
+                with_read_buffer(
+                    |buf| match read_at_into(file, *len, offset as u64, size, buf) {
+                        Ok(_) => reply.data(buf),
+                        Err(err) => reply.error(io_errno(err, libc::EIO)),
+                    },
+                );
             }
-        };
-        if let Err(err) = file.seek(SeekFrom::Start(offset as u64)) {
-            reply.error(io_errno(err, libc::EIO));
-            return;
-        }
-        let mut buf = vec![0u8; size as usize];
-        match file.read(&mut buf) {
-            Ok(n) => reply.data(&buf[..n]),
-            Err(err) => reply.error(io_errno(err, libc::EIO)),
+            Some(OpenFile::Cached(data)) => reply.data(slice_range(data, offset as u64, size).0),
+            None => reply.error(libc::EBADF),
         }
     }
 
@@ -1014,8 +1964,18 @@ impl Filesystem for GhFs {
         }
     }
 
+    fn access(&mut self, req: &Request<'_>, ino: u64, mask: i32, reply: ReplyEmpty) {
+        match self.stat_inode(ino) {
+            Ok(attr) if access_allowed(self.uid, req.uid(), attr.perm, mask) => reply.ok(),
+            Ok(_) => reply.error(libc::EACCES),
+            Err(err) => reply.error(err),
+        }
+    }
+
     fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: fuser::ReplyStatfs) {
-        reply.statfs(0, 0, 0, 0, 0, 4096, 255, 4096);
+        let (files, ffree) = self.statfs_files();
+        let block_size = self.io_sizes.block_size;
+        reply.statfs(0, 0, 0, files, ffree, block_size, 255, block_size);
     }
 
     fn getxattr(
@@ -1026,44 +1986,22 @@ impl Filesystem for GhFs {
         size: u32,
         reply: ReplyXattr,
     ) {
-        if self.inodes.get(ino).is_none() {
+        let Some(data) = self.inodes.get(ino) else {
             reply.error(libc::ENOENT);
             return;
-        }
-        let Some(name_str) = name.to_str() else {
-            reply.error(libc::ENODATA);
-            return;
         };
-        if name_str != FINDER_INFO_XATTR {
-            reply.error(libc::ENODATA);
-            return;
-        }
-        let data = [0u8; FINDER_INFO_SIZE];
-        if size == 0 {
-            reply.size(data.len() as u32);
-            return;
+        match name.to_str().and_then(|name| xattr_value(&data, name)) {
+            Some(value) => reply_xattr(reply, size, &value),
+            None => reply.error(libc::ENODATA),
         }
-        if size < data.len() as u32 {
-            reply.error(libc::ERANGE);
-            return;
-        }
-        reply.data(&data);
     }
 
     fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: fuser::ReplyXattr) {
-        if self.inodes.get(ino).is_none() {
+        let Some(data) = self.inodes.get(ino) else {
             reply.error(libc::ENOENT);
             return;
-        }
-        if size == 0 {
-            reply.size(FINDER_INFO_XATTR_LIST.len() as u32);
-            return;
-        }
-        if size < FINDER_INFO_XATTR_LIST.len() as u32 {
-            reply.error(libc::ERANGE);
-            return;
-        }
-        reply.data(FINDER_INFO_XATTR_LIST);
+        };
+        reply_xattr(reply, size, &xattr_names(&data));
     }
 
     fn setxattr(
@@ -1206,3 +2144,1009 @@ impl Filesystem for GhFs {
         reply.error(libc::EROFS);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_fixtures::{commit, commit_tree, init_bare};
+    use tempfile::tempdir;
+
+    /// Build a `GhFs` over a local mirror for `octocat/hello-world` whose
+    /// root tree holds a single file named `name` containing `contents`.
+    fn fs_with_file(name: &[u8], contents: &[u8]) -> (GhFs, tempfile::TempDir) {
+        fs_with_files(&[(name, contents, 0o100644)])
+    }
+
+    /// `fs_with_file` with several `(name, contents, mode)` root entries.
+    fn fs_with_files(files: &[(&[u8], &[u8], i32)]) -> (GhFs, tempfile::TempDir) {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+
+        let repo = init_bare(&paths.mirror_dir(&key));
+        let mut builder = repo.treebuilder(None).unwrap();
+        for (name, contents, mode) in files {
+            let blob = repo.blob(contents).unwrap();
+            builder
+                .insert(OsStr::from_bytes(name), blob, *mode)
+                .unwrap();
+        }
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        commit_tree(&repo, "refs/heads/main", "init", &tree);
+
+        let store = Store::new(paths);
+        let worker = Arc::new(WorkerHandle::spawn(
+            store.clone(),
+            None,
+            crate::daemon::Quotas::default(),
+        ));
+        (GhFs::new(store, worker), dir)
+    }
+
+    /// A reader over `data` that counts seeks and reads.
+    struct CountingReader {
+        inner: std::io::Cursor<Vec<u8>>,
+        calls: usize,
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.calls += 1;
+            self.inner.read(buf)
+        }
+    }
+
+    impl Seek for CountingReader {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.calls += 1;
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn read_at_or_past_eof_skips_the_file() {
+        let mut file = CountingReader {
+            inner: std::io::Cursor::new(b"hello".to_vec()),
+            calls: 0,
+        };
+
+        assert_eq!(
+            read_at(&mut file, 5, 3, 10).unwrap(),
+            (b"lo".to_vec(), true)
+        );
+        assert_eq!(
+            read_at(&mut file, 5, 0, 2).unwrap(),
+            (b"he".to_vec(), false)
+        );
+        let calls = file.calls;
+
+        assert_eq!(read_at(&mut file, 5, 5, 10).unwrap(), (Vec::new(), true));
+        assert_eq!(read_at(&mut file, 5, 100, 10).unwrap(), (Vec::new(), true));
+        assert_eq!(file.calls, calls);
+    }
+
+    #[test]
+    fn read_at_reads_across_holes_and_stops_at_eof() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sparse");
+        let len = 1 << 20;
+        let mut file = File::options()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(&mut file, b"head").unwrap();
+        file.seek(SeekFrom::Start(len - 4)).unwrap();
+        std::io::Write::write_all(&mut file, b"tail").unwrap();
+        assert_eq!(file.metadata().unwrap().len(), len);
+
+        let (data, eof) = read_at(&mut file, len, 2, 8).unwrap();
+        assert_eq!(data, b"ad\0\0\0\0\0\0");
+        assert!(!eof);
+
+        let (data, eof) = read_at(&mut file, len, len - 6, 128 * 1024).unwrap();
+        assert_eq!(data, b"\0\0tail");
+        assert!(data.capacity() < 128 * 1024);
+        assert!(eof);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn reads_reuse_the_thread_buffer() {
+        let data = vec![7u8; 64 * 1024];
+        let mut file = std::io::Cursor::new(data);
+        let read = |file: &mut std::io::Cursor<Vec<u8>>, offset: u64, size: u32| {
+            with_read_buffer(|buf| {
+                read_at_into(file, 64 * 1024, offset, size, buf).unwrap();
+                (buf.len(), buf.as_ptr(), buf.capacity())
+            })
+        };
+
+        // The first full-size read allocates; smaller and equal reads after
+        // it keep the same allocation.
+        let (len, ptr, capacity) = read(&mut file, 0, 16 * 1024);
+        assert_eq!(len, 16 * 1024);
+        for i in 0..1000u64 {
+            let (len, again, grown) = read(&mut file, i * 37 % (60 * 1024), 4096);
+            assert_eq!(len, 4096);
+            assert_eq!((again, grown), (ptr, capacity), "reallocated on read {i}");
+        }
+        // A read past the end empties the buffer without freeing it.
+        assert_eq!(read(&mut file, 1 << 20, 4096), (0, ptr, capacity));
+    }
+
+    /// An endless source of zeros that returns one byte per `read`.
+    struct Trickle;
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match buf.first_mut() {
+                Some(byte) => {
+                    *byte = 0;
+                    Ok(1)
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl Seek for Trickle {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            match pos {
+                SeekFrom::Start(offset) => Ok(offset),
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn read_at_fills_short_reads_past_u32_remaining() {
+        // More than u32::MAX bytes remain, which must not truncate the
+        // request size, and each `read` returns a single byte.
+        let file_size = u64::from(u32::MAX) + 2;
+        let (data, eof) = read_at(&mut Trickle, file_size, 0, 4096).unwrap();
+        assert_eq!(data.len(), 4096);
+        assert!(!eof);
+
+        let (data, eof) = read_at(&mut Trickle, file_size, file_size - 3, 4096).unwrap();
+        assert_eq!(data.len(), 3);
+        assert!(eof);
+    }
+
+    #[test]
+    fn lookup_and_read_non_utf8_filename() {
+        let name = b"caf\xe9.txt";
+        let (fs, _dir) = fs_with_file(name, b"latin-1\n");
+
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+
+        let listed = fs.list_children(repo).unwrap();
+        assert!(listed.iter().any(|e| e.name.as_bytes() == name));
+
+        let ino = fs.lookup_inode(repo, OsStr::from_bytes(name)).unwrap();
+        let data = fs.cached_blob(ino).unwrap().unwrap();
+        assert_eq!(&*data, b"latin-1\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn unshared_repos_refuse_other_users_on_open() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let fs = fs.with_access_policy(AccessPolicy {
+            allow_other: true,
+            shared_repos: vec!["octocat/other".parse().unwrap()],
+        });
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let ino = fs.lookup_inode(repo, OsStr::new("README")).unwrap();
+        let other = fs.uid + 1;
+
+        assert_eq!(
+            fs.open_file(other, ino, libc::O_RDONLY).err(),
+            Some(libc::EACCES)
+        );
+        assert_eq!(fs.check_access(other, repo, libc::X_OK), Err(libc::EACCES));
+        assert_eq!(fs.check_access(other, owner, libc::X_OK), Ok(()));
+        assert!(fs.open_file(fs.uid, ino, libc::O_RDONLY).is_ok());
+
+        // Sharing every repo lets other users in.
+        let fs = fs.with_access_policy(AccessPolicy {
+            allow_other: true,
+            shared_repos: Vec::new(),
+        });
+        assert!(fs.open_file(other, ino, libc::O_RDONLY).is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn large_files_open_without_going_through_the_content_cache() {
+        let big = vec![b'x'; MAX_CACHED_FILE_SIZE as usize + 1];
+        let (fs, _dir) = fs_with_file(b"big", &big);
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let ino = fs.lookup_inode(repo, OsStr::new("big")).unwrap();
+        assert_eq!(fs.stat_inode(ino).unwrap().size, big.len() as u64);
+
+        assert!(fs.cached_blob(ino).unwrap().is_none());
+        assert!(matches!(
+            fs.open_file(fs.uid, ino, libc::O_RDONLY),
+            Ok(OpenFile::File { size, .. }) if size == big.len() as u64
+        ));
+        assert_eq!(fs.content_cache.entry_count(), 0);
+    }
+
+    #[test]
+    fn stats_probe_tracks_inodes_and_open_files() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let probe = fs.stats_probe();
+        assert_eq!(
+            probe.snapshot(),
+            FsStats {
+                virtual_inodes: 2,
+                ..Default::default()
+            }
+        );
+
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let ino = fs.lookup_inode(repo, OsStr::new("README")).unwrap();
+        let data = fs.cached_blob(ino).unwrap().unwrap();
+        assert_eq!(&*data, b"hi\n");
+        #[cfg(target_os = "linux")]
+        fs.open_files.lock().unwrap().insert(
+            1,
+            OpenHandle {
+                repo: Some("octocat/hello-world".parse().unwrap()),
+                file: OpenFile::Cached(Arc::clone(&data)),
+            },
+        );
+
+        let stats = probe.snapshot();
+        assert_eq!(stats.virtual_inodes, 4);
+        assert_eq!(stats.passthrough_inodes, 1);
+        assert_eq!(stats.cached_blobs, 1);
+        assert!(stats.cached_trees >= 1);
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(stats.open_files, 1);
+            assert_eq!(probe.open_in(&"OctoCat/Hello-World".parse().unwrap()), 1);
+            assert_eq!(probe.open_in(&"octocat/other".parse().unwrap()), 0);
+        }
+    }
+
+    #[test]
+    fn empty_repo_mounts_as_empty_directory() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/brand-new".parse().unwrap();
+        git2::Repository::init_bare(paths.mirror_dir(&key)).unwrap();
+        let store = Store::new(paths);
+        let worker = Arc::new(WorkerHandle::spawn(
+            store.clone(),
+            None,
+            crate::daemon::Quotas::default(),
+        ));
+        let fs = GhFs::new(store, worker);
+
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("brand-new")).unwrap();
+        let names: Vec<OsString> = fs
+            .list_children(repo)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, [OsString::from(META_DIR)]);
+        assert_eq!(
+            fs.lookup_inode(repo, OsStr::new("README")),
+            Err(libc::ENOENT)
+        );
+    }
+
+    #[test]
+    fn opening_a_file_records_its_repo_access_once_per_interval() {
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let state = Arc::new(crate::daemon::State::open_in_memory().unwrap());
+        state.init().unwrap();
+        let accessed = |state: &crate::daemon::State| {
+            state
+                .list_repos()
+                .unwrap()
+                .iter()
+                .any(|row| row.repo == "hello-world" && row.last_access_at.is_some())
+        };
+        let flush = |fs: &GhFs| fs.access_times.as_ref().unwrap().flush();
+        let readme = |fs: &GhFs| {
+            let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+            let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+            fs.lookup_inode(repo, OsStr::new("README")).unwrap()
+        };
+
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let fs = fs.with_access_times(AccessTimes::new(Arc::clone(&state)));
+        let ino = readme(&fs);
+        assert!(!accessed(&state), "lookups alone aren't reads");
+        fs.cached_blob(ino).unwrap();
+        flush(&fs);
+        assert!(accessed(&state));
+
+        // Within the interval, further reads leave the database alone.
+        state.delete_repo(&key).unwrap();
+        fs.cached_blob(ino).unwrap();
+        flush(&fs);
+        assert!(!accessed(&state));
+
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let fs = fs.with_access_times(AccessTimes::with_interval(
+            Arc::clone(&state),
+            Duration::ZERO,
+        ));
+        let ino = readme(&fs);
+        fs.cached_blob(ino).unwrap();
+        flush(&fs);
+        state.delete_repo(&key).unwrap();
+        fs.cached_blob(ino).unwrap();
+        flush(&fs);
+        assert!(accessed(&state));
+    }
+
+    #[test]
+    fn removing_cached_repo_frees_its_virtual_inodes() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        assert_eq!(fs.reclaim_virtual_inodes(), 0);
+
+        fs.store.remove(&key).unwrap();
+        assert_eq!(fs.reclaim_virtual_inodes(), 2);
+        assert!(fs.inodes.get(repo).is_none());
+
+        let reused = fs.lookup_inode(ROOT_INO, OsStr::new("someone")).unwrap();
+        assert!(reused == owner || reused == repo);
+        assert_ne!(fs.inodes.generation(reused), 0);
+    }
+
+    #[test]
+    fn meta_head_holds_served_commit() {
+        let (fs, dir) = fs_with_file(b"README", b"hi\n");
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mirror =
+            git2::Repository::open_bare(CachePaths::new(dir.path()).mirror_dir(&key)).unwrap();
+        let head = mirror.refname_to_id("refs/heads/main").unwrap();
+
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let listed = fs.list_children(repo).unwrap();
+        assert!(listed.iter().any(|e| e.name == META_DIR));
+
+        let meta = fs.lookup_inode(repo, OsStr::new(META_DIR)).unwrap();
+        let file = fs.lookup_inode(meta, OsStr::new(META_HEAD)).unwrap();
+        let data = fs.cached_blob(file).unwrap().unwrap();
+        assert_eq!(&*data, format!("{head}\n").as_bytes());
+        assert_eq!(fs.stat_inode(file).unwrap().size, data.len() as u64);
+        assert_eq!(fs.parent_inode(file), meta);
+        assert_eq!(fs.parent_inode(meta), repo);
+    }
+
+    #[test]
+    fn unlisted_meta_dir_still_resolves() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let fs = fs.with_meta_dir_listed(false);
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+
+        let names: Vec<_> = fs
+            .list_children(repo)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, [OsString::from("README")]);
+
+        let meta = fs.lookup_inode(repo, OsStr::new(META_DIR)).unwrap();
+        assert!(fs.lookup_inode(meta, OsStr::new(META_HEAD)).is_ok());
+    }
+
+    #[test]
+    fn names_differing_only_in_case_are_distinct_entries() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let repo = init_bare(&paths.mirror_dir(&key));
+        commit(
+            &repo,
+            "init",
+            &[("README", "upper\n"), ("Readme", "mixed\n")],
+        );
+        let store = Store::new(paths);
+        let worker = Arc::new(WorkerHandle::spawn(
+            store.clone(),
+            None,
+            crate::daemon::Quotas::default(),
+        ));
+        let fs = GhFs::new(store, worker);
+
+        // Trees are served by exact name and blobs cached by OID, so
+        // nothing is ever written under a case-folded name.
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let root = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let upper = fs.lookup_inode(root, OsStr::new("README")).unwrap();
+        let mixed = fs.lookup_inode(root, OsStr::new("Readme")).unwrap();
+        assert_ne!(upper, mixed);
+        assert_eq!(&*fs.cached_blob(upper).unwrap().unwrap(), b"upper\n");
+        assert_eq!(&*fs.cached_blob(mixed).unwrap().unwrap(), b"mixed\n");
+        assert_eq!(
+            fs.lookup_inode(root, OsStr::new("readme")),
+            Err(libc::ENOENT)
+        );
+    }
+
+    #[test]
+    fn flat_layout_serves_repos_at_root() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let fs = fs.with_layout(Layout::flat("+").unwrap());
+
+        let names: Vec<OsString> = fs
+            .list_children(ROOT_INO)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                OsString::from(BY_REF_ROOT),
+                OsString::from("octocat+hello-world")
+            ]
+        );
+
+        let repo = fs
+            .lookup_inode(ROOT_INO, OsStr::new("octocat+hello-world"))
+            .unwrap();
+        assert!(matches!(
+            fs.inodes.get(repo),
+            Some(InodeData::Repo { selector: None, .. })
+        ));
+        assert!(fs.lookup_inode(repo, OsStr::new("README")).is_ok());
+        assert_eq!(
+            fs.lookup_inode(ROOT_INO, OsStr::new("octocat")),
+            Err(libc::ENOENT)
+        );
+        #[cfg(target_os = "linux")]
+        assert_eq!(
+            fs.lookup_ttl(ROOT_INO, "octocat+hello-world"),
+            fs.ttls.ref_ttl
+        );
+        // by-ref stays nested.
+        let ref_owner = fs.lookup_inode(BY_REF_INO, OsStr::new("octocat")).unwrap();
+        assert!(
+            fs.lookup_inode(ref_owner, OsStr::new("hello-world"))
+                .is_ok()
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn xattrs_report_git_mode_of_files() {
+        let (fs, _dir) = fs_with_files(&[
+            (b"build.sh", b"#!/bin/sh\n", 0o100755),
+            (b"README", b"hi\n", 0o100644),
+        ]);
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let data = |name: &str| {
+            let ino = fs.lookup_inode(repo, OsStr::new(name)).unwrap();
+            fs.inodes.get(ino).unwrap()
+        };
+
+        let script = data("build.sh");
+        assert_eq!(xattr_value(&script, GIT_MODE_XATTR).unwrap(), b"100755");
+        assert_eq!(xattr_value(&script, EXECUTABLE_XATTR).unwrap(), b"1");
+        let readme = data("README");
+        assert_eq!(xattr_value(&readme, GIT_MODE_XATTR).unwrap(), b"100644");
+        assert_eq!(xattr_value(&readme, EXECUTABLE_XATTR).unwrap(), b"0");
+        assert_eq!(
+            xattr_names(&readme),
+            b"com.apple.FinderInfo\0user.ghfs.mode\0user.ghfs.executable\0"
+        );
+        assert_eq!(xattr_value(&readme, "user.ghfs.other"), None);
+
+        // Directories only keep FinderInfo.
+        let root = fs.inodes.get(repo).unwrap();
+        assert_eq!(xattr_value(&root, GIT_MODE_XATTR), None);
+        assert_eq!(
+            xattr_value(&root, FINDER_INFO_XATTR).unwrap(),
+            [0; FINDER_INFO_SIZE]
+        );
+        assert_eq!(xattr_names(&root), FINDER_INFO_XATTR_LIST);
+    }
+
+    #[test]
+    fn hidden_paths_vanish_from_listing_and_lookup() {
+        let (fs, _dir) = fs_with_files(&[
+            (b"README", b"hi\n", 0o100644),
+            (b"secrets.env", b"TOKEN=1\n", 0o100644),
+            (b"setup.sh", b"#!/bin/sh\n", 0o100755),
+        ]);
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let fs = fs.with_hidden_paths(HiddenPaths::parse(&format!("{key}:*.env")));
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+
+        let names: Vec<OsString> = fs
+            .list_children(repo)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                OsString::from("README"),
+                OsString::from("setup.sh"),
+                OsString::from(META_DIR)
+            ]
+        );
+        assert_eq!(
+            fs.lookup_inode(repo, OsStr::new("secrets.env")),
+            Err(libc::ENOENT)
+        );
+        assert!(fs.lookup_inode(repo, OsStr::new("setup.sh")).is_ok());
+    }
+
+    #[test]
+    fn single_repo_layout_serves_the_repo_at_root() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let fs = fs.with_layout(Layout::Single(key));
+
+        let names: Vec<OsString> = fs
+            .list_children(ROOT_INO)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, [OsString::from("README"), OsString::from(META_DIR)]);
+
+        let readme = fs.lookup_inode(ROOT_INO, OsStr::new("README")).unwrap();
+        assert_eq!(&*fs.cached_blob(readme).unwrap().unwrap(), b"hi\n");
+        assert_eq!(fs.parent_inode(readme), ROOT_INO);
+        assert!(fs.lookup_inode(ROOT_INO, OsStr::new(META_DIR)).is_ok());
+        // No discovery directories or by-ref namespace.
+        for name in ["octocat", BY_REF_ROOT] {
+            assert_eq!(
+                fs.lookup_inode(ROOT_INO, OsStr::new(name)),
+                Err(libc::ENOENT)
+            );
+        }
+        #[cfg(target_os = "linux")]
+        assert_eq!(fs.lookup_ttl(ROOT_INO, "README"), fs.ttls.ref_ttl);
+    }
+
+    #[test]
+    fn real_entry_named_like_meta_dir_wins() {
+        let (fs, _dir) = fs_with_file(b".ghfs", b"real\n");
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+
+        let listed = fs.list_children(repo).unwrap();
+        let matching: Vec<_> = listed.iter().filter(|e| e.name == META_DIR).collect();
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].kind, FsKind::RegularFile);
+
+        let ino = fs.lookup_inode(repo, OsStr::new(META_DIR)).unwrap();
+        assert_eq!(ino, matching[0].ino);
+        assert!(matches!(fs.inodes.get(ino), Some(InodeData::Path { .. })));
+        assert_eq!(&*fs.cached_blob(ino).unwrap().unwrap(), b"real\n");
+    }
+
+    #[test]
+    fn renamed_meta_dir_sits_beside_real_entry() {
+        let (fs, _dir) = fs_with_file(b".ghfs", b"real\n");
+        let fs = fs.with_meta_dir("_ghfs");
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+
+        let mut names: Vec<_> = fs
+            .list_children(repo)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, [OsString::from(".ghfs"), OsString::from("_ghfs")]);
+
+        let meta = fs.lookup_inode(repo, OsStr::new("_ghfs")).unwrap();
+        assert!(matches!(
+            fs.inodes.get(meta),
+            Some(InodeData::MetaDir { .. })
+        ));
+        assert!(fs.lookup_inode(meta, OsStr::new(META_HEAD)).is_ok());
+
+        assert!(is_single_component(".meta"));
+        assert!(!is_single_component("a/b"));
+        assert!(!is_single_component(".."));
+        assert!(!is_single_component(""));
+    }
+
+    #[test]
+    fn rapid_repo_lookups_materialize_once_per_window() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        for _ in 0..50 {
+            fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        }
+        assert_eq!(fs.op_counters.snapshot().materialize, 1);
+
+        // With no window, every lookup asks the worker.
+        let fs = fs.with_ttls(TtlConfig {
+            ref_ttl: Duration::ZERO,
+            ..TtlConfig::default()
+        });
+        fs.recent_heads.clear();
+        for _ in 0..3 {
+            fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        }
+        assert_eq!(fs.op_counters.snapshot().materialize, 4);
+    }
+
+    #[test]
+    fn by_ref_commits_are_browsable_independently() {
+        let (fs, dir) = fs_with_file(b"README", b"first\n");
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mirror =
+            git2::Repository::open_bare(CachePaths::new(dir.path()).mirror_dir(&key)).unwrap();
+        let first = mirror.refname_to_id("refs/heads/main").unwrap();
+        let second = commit(&mirror, "second", &[("README", "second\n")]);
+
+        let owner = fs.lookup_inode(BY_REF_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let read_readme = |commit: git2::Oid| {
+            let root = fs
+                .lookup_inode(repo, OsStr::new(&commit.to_string()))
+                .unwrap();
+            let file = fs.lookup_inode(root, OsStr::new("README")).unwrap();
+            fs.cached_blob(file).unwrap().unwrap()
+        };
+        assert_eq!(&*read_readme(first), b"first\n");
+        assert_eq!(&*read_readme(second), b"second\n");
+
+        let missing = "0123456789abcdef0123456789abcdef01234567";
+        assert_eq!(
+            fs.lookup_inode(repo, OsStr::new(missing)),
+            Err(libc::ENOENT)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn large_directories_are_listed_incrementally() {
+        let (fs, dir) = fs_with_file(b"README", b"hi\n");
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let repo =
+            git2::Repository::open_bare(CachePaths::new(dir.path()).mirror_dir(&key)).unwrap();
+        let blob = repo.blob(b"x").unwrap();
+        let mut big = repo.treebuilder(None).unwrap();
+        for i in 0..5000 {
+            big.insert(format!("f{i:05}"), blob, 0o100644).unwrap();
+        }
+        let big = big.write().unwrap();
+        let mut root = repo.treebuilder(None).unwrap();
+        root.insert("big", big, 0o040000).unwrap();
+        let tree = repo.find_tree(root.write().unwrap()).unwrap();
+        commit_tree(&repo, "refs/heads/main", "big", &tree);
+
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo_ino = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let big = fs.lookup_inode(repo_ino, OsStr::new("big")).unwrap();
+        let paths = || fs.inodes.counts().1;
+
+        // Only the entries actually pulled get inodes.
+        let before = paths();
+        let first: Vec<_> = fs.children_from(big, 0).unwrap().take(3).collect();
+        assert_eq!(paths(), before + 3);
+        let names: Vec<_> = first.iter().map(|e| e.name.clone()).collect();
+        assert_eq!(names, ["f00000", "f00001", "f00002"]);
+
+        let tail: Vec<_> = fs.children_from(big, 4998).unwrap().collect();
+        assert_eq!(paths(), before + 5);
+        assert_eq!(tail[1].name, "f04999");
+    }
+
+    #[test]
+    fn file_size_is_statted_once_per_blob() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let file = fs.lookup_inode(repo, OsStr::new("README")).unwrap();
+
+        // The lookup's stat hydrates the blob and records its size.
+        assert_eq!(fs.stat_inode(file).unwrap().size, 3);
+        let Some(InodeData::Path { oid, .. }) = fs.inodes.get(file) else {
+            panic!("README is not a path node");
+        };
+        let (blob, _) = fs
+            .store
+            .hydrate_blob(&key, parse_oid(&oid).unwrap())
+            .unwrap();
+        std::fs::remove_file(&blob).unwrap();
+
+        // A later getattr answers from memory instead of statting (and
+        // here, re-hydrating) the blob.
+        assert_eq!(fs.stat_inode(file).unwrap().size, 3);
+        assert!(!blob.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn attrs_report_the_configured_block_size() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let fs = fs.with_io_sizes(IoSizes {
+            block_size: 65536,
+            max_read: Some(1 << 20),
+        });
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let readme = fs.lookup_inode(repo, OsStr::new("README")).unwrap();
+
+        for ino in [ROOT_INO, owner, repo, readme] {
+            assert_eq!(fs.stat_inode(ino).unwrap().blksize, 65536);
+        }
+        // st_blocks stays in 512-byte units.
+        assert_eq!(fs.stat_inode(readme).unwrap().blocks, 1);
+    }
+
+    #[test]
+    fn statfs_file_count_tracks_live_inodes() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        let (files, ffree) = fs.statfs_files();
+        assert!(files > 0);
+        assert_eq!(ffree, STATFS_INODE_HEADROOM);
+
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        fs.lookup_inode(repo, OsStr::new("README")).unwrap();
+        let (grown, ffree) = fs.statfs_files();
+        assert_eq!(grown, files + 3);
+        assert_eq!(ffree, STATFS_INODE_HEADROOM);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn ttl_for_inode_uses_configured_values() {
+        let (fs, dir) = fs_with_file(b"README", b"hi\n");
+        let ttls = TtlConfig {
+            virtual_ttl: Duration::from_secs(11),
+            ref_ttl: Duration::from_secs(22),
+            commit_ttl: Duration::from_secs(33),
+        };
+        let fs = fs.with_ttls(ttls);
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let head = git2::Repository::open_bare(CachePaths::new(dir.path()).mirror_dir(&key))
+            .unwrap()
+            .refname_to_id("refs/heads/main")
+            .unwrap();
+
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let file = fs.lookup_inode(repo, OsStr::new("README")).unwrap();
+        let ref_owner = fs.lookup_inode(BY_REF_INO, OsStr::new("octocat")).unwrap();
+        let ref_repo = fs
+            .lookup_inode(ref_owner, OsStr::new("hello-world"))
+            .unwrap();
+        let by_branch = fs.lookup_inode(ref_repo, OsStr::new("main")).unwrap();
+        let by_commit = fs
+            .lookup_inode(ref_repo, OsStr::new(&head.to_string()))
+            .unwrap();
+        git2::Repository::open_bare(CachePaths::new(dir.path()).mirror_dir(&key))
+            .unwrap()
+            .reference("refs/tags/v1.0", head, false, "tag")
+            .unwrap();
+        let tag = encode_ref("refs/tags/v1.0");
+        let by_tag = fs.lookup_inode(ref_repo, OsStr::new(&tag)).unwrap();
+        let by_short_tag = fs.lookup_inode(ref_repo, OsStr::new("v1.0")).unwrap();
+
+        assert_eq!(fs.ttl_for_inode(owner), ttls.virtual_ttl);
+        assert_eq!(fs.ttl_for_inode(repo), ttls.ref_ttl);
+        assert_eq!(fs.ttl_for_inode(by_branch), ttls.ref_ttl);
+        assert_eq!(fs.ttl_for_inode(by_commit), ttls.commit_ttl);
+        assert_eq!(fs.ttl_for_inode(by_tag), ttls.commit_ttl);
+        // A short name could start naming a new branch instead.
+        assert_eq!(fs.ttl_for_inode(by_short_tag), ttls.ref_ttl);
+        assert_eq!(fs.ttl_for_inode(file), ttls.commit_ttl);
+        assert_eq!(fs.lookup_ttl(ROOT_INO, "octocat"), ttls.virtual_ttl);
+        assert_eq!(fs.lookup_ttl(owner, "hello-world"), ttls.ref_ttl);
+        assert_eq!(fs.lookup_ttl(ref_repo, &tag), ttls.commit_ttl);
+        assert_eq!(fs.lookup_ttl(ref_repo, "v1.0"), ttls.ref_ttl);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn negative_lookups_are_cached_only_below_commit_pinned_roots() {
+        let (fs, dir) = fs_with_file(b"README", b"hi\n");
+        let ttls = TtlConfig {
+            virtual_ttl: Duration::from_secs(11),
+            ref_ttl: Duration::from_secs(22),
+            commit_ttl: Duration::from_secs(33),
+        };
+        let fs = fs.with_ttls(ttls);
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let head = git2::Repository::open_bare(CachePaths::new(dir.path()).mirror_dir(&key))
+            .unwrap()
+            .refname_to_id("refs/heads/main")
+            .unwrap();
+
+        let owner = fs.lookup_inode(ROOT_INO, OsStr::new("octocat")).unwrap();
+        let repo = fs.lookup_inode(owner, OsStr::new("hello-world")).unwrap();
+        let meta = fs.lookup_inode(repo, OsStr::new(META_DIR)).unwrap();
+        let ref_owner = fs.lookup_inode(BY_REF_INO, OsStr::new("octocat")).unwrap();
+        let ref_repo = fs
+            .lookup_inode(ref_owner, OsStr::new("hello-world"))
+            .unwrap();
+        let by_branch = fs.lookup_inode(ref_repo, OsStr::new("main")).unwrap();
+        let by_commit = fs
+            .lookup_inode(ref_repo, OsStr::new(&head.to_string()))
+            .unwrap();
+
+        for parent in [by_commit, meta] {
+            assert_eq!(
+                fs.lookup_inode(parent, OsStr::new("missing")),
+                Err(libc::ENOENT)
+            );
+            assert_eq!(fs.negative_lookup_ttl(parent), ttls.commit_ttl);
+        }
+        for parent in [ROOT_INO, owner, repo, ref_repo, by_branch] {
+            assert_eq!(fs.negative_lookup_ttl(parent), Duration::ZERO);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn shutdown_watcher_unmounts_once_flag_is_set() {
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let done = Arc::new(AtomicBool::new(false));
+        let fired = Arc::new(AtomicBool::new(false));
+
+        let flag = Arc::clone(&fired);
+        let watcher = spawn_shutdown_watcher(Arc::clone(&shutdown), done, move || {
+            flag.store(true, Ordering::SeqCst)
+        });
+        assert!(!fired.load(Ordering::SeqCst));
+
+        shutdown.store(true, Ordering::SeqCst);
+        watcher.join().unwrap();
+        assert!(fired.load(Ordering::SeqCst));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn shutdown_watcher_exits_quietly_when_session_ends() {
+        let done = Arc::new(AtomicBool::new(false));
+        let fired = Arc::new(AtomicBool::new(false));
+
+        let flag = Arc::clone(&fired);
+        let watcher = spawn_shutdown_watcher(
+            Arc::new(AtomicBool::new(false)),
+            Arc::clone(&done),
+            move || flag.store(true, Ordering::SeqCst),
+        );
+        done.store(true, Ordering::SeqCst);
+        watcher.join().unwrap();
+        assert!(!fired.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn virtual_layer_rejects_non_utf8_names() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        assert_eq!(
+            fs.lookup_inode(ROOT_INO, OsStr::from_bytes(b"caf\xe9")),
+            Err(libc::ENOENT)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn pending_signals_and_exits_count_as_giving_up() {
+        let status = |state: &str, pending: &str, shared: &str, blocked: &str| {
+            format!(
+                "Name:\tls\nState:\t{state}\nSigPnd:\t{pending}\n\
+                 ShdPnd:\t{shared}\nSigBlk:\t{blocked}\n"
+            )
+        };
+        let idle = "0000000000000000";
+        let sigint = "0000000000000002";
+        assert!(!status_shows_signal(&status(
+            "S (sleeping)",
+            idle,
+            idle,
+            idle
+        )));
+        assert!(status_shows_signal(&status(
+            "S (sleeping)",
+            idle,
+            sigint,
+            idle
+        )));
+        assert!(status_shows_signal(&status(
+            "S (sleeping)",
+            sigint,
+            idle,
+            idle
+        )));
+        assert!(!status_shows_signal(&status(
+            "S (sleeping)",
+            idle,
+            sigint,
+            sigint
+        )));
+        assert!(status_shows_signal(&status("Z (zombie)", idle, idle, idle)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn only_a_vanished_caller_counts_as_giving_up() {
+        let (fs, _dir) = fs_with_file(b"README", b"hi\n");
+        assert!(!fs.caller_gave_up());
+        fs.caller.store(std::process::id(), Ordering::Relaxed);
+        assert!(!fs.caller_gave_up());
+        // Above any pid_max, so there's no such process.
+        fs.caller.store(u32::MAX - 1, Ordering::Relaxed);
+        assert!(fs.caller_gave_up());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn open_flags_are_checked_against_the_inode_kind() {
+        use libc::{EISDIR, ELOOP, ENOTDIR, EROFS, O_DIRECTORY, O_NOFOLLOW, O_RDONLY, O_RDWR};
+
+        let cases = [
+            (EntryKind::Blob, O_RDONLY, None),
+            (EntryKind::Blob, O_RDONLY | O_NOFOLLOW, None),
+            (EntryKind::Blob, O_RDONLY | O_DIRECTORY, Some(ENOTDIR)),
+            (EntryKind::Executable, O_RDONLY | O_DIRECTORY, Some(ENOTDIR)),
+            (EntryKind::Gitlink, O_RDONLY | O_DIRECTORY, Some(ENOTDIR)),
+            (EntryKind::Tree, O_RDONLY, Some(EISDIR)),
+            (EntryKind::Tree, O_RDONLY | O_NOFOLLOW, Some(EISDIR)),
+            (EntryKind::Tree, O_RDONLY | O_DIRECTORY, Some(EISDIR)),
+            (EntryKind::Symlink, O_RDONLY, None),
+            (EntryKind::Symlink, O_RDONLY | O_NOFOLLOW, Some(ELOOP)),
+            (EntryKind::Symlink, O_RDONLY | O_DIRECTORY, Some(ENOTDIR)),
+            (
+                EntryKind::Symlink,
+                O_RDONLY | O_DIRECTORY | O_NOFOLLOW,
+                Some(ENOTDIR),
+            ),
+            (EntryKind::Blob, O_RDWR, Some(EROFS)),
+            (EntryKind::Symlink, O_RDWR | O_NOFOLLOW, Some(EROFS)),
+        ];
+        for (kind, flags, expected) in cases {
+            assert_eq!(
+                open_flags_errno(kind, flags),
+                expected,
+                "{kind:?} with flags {flags:#o}"
+            );
+        }
+    }
+
+    #[test]
+    fn disk_full_errors_are_enospc() {
+        use crate::store::{BlobError, GitError};
+
+        let full = || GitError::DiskFull("No space left on device".to_string());
+        assert_eq!(store_err_errno(&full().into()), libc::ENOSPC);
+        assert_eq!(
+            store_err_errno(&BlobError::Git(full()).into()),
+            libc::ENOSPC
+        );
+        assert_eq!(
+            store_err_errno(&GitError::FetchError("curl 56".to_string()).into()),
+            libc::EIO
+        );
+    }
+}
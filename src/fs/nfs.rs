@@ -15,7 +15,7 @@ use nfsserve::nfs::{
 use nfsserve::tcp::{NFSTcp, NFSTcpListener};
 use nfsserve::vfs::{DirEntry, NFSFileSystem, ReadDirResult, VFSCapabilities};
 
-use super::{FsKind, GhFs, NodeAttr, ROOT_INO};
+use super::{FsKind, GhFs, NodeAttr, Op, ROOT_INO};
 
 const DEFAULT_NFS_PORT: u16 = 11111;
 const MAX_PORT_SCAN: u16 = 100;
@@ -92,11 +92,15 @@ impl NFSFileSystem for GhFs {
     }
 
     async fn lookup(&self, dirid: fileid3, filename: &filename3) -> Result<fileid3, nfsstat3> {
+        self.op_counters.record(Op::Lookup);
         let name = OsStr::from_bytes(&filename.0);
-        self.lookup_inode(dirid, name).map_err(errno_to_nfs)
+        let ino = self.lookup_inode(dirid, name).map_err(errno_to_nfs)?;
+        self.inodes.record_lookup(ino);
+        Ok(ino)
     }
 
     async fn getattr(&self, id: fileid3) -> Result<fattr3, nfsstat3> {
+        self.op_counters.record(Op::Getattr);
         self.stat_inode(id)
             .map(|attr| attr.to_nfs_attr())
             .map_err(errno_to_nfs)
@@ -112,6 +116,7 @@ impl NFSFileSystem for GhFs {
         offset: u64,
         count: u32,
     ) -> Result<(Vec<u8>, bool), nfsstat3> {
+        self.op_counters.record(Op::Read);
         self.read_file_range(id, offset, count)
             .map_err(errno_to_nfs)
     }
@@ -165,6 +170,7 @@ impl NFSFileSystem for GhFs {
         start_after: fileid3,
         max_entries: usize,
     ) -> Result<ReadDirResult, nfsstat3> {
+        self.op_counters.record(Op::Readdir);
         let entries = self.list_children(dirid).map_err(errno_to_nfs)?;
         let start_index = if start_after == 0 {
             0
@@ -0,0 +1,184 @@
+//! Owner directory listings backed by the GitHub API.
+//!
+//! By default an owner directory lists only the repos already mirrored on
+//! disk. With remote listing enabled, it also lists every repo GitHub
+//! reports for the owner. Those entries are plain virtual nodes; nothing is
+//! cloned until something looks inside one.
+//!
+//! Listings are cached per owner for [`LISTING_TTL`]. When the API is
+//! unreachable or rate limited, the last listing (however old) is reused,
+//! and an owner never listed falls back to the cached repos alone.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::github::{ApiClient, ApiError};
+
+/// Environment variable enabling remote owner listings (`1` or `true`).
+pub const LIST_REMOTE_REPOS_ENV: &str = "GHFS_LIST_REMOTE_REPOS";
+
+/// How long an owner's API listing is reused before it is fetched again.
+pub const LISTING_TTL: Duration = Duration::from_secs(300);
+
+/// Repos requested per API page (GitHub's maximum).
+const PER_PAGE: usize = 100;
+
+/// Pages fetched per owner. Caps a listing at 1000 repos so an `ls` of a
+/// huge organization doesn't burn through the rate limit.
+const MAX_PAGES: usize = 10;
+
+#[derive(Deserialize)]
+struct ApiRepo {
+    name: String,
+}
+
+/// Per-owner cache of repo names listed by the GitHub API.
+pub struct RemoteRepos {
+    ttl: Duration,
+    listings: Mutex<HashMap<String, (Instant, Vec<String>)>>,
+}
+
+impl RemoteRepos {
+    /// An empty cache keeping listings for `ttl`.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            listings: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `owner`'s repos according to GitHub, from cache when fresh.
+    pub fn list(&self, owner: &str) -> Vec<String> {
+        self.list_with(owner, fetch_repo_names)
+    }
+
+    fn list_with(
+        &self,
+        owner: &str,
+        fetch: impl FnOnce(&str) -> Result<Vec<String>, ApiError>,
+    ) -> Vec<String> {
+        let owner = owner.to_ascii_lowercase();
+        if let Some((fetched, names)) = self.listings.lock().expect("listings poisoned").get(&owner)
+            && fetched.elapsed() < self.ttl
+        {
+            return names.clone();
+        }
+
+        match fetch(&owner) {
+            Ok(names) => {
+                self.listings
+                    .lock()
+                    .expect("listings poisoned")
+                    .insert(owner, (Instant::now(), names.clone()));
+                names
+            }
+            Err(e) => {
+                log::debug!("Listing repos for {owner} from GitHub failed: {e}");
+                self.listings
+                    .lock()
+                    .expect("listings poisoned")
+                    .get(&owner)
+                    .map(|(_, names)| names.clone())
+                    .unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// Fetch the names of `owner`'s repos. The users endpoint serves
+/// organizations too.
+pub fn fetch_repo_names(owner: &str) -> Result<Vec<String>, ApiError> {
+    let client = ApiClient::shared();
+    let mut names = Vec::new();
+    for page in 1..=MAX_PAGES {
+        let repos: Vec<ApiRepo> = client.get_json(&format!(
+            "users/{owner}/repos?per_page={PER_PAGE}&page={page}"
+        ))?;
+        let done = repos.len() < PER_PAGE;
+        names.extend(repos.into_iter().map(|r| r.name));
+        if done {
+            break;
+        }
+    }
+    Ok(names)
+}
+
+/// Merge cached and API-listed repo names into one sorted listing.
+///
+/// Mirrors live under lowercase names, so API names are lowercased to
+/// match and duplicates collapse. Names that aren't valid repo names are
+/// dropped.
+pub fn merge_repo_names(cached: Vec<String>, remote: &[String]) -> Vec<String> {
+    let mut names = cached;
+    names.extend(
+        remote
+            .iter()
+            .map(|name| name.to_ascii_lowercase())
+            .filter(|name| name.parse::<crate::types::Repo>().is_ok()),
+    );
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn merge_unions_cached_and_remote_names() {
+        let cached = strings(&["hello-world", "spoon-knife"]);
+        let remote = strings(&[
+            "Hello-World",
+            "linguist",
+            "..",
+            "Spoon-Knife",
+            "octocat.github.io",
+        ]);
+        assert_eq!(
+            merge_repo_names(cached, &remote),
+            strings(&[
+                "hello-world",
+                "linguist",
+                "octocat.github.io",
+                "spoon-knife"
+            ])
+        );
+        assert_eq!(
+            merge_repo_names(strings(&["only-cached"]), &[]),
+            strings(&["only-cached"])
+        );
+    }
+
+    #[test]
+    fn listings_are_cached_until_ttl_and_survive_failures() {
+        let repos = RemoteRepos::new(Duration::from_secs(60));
+        let fetched = repos.list_with("Octocat", |owner| {
+            assert_eq!(owner, "octocat");
+            Ok(strings(&["hello-world"]))
+        });
+        assert_eq!(fetched, strings(&["hello-world"]));
+
+        // Fresh: served from cache without calling fetch.
+        let cached = repos.list_with("octocat", |_| panic!("fetched a fresh listing"));
+        assert_eq!(cached, strings(&["hello-world"]));
+
+        // Expired: refetched, and a failure falls back to the stale listing.
+        let expired = RemoteRepos::new(Duration::ZERO);
+        expired.list_with("octocat", |_| Ok(strings(&["hello-world"])));
+        let stale = expired.list_with("octocat", |_| Err(ApiError::Status(500)));
+        assert_eq!(stale, strings(&["hello-world"]));
+        assert!(
+            expired
+                .list_with("nobody", |_| Err(ApiError::NotFound))
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Operation counters for `ghfs top`.
+//!
+//! Backend callbacks bump a relaxed atomic per operation; the socket server
+//! hands out snapshots and the CLI turns two snapshots into rates.
+//!
+//! [`FsStatsProbe`] does the same for the sizes of the filesystem's in-memory
+//! tables, backing `ghfs fsstats`.
+
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+use std::sync::Arc;
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(target_os = "linux")]
+use super::OpenHandle;
+use super::content_cache::ContentCache;
+use super::inode::InodeTable;
+use crate::store::Store;
+use crate::types::RepoKey;
+
+/// A filesystem operation worth counting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Lookup,
+    Getattr,
+    Readdir,
+    Read,
+    /// A repo's mirror was ensured and its HEAD resolved (may clone).
+    Materialize,
+}
+
+/// Monotonic per-operation counters, shared between the mounted backend
+/// and the socket server.
+#[derive(Debug, Default)]
+pub struct OpCounters {
+    lookup: AtomicU64,
+    getattr: AtomicU64,
+    readdir: AtomicU64,
+    read: AtomicU64,
+    materialize: AtomicU64,
+}
+
+/// Point-in-time copy of [`OpCounters`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpCounts {
+    pub lookup: u64,
+    pub getattr: u64,
+    pub readdir: u64,
+    pub read: u64,
+    pub materialize: u64,
+}
+
+impl OpCounters {
+    pub fn record(&self, op: Op) {
+        let counter = match op {
+            Op::Lookup => &self.lookup,
+            Op::Getattr => &self.getattr,
+            Op::Readdir => &self.readdir,
+            Op::Read => &self.read,
+            Op::Materialize => &self.materialize,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> OpCounts {
+        OpCounts {
+            lookup: self.lookup.load(Ordering::Relaxed),
+            getattr: self.getattr.load(Ordering::Relaxed),
+            readdir: self.readdir.load(Ordering::Relaxed),
+            read: self.read.load(Ordering::Relaxed),
+            materialize: self.materialize.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time sizes of the filesystem's in-memory tables.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FsStats {
+    /// Inodes for paths inside a commit's tree.
+    pub passthrough_inodes: usize,
+    /// Inodes for the synthetic layer (owners, repos, `.ghfs`, `by-ref`).
+    pub virtual_inodes: usize,
+    /// File handles currently open (always zero on the NFS backend).
+    pub open_files: usize,
+    /// Parsed tree listings held by the store.
+    pub cached_trees: usize,
+    /// Small blobs held by the content cache.
+    pub cached_blobs: usize,
+}
+
+/// Read-only view of a [`GhFs`](super::GhFs)'s tables, handed to the
+/// socket server before the filesystem moves into its mount.
+#[derive(Clone)]
+pub struct FsStatsProbe {
+    pub(super) store: Store,
+    pub(super) inodes: Arc<InodeTable>,
+    pub(super) content_cache: Arc<ContentCache>,
+    #[cfg(target_os = "linux")]
+    pub(super) open_files: Arc<Mutex<HashMap<u64, OpenHandle>>>,
+}
+
+impl FsStatsProbe {
+    pub fn snapshot(&self) -> FsStats {
+        let (virtual_inodes, passthrough_inodes) = self.inodes.counts();
+        #[cfg(target_os = "linux")]
+        let open_files = self.open_files.lock().map(|f| f.len()).unwrap_or(0);
+        #[cfg(not(target_os = "linux"))]
+        let open_files = 0;
+        FsStats {
+            passthrough_inodes,
+            virtual_inodes,
+            open_files,
+            cached_trees: self.store.cached_trees(),
+            cached_blobs: self.content_cache.entry_count(),
+        }
+    }
+
+    /// File handles currently open under `key` (always zero on the NFS
+    /// backend).
+    pub fn open_in(&self, key: &RepoKey) -> usize {
+        #[cfg(target_os = "linux")]
+        {
+            let key = key.canonical();
+            self.open_files
+                .lock()
+                .map(|files| {
+                    files
+                        .values()
+                        .filter(|handle| handle.repo.as_ref() == Some(&key))
+                        .count()
+                })
+                .unwrap_or(0)
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = key;
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_reflects_recorded_ops() {
+        let counters = OpCounters::default();
+        counters.record(Op::Lookup);
+        counters.record(Op::Lookup);
+        counters.record(Op::Read);
+        assert_eq!(
+            counters.snapshot(),
+            OpCounts {
+                lookup: 2,
+                read: 1,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Kernel cache TTLs for attributes and lookups.
+//!
+//! Three classes of node differ in how often they can change: discovery
+//! directories, nodes that follow a mutable ref, and content pinned to an
+//! immutable commit. Each class has its own TTL, overridable from the
+//! environment.
+
+use std::time::Duration;
+
+/// Environment variable overriding [`TtlConfig::virtual_ttl`], in seconds.
+pub const VIRTUAL_TTL_ENV: &str = "GHFS_VIRTUAL_TTL";
+/// Environment variable overriding [`TtlConfig::ref_ttl`], in seconds.
+pub const REF_TTL_ENV: &str = "GHFS_REF_TTL";
+/// Environment variable overriding [`TtlConfig::commit_ttl`], in seconds.
+pub const COMMIT_TTL_ENV: &str = "GHFS_COMMIT_TTL";
+
+/// TTLs reported to the kernel per node class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TtlConfig {
+    /// Virtual discovery nodes (root, owners, by-ref roots, ref-repo
+    /// directory listings).
+    pub virtual_ttl: Duration,
+    /// Mutable ref resolution (default-branch alias and named ref
+    /// selectors). Short so a moved branch re-resolves promptly.
+    pub ref_ttl: Duration,
+    /// Content pinned to an immutable commit (commit-OID selectors and all
+    /// repository path nodes). Long, since git objects never change.
+    pub commit_ttl: Duration,
+}
+
+impl Default for TtlConfig {
+    fn default() -> Self {
+        Self {
+            virtual_ttl: Duration::from_secs(60),
+            ref_ttl: Duration::from_secs(5),
+            commit_ttl: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl TtlConfig {
+    /// Defaults overridden by [`VIRTUAL_TTL_ENV`], [`REF_TTL_ENV`], and
+    /// [`COMMIT_TTL_ENV`]. Unparseable values are logged and ignored.
+    pub fn from_env() -> Self {
+        Self::from_lookup(crate::config::var)
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = Self::default();
+        let secs = |name: &str, default: Duration| match lookup(name) {
+            None => default,
+            Some(value) => match value.trim().parse::<u64>() {
+                Ok(secs) => Duration::from_secs(secs),
+                Err(_) => {
+                    log::warn!("Ignoring invalid {name}={value:?}; expected seconds");
+                    default
+                }
+            },
+        };
+        Self {
+            virtual_ttl: secs(VIRTUAL_TTL_ENV, defaults.virtual_ttl),
+            ref_ttl: secs(REF_TTL_ENV, defaults.ref_ttl),
+            commit_ttl: secs(COMMIT_TTL_ENV, defaults.commit_ttl),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_overrides_defaults_and_skips_bad_values() {
+        let ttls = TtlConfig::from_lookup(|name| match name {
+            REF_TTL_ENV => Some("1".to_string()),
+            COMMIT_TTL_ENV => Some("forever".to_string()),
+            _ => None,
+        });
+        assert_eq!(
+            ttls,
+            TtlConfig {
+                ref_ttl: Duration::from_secs(1),
+                ..TtlConfig::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Git repositories for unit tests: bare mirrors with commits on `main`,
+//! all signed by the same throwaway identity.
+
+use std::path::Path;
+
+use git2::{Oid, Repository, Signature, Tree};
+
+use crate::cache::CachePaths;
+use crate::types::RepoKey;
+
+/// The author and committer of every fixture commit.
+pub fn signature() -> Signature<'static> {
+    Signature::now("T", "t@t.com").unwrap()
+}
+
+/// Init a bare repository at `path` whose HEAD is the (unborn) `main`.
+pub fn init_bare(path: &Path) -> Repository {
+    let repo = Repository::init_bare(path).unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+    repo
+}
+
+/// Commit `tree` to `refname`, on top of its current commit if it has one.
+pub fn commit_tree(repo: &Repository, refname: &str, message: &str, tree: &Tree<'_>) -> Oid {
+    let sig = signature();
+    let parent = repo
+        .refname_to_id(refname)
+        .ok()
+        .map(|id| repo.find_commit(id).unwrap());
+    let parents: Vec<_> = parent.iter().collect();
+    repo.commit(Some(refname), &sig, &sig, message, tree, &parents)
+        .unwrap()
+}
+
+/// Commit `files` (name and contents, as regular files) as the whole tree
+/// on `main`.
+pub fn commit(repo: &Repository, message: &str, files: &[(&str, &str)]) -> Oid {
+    let mut builder = repo.treebuilder(None).unwrap();
+    for (name, contents) in files {
+        let blob = repo.blob(contents.as_bytes()).unwrap();
+        builder.insert(name, blob, 0o100644).unwrap();
+    }
+    let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+    commit_tree(repo, "refs/heads/main", message, &tree)
+}
+
+/// Create a bare mirror for `key` holding a single empty commit on `main`.
+pub fn mirror_with_commit(paths: &CachePaths, key: &RepoKey) -> Oid {
+    let repo = init_bare(&paths.mirror_dir(key));
+    commit(&repo, "init", &[])
+}
@@ -0,0 +1,293 @@
+//! Small GitHub REST API client shared by the features that need it.
+//!
+//! Everything built on the API is optional: the filesystem itself only talks
+//! to GitHub over git. Callers therefore get a typed [`ApiError`] and are
+//! expected to skip their feature on failure rather than fail the operation
+//! that triggered it.
+//!
+//! Rate limits are honored in two ways. Short waits (a `Retry-After` or a
+//! reset a few seconds away) are slept through and the request retried.
+//! Longer ones fail fast with [`ApiError::RateLimited`], and the client
+//! remembers the reset time so later calls don't hit the API until then.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+use crate::time::unix_now;
+
+/// Environment variables checked, in order, for an API token.
+pub const TOKEN_ENVS: [&str; 2] = ["GHFS_GITHUB_TOKEN", "GITHUB_TOKEN"];
+
+const API_BASE: &str = "https://api.github.com";
+
+/// Per-request timeout. API lookups sit on user-facing paths, so keep it short.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest rate-limit wait slept through before giving up on a request.
+const MAX_WAIT: Duration = Duration::from_secs(10);
+
+/// Retries after a rate-limited response before giving up.
+const MAX_RETRIES: u32 = 3;
+
+/// First delay when GitHub rate-limits without saying for how long;
+/// doubles on each retry.
+const BASE_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Errors returned by [`ApiClient`].
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("not found")]
+    NotFound,
+
+    #[error("unauthorized or forbidden (HTTP {0})")]
+    Forbidden(u16),
+
+    #[error("rate limited; retry in {}s", wait.as_secs())]
+    RateLimited { wait: Duration },
+
+    #[error("unexpected HTTP status {0}")]
+    Status(u16),
+
+    #[error("transport error: {0}")]
+    Transport(String),
+
+    #[error("invalid response: {0}")]
+    Decode(String),
+}
+
+/// Rate-limit information from a response's headers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// `X-RateLimit-Remaining`
+    pub remaining: Option<u64>,
+    /// `X-RateLimit-Reset`, in Unix seconds
+    pub reset: Option<u64>,
+    /// `Retry-After`, in seconds
+    pub retry_after: Option<u64>,
+}
+
+impl RateLimit {
+    /// Parse the rate-limit headers via `header`, which returns a header's
+    /// value by name. Missing or malformed headers are left as `None`.
+    pub fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Self {
+        let number = |name: &str| header(name).and_then(|v| v.trim().parse().ok());
+        Self {
+            remaining: number("x-ratelimit-remaining"),
+            reset: number("x-ratelimit-reset"),
+            retry_after: number("retry-after"),
+        }
+    }
+
+    /// How long to wait before the next request, given the current time in
+    /// Unix seconds. `None` means the headers don't ask for a wait.
+    pub fn wait(&self, now: u64) -> Option<Duration> {
+        if let Some(secs) = self.retry_after {
+            return Some(Duration::from_secs(secs));
+        }
+        match (self.remaining, self.reset) {
+            // The reset has second granularity; wait at least one.
+            (Some(0), Some(reset)) => Some(Duration::from_secs(reset.saturating_sub(now).max(1))),
+            _ => None,
+        }
+    }
+}
+
+/// Backoff delay for the `attempt`th retry (0-based) when GitHub gives no
+/// hint of its own.
+pub fn backoff(attempt: u32) -> Duration {
+    BASE_BACKOFF.saturating_mul(1 << attempt.min(16))
+}
+
+/// GitHub API client with token auth and rate-limit backoff.
+pub struct ApiClient {
+    agent: ureq::Agent,
+    token: Option<String>,
+    user_agent: String,
+    /// Unix second before which requests fail fast without a round trip.
+    blocked_until: Mutex<Option<u64>>,
+}
+
+impl ApiClient {
+    /// Create a client authenticating with `token`, if any.
+    pub fn new(token: Option<String>) -> Self {
+        Self {
+            agent: ureq::builder().timeout(TIMEOUT).build(),
+            token,
+            user_agent: crate::store::git::user_agent(),
+            blocked_until: Mutex::new(None),
+        }
+    }
+
+    /// Create a client using the first token set in [`TOKEN_ENVS`].
+    pub fn from_env() -> Self {
+        let token = TOKEN_ENVS
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.trim().is_empty());
+        Self::new(token)
+    }
+
+    /// Whether requests are authenticated.
+    pub fn has_token(&self) -> bool {
+        self.token.is_some()
+    }
+
+    /// The process-wide client, so every feature shares one rate-limit view.
+    pub fn shared() -> &'static Self {
+        static CLIENT: OnceLock<ApiClient> = OnceLock::new();
+        CLIENT.get_or_init(Self::from_env)
+    }
+
+    /// `GET {path}` (relative to the API root) and decode the JSON body.
+    pub fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, ApiError> {
+        let body = self
+            .send("GET", path)?
+            .into_string()
+            .map_err(|e| ApiError::Transport(e.to_string()))?;
+        serde_json::from_str(&body).map_err(|e| ApiError::Decode(e.to_string()))
+    }
+
+    /// `HEAD {path}`, succeeding when GitHub answers 2xx.
+    pub fn head(&self, path: &str) -> Result<(), ApiError> {
+        self.send("HEAD", path).map(drop)
+    }
+
+    fn send(&self, method: &str, path: &str) -> Result<ureq::Response, ApiError> {
+        let url = format!("{API_BASE}/{}", path.trim_start_matches('/'));
+        for attempt in 0..=MAX_RETRIES {
+            self.check_blocked()?;
+
+            let mut request = self
+                .agent
+                .request(method, &url)
+                .set("User-Agent", &self.user_agent)
+                .set("Accept", "application/vnd.github+json");
+            if let Some(token) = &self.token {
+                request = request.set("Authorization", &format!("Bearer {token}"));
+            }
+
+            let (code, response) = match request.call() {
+                Ok(response) => return Ok(response),
+                Err(ureq::Error::Status(code, response)) => (code, response),
+                Err(ureq::Error::Transport(e)) => return Err(ApiError::Transport(e.to_string())),
+            };
+            let limit = RateLimit::from_headers(|name| response.header(name));
+            let wait = match (code, limit.wait(unix_now() as u64)) {
+                (403 | 429, Some(wait)) => wait,
+                (429, None) => backoff(attempt),
+                (401 | 403, None) => return Err(ApiError::Forbidden(code)),
+                (404, _) => return Err(ApiError::NotFound),
+                _ => return Err(ApiError::Status(code)),
+            };
+
+            if wait > MAX_WAIT || attempt == MAX_RETRIES {
+                self.block_for(wait);
+                return Err(ApiError::RateLimited { wait });
+            }
+            log::debug!("GitHub API rate limited; retrying {url} in {wait:?}");
+            std::thread::sleep(wait);
+        }
+        unreachable!("the last attempt always returns")
+    }
+
+    fn check_blocked(&self) -> Result<(), ApiError> {
+        let mut blocked = self.blocked_until.lock().expect("api client poisoned");
+        let now = unix_now() as u64;
+        match *blocked {
+            Some(until) if until > now => Err(ApiError::RateLimited {
+                wait: Duration::from_secs(until - now),
+            }),
+            Some(_) => {
+                *blocked = None;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn block_for(&self, wait: Duration) {
+        let until = unix_now() as u64 + wait.as_secs();
+        log::warn!(
+            "GitHub API rate limited for {}s; skipping API lookups until then",
+            wait.as_secs()
+        );
+        *self.blocked_until.lock().expect("api client poisoned") = Some(until);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers<'a>(pairs: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<&'a str> {
+        move |name| {
+            pairs
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| *v)
+        }
+    }
+
+    #[test]
+    fn parses_rate_limit_headers() {
+        let limit = RateLimit::from_headers(headers(&[
+            ("X-RateLimit-Remaining", "0"),
+            ("X-RateLimit-Reset", "1700000060"),
+            ("Retry-After", "bogus"),
+        ]));
+        assert_eq!(
+            limit,
+            RateLimit {
+                remaining: Some(0),
+                reset: Some(1_700_000_060),
+                retry_after: None,
+            }
+        );
+        assert_eq!(RateLimit::from_headers(headers(&[])), RateLimit::default());
+    }
+
+    #[test]
+    fn wait_prefers_retry_after_then_reset() {
+        let now = 1_700_000_000;
+        let retry = RateLimit {
+            remaining: Some(0),
+            reset: Some(now + 60),
+            retry_after: Some(5),
+        };
+        assert_eq!(retry.wait(now), Some(Duration::from_secs(5)));
+
+        let exhausted = RateLimit {
+            retry_after: None,
+            ..retry
+        };
+        assert_eq!(exhausted.wait(now), Some(Duration::from_secs(60)));
+        // A reset already in the past still waits a second.
+        assert_eq!(exhausted.wait(now + 120), Some(Duration::from_secs(1)));
+
+        let remaining = RateLimit {
+            remaining: Some(10),
+            ..exhausted
+        };
+        assert_eq!(remaining.wait(now), None);
+    }
+
+    #[test]
+    fn backoff_doubles() {
+        assert_eq!(backoff(0), Duration::from_secs(1));
+        assert_eq!(backoff(2), Duration::from_secs(4));
+        assert_eq!(backoff(100), backoff(16));
+    }
+
+    #[test]
+    fn blocked_client_fails_fast() {
+        let client = ApiClient::new(None);
+        client.block_for(Duration::from_secs(60));
+        assert!(matches!(
+            client.head("repos/octocat/Hello-World"),
+            Err(ApiError::RateLimited { .. })
+        ));
+    }
+}
@@ -5,9 +5,14 @@
 
 pub mod cache;
 pub mod cli;
+pub mod config;
 pub mod daemon;
 pub mod fs;
+#[cfg(test)]
+pub(crate) mod git_fixtures;
+pub mod github;
 pub mod protocol;
 pub mod service;
 pub mod store;
+pub mod time;
 pub mod types;
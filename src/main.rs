@@ -1,25 +1,18 @@
 use ghfs::{
+    cache::CachePaths,
     cli,
-    cli::{Client, ClientError},
-    daemon, service,
+    cli::{CliError, Client, exit_code},
+    config, daemon, service,
+    store::Store,
     types::RepoKey,
 };
 
 use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
 
 #[allow(unused_imports)]
 use ghfs::protocol::{Request, VersionResult};
 
-fn command_in_path(name: &str) -> bool {
-    std::env::var_os("PATH")
-        .map(|paths| {
-            std::env::split_paths(&paths)
-                .map(|dir| dir.join(name))
-                .any(|candidate| candidate.is_file())
-        })
-        .unwrap_or(false)
-}
-
 #[derive(Parser)]
 #[command(
     name = "ghfs",
@@ -33,7 +26,66 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Start the daemon in the foreground (used by service managers)
-    Daemon,
+    Daemon {
+        /// Mount point to use instead of the default (or GHFS_MOUNT_POINT)
+        #[arg(long, value_name = "PATH")]
+        mount_point: Option<PathBuf>,
+
+        /// Cache directory to use instead of the default
+        #[arg(long, value_name = "PATH")]
+        cache_dir: Option<PathBuf>,
+
+        /// Command run when a sync moves a repo's HEAD (or GHFS_POST_SYNC_HOOK).
+        /// Split on whitespace; {owner}, {repo}, {commit}, {path}, {mirror}
+        /// are substituted per argument
+        #[arg(long, value_name = "TEMPLATE")]
+        post_sync_hook: Option<String>,
+
+        /// Command run before mounting (or GHFS_PRE_MOUNT_HOOK); the daemon
+        /// exits if it fails. {mount} and {cache} are substituted
+        #[arg(long, value_name = "TEMPLATE")]
+        pre_mount_hook: Option<String>,
+
+        /// Command run after unmounting (or GHFS_POST_UNMOUNT_HOOK); {mount}
+        /// and {cache} are substituted
+        #[arg(long, value_name = "TEMPLATE")]
+        post_unmount_hook: Option<String>,
+
+        /// Let other users access the mount (or GHFS_ALLOW_OTHER=1); needs
+        /// user_allow_other in /etc/fuse.conf
+        #[arg(long)]
+        allow_other: bool,
+
+        /// Repo other users may read when --allow-other is set (repeatable,
+        /// or GHFS_SHARED_REPOS); all repos are shared if none are given
+        #[arg(long = "share", value_name = "OWNER/REPO")]
+        shared_repos: Vec<RepoKey>,
+
+        /// Before each refresh, ask the remote for its HEAD and skip the
+        /// fetch if it's unchanged (or GHFS_REMOTE_PRECHECK=1)
+        #[arg(long)]
+        remote_precheck: bool,
+
+        /// Cache repos GitHub reports as renamed under their new name, with
+        /// the old name aliased to it (or GHFS_FOLLOW_RENAMES=1)
+        #[arg(long)]
+        follow_renames: bool,
+
+        /// On startup, re-clone repos the state database knows about whose
+        /// mirror is missing (e.g. after wiping mirrors/)
+        #[arg(long)]
+        clone_missing: bool,
+
+        /// List every repo GitHub reports for an owner in its directory,
+        /// cloning on first access (or GHFS_LIST_REMOTE_REPOS=1)
+        #[arg(long)]
+        list_remote_repos: bool,
+
+        /// Serve only this repo, with its default-branch tree at the mount
+        /// root instead of under owner/repo
+        #[arg(long, value_name = "OWNER/REPO")]
+        repo: Option<RepoKey>,
+    },
 
     /// Manage the background service
     Service {
@@ -41,20 +93,283 @@ enum Commands {
         action: ServiceAction,
     },
 
+    /// View and change settings in the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
     /// Show daemon status
-    Status,
+    Status {
+        /// Also ask each repo's remote whether the synced commit is behind
+        #[arg(long)]
+        remote: bool,
+    },
+
+    /// Restart the service if the daemon runs a different version than this binary
+    Upgrade {
+        /// Restart without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
 
     /// Force sync a repository
+    #[command(
+        after_help = "Exit codes: 0 success, 1 other failure, 2 invalid repo, \
+3 repo not found, 4 network failure, 5 daemon not running, \
+6 repo blocked by GitHub"
+    )]
     Sync {
+        /// Repository in owner/repo format, or - to sync each one listed on stdin
+        repo: String,
+
+        /// Print nothing on success
+        #[arg(short, long)]
+        quiet: bool,
+
+        /// Serve this commit as HEAD until the next sync, without pinning
+        #[arg(long, value_name = "SHA")]
+        commit: Option<String>,
+
+        /// Only sync if the last fetch is older than this (e.g. 90s, 30m, 6h, 2d)
+        #[arg(long, value_name = "DUR", value_parser = cli::parse_duration, conflicts_with = "commit")]
+        if_older_than: Option<std::time::Duration>,
+
+        /// Afterwards, repack the mirror and delete unreachable objects now
+        /// (refused while files under the repo are open)
+        #[arg(long, conflicts_with = "commit")]
+        prune: bool,
+
+        /// Queue the sync and print its id without waiting; check on it with
+        /// `ghfs sync-status`
+        #[arg(long = "async", conflicts_with = "prune")]
+        submit: bool,
+
+        /// Print the result as JSON
+        #[arg(long, conflicts_with_all = ["quiet", "prune", "submit"])]
+        json: bool,
+
+        /// Exit with code 7 if HEAD didn't move (including when the mirror
+        /// was fresh enough not to fetch)
+        #[arg(long, conflicts_with = "submit")]
+        exit_unchanged: bool,
+    },
+
+    /// Show whether a sync queued with `ghfs sync --async` has finished
+    SyncStatus {
+        /// Id printed by `ghfs sync --async`
+        id: u64,
+
+        /// Print the status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show the mirror, HEAD ref, and commit a repository currently serves
+    Which {
+        /// Repository in owner/repo format
+        repo: String,
+    },
+
+    /// Show everything known about a cached repository
+    Info {
+        /// Repository in owner/repo format
+        repo: String,
+
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List the generations recorded for a repository
+    History {
+        /// Repository in owner/repo format
+        repo: String,
+
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Time resolution, directory walks, and file reads for a repository
+    Bench {
+        /// Repository in owner/repo format
+        repo: String,
+
+        /// Print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show what changed between two commits of a cached repository
+    #[command(after_help = "Each side is a branch, tag, or commit SHA, as under /by-ref.")]
+    Diff {
+        /// Repository in owner/repo format
+        repo: String,
+
+        /// Older side
+        from: String,
+
+        /// Newer side
+        to: String,
+
+        /// Print the full patch instead of a diffstat
+        #[arg(long, conflicts_with_all = ["name_only", "json"])]
+        patch: bool,
+
+        /// Print only the changed paths
+        #[arg(long, conflicts_with = "json")]
+        name_only: bool,
+
+        /// Print the resolved commits and changed paths as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Delete a repository's mirror and state from the cache
+    Rm {
+        /// Repository in owner/repo format
+        repo: String,
+    },
+
+    /// Make a repository borrow git objects from another cached repository
+    Link {
         /// Repository in owner/repo format
         repo: String,
+
+        /// Repository to borrow objects from, usually the one it was forked from
+        #[arg(long, value_name = "OWNER/REPO")]
+        reference: String,
+    },
+
+    /// Serve a branch other than the remote's default at /<owner>/<repo>
+    SetBranch {
+        /// Repository in owner/repo format
+        repo: String,
+
+        /// Branch to serve, e.g. develop
+        #[arg(required_unless_present = "clear")]
+        branch: Option<String>,
+
+        /// Go back to serving the remote's default branch
+        #[arg(long, conflicts_with = "branch")]
+        clear: bool,
+    },
+
+    /// Refresh a repository on its own interval instead of the default 24h
+    SetMaxAge {
+        /// Repository in owner/repo format
+        repo: String,
+
+        /// How long the repository stays fresh, e.g. 1h or 7d
+        #[arg(value_parser = cli::parse_duration, required_unless_present = "clear")]
+        max_age: Option<std::time::Duration>,
+
+        /// Go back to the default refresh interval
+        #[arg(long, conflicts_with = "max_age")]
+        clear: bool,
     },
 
     /// Garbage collect cache metadata and stale state
     Gc,
 
+    /// Rebuild the state database from the on-disk cache (daemon must be stopped)
+    RepairDb,
+
+    /// Cross-check the state database against the mirrors on disk
+    FsckCache {
+        /// Bring the database in line with the disk (daemon must be stopped)
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Show when the scheduler will next refresh each repository
+    Schedule,
+
+    /// Print the cache root directory
+    CachePath {
+        /// Print the cache root, mount point, and socket path as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print the mount point
+    MountPath {
+        /// Print the cache root, mount point, and socket path as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print a `ghcd owner/repo` shell function that syncs a repo and cds into it
+    ShellInit {
+        /// Shell to generate the function for
+        #[arg(value_enum)]
+        shell: cli::Shell,
+    },
+
+    /// Show live filesystem operation rates
+    Top {
+        /// Seconds between refreshes
+        #[arg(long, value_name = "SECS", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+
+    /// Re-clone repos the daemon knows about whose mirror is missing
+    CloneMissing,
+
+    /// Keep every repo of an owner fresh, including repos created later
+    #[command(
+        after_help = "Listing an owner's repos uses the GitHub API and needs a token \
+(GHFS_GITHUB_TOKEN or GITHUB_TOKEN) in the daemon's environment."
+    )]
+    Watch {
+        /// Owner (user or organization) to watch
+        #[arg(required_unless_present = "list")]
+        owner: Option<String>,
+
+        /// Watch all of the owner's repos and add new ones as they appear
+        #[arg(short, long, required_unless_present_any = ["remove", "list"])]
+        recursive: bool,
+
+        /// Stop adding the owner's new repos; repos already added stay
+        #[arg(long, conflicts_with = "recursive")]
+        remove: bool,
+
+        /// List watched owners
+        #[arg(long, conflicts_with_all = ["owner", "recursive", "remove"])]
+        list: bool,
+    },
+
+    /// Stop adding an owner's new repos (same as watch --remove)
+    Unwatch {
+        /// Owner (user or organization) to stop watching
+        owner: String,
+    },
+
+    /// Show the sizes of the filesystem's in-memory tables
+    Fsstats {
+        /// Print the counts as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Follow sync events as the daemon publishes them
+    Events {
+        /// Print each event as a line of JSON
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Check dependencies
-    Doctor,
+    Doctor {
+        /// Also mount an empty filesystem on a temp dir, stat it, and unmount
+        #[arg(long)]
+        mount_test: bool,
+
+        /// Print the checks as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -64,6 +379,11 @@ enum ServiceAction {
         /// Install but don't start immediately
         #[arg(long)]
         no_start: bool,
+
+        /// Let other users access the mount: saves allow_other to the config
+        /// file and checks /etc/fuse.conf for user_allow_other
+        #[arg(long)]
+        allow_other: bool,
     },
 
     /// Stop and remove the daemon service
@@ -89,38 +409,200 @@ enum ServiceAction {
     Logs,
 }
 
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print a setting's effective value
+    Get {
+        /// Setting name, e.g. sync_concurrency
+        key: String,
+    },
+
+    /// Save a setting to the config file
+    Set {
+        /// Setting name, e.g. sync_concurrency
+        key: String,
+
+        /// New value
+        value: String,
+    },
+
+    /// List every setting with its effective value and where it comes from
+    List {
+        /// Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
 fn main() {
     env_logger::init();
 
     let cli = Cli::parse();
 
     let result = match cli.command {
-        Commands::Daemon => cmd_daemon(),
+        Commands::Daemon {
+            mount_point,
+            cache_dir,
+            post_sync_hook,
+            pre_mount_hook,
+            post_unmount_hook,
+            allow_other,
+            shared_repos,
+            remote_precheck,
+            follow_renames,
+            clone_missing,
+            list_remote_repos,
+            repo,
+        } => cmd_daemon(daemon::DaemonOptions {
+            mount_point,
+            cache_dir,
+            post_sync_hook,
+            pre_mount_hook,
+            post_unmount_hook,
+            allow_other,
+            shared_repos,
+            remote_precheck,
+            follow_renames,
+            clone_missing,
+            list_remote_repos,
+            single_repo: repo,
+        }),
         Commands::Service { action } => cmd_service(action),
-        Commands::Status => cmd_status(),
-        Commands::Sync { repo } => cmd_sync(&repo),
+        Commands::Config { action } => cmd_config(action),
+        Commands::Status { remote } => cmd_status(remote),
+        Commands::Upgrade { yes } => cli::upgrade(yes),
+        Commands::Sync {
+            repo,
+            quiet,
+            commit,
+            if_older_than,
+            prune,
+            submit: false,
+            json,
+            exit_unchanged,
+        } => cmd_sync(
+            &repo,
+            quiet,
+            commit.as_deref(),
+            if_older_than,
+            prune,
+            json,
+            exit_unchanged,
+        ),
+        Commands::Sync {
+            repo,
+            commit,
+            if_older_than,
+            submit: true,
+            ..
+        } => cmd_submit_sync(&repo, commit.as_deref(), if_older_than),
+        Commands::SyncStatus { id, json } => cli::sync_status(id, json),
+        Commands::Which { repo } => cmd_which(&repo),
+        Commands::Info { repo, json } => cmd_info(&repo, json),
+        Commands::History { repo, json } => cmd_history(&repo, json),
+        Commands::Bench { repo, json } => cmd_bench(&repo, json),
+        Commands::Diff {
+            repo,
+            from,
+            to,
+            patch,
+            name_only,
+            json,
+        } => cmd_diff(&repo, &from, &to, patch, name_only, json),
+        Commands::Rm { repo } => cmd_rm(&repo),
+        Commands::Link { repo, reference } => cmd_link(&repo, &reference),
+        Commands::SetBranch { repo, branch, .. } => cmd_set_branch(&repo, branch.as_deref()),
+        Commands::SetMaxAge { repo, max_age, .. } => cmd_set_max_age(&repo, max_age),
         Commands::Gc => cmd_gc(),
-        Commands::Doctor => cmd_doctor(),
+        Commands::RepairDb => cmd_repair_db(),
+        Commands::FsckCache { fix } => cmd_fsck_cache(fix),
+        Commands::Schedule => cmd_schedule(),
+        Commands::CachePath { json } => cmd_paths(json, |p| p.cache_root),
+        Commands::MountPath { json } => cmd_paths(json, |p| p.mount_point),
+        Commands::ShellInit { shell } => cmd_shell_init(shell),
+        Commands::Top { interval } => cmd_top(interval),
+        Commands::CloneMissing => cmd_clone_missing(),
+        Commands::Watch { owner, remove, .. } => cmd_watch(owner.as_deref(), remove),
+        Commands::Unwatch { owner } => cli::unwatch(&owner),
+        Commands::Fsstats { json } => cmd_fsstats(json),
+        Commands::Events { json } => cmd_events(json),
+        Commands::Doctor { mount_test, json } => cmd_doctor(mount_test, json),
     };
 
     if let Err(e) = result {
         eprintln!("Error: {}", e);
-        if let Some(ClientError::NotRunning) = e.downcast_ref::<ClientError>() {
+        if e.is_not_running() {
             eprintln!();
             eprintln!("Hint: Start the daemon with: ghfs service start");
         }
-        std::process::exit(1);
+        std::process::exit(exit_code::for_cli_error(&e));
     }
 }
 
-fn cmd_daemon() -> Result<(), Box<dyn std::error::Error>> {
-    daemon::start()?;
+fn cmd_daemon(options: daemon::DaemonOptions) -> Result<(), CliError> {
+    daemon::start(options)?;
+    Ok(())
+}
+
+fn cmd_config(action: ConfigAction) -> Result<(), CliError> {
+    let path = config::config_path();
+    let env = |name: &str| std::env::var(name).ok();
+    match action {
+        ConfigAction::Get { key } => {
+            let setting = config::lookup(&key)?;
+            let effective = config::ConfigFile::load(&path)?.effective(setting, env);
+            if let Some(value) = effective.value {
+                println!("{value}");
+            }
+        }
+        ConfigAction::Set { key, value } => {
+            let mut file = config::ConfigFile::load(&path)?;
+            file.set(&key, &value)?;
+            file.save(&path)?;
+            let setting = config::lookup(&key)?;
+            if env(setting.env).is_some() {
+                eprintln!(
+                    "Note: {} is set in the environment and overrides the config file",
+                    setting.env
+                );
+            }
+        }
+        ConfigAction::List { json } => {
+            let file = config::ConfigFile::load(&path)?;
+            let settings: Vec<_> = config::SETTINGS
+                .iter()
+                .map(|setting| file.effective(setting, env))
+                .collect();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&settings)?);
+                return Ok(());
+            }
+            println!("Config file: {}", path.display());
+            for setting in settings {
+                let source = match setting.source {
+                    config::Source::Env => format!("(from {})", setting.env),
+                    config::Source::File => "(from config file)".to_string(),
+                    config::Source::Default => "(default)".to_string(),
+                    config::Source::Unset => String::new(),
+                };
+                println!(
+                    "  {:<22} {:<30} {}",
+                    setting.key,
+                    setting.value.as_deref().unwrap_or("(unset)"),
+                    source
+                );
+            }
+        }
+    }
     Ok(())
 }
 
-fn cmd_service(action: ServiceAction) -> Result<(), Box<dyn std::error::Error>> {
+fn cmd_service(action: ServiceAction) -> Result<(), CliError> {
     match action {
-        ServiceAction::Install { no_start } => service::install(no_start)?,
+        ServiceAction::Install {
+            no_start,
+            allow_other,
+        } => service::install(no_start, allow_other)?,
         ServiceAction::Uninstall => service::uninstall()?,
         ServiceAction::Start => service::start()?,
         ServiceAction::Stop { force } => service::stop(force)?,
@@ -132,22 +614,91 @@ fn cmd_service(action: ServiceAction) -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
-fn cmd_status() -> Result<(), Box<dyn std::error::Error>> {
-    cli::print_status()
+fn cmd_status(remote: bool) -> Result<(), CliError> {
+    cli::print_status(remote)
 }
 
-fn cmd_sync(repo: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // Validate repo format first
-    let _: RepoKey = repo
-        .parse()
-        .map_err(|e| format!("Invalid repo format: {}", e))?;
+fn cmd_submit_sync(
+    repo: &str,
+    commit: Option<&str>,
+    if_older_than: Option<std::time::Duration>,
+) -> Result<(), CliError> {
+    if repo == "-" {
+        return Err(CliError::Usage(
+            "--async needs a single repo, not -".to_string(),
+        ));
+    }
+    let _: RepoKey = repo.parse()?;
+    cli::submit_sync(repo, commit, if_older_than)
+}
+
+fn cmd_sync(
+    repo: &str,
+    quiet: bool,
+    commit: Option<&str>,
+    if_older_than: Option<std::time::Duration>,
+    prune: bool,
+    json: bool,
+    exit_unchanged: bool,
+) -> Result<(), CliError> {
+    if repo == "-" {
+        if commit.is_some() || prune || json || exit_unchanged {
+            return Err(CliError::Usage(
+                "--commit, --prune, --json, and --exit-unchanged need a single repo, not -"
+                    .to_string(),
+            ));
+        }
+        return cli::sync_stdin(quiet, if_older_than);
+    }
 
-    println!("Syncing {}...", repo);
+    // Validate repo format first; a ParseError maps to the usage exit code.
+    let key: RepoKey = repo.parse()?;
+    let quiet = quiet || json;
+
+    if !quiet {
+        match commit {
+            Some(sha) => println!("Syncing {} to {}...", repo, sha),
+            None => println!("Syncing {}...", repo),
+        }
+    }
 
     let mut client = Client::connect()?;
-    let result = client.sync(repo)?;
+    let progress = if quiet {
+        None
+    } else {
+        cli::SyncProgress::start(&key)
+    };
+    let result = client.sync(repo, commit, if_older_than);
+    drop(progress);
+    let result = result?;
+
+    let pruned = if prune {
+        let status = client.status()?;
+        cli::ensure_not_open(Path::new(&status.mount_point), &key)?;
+        Some(client.prune(repo)?)
+    } else {
+        None
+    };
+
+    let unchanged = if exit_unchanged && !result.changed {
+        Err(CliError::Unchanged(key.to_string()))
+    } else {
+        Ok(())
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    }
+    if quiet {
+        return unchanged;
+    }
 
-    println!("Synced successfully");
+    if result.fresh {
+        println!("Already fresh, not synced");
+    } else if !result.changed {
+        println!("Synced; HEAD unchanged");
+    } else {
+        println!("Synced successfully");
+    }
     println!(
         "  Commit: {}",
         if result.commit.len() > 12 {
@@ -156,11 +707,157 @@ fn cmd_sync(repo: &str) -> Result<(), Box<dyn std::error::Error>> {
             &result.commit
         }
     );
+    if let Some(pruned) = pruned {
+        println!("  Pruned: {} bytes freed", pruned.freed_bytes);
+    }
+
+    unchanged
+}
+
+fn cmd_which(repo: &str) -> Result<(), CliError> {
+    let key: RepoKey = repo.parse()?;
+
+    let info = cli::which(&CachePaths::default(), &key)?;
+
+    println!("{}", key);
+    println!(
+        "  Mount:  {}",
+        daemon::mount_point()
+            .join(key.owner.as_str())
+            .join(key.repo.as_str())
+            .display()
+    );
+    println!("  Mirror: {}", info.mirror.display());
+    println!(
+        "  HEAD:   {}",
+        info.head_ref.as_deref().unwrap_or("(detached)")
+    );
+    println!(
+        "  Commit: {}",
+        info.commit.as_deref().unwrap_or("(no commits yet)")
+    );
+
+    Ok(())
+}
+
+fn cmd_info(repo: &str, json: bool) -> Result<(), CliError> {
+    let key: RepoKey = repo.parse()?;
+
+    let details = cli::info(&CachePaths::default(), &key)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&details)?);
+    } else {
+        print!("{}", details.render());
+    }
+
+    Ok(())
+}
+
+fn cmd_history(repo: &str, json: bool) -> Result<(), CliError> {
+    let key: RepoKey = repo.parse()?;
+
+    let history = cli::history(&CachePaths::default(), &key)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&history)?);
+    } else {
+        print!("{}", history.render());
+    }
+
+    Ok(())
+}
+
+fn cmd_diff(
+    repo: &str,
+    from: &str,
+    to: &str,
+    patch: bool,
+    name_only: bool,
+    json: bool,
+) -> Result<(), CliError> {
+    let key: RepoKey = repo.parse()?;
+    let paths = CachePaths::default();
+
+    if json || name_only {
+        let diff = cli::diff(&paths, &key, from, to)?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&diff)?);
+        } else {
+            for file in diff.files {
+                println!("{}", file.path);
+            }
+        }
+    } else {
+        let format = if patch {
+            cli::DiffFormat::Patch
+        } else {
+            cli::DiffFormat::Stat
+        };
+        print!("{}", cli::git_diff(&paths, &key, from, to, format)?);
+    }
+
+    Ok(())
+}
+
+fn cmd_bench(repo: &str, json: bool) -> Result<(), CliError> {
+    let key: RepoKey = repo.parse()?;
+
+    let report = cli::bench(&CachePaths::default(), &key)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.render());
+    }
+
+    Ok(())
+}
+
+fn cmd_rm(repo: &str) -> Result<(), CliError> {
+    let key: RepoKey = repo.parse()?;
+
+    cli::remove(&key)?;
+
+    println!("Removed {}", key);
+    Ok(())
+}
+
+fn cmd_link(repo: &str, reference: &str) -> Result<(), CliError> {
+    let key: RepoKey = repo.parse()?;
+    let reference: RepoKey = reference.parse()?;
 
+    if Store::new(CachePaths::default()).link(&key, &reference)? {
+        println!("{key} now shares objects with {reference}");
+    } else {
+        println!("{key} will share objects with {reference} once both are cached");
+    }
     Ok(())
 }
 
-fn cmd_gc() -> Result<(), Box<dyn std::error::Error>> {
+fn cmd_set_branch(repo: &str, branch: Option<&str>) -> Result<(), CliError> {
+    let key: RepoKey = repo.parse()?;
+
+    Store::new(CachePaths::default()).set_default_branch(&key, branch)?;
+    match branch {
+        Some(branch) => println!("{key} now serves {branch} instead of its default branch"),
+        None => println!("{key} now serves its default branch"),
+    }
+    Ok(())
+}
+
+fn cmd_set_max_age(repo: &str, max_age: Option<std::time::Duration>) -> Result<(), CliError> {
+    let key: RepoKey = repo.parse()?;
+
+    cli::set_max_age(&CachePaths::default(), &key, max_age)?;
+    match max_age {
+        Some(max_age) => println!("{key} will be refreshed every {}s", max_age.as_secs()),
+        None => println!("{key} will be refreshed on the default interval"),
+    }
+    Ok(())
+}
+
+fn cmd_gc() -> Result<(), CliError> {
     let mut client = Client::connect()?;
     let result = client.gc()?;
 
@@ -172,120 +869,124 @@ fn cmd_gc() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn cmd_doctor() -> Result<(), Box<dyn std::error::Error>> {
-    println!("GHFS System Check\n");
+fn cmd_schedule() -> Result<(), CliError> {
+    let mut client = Client::connect()?;
+    let result = client.schedule()?;
 
-    // Check git
-    let git_ok = std::process::Command::new("git")
-        .args(["--version"])
-        .output()
-        .map(|o| o.status.success())
-        .unwrap_or(false);
     println!(
-        "[{}] git: {}",
-        if git_ok { "OK" } else { "FAIL" },
-        if git_ok { "available" } else { "not found" }
+        "Scheduler (checks every {}s, refreshes after {}s)",
+        result.check_interval_secs, result.max_age_secs
     );
+    if result.entries.is_empty() {
+        println!("  (no repositories)");
+        return Ok(());
+    }
 
-    #[cfg(target_os = "linux")]
-    let (backend_ok, backend_label, backend_detail) = {
-        let dev_fuse_ok = std::path::Path::new("/dev/fuse").exists();
-        let fuse_helper_ok = command_in_path("fusermount3") || command_in_path("fusermount");
-        let ok = dev_fuse_ok && fuse_helper_ok;
-        let detail = if ok {
-            "available (/dev/fuse + fusermount helper)".to_string()
-        } else if !dev_fuse_ok && !fuse_helper_ok {
-            "missing /dev/fuse and fusermount helper (install/enable FUSE)".to_string()
-        } else if !dev_fuse_ok {
-            "missing /dev/fuse (install/enable FUSE kernel support)".to_string()
-        } else {
-            "missing fusermount helper (install FUSE userspace tools, usually fuse3)".to_string()
-        };
-        (ok, "FUSE backend", detail)
-    };
-
-    #[cfg(target_os = "macos")]
-    let (backend_ok, backend_label, backend_detail) = {
-        let mount_nfs_ok = std::process::Command::new("/sbin/mount_nfs")
-            .arg("-h")
-            .output()
-            .is_ok();
+    for entry in &result.entries {
+        let name = format!("{}/{}", entry.owner, entry.repo);
+        println!(
+            "  {:<40} {:<8} last sync: {:<10} next due: {}",
+            name,
+            if entry.eligible { "due" } else { "waiting" },
+            entry.last_sync.as_deref().unwrap_or("never"),
+            entry.next_due.as_deref().unwrap_or("now"),
+        );
+    }
 
-        let detail = if mount_nfs_ok {
-            "mount_nfs available".to_string()
-        } else {
-            "mount_nfs not found (install/enable macOS NFS client tools)".to_string()
-        };
+    Ok(())
+}
 
-        (mount_nfs_ok, "NFS backend", detail)
-    };
+fn cmd_paths(json: bool, pick: fn(cli::ResolvedPaths) -> PathBuf) -> Result<(), CliError> {
+    let paths = cli::ResolvedPaths::resolve();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&paths)?);
+    } else {
+        println!("{}", pick(paths).display());
+    }
+    Ok(())
+}
 
-    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
-    let (backend_ok, backend_label, backend_detail) =
-        (false, "Backend", "unsupported platform".to_string());
+fn cmd_shell_init(shell: cli::Shell) -> Result<(), CliError> {
+    print!("{}", cli::shell_init(shell, &daemon::mount_point()));
+    Ok(())
+}
 
-    println!(
-        "[{}] {}: {}",
-        if backend_ok { "OK" } else { "FAIL" },
-        backend_label,
-        backend_detail
-    );
+fn cmd_top(interval: u64) -> Result<(), CliError> {
+    cli::top(std::time::Duration::from_secs(interval))
+}
 
-    // Check cache directory
-    let cache_dir = dirs::cache_dir().map(|p| p.join("ghfs"));
-    let cache_ok = cache_dir.as_ref().map(|p| p.exists()).unwrap_or(false);
-    println!(
-        "[{}] Cache dir: {}",
-        if cache_ok { "OK" } else { "INFO" },
-        cache_dir
-            .as_ref()
-            .map(|p| p.display().to_string())
-            .unwrap_or("unknown".into())
-    );
+fn cmd_clone_missing() -> Result<(), CliError> {
+    let mut client = Client::connect()?;
+    let result = client.clone_missing()?;
 
-    // Check if daemon is running
-    let daemon_running = daemon::is_daemon_running();
-    println!(
-        "[{}] Daemon: {}",
-        if daemon_running { "OK" } else { "INFO" },
-        if daemon_running {
-            "running"
-        } else {
-            "not running"
-        }
-    );
+    if result.queued.is_empty() {
+        println!("Every known repo has a mirror");
+        return Ok(());
+    }
+    println!("Queued {} repos for cloning:", result.queued.len());
+    for repo in &result.queued {
+        println!("  {repo}");
+    }
+    Ok(())
+}
 
-    match service::installation_status() {
-        Ok(install) if install.installed => {
-            println!(
-                "[OK] Service: installed ({})",
-                install.backend.installed_kind()
-            );
-        }
-        Ok(_) => {
-            println!("[INFO] Service: not installed (run 'ghfs service install')");
-        }
-        Err(service::ServiceError::UnsupportedPlatform)
-        | Err(service::ServiceError::BackendUnavailable(_)) => {
-            println!("[INFO] Service: unsupported on this platform");
-        }
-        Err(err) => {
-            println!("[INFO] Service: check failed ({})", err);
-        }
+fn cmd_watch(owner: Option<&str>, remove: bool) -> Result<(), CliError> {
+    match owner {
+        Some(owner) if remove => cli::unwatch(owner),
+        owner => cli::watch(owner),
     }
+}
 
-    // Check mount point
-    let mount_point = daemon::mount_point();
-    let mount_ok = mount_point.exists();
-    println!(
-        "[{}] Mount point: {}",
-        if mount_ok { "OK" } else { "INFO" },
-        mount_point.display()
-    );
+fn cmd_fsstats(json: bool) -> Result<(), CliError> {
+    cli::fs_stats(json)
+}
+
+fn cmd_events(json: bool) -> Result<(), CliError> {
+    cli::events(json)
+}
+
+fn cmd_repair_db() -> Result<(), CliError> {
+    let stats = daemon::repair_db(&CachePaths::default()).map_err(|e| match e {
+        daemon::DaemonError::AlreadyRunning => CliError::DaemonRunning,
+        e => e.into(),
+    })?;
+
+    println!("State database rebuilt");
+    println!("  Repositories restored: {}", stats.repos_restored);
+    println!("  HEAD commits resolved: {}", stats.heads_resolved);
+
+    Ok(())
+}
 
-    if !git_ok || !backend_ok {
-        std::process::exit(1);
+fn cmd_fsck_cache(fix: bool) -> Result<(), CliError> {
+    let mismatches = daemon::fsck_cache(&CachePaths::default(), fix).map_err(|e| match e {
+        daemon::DaemonError::AlreadyRunning => CliError::DaemonRunning,
+        e => e.into(),
+    })?;
+
+    if mismatches.is_empty() {
+        println!("State database matches the cache");
+        return Ok(());
+    }
+    for mismatch in &mismatches {
+        println!("{mismatch}");
     }
+    if fix {
+        println!("Fixed {} mismatches", mismatches.len());
+        return Ok(());
+    }
+    Err(CliError::CacheMismatch(mismatches.len()))
+}
 
+fn cmd_doctor(mount_test: bool, json: bool) -> Result<(), CliError> {
+    let report = cli::doctor(mount_test);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.render());
+    }
+    if !report.passed {
+        return Err(CliError::DoctorFailed(report.failures()));
+    }
     Ok(())
 }
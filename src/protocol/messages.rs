@@ -7,8 +7,34 @@ pub enum Request {
     /// Get daemon status
     Status,
 
-    /// Force sync a repo
-    Sync { repo: String },
+    /// Force sync a repo, optionally serving `commit` as HEAD until the next
+    /// sync. With `if_older_than_secs`, only sync if the mirror last fetched
+    /// longer ago than that
+    Sync {
+        repo: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        commit: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        if_older_than_secs: Option<u64>,
+    },
+
+    /// Queue a sync like `Sync` and return its id without waiting
+    SubmitSync {
+        repo: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        commit: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        if_older_than_secs: Option<u64>,
+    },
+
+    /// Check on a sync queued by `SubmitSync`
+    SyncStatus { sync_id: u64 },
+
+    /// Delete a repo's mirror and state
+    Remove { repo: String },
+
+    /// Repack a repo's mirror and delete unreachable objects immediately
+    Prune { repo: String },
 
     /// Run cache metadata garbage collection
     Gc,
@@ -16,6 +42,34 @@ pub enum Request {
     /// List all known repos
     List,
 
+    /// Show the scheduler's refresh plan without syncing anything
+    Schedule,
+
+    /// Compare each synced repo's commit with the remote's current head
+    RemoteStatus,
+
+    /// Snapshot the filesystem operation counters
+    OpStats,
+
+    /// Report the sizes of the filesystem's in-memory tables
+    FsStats,
+
+    /// Queue a clone for every known repo whose mirror is missing
+    CloneMissing,
+
+    /// Watch every repo of `owner`, including repos created later
+    Watch { owner: String },
+
+    /// Stop auto-adding `owner`'s new repos. Repos already added stay
+    Unwatch { owner: String },
+
+    /// List watched owners
+    Watches,
+
+    /// Stream sync events. The daemon acknowledges with `null`, then writes
+    /// one [`SyncEvent`] per line until the client disconnects
+    Subscribe,
+
     /// Get daemon version
     Version,
 
@@ -40,6 +94,58 @@ pub struct StatusResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncResult {
     pub commit: String,
+    /// The mirror was recent enough that nothing was fetched
+    #[serde(default)]
+    pub fresh: bool,
+    /// HEAD moved. Daemons that predate this field are assumed to have
+    /// moved it, so scripts rebuild rather than miss an update.
+    #[serde(default = "assume_changed")]
+    pub changed: bool,
+}
+
+fn assume_changed() -> bool {
+    true
+}
+
+/// SubmitSync response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitSyncResult {
+    pub sync_id: u64,
+}
+
+/// Where a submitted sync has got to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncState {
+    Pending,
+    Done,
+    Failed,
+}
+
+/// SyncStatus response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncStatusResult {
+    pub sync_id: u64,
+    pub repo: String,
+    pub state: SyncState,
+    /// The commit served, once done
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// The mirror was recent enough that nothing was fetched
+    #[serde(default)]
+    pub fresh: bool,
+    /// HEAD moved, once done
+    #[serde(default)]
+    pub changed: bool,
+    /// Why the sync failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Prune response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PruneResult {
+    pub freed_bytes: u64,
 }
 
 /// GC response
@@ -60,6 +166,9 @@ pub struct RepoInfo {
     pub last_access: Option<String>, // Human-readable timestamp
     #[serde(default)]
     pub total_size_bytes: u64,
+    /// New `owner/repo` when GitHub reported this repo as renamed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_to: Option<String>,
 }
 
 /// List response
@@ -68,6 +177,133 @@ pub struct ListResult {
     pub repos: Vec<RepoInfo>,
 }
 
+/// How a repo's synced commit compares with its remote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteStatus {
+    UpToDate,
+    Behind,
+    /// Never synced, or the remote couldn't be asked
+    Unknown,
+}
+
+impl RemoteStatus {
+    /// Compare the synced `local` commit with the `remote` head.
+    pub fn compare(local: Option<&str>, remote: Option<&str>) -> Self {
+        match (local, remote) {
+            (Some(local), Some(remote)) if local.eq_ignore_ascii_case(remote) => Self::UpToDate,
+            (Some(_), Some(_)) => Self::Behind,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Single repo entry in a remote status check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteCheck {
+    pub owner: String,
+    pub repo: String,
+    pub commit: Option<String>,
+    pub remote_commit: Option<String>,
+    pub status: RemoteStatus,
+}
+
+/// Remote status response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteStatusResult {
+    pub checks: Vec<RemoteCheck>,
+}
+
+/// Single repo entry in the scheduler's plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    pub owner: String,
+    pub repo: String,
+    pub last_sync: Option<String>, // Human-readable timestamp
+    pub next_due: Option<String>,  // Human-readable timestamp, None if never synced
+    pub eligible: bool,
+}
+
+/// Schedule response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleResult {
+    pub check_interval_secs: u64,
+    pub max_age_secs: u64,
+    pub entries: Vec<ScheduleEntry>,
+}
+
+/// Op stats response: cumulative filesystem operation counts since the
+/// daemon started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpStatsResult {
+    pub uptime_ms: u64,
+    pub lookup: u64,
+    pub getattr: u64,
+    pub readdir: u64,
+    pub read: u64,
+    pub materialize: u64,
+}
+
+/// Fs stats response: current sizes of the filesystem's in-memory tables
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FsStatsResult {
+    pub passthrough_inodes: u64,
+    pub virtual_inodes: u64,
+    pub open_files: u64,
+    pub cached_trees: u64,
+    pub cached_blobs: u64,
+}
+
+/// Clone-missing response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloneMissingResult {
+    /// `owner/repo` of every repo queued for cloning
+    pub queued: Vec<String>,
+}
+
+/// Watch and unwatch response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchResult {
+    /// `owner/repo` of every repo the watch added and queued for cloning
+    pub added: Vec<String>,
+    /// Unix timestamp the owner had been watched since before this call,
+    /// `None` if it wasn't watched
+    #[serde(default)]
+    pub watched_since: Option<i64>,
+}
+
+/// Watches response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchesResult {
+    pub owners: Vec<String>,
+}
+
+/// Event pushed to subscribers as syncs and refreshes complete
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SyncEvent {
+    /// A sync or refresh moved the repo's HEAD
+    Synced {
+        repo: String,
+        commit: String,
+        previous: Option<String>,
+        at: i64, // Unix timestamp
+    },
+    /// A sync or refresh failed
+    Failed {
+        repo: String,
+        error: String,
+        at: i64, // Unix timestamp
+    },
+    /// A clone or fetch in progress reached `percent` of `phase` (git's
+    /// progress label, e.g. "Receiving objects")
+    Progress {
+        repo: String,
+        phase: String,
+        percent: u8,
+    },
+}
+
 /// Version response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VersionResult {
@@ -80,10 +316,21 @@ pub struct VersionResult {
 #[serde(untagged)]
 pub enum Response {
     Status(StatusResult),
+    // Before `SubmitSync` and `Sync`, whose fields it also carries.
+    SyncStatus(SyncStatusResult),
+    SubmitSync(SubmitSyncResult),
     Sync(SyncResult),
     Gc(GcResult),
     List(ListResult),
     Version(VersionResult),
+    Schedule(ScheduleResult),
+    OpStats(OpStatsResult),
+    FsStats(FsStatsResult),
+    CloneMissing(CloneMissingResult),
+    Prune(PruneResult),
+    Watch(WatchResult),
+    Watches(WatchesResult),
+    RemoteStatus(RemoteStatusResult),
     Ok(()), // For stop - unit type serializes as null
 }
 
@@ -95,6 +342,13 @@ pub struct RpcError {
 }
 
 impl RpcError {
+    pub const INVALID_PARAMS: i32 = -32602;
+    pub const INTERNAL: i32 = -32603;
+    pub const NOT_FOUND: i32 = -1;
+    pub const NETWORK: i32 = -2;
+    pub const UNAVAILABLE: i32 = -3;
+    pub const BUSY: i32 = -4;
+
     pub fn new(code: i32, message: impl Into<String>) -> Self {
         Self {
             code,
@@ -103,15 +357,30 @@ impl RpcError {
     }
 
     pub fn invalid_params(msg: impl Into<String>) -> Self {
-        Self::new(-32602, msg)
+        Self::new(Self::INVALID_PARAMS, msg)
     }
 
     pub fn internal(msg: impl Into<String>) -> Self {
-        Self::new(-32603, msg)
+        Self::new(Self::INTERNAL, msg)
     }
 
     pub fn not_found(msg: impl Into<String>) -> Self {
-        Self::new(-1, msg)
+        Self::new(Self::NOT_FOUND, msg)
+    }
+
+    /// Clone/fetch against the remote failed.
+    pub fn network(msg: impl Into<String>) -> Self {
+        Self::new(Self::NETWORK, msg)
+    }
+
+    /// GitHub refuses to serve the repo (takedown, disabled, terms).
+    pub fn unavailable(msg: impl Into<String>) -> Self {
+        Self::new(Self::UNAVAILABLE, msg)
+    }
+
+    /// The repo is in use (e.g. files open on the mount).
+    pub fn busy(msg: impl Into<String>) -> Self {
+        Self::new(Self::BUSY, msg)
     }
 }
 
@@ -123,6 +392,8 @@ mod tests {
     fn test_serialize_request_with_params() {
         let req = Request::Sync {
             repo: "owner/repo".to_string(),
+            commit: None,
+            if_older_than_secs: None,
         };
         let json = serde_json::to_string(&req).unwrap();
 
@@ -161,13 +432,156 @@ mod tests {
         assert!(!json.contains(r#""params""#));
     }
 
+    #[test]
+    fn test_schedule_response_does_not_parse_as_list() {
+        let resp = Response::Schedule(ScheduleResult {
+            check_interval_secs: 300,
+            max_age_secs: 86400,
+            entries: vec![ScheduleEntry {
+                owner: "octocat".to_string(),
+                repo: "hello-world".to_string(),
+                last_sync: None,
+                next_due: None,
+                eligible: true,
+            }],
+        });
+        let json = serde_json::to_string(&resp).unwrap();
+        let parsed: Response = serde_json::from_str(&json).unwrap();
+
+        match parsed {
+            Response::Schedule(s) => {
+                assert_eq!(s.entries.len(), 1);
+                assert!(s.entries[0].eligible);
+            }
+            other => panic!("Expected Schedule response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_op_stats_response_round_trips() {
+        let resp = Response::OpStats(OpStatsResult {
+            uptime_ms: 1_500,
+            lookup: 3,
+            read: 7,
+            ..Default::default()
+        });
+        let json = serde_json::to_string(&resp).unwrap();
+        let parsed: Response = serde_json::from_str(&json).unwrap();
+
+        match parsed {
+            Response::OpStats(s) => {
+                assert_eq!(s.lookup, 3);
+                assert_eq!(s.read, 7);
+            }
+            other => panic!("Expected OpStats response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_fs_stats_response_round_trips() {
+        let resp = Response::FsStats(FsStatsResult {
+            passthrough_inodes: 12,
+            virtual_inodes: 4,
+            open_files: 1,
+            ..Default::default()
+        });
+        let json = serde_json::to_string(&resp).unwrap();
+        let parsed: Response = serde_json::from_str(&json).unwrap();
+
+        match parsed {
+            Response::FsStats(s) => {
+                assert_eq!(s.passthrough_inodes, 12);
+                assert_eq!(s.open_files, 1);
+            }
+            other => panic!("Expected FsStats response, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_submitted_sync_responses_round_trip() {
+        let json =
+            serde_json::to_string(&Response::SubmitSync(SubmitSyncResult { sync_id: 7 })).unwrap();
+        match serde_json::from_str::<Response>(&json).unwrap() {
+            Response::SubmitSync(s) => assert_eq!(s.sync_id, 7),
+            other => panic!("Expected SubmitSync response, got {:?}", other),
+        }
+
+        // A finished status carries a commit like `Sync`, but stays a status.
+        let json = serde_json::to_string(&Response::SyncStatus(SyncStatusResult {
+            sync_id: 7,
+            repo: "octocat/hello-world".to_string(),
+            state: SyncState::Done,
+            commit: Some("abc123".to_string()),
+            fresh: false,
+            changed: true,
+            error: None,
+        }))
+        .unwrap();
+        assert!(json.contains(r#""state":"done""#));
+        match serde_json::from_str::<Response>(&json).unwrap() {
+            Response::SyncStatus(s) => {
+                assert_eq!(s.state, SyncState::Done);
+                assert_eq!(s.commit.as_deref(), Some("abc123"));
+            }
+            other => panic!("Expected SyncStatus response, got {:?}", other),
+        }
+
+        let json = serde_json::to_string(&Response::Sync(SyncResult {
+            commit: "abc123".to_string(),
+            fresh: true,
+            changed: false,
+        }))
+        .unwrap();
+        assert!(matches!(
+            serde_json::from_str::<Response>(&json).unwrap(),
+            Response::Sync(SyncResult { changed: false, .. })
+        ));
+        // An older daemon's reply says nothing about it.
+        assert!(matches!(
+            serde_json::from_str::<Response>(r#"{"commit":"abc123"}"#).unwrap(),
+            Response::Sync(SyncResult { changed: true, .. })
+        ));
+    }
+
+    #[test]
+    fn test_sync_event_is_tagged() {
+        let event = SyncEvent::Synced {
+            repo: "octocat/hello-world".to_string(),
+            commit: "abc123".to_string(),
+            previous: None,
+            at: 1_700_000_000,
+        };
+        let json = serde_json::to_string(&event).unwrap();
+
+        assert!(json.contains(r#""event":"synced""#));
+        assert!(json.contains(r#""commit":"abc123""#));
+        assert_eq!(serde_json::from_str::<SyncEvent>(&json).unwrap(), event);
+
+        let progress = SyncEvent::Progress {
+            repo: "octocat/hello-world".to_string(),
+            phase: "Receiving objects".to_string(),
+            percent: 45,
+        };
+        let json = serde_json::to_string(&progress).unwrap();
+        assert!(json.contains(r#""event":"progress""#));
+        assert_eq!(serde_json::from_str::<SyncEvent>(&json).unwrap(), progress);
+    }
+
     #[test]
     fn test_deserialize_request_with_params() {
         let json = r#"{"method":"sync","params":{"repo":"owner/repo"}}"#;
         let req: Request = serde_json::from_str(json).unwrap();
 
         match req {
-            Request::Sync { repo } => assert_eq!(repo, "owner/repo"),
+            Request::Sync {
+                repo,
+                commit,
+                if_older_than_secs,
+            } => {
+                assert_eq!(repo, "owner/repo");
+                assert_eq!(commit, None);
+                assert_eq!(if_older_than_secs, None);
+            }
             _ => panic!("Expected Sync request"),
         }
     }
@@ -216,5 +630,32 @@ mod tests {
 
         let not_found = RpcError::not_found("repo not found");
         assert_eq!(not_found.code, -1);
+
+        let network = RpcError::network("fetch failed");
+        assert_eq!(network.code, -2);
+    }
+
+    #[test]
+    fn remote_status_compares_synced_and_remote_heads() {
+        let a = "0123456789abcdef0123456789abcdef01234567";
+        let b = "fedcba9876543210fedcba9876543210fedcba98";
+        assert_eq!(
+            RemoteStatus::compare(Some(a), Some(a)),
+            RemoteStatus::UpToDate
+        );
+        assert_eq!(
+            RemoteStatus::compare(Some(a), Some(&a.to_uppercase())),
+            RemoteStatus::UpToDate
+        );
+        assert_eq!(
+            RemoteStatus::compare(Some(a), Some(b)),
+            RemoteStatus::Behind
+        );
+        assert_eq!(RemoteStatus::compare(None, Some(b)), RemoteStatus::Unknown);
+        assert_eq!(RemoteStatus::compare(Some(a), None), RemoteStatus::Unknown);
+        assert_eq!(
+            serde_json::to_string(&RemoteStatus::UpToDate).unwrap(),
+            "\"up_to_date\""
+        );
     }
 }
@@ -118,6 +118,8 @@ mod tests {
         let req = RpcRequest::new(
             Request::Sync {
                 repo: "owner/repo".to_string(),
+                commit: None,
+                if_older_than_secs: None,
             },
             1,
         );
@@ -135,6 +137,8 @@ mod tests {
         let original = RpcRequest::new(
             Request::Sync {
                 repo: "rust-lang/rust".to_string(),
+                commit: None,
+                if_older_than_secs: None,
             },
             42,
         );
@@ -150,7 +154,7 @@ mod tests {
         assert_eq!(parsed.jsonrpc, "2.0");
         assert_eq!(parsed.id, 42);
         match parsed.request {
-            Request::Sync { repo } => assert_eq!(repo, "rust-lang/rust"),
+            Request::Sync { repo, .. } => assert_eq!(repo, "rust-lang/rust"),
             _ => panic!("Expected Sync request"),
         }
     }
@@ -273,6 +277,8 @@ mod tests {
             &RpcRequest::new(
                 Request::Sync {
                     repo: "foo/bar".to_string(),
+                    commit: None,
+                    if_older_than_secs: None,
                 },
                 2,
             ),
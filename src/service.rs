@@ -5,6 +5,7 @@ use std::process::Command;
 use thiserror::Error;
 
 use crate::cli::{Client, ClientError};
+use crate::config::{self, ConfigError};
 use crate::daemon;
 use crate::protocol::{Request, VersionResult};
 
@@ -12,6 +13,7 @@ const SYSTEMD_SERVICE_NAME: &str = "ghfs";
 const SYSTEMD_UNIT_FILE: &str = "ghfs.service";
 const LAUNCHD_LABEL: &str = "com.ghfs.daemon";
 const LAUNCHD_PLIST_FILE: &str = "com.ghfs.daemon.plist";
+const FUSE_CONF: &str = "/etc/fuse.conf";
 const DEFAULT_SERVICE_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/usr/local/sbin:/usr/sbin:/sbin";
 
 #[derive(Debug, Clone, Copy)]
@@ -101,6 +103,9 @@ pub enum ServiceError {
     #[error("client error: {0}")]
     Client(#[from] ClientError),
 
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
     #[error("command failed: {command} (exit code: {code:?}){stderr}")]
     CommandFailed {
         command: String,
@@ -109,13 +114,92 @@ pub enum ServiceError {
     },
 }
 
-pub fn install(no_start: bool) -> Result<(), ServiceError> {
+pub fn install(no_start: bool, allow_other: bool) -> Result<(), ServiceError> {
+    if allow_other {
+        enable_allow_other()?;
+    }
     match ServiceBackend::detect()? {
         ServiceBackend::Systemd => install_systemd(no_start),
         ServiceBackend::Launchd => install_launchd(no_start),
     }
 }
 
+/// What `service install --allow-other` does about `/etc/fuse.conf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuseConfAction {
+    /// `user_allow_other` is already set.
+    Ready,
+    /// Not set, and we may write the file: append the line.
+    Append,
+    /// Not set, and we can't write the file: tell the user how.
+    Instruct,
+}
+
+/// Whether fuse.conf `contents` enable `user_allow_other`, ignoring
+/// comments.
+pub fn fuse_conf_allows_other(contents: &str) -> bool {
+    contents
+        .lines()
+        .any(|line| line.split('#').next().unwrap_or("").trim() == "user_allow_other")
+}
+
+/// Decide what to do given fuse.conf's `contents` (`None` if it doesn't
+/// exist) and whether we can write it.
+pub fn fuse_conf_action(contents: Option<&str>, writable: bool) -> FuseConfAction {
+    if contents.is_some_and(fuse_conf_allows_other) {
+        FuseConfAction::Ready
+    } else if writable {
+        FuseConfAction::Append
+    } else {
+        FuseConfAction::Instruct
+    }
+}
+
+/// Save `allow_other` to the config file so the installed daemon mounts
+/// with it, and on Linux make sure fusermount will permit it.
+fn enable_allow_other() -> Result<(), ServiceError> {
+    let path = config::config_path();
+    let mut file = config::ConfigFile::load(&path)?;
+    file.set("allow_other", "true")?;
+    file.save(&path)?;
+    println!(
+        "Saved allow_other = true to {}",
+        display_home_relative(&path)
+    );
+
+    // The NFS mount on macOS needs no system setting; access follows the
+    // modes ghfs reports.
+    if !cfg!(target_os = "linux") {
+        return Ok(());
+    }
+    let contents = match fs::read_to_string(FUSE_CONF) {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let writable = unsafe { libc::geteuid() } == 0;
+    match fuse_conf_action(contents.as_deref(), writable) {
+        FuseConfAction::Ready => {}
+        FuseConfAction::Append => {
+            let mut updated = contents.unwrap_or_default();
+            if !updated.is_empty() && !updated.ends_with('\n') {
+                updated.push('\n');
+            }
+            updated.push_str("user_allow_other\n");
+            fs::write(FUSE_CONF, updated)?;
+            println!("Added user_allow_other to {FUSE_CONF}");
+        }
+        FuseConfAction::Instruct => {
+            eprintln!(
+                "Warning: {FUSE_CONF} doesn't enable user_allow_other, so the mount will fail \
+                 with allow_other. Enable it with:"
+            );
+            eprintln!("    echo user_allow_other | sudo tee -a {FUSE_CONF}");
+        }
+    }
+    Ok(())
+}
+
 pub fn uninstall() -> Result<(), ServiceError> {
     match ServiceBackend::detect()? {
         ServiceBackend::Systemd => uninstall_systemd(),
@@ -214,10 +298,10 @@ pub fn status() -> Result<(), ServiceError> {
         println!("Managed: Nix");
     }
 
-    if let ServiceBackend::Systemd = install.backend {
-        if let Ok(enabled) = systemd_is_enabled() {
-            println!("Enabled: {}", if enabled { "yes" } else { "no" });
-        }
+    if let ServiceBackend::Systemd = install.backend
+        && let Ok(enabled) = systemd_is_enabled()
+    {
+        println!("Enabled: {}", if enabled { "yes" } else { "no" });
     }
 
     if let Some(version) = daemon {
@@ -228,7 +312,7 @@ pub fn status() -> Result<(), ServiceError> {
                 version.version, cli_version
             );
             println!(
-                "         ! Daemon is outdated. Run 'ghfs service restart' to pick up the new version."
+                "         ! Daemon is outdated. Run 'ghfs upgrade' to restart it on the new version."
             );
         }
     }
@@ -274,7 +358,7 @@ pub fn kill_open_mount_processes() -> Result<(), ServiceError> {
 fn try_graceful_stop() {
     if let Ok(mut client) = Client::connect() {
         let _ = client.call(Request::Stop);
-        // Give spawn_unmount time to run
+        // Give the daemon time to notice shutdown and unmount
         std::thread::sleep(std::time::Duration::from_millis(300));
     }
 }
@@ -644,10 +728,10 @@ fn parse_launchctl_pid(output: &str) -> Option<u32> {
         }
 
         let digits: String = line.chars().filter(|c| c.is_ascii_digit()).collect();
-        if let Ok(pid) = digits.parse::<u32>() {
-            if pid > 0 {
-                return Some(pid);
-            }
+        if let Ok(pid) = digits.parse::<u32>()
+            && pid > 0
+        {
+            return Some(pid);
         }
     }
     None
@@ -816,11 +900,11 @@ fn find_open_file_pids(target_path: &str) -> Vec<u32> {
         };
 
         for fd_entry in fds.flatten() {
-            if let Ok(link_target) = fs::read_link(fd_entry.path()) {
-                if link_target.to_string_lossy().starts_with(target_path) {
-                    pids.push(pid);
-                    break;
-                }
+            if let Ok(link_target) = fs::read_link(fd_entry.path())
+                && link_target.to_string_lossy().starts_with(target_path)
+            {
+                pids.push(pid);
+                break;
             }
         }
     }
@@ -859,7 +943,33 @@ fn find_open_file_pids(_target_path: &str) -> Vec<u32> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_launchctl_pid;
+    use super::{FuseConfAction, fuse_conf_action, parse_launchctl_pid};
+
+    #[test]
+    fn fuse_conf_decides_between_ready_append_and_instruct() {
+        let enabled = "# mount_max = 1000\nuser_allow_other\n";
+        assert_eq!(
+            fuse_conf_action(Some(enabled), false),
+            FuseConfAction::Ready
+        );
+        assert_eq!(fuse_conf_action(Some(enabled), true), FuseConfAction::Ready);
+        assert_eq!(
+            fuse_conf_action(Some("  user_allow_other # shared host\n"), false),
+            FuseConfAction::Ready
+        );
+
+        let commented = "#user_allow_other\n";
+        assert_eq!(
+            fuse_conf_action(Some(commented), false),
+            FuseConfAction::Instruct
+        );
+        assert_eq!(
+            fuse_conf_action(Some(commented), true),
+            FuseConfAction::Append
+        );
+        assert_eq!(fuse_conf_action(None, false), FuseConfAction::Instruct);
+        assert_eq!(fuse_conf_action(None, true), FuseConfAction::Append);
+    }
 
     #[test]
     fn parse_pid_from_launchctl_output() {
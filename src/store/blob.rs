@@ -17,11 +17,11 @@ use git2::Oid;
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::process::{Child, ChildStdin, ChildStdout, Stdio};
 use std::sync::{Arc, Condvar, Mutex};
 use thiserror::Error;
 
-use super::git::GitError;
+use super::git::{GitCli, GitError};
 
 /// Errors returned by blob hydration.
 #[derive(Error, Debug)]
@@ -126,11 +126,11 @@ impl BlobCache {
         // requested OID. We only check the prefix we asked for; a full
         // re-hash is left to `git fsck`-style maintenance, not the hot path.
         let oid_hex = oid.to_string();
-        if let Some(stored) = stored_oid_hex(oid, expected_size) {
-            if !oid_hex.starts_with(&stored[..oid_hex.len().min(stored.len())]) {
-                let _ = std::fs::remove_file(&temp);
-                return Err(BlobError::ChecksumMismatch { expected: oid_hex });
-            }
+        if let Some(stored) = stored_oid_hex(oid, expected_size)
+            && !oid_hex.starts_with(&stored[..oid_hex.len().min(stored.len())])
+        {
+            let _ = std::fs::remove_file(&temp);
+            return Err(BlobError::ChecksumMismatch { expected: oid_hex });
         }
 
         let final_path = self.path(oid);
@@ -160,15 +160,16 @@ pub struct CatFileBatch {
 
 impl CatFileBatch {
     /// Spawn a `git cat-file --batch` against the mirror at `mirror_path`.
-    pub fn spawn(mirror_path: &Path) -> Result<Self, BlobError> {
+    ///
+    /// Uses `cli`'s hardened command so promisor lazy fetches get the same
+    /// environment and proxy settings as clone/fetch.
+    pub fn spawn(cli: &GitCli, mirror_path: &Path) -> Result<Self, BlobError> {
         // Inline `-c` options must precede the subcommand so git accepts them.
-        let mut child = Command::new("git")
-            .args(["-c", "core.hooksPath="])
+        let mut child = cli
+            .command()
             .arg("-C")
             .arg(mirror_path)
             .args(["cat-file", "--batch"])
-            .env("GIT_LFS_SKIP_SMUDGE", "1")
-            .env("GIT_TERMINAL_PROMPT", "0")
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::null())
@@ -300,6 +301,7 @@ impl Pool {
 /// per-OID dedup map. It's cheap to clone (the batch process is wrapped in
 /// `Mutex<Option<_>>` and respawned if poisoned/EOFed).
 pub struct Hydrator {
+    cli: GitCli,
     mirror_path: PathBuf,
     blobs: BlobCache,
     batch: Mutex<Option<CatFileBatch>>,
@@ -308,9 +310,10 @@ pub struct Hydrator {
 
 impl Hydrator {
     /// Create a hydrator for `mirror_path` writing blobs to `blobs`. The
-    /// `cat-file` process is spawned lazily on first use.
-    pub fn new(mirror_path: PathBuf, blobs: BlobCache) -> Self {
+    /// `cat-file` process is spawned lazily on first use via `cli`.
+    pub fn new(cli: GitCli, mirror_path: PathBuf, blobs: BlobCache) -> Self {
         Self {
+            cli,
             mirror_path,
             blobs,
             batch: Mutex::new(None),
@@ -389,7 +392,7 @@ impl Hydrator {
         for _ in 0..2 {
             let mut guard = self.batch.lock().expect("batch mutex poisoned");
             if guard.is_none() {
-                match CatFileBatch::spawn(&self.mirror_path) {
+                match CatFileBatch::spawn(&self.cli, &self.mirror_path) {
                     Ok(b) => *guard = Some(b),
                     Err(e) => {
                         drop(guard);
@@ -507,7 +510,7 @@ mod tests {
         let (dir, _repo, _head, blob_oid) = make_repo_with_blob();
         let blobs_dir = tempdir().unwrap();
         let cache = BlobCache::new(blobs_dir.path().to_path_buf(), "sha1");
-        let hydrator = Hydrator::new(dir.path().to_path_buf(), cache);
+        let hydrator = Hydrator::new(GitCli::new(), dir.path().to_path_buf(), cache);
         let (path, size) = hydrator.hydrate(blob_oid).unwrap();
         assert_eq!(std::fs::read(&path).unwrap(), b"hello world\n");
         assert_eq!(size, b"hello world\n".len() as u64);
@@ -518,7 +521,7 @@ mod tests {
         let (dir, _repo, _head, blob_oid) = make_repo_with_blob();
         let blobs_dir = tempdir().unwrap();
         let cache = BlobCache::new(blobs_dir.path().to_path_buf(), "sha1");
-        let hydrator = Hydrator::new(dir.path().to_path_buf(), cache.clone());
+        let hydrator = Hydrator::new(GitCli::new(), dir.path().to_path_buf(), cache.clone());
         let (path1, _size1) = hydrator.hydrate(blob_oid).unwrap();
         // Second call hits the disk fast path; same path, no fetch.
         assert!(cache.contains(blob_oid));
@@ -531,7 +534,7 @@ mod tests {
         let (dir, _repo, _head, _blob_oid) = make_repo_with_blob();
         let blobs_dir = tempdir().unwrap();
         let cache = BlobCache::new(blobs_dir.path().to_path_buf(), "sha1");
-        let hydrator = Hydrator::new(dir.path().to_path_buf(), cache);
+        let hydrator = Hydrator::new(GitCli::new(), dir.path().to_path_buf(), cache);
         let fake = Oid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap();
         let res = hydrator.hydrate(fake);
         assert!(
@@ -578,7 +581,7 @@ mod tests {
 
         let cache_dir = tempdir().unwrap();
         let cache = BlobCache::new(cache_dir.path().to_path_buf(), "sha1");
-        let hydrator = Hydrator::new(mirror.clone(), cache.clone());
+        let hydrator = Hydrator::new(GitCli::new(), mirror.clone(), cache.clone());
         let (path, size) = hydrator.hydrate(blob_oid).unwrap();
         assert!(size > 0);
         assert!(cache.contains(blob_oid));
@@ -11,8 +11,12 @@
 //! store.
 
 use git2::{BranchType, Oid, Repository};
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::ffi::OsString;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::Mutex;
+use std::time::SystemTime;
 use thiserror::Error;
 
 use crate::types::RepoKey;
@@ -32,6 +36,12 @@ pub enum GitError {
     CloneError(String),
     #[error("fetch failed: {0}")]
     FetchError(String),
+    #[error("repository unavailable: {0}")]
+    Unavailable(String),
+    #[error("no space left in the cache: {0}")]
+    DiskFull(String),
+    #[error("repack failed: {0}")]
+    RepackError(String),
     #[error("unresolved ref: {0}")]
     RefNotFound(String),
     #[error("ambiguous ref: {0}")]
@@ -44,6 +54,8 @@ pub enum GitError {
 
 /// Minimum abbreviated commit OID length we accept.
 pub const MIN_OID_LEN: usize = 4;
+/// Length of a full SHA-1 commit OID in hex.
+pub const FULL_OID_LEN: usize = 40;
 
 /// Validate that a string is non-empty, contains no `..`, does not start with
 /// `-`, and has no control or null bytes. Used to reject obviously malicious
@@ -78,6 +90,211 @@ pub fn validate_selector(value: &str) -> Result<(), GitError> {
     Ok(())
 }
 
+/// Validate a commit SHA given to `ghfs sync --commit`: it must pass
+/// [`validate_selector`] and be hex of at least [`MIN_OID_LEN`] characters.
+/// Fetching an unknown commit from the remote requires the full OID.
+pub fn validate_commit_sha(value: &str) -> Result<(), GitError> {
+    validate_selector(value)?;
+    if value.len() < MIN_OID_LEN || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(GitError::InvalidInput(format!(
+            "'{value}' is not a commit SHA"
+        )));
+    }
+    Ok(())
+}
+
+/// Validate a branch name given to `ghfs set-branch`: it must pass
+/// [`validate_selector`] and make a valid `refs/heads/<name>` ref.
+pub fn validate_branch_name(value: &str) -> Result<(), GitError> {
+    validate_selector(value)?;
+    if !git2::Reference::is_valid_name(&format!("refs/heads/{value}")) {
+        return Err(GitError::InvalidInput(format!(
+            "'{value}' is not a valid branch name"
+        )));
+    }
+    Ok(())
+}
+
+/// Environment variables consulted for the HTTP(S) proxy, highest precedence
+/// first. `GHFS_HTTP_PROXY` lets ghfs use a proxy without exporting one to
+/// every other process; the rest are the conventional curl variables.
+pub const PROXY_ENV_VARS: &[&str] = &[
+    "GHFS_HTTP_PROXY",
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+];
+
+/// Resolve the proxy URL from `lookup` following [`PROXY_ENV_VARS`] order.
+/// Empty values are skipped so `HTTPS_PROXY=` can't mask a lower entry.
+fn proxy_from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Option<String> {
+    PROXY_ENV_VARS
+        .iter()
+        .filter_map(|name| lookup(name))
+        .map(|value| value.trim().to_string())
+        .find(|value| !value.is_empty())
+}
+
+/// One update from git's `--progress` output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitProgress {
+    /// What git is doing, e.g. `Receiving objects`.
+    pub phase: String,
+    pub percent: u8,
+}
+
+/// Parse one line of git progress output, such as
+/// `Receiving objects:  45% (450/1000), 1.20 MiB | 2.40 MiB/s`. Lines
+/// relayed from the server (`remote: Counting objects: 10% ...`) parse too.
+pub fn parse_progress(line: &str) -> Option<GitProgress> {
+    let line = line.trim();
+    let line = line.strip_prefix("remote:").map_or(line, str::trim_start);
+    let (phase, rest) = line.split_once(':')?;
+    let (percent, _) = rest.split_once('%')?;
+    let percent = percent.trim().parse::<u8>().ok().filter(|p| *p <= 100)?;
+    if phase.is_empty() || phase.contains(['%', '(']) {
+        return None;
+    }
+    Some(GitProgress {
+        phase: phase.to_string(),
+        percent,
+    })
+}
+
+/// PIDs of the clones and fetches running now, for [`abort_running`].
+static RUNNING: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// A spawned git process listed in [`RUNNING`] until it has been waited on.
+struct Tracked(Child);
+
+impl Tracked {
+    fn spawn(cmd: &mut Command) -> std::io::Result<Self> {
+        let child = cmd.spawn()?;
+        RUNNING
+            .lock()
+            .expect("running git list poisoned")
+            .push(child.id());
+        Ok(Self(child))
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let id = self.0.id();
+        RUNNING
+            .lock()
+            .expect("running git list poisoned")
+            .retain(|pid| *pid != id);
+    }
+}
+
+/// Send `SIGTERM` to every running clone and fetch, which then fail and
+/// clean up like any other failed operation. Returns how many were
+/// signalled.
+pub fn abort_running() -> usize {
+    signal_running(|_| true)
+}
+
+/// Send `SIGTERM` to the running clones and fetches whose PID passes
+/// `filter`. A process stays listed until it has been reaped, so its PID
+/// can't have been reused.
+fn signal_running(filter: impl Fn(u32) -> bool) -> usize {
+    let running = RUNNING.lock().expect("running git list poisoned");
+    let mut signalled = 0;
+    for &pid in running.iter().filter(|&&pid| filter(pid)) {
+        // SAFETY: kill has no memory-safety preconditions.
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGTERM);
+        }
+        signalled += 1;
+    }
+    signalled
+}
+
+/// Run `cmd`, streaming progress to `on_progress` if given, and return its
+/// exit status and stderr.
+fn run(
+    mut cmd: Command,
+    on_progress: Option<&dyn Fn(GitProgress)>,
+) -> std::io::Result<(ExitStatus, String)> {
+    match on_progress {
+        Some(on_progress) => run_with_progress(cmd, on_progress),
+        None => {
+            let mut child = Tracked::spawn(cmd.stdout(Stdio::null()).stderr(Stdio::piped()))?;
+            let mut stderr = Vec::new();
+            if let Some(mut pipe) = child.0.stderr.take() {
+                pipe.read_to_end(&mut stderr)?;
+            }
+            let status = child.0.wait()?;
+            Ok((status, String::from_utf8_lossy(&stderr).into_owned()))
+        }
+    }
+}
+
+/// Run `cmd` with stderr streamed through [`parse_progress`]. Each change
+/// of progress goes to `on_progress`. Everything else on stderr is returned
+/// with the exit status, so error messages don't carry progress noise.
+fn run_with_progress(
+    mut cmd: Command,
+    on_progress: &dyn Fn(GitProgress),
+) -> std::io::Result<(ExitStatus, String)> {
+    let mut child = Tracked::spawn(cmd.stdout(Stdio::null()).stderr(Stdio::piped()))?;
+    let mut stderr = child.0.stderr.take().expect("stderr is piped");
+
+    let mut messages = String::new();
+    let mut line = Vec::new();
+    let mut last = None;
+    let mut buf = [0u8; 4096];
+    let mut finish_line = |line: &mut Vec<u8>, messages: &mut String| {
+        let text = String::from_utf8_lossy(line);
+        match parse_progress(&text) {
+            Some(progress) if last.as_ref() != Some(&progress) => {
+                on_progress(progress.clone());
+                last = Some(progress);
+            }
+            Some(_) => {}
+            None if text.trim().is_empty() => {}
+            None => {
+                messages.push_str(&text);
+                messages.push('\n');
+            }
+        }
+        line.clear();
+    };
+    loop {
+        let n = stderr.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        // Progress updates end in `\r`, everything else in `\n`.
+        for &byte in &buf[..n] {
+            if byte == b'\r' || byte == b'\n' {
+                finish_line(&mut line, &mut messages);
+            } else {
+                line.push(byte);
+            }
+        }
+    }
+    finish_line(&mut line, &mut messages);
+    Ok((child.0.wait()?, messages))
+}
+
+/// Environment variable overriding the `http.userAgent` git sends.
+pub const USER_AGENT_ENV: &str = "GHFS_USER_AGENT";
+
+/// User agent sent by default, so server logs can tell ghfs traffic apart.
+pub const DEFAULT_USER_AGENT: &str = concat!("ghfs/", env!("CARGO_PKG_VERSION"));
+
+/// The user agent from [`USER_AGENT_ENV`], or [`DEFAULT_USER_AGENT`] when
+/// unset or blank.
+pub fn user_agent() -> String {
+    crate::config::var(USER_AGENT_ENV)
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| DEFAULT_USER_AGENT.to_string())
+}
+
 /// Hardened git CLI wrapper used for network operations (clone/fetch).
 ///
 /// Libgit2 does not reliably perform promisor lazy fetches, so partial-clone
@@ -86,6 +303,8 @@ pub fn validate_selector(value: &str) -> Result<(), GitError> {
 #[derive(Clone)]
 pub struct GitCli {
     git_path: String,
+    proxy: Option<String>,
+    user_agent: String,
 }
 
 impl Default for GitCli {
@@ -95,23 +314,51 @@ impl Default for GitCli {
 }
 
 impl GitCli {
-    /// Create a new CLI wrapper using the system `git`.
+    /// Create a new CLI wrapper using the system `git`, with the proxy taken
+    /// from the environment (see [`PROXY_ENV_VARS`]) and the user agent from
+    /// [`user_agent`].
     pub fn new() -> Self {
+        Self::with_proxy(proxy_from_lookup(crate::config::var)).with_user_agent(user_agent())
+    }
+
+    /// Create a CLI wrapper with an explicit proxy URL, ignoring the
+    /// environment. `None` leaves git's own proxy handling untouched.
+    pub fn with_proxy(proxy: Option<String>) -> Self {
         Self {
             git_path: "git".to_string(),
+            proxy,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
         }
     }
 
+    /// Send `user_agent` as git's `http.userAgent` instead of the default.
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// The proxy URL injected into every command, if any.
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
     /// Build a hardened `git` [`Command`] with security settings:
     /// - `GIT_LFS_SKIP_SMUDGE=1` (skip LFS downloads)
     /// - `GIT_TERMINAL_PROMPT=0` (no interactive prompts)
     /// - `core.hooksPath=` (disable hooks)
+    /// - `http.userAgent=<agent>` identifying ghfs
+    /// - `http.proxy=<url>` when a proxy is configured
     /// - null stdin
-    fn command(&self) -> Command {
+    pub(crate) fn command(&self) -> Command {
         let mut cmd = Command::new(&self.git_path);
         cmd.env("GIT_LFS_SKIP_SMUDGE", "1");
         cmd.env("GIT_TERMINAL_PROMPT", "0");
         cmd.args(["-c", "core.hooksPath="]);
+        cmd.arg("-c")
+            .arg(format!("http.userAgent={}", self.user_agent));
+        if let Some(proxy) = &self.proxy {
+            cmd.arg("-c").arg(format!("http.proxy={proxy}"));
+        }
         cmd.stdin(Stdio::null());
         cmd
     }
@@ -122,7 +369,25 @@ impl GitCli {
     ///
     /// The clone writes to a temporary sibling directory first and renames
     /// into place atomically so a crashed clone never leaves a half-mirror.
-    pub fn clone_blobless(&self, key: &RepoKey, dest: &Path) -> Result<(), GitError> {
+    /// Returns the repo GitHub redirected to, if it reported one (the repo
+    /// was renamed or transferred).
+    ///
+    /// A server that can't filter gets a full clone instead, marked with
+    /// [`FULL_CLONE_MARKER`].
+    pub fn clone_blobless(&self, key: &RepoKey, dest: &Path) -> Result<Option<RepoKey>, GitError> {
+        self.clone_blobless_reporting(key, dest, None, None)
+    }
+
+    /// [`GitCli::clone_blobless`], borrowing objects from the mirror at
+    /// `reference` when given (see [`clone_args`]) and passing git's
+    /// progress to `on_progress`.
+    pub fn clone_blobless_reporting(
+        &self,
+        key: &RepoKey,
+        dest: &Path,
+        reference: Option<&Path>,
+        on_progress: Option<&dyn Fn(GitProgress)>,
+    ) -> Result<Option<RepoKey>, GitError> {
         let url = format!("https://github.com/{}/{}.git", key.owner, key.repo);
 
         if let Some(parent) = dest.parent() {
@@ -132,53 +397,401 @@ impl GitCli {
         let temporary = dest.with_extension("clone.tmp");
         let _ = std::fs::remove_dir_all(&temporary);
 
-        let output = self
-            .command()
-            .args(["clone", "--bare", "--filter=blob:none"])
-            .arg(&url)
-            .arg(temporary.to_str().unwrap())
-            .output()?;
+        let clone = |blobless| {
+            let mut cmd = self.command();
+            cmd.args(clone_args(
+                &url,
+                &temporary,
+                reference,
+                on_progress.is_some(),
+                blobless,
+            ));
+            run(cmd, on_progress)
+        };
+        let (mut status, mut stderr) = clone(true)?;
+        // Git falls back to a full clone by itself when the server merely
+        // ignores the filter; when the server refuses it, clone in full.
+        let full = filter_unsupported(&stderr);
+        if !status.success() && full {
+            log::info!("{key}: the server refused a blobless clone; cloning in full");
+            let _ = std::fs::remove_dir_all(&temporary);
+            (status, stderr) = clone(false)?;
+        }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        if !status.success() {
             let _ = std::fs::remove_dir_all(&temporary);
-            return Err(GitError::CloneError(super::redact_creds(&stderr)));
+            return Err(remote_failure(&stderr, GitError::CloneError));
         }
 
+        if full && let Err(err) = std::fs::write(temporary.join(FULL_CLONE_MARKER), b"") {
+            let _ = std::fs::remove_dir_all(&temporary);
+            return Err(err.into());
+        }
         std::fs::rename(&temporary, dest)?;
-        Ok(())
+        Ok(parse_redirect(&stderr))
     }
 
     /// Incrementally update a blobless mirror with the latest refs from
     /// `origin`. All branches and tags are force-updated; deleted remote refs
     /// are pruned locally so `/by-ref/<gone>` lookups fail promptly.
     pub fn fetch_blobless(&self, mirror_path: &Path) -> Result<(), GitError> {
+        self.fetch_blobless_reporting(mirror_path, None)
+    }
+
+    /// [`GitCli::fetch_blobless`], passing git's progress to `on_progress`
+    /// when given.
+    pub fn fetch_blobless_reporting(
+        &self,
+        mirror_path: &Path,
+        on_progress: Option<&dyn Fn(GitProgress)>,
+    ) -> Result<(), GitError> {
         let mirror_str = mirror_path
             .to_str()
             .ok_or_else(|| GitError::ParseError("mirror path is not valid UTF-8".into()))?;
 
+        let mut cmd = self.command();
+        cmd.arg("-C")
+            .arg(mirror_str)
+            .arg("fetch")
+            .args(fetch_filter(mirror_path))
+            .arg("--prune");
+        if on_progress.is_some() {
+            cmd.arg("--progress");
+        }
+        cmd.args([
+            "origin",
+            "+refs/heads/*:refs/heads/*",
+            "+refs/tags/*:refs/tags/*",
+        ]);
+        let (status, stderr) = run(cmd, on_progress)?;
+
+        if !status.success() {
+            return Err(fetch_failure(mirror_path, &stderr));
+        }
+        Ok(())
+    }
+
+    /// Ask `origin` which commit `refname` (`HEAD` or a full ref) points
+    /// at, without fetching anything. One round trip; used to skip fetches
+    /// when nothing moved.
+    pub fn ls_remote_ref(&self, mirror_path: &Path, refname: &str) -> Result<Oid, GitError> {
         let output = self
             .command()
             .arg("-C")
-            .arg(mirror_str)
-            .args([
-                "fetch",
-                "--filter=blob:none",
-                "--prune",
-                "origin",
-                "+refs/heads/*:refs/heads/*",
-                "+refs/tags/*:refs/tags/*",
-            ])
+            .arg(mirror_path)
+            .args(["ls-remote", "origin", refname])
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(remote_failure(&stderr, GitError::FetchError));
+        }
+        parse_ls_remote(&String::from_utf8_lossy(&output.stdout), refname)
+            .ok_or_else(|| GitError::ParseError(format!("no {refname} in ls-remote output")))
+    }
+
+    /// Fetch a single commit (and its trees, bloblessly) that no ref in the
+    /// mirror reaches. `sha` must be a full OID; servers don't resolve
+    /// abbreviations.
+    pub fn fetch_commit(&self, mirror_path: &Path, sha: &str) -> Result<(), GitError> {
+        validate_commit_sha(sha)?;
+        if sha.len() != FULL_OID_LEN {
+            return Err(GitError::InvalidInput(format!(
+                "fetching '{sha}' needs the full {FULL_OID_LEN}-character commit SHA"
+            )));
+        }
+        let output = self
+            .command()
+            .arg("-C")
+            .arg(mirror_path)
+            .arg("fetch")
+            .args(fetch_filter(mirror_path))
+            .args(["origin", sha])
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(fetch_failure(mirror_path, &stderr));
+        }
+        Ok(())
+    }
+
+    /// Fetch a single tag (`refs/tags/<tag>`) from origin into the mirror,
+    /// for tags the last full fetch didn't bring in.
+    pub fn fetch_tag(&self, mirror_path: &Path, tag: &str) -> Result<(), GitError> {
+        let refname = format!("refs/tags/{tag}");
+        if !git2::Reference::is_valid_name(&refname) {
+            return Err(GitError::InvalidInput(format!("invalid tag name: {tag}")));
+        }
+        let output = self
+            .command()
+            .arg("-C")
+            .arg(mirror_path)
+            .arg("fetch")
+            .args(fetch_filter(mirror_path))
+            .arg("origin")
+            .arg(format!("+{refname}:{refname}"))
+            .output()?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(fetch_failure(mirror_path, &stderr));
+        }
+        Ok(())
+    }
+
+    /// Make `mirror` borrow objects from the mirror at `reference`: add it
+    /// to the alternates, then repack locally so objects the two share are
+    /// dropped from `mirror`'s own packs.
+    pub fn borrow_objects(&self, mirror: &Path, reference: &Path) -> Result<(), GitError> {
+        let objects = reference.join("objects");
+        if alternates(mirror).contains(&objects) {
+            return Ok(());
+        }
+        let info = mirror.join("objects").join("info");
+        std::fs::create_dir_all(&info)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(info.join("alternates"))?;
+        writeln!(file, "{}", objects.display())?;
+        self.repack(mirror, &["-a", "-d", "-l"])
+    }
+
+    /// Copy everything `mirror` borrows through its alternates into its own
+    /// packs and drop the alternates, so it no longer depends on the mirrors
+    /// it referenced.
+    pub fn dissociate(&self, mirror: &Path) -> Result<(), GitError> {
+        let file = mirror.join("objects").join("info").join("alternates");
+        if !file.exists() {
+            return Ok(());
+        }
+        self.repack(mirror, &["-a", "-d"])?;
+        std::fs::remove_file(file)?;
+        Ok(())
+    }
+
+    /// Repack `mirror` and delete unreachable objects now, skipping the
+    /// grace period `git gc` normally gives them.
+    pub fn prune_unreachable(&self, mirror: &Path) -> Result<(), GitError> {
+        self.repack(mirror, &["-a", "-d", "-l"])?;
+        let output = self
+            .command()
+            .arg("-C")
+            .arg(mirror)
+            .args(["prune", "--expire=now"])
             .output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            return Err(GitError::RepackError(stderr));
+        }
+        Ok(())
+    }
 
+    fn repack(&self, mirror: &Path, args: &[&str]) -> Result<(), GitError> {
+        let output = self
+            .command()
+            .arg("-C")
+            .arg(mirror)
+            .arg("repack")
+            .args(args)
+            .output()?;
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
-            return Err(GitError::FetchError(super::redact_creds(&stderr)));
+            return Err(GitError::RepackError(stderr));
         }
         Ok(())
     }
 }
 
+/// File in a mirror marking it as a full clone: its server refused (or
+/// ignored) `--filter=blob:none`, so fetches into it leave the filter off.
+pub const FULL_CLONE_MARKER: &str = "ghfs-full-clone";
+
+/// Stderr from git when the server can't serve a filtered clone or fetch.
+const FILTER_UNSUPPORTED_PATTERNS: &[&str] = &[
+    "filtering not recognized by server",
+    "server does not support filter",
+    "does not support filter",
+    "dumb http transport does not support",
+];
+
+/// Whether a clone's stderr says the server doesn't support
+/// `--filter=blob:none`.
+pub fn filter_unsupported(stderr: &str) -> bool {
+    let stderr = stderr.to_ascii_lowercase();
+    FILTER_UNSUPPORTED_PATTERNS
+        .iter()
+        .any(|pattern| stderr.contains(pattern))
+}
+
+/// Whether `mirror` was cloned in full (see [`FULL_CLONE_MARKER`]).
+pub fn is_full_clone(mirror: &Path) -> bool {
+    mirror.join(FULL_CLONE_MARKER).exists()
+}
+
+/// The filter argument for fetches into `mirror`: none for full clones.
+fn fetch_filter(mirror: &Path) -> &'static [&'static str] {
+    if is_full_clone(mirror) {
+        &[]
+    } else {
+        &["--filter=blob:none"]
+    }
+}
+
+/// Arguments for a bare clone of `url` into `dest`, blobless unless
+/// `blobless` is false.
+///
+/// With a `reference` mirror, the clone lists that mirror's object store in
+/// its alternates and only downloads what the reference lacks, so forks of
+/// an already-cached repo share its commits and trees. `--reference-if-able`
+/// turns a vanished reference into a plain clone instead of an error.
+pub fn clone_args(
+    url: &str,
+    dest: &Path,
+    reference: Option<&Path>,
+    progress: bool,
+    blobless: bool,
+) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["clone".into(), "--bare".into()];
+    if blobless {
+        args.push("--filter=blob:none".into());
+    }
+    if progress {
+        args.push("--progress".into());
+    }
+    if let Some(reference) = reference {
+        args.push("--reference-if-able".into());
+        args.push(reference.into());
+    }
+    args.push(url.into());
+    args.push(dest.into());
+    args
+}
+
+/// The object directories `mirror` borrows from, as listed in its
+/// `objects/info/alternates`. Relative entries are resolved against
+/// `mirror/objects`, as git does.
+pub fn alternates(mirror: &Path) -> Vec<PathBuf> {
+    let objects = mirror.join("objects");
+    let Ok(contents) = std::fs::read_to_string(objects.join("info").join("alternates")) else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| objects.join(line))
+        .collect()
+}
+
+/// Replace `mirror`'s alternates with `entries`.
+pub fn set_alternates(mirror: &Path, entries: &[PathBuf]) -> std::io::Result<()> {
+    let contents: String = entries
+        .iter()
+        .map(|entry| format!("{}\n", entry.display()))
+        .collect();
+    std::fs::write(
+        mirror.join("objects").join("info").join("alternates"),
+        contents,
+    )
+}
+
+/// Extract the `HEAD` OID from `git ls-remote` output
+/// (`<oid>\t<ref>` lines).
+fn parse_ls_remote(stdout: &str, refname: &str) -> Option<Oid> {
+    stdout.lines().find_map(|line| {
+        let (oid, name) = line.split_once('\t')?;
+        if name.trim() != refname {
+            return None;
+        }
+        Oid::from_str(oid.trim()).ok()
+    })
+}
+
+/// Phrases in git's stderr meaning GitHub refuses to serve the repo at
+/// all: a DMCA takedown, a staff-disabled repo, or terms that have to be
+/// accepted on the website first. Matched case-insensitively.
+const UNAVAILABLE_PATTERNS: &[&str] = &[
+    "dmca takedown",
+    "access to this repository has been disabled",
+    "repository access blocked",
+    "accept the terms",
+    "unavailable for legal reasons",
+    "returned error: 451",
+];
+
+/// Whether a clone or fetch failure's stderr says the repo is blocked for
+/// good, so retrying can't help.
+pub fn is_unavailable(stderr: &str) -> bool {
+    let stderr = stderr.to_ascii_lowercase();
+    UNAVAILABLE_PATTERNS
+        .iter()
+        .any(|pattern| stderr.contains(pattern))
+}
+
+/// Stderr from git when the cache's filesystem filled up mid-write.
+/// Matched case-insensitively.
+const DISK_FULL_PATTERNS: &[&str] = &["no space left on device", "disk quota exceeded"];
+
+/// Whether a clone or fetch failure's stderr says the disk is full.
+pub fn is_disk_full(stderr: &str) -> bool {
+    let stderr = stderr.to_ascii_lowercase();
+    DISK_FULL_PATTERNS
+        .iter()
+        .any(|pattern| stderr.contains(pattern))
+}
+
+/// The error for a failed clone or fetch: [`GitError::DiskFull`] when the
+/// disk filled up, [`GitError::Unavailable`] when the repo is blocked,
+/// otherwise `error`. Credentials are redacted.
+fn remote_failure(stderr: &str, error: fn(String) -> GitError) -> GitError {
+    let message = super::redact_creds(stderr);
+    if is_disk_full(&message) {
+        GitError::DiskFull(message)
+    } else if is_unavailable(&message) {
+        GitError::Unavailable(message)
+    } else {
+        error(message)
+    }
+}
+
+/// [`remote_failure`] for a fetch into `mirror`. A fetch cut short by a
+/// full disk leaves its half-written packs in `objects/pack`; they're
+/// removed so they don't keep the space.
+fn fetch_failure(mirror: &Path, stderr: &str) -> GitError {
+    let err = remote_failure(stderr, GitError::FetchError);
+    if matches!(err, GitError::DiskFull(_)) {
+        remove_partial_packs(mirror);
+    }
+    err
+}
+
+/// Delete the `tmp_*` files git writes packs to before moving them into
+/// place. Returns how many were removed.
+fn remove_partial_packs(mirror: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(mirror.join("objects/pack")) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("tmp_"))
+        .filter(|entry| std::fs::remove_file(entry.path()).is_ok())
+        .count()
+}
+
+/// Extract the repo git was redirected to from its stderr
+/// (`warning: redirecting to https://github.com/<owner>/<repo>.git/`).
+pub fn parse_redirect(stderr: &str) -> Option<RepoKey> {
+    let url = stderr
+        .lines()
+        .find_map(|line| line.split_once("redirecting to ").map(|(_, url)| url))?;
+    let path = url.trim().split_once("://")?.1.split_once('/')?.1;
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    path.parse().ok()
+}
+
 /// Open an existing repository at `path`.
 pub fn open_repository(path: &Path) -> Result<Repository, GitError> {
     let repo = Repository::open(path).map_err(|e| {
@@ -191,6 +804,16 @@ pub fn open_repository(path: &Path) -> Result<Repository, GitError> {
     Ok(repo)
 }
 
+/// Best guess at when `mirror` last synced: `FETCH_HEAD`'s mtime, or the
+/// mirror directory's own mtime for never-fetched clones. `None` if the
+/// mirror doesn't exist.
+pub fn last_fetch_time(mirror: &Path) -> Option<SystemTime> {
+    std::fs::metadata(mirror.join("FETCH_HEAD"))
+        .or_else(|_| std::fs::metadata(mirror))
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
 /// Resolve a reference to the commit OID it ultimately points to, peeling
 /// through annotated tags as needed.
 fn reference_to_commit_oid(reference: &git2::Reference<'_>) -> Result<Oid, GitError> {
@@ -200,10 +823,19 @@ fn reference_to_commit_oid(reference: &git2::Reference<'_>) -> Result<Oid, GitEr
 
 /// Resolve the repository's HEAD to its commit OID. Used by the default
 /// `/<owner>/<repo>` alias.
-pub fn resolve_head(repo: &Repository) -> Result<Oid, GitError> {
-    let head = repo.head()?;
+///
+/// `None` for a repository with no commits yet (unborn HEAD, no branches),
+/// such as a freshly created GitHub repo.
+pub fn resolve_head(repo: &Repository) -> Result<Option<Oid>, GitError> {
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch && repo.is_empty().unwrap_or(false) => {
+            return Ok(None);
+        }
+        Err(e) => return Err(e.into()),
+    };
     let commit = head.peel_to_commit()?;
-    Ok(commit.id())
+    Ok(Some(commit.id()))
 }
 
 /// Resolve a user-supplied ref selector to a concrete commit OID.
@@ -232,10 +864,10 @@ pub fn resolve_revision(repo: &Repository, selector: &str) -> Result<Oid, GitErr
 
     // 2. Pure-hex commit OID (abbreviated allowed).
     if selector.len() >= MIN_OID_LEN && selector.chars().all(|c| c.is_ascii_hexdigit()) {
-        if let Ok(obj) = repo.revparse_single(selector) {
-            if let Ok(commit) = obj.peel_to_commit() {
-                return Ok(commit.id());
-            }
+        if let Ok(obj) = repo.revparse_single(selector)
+            && let Ok(commit) = obj.peel_to_commit()
+        {
+            return Ok(commit.id());
         }
         return Err(GitError::RefNotFound(selector.to_string()));
     }
@@ -287,6 +919,7 @@ pub fn list_tags(repo: &Repository) -> Result<Vec<String>, GitError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::git_fixtures::{commit, init_bare};
     use tempfile::tempdir;
 
     fn check_git_available() -> bool {
@@ -343,10 +976,17 @@ mod tests {
     #[test]
     fn resolve_head_returns_commit() {
         let (repo, _dir) = make_local_repo();
-        let oid = resolve_head(&repo).unwrap();
+        let oid = resolve_head(&repo).unwrap().unwrap();
         assert!(repo.find_commit(oid).is_ok());
     }
 
+    #[test]
+    fn resolve_head_is_none_without_commits() {
+        let dir = tempdir().unwrap();
+        let repo = git2::Repository::init_bare(dir.path().join("empty.git")).unwrap();
+        assert_eq!(resolve_head(&repo).unwrap(), None);
+    }
+
     #[test]
     fn resolve_revision_full_ref() {
         let (repo, _dir) = make_local_repo();
@@ -358,7 +998,7 @@ mod tests {
     fn resolve_revision_head_keyword() {
         let (repo, _dir) = make_local_repo();
         let oid = resolve_revision(&repo, "HEAD").unwrap();
-        assert_eq!(oid, resolve_head(&repo).unwrap());
+        assert_eq!(oid, resolve_head(&repo).unwrap().unwrap());
     }
 
     #[test]
@@ -388,15 +1028,15 @@ mod tests {
     #[test]
     fn resolve_revision_full_commit_oid() {
         let (repo, _dir) = make_local_repo();
-        let head = resolve_head(&repo).unwrap().to_string();
+        let head = resolve_head(&repo).unwrap().unwrap().to_string();
         let short = &head[..7];
         assert_eq!(
             resolve_revision(&repo, &head).unwrap(),
-            resolve_head(&repo).unwrap()
+            resolve_head(&repo).unwrap().unwrap()
         );
         assert_eq!(
             resolve_revision(&repo, short).unwrap(),
-            resolve_head(&repo).unwrap()
+            resolve_head(&repo).unwrap().unwrap()
         );
     }
 
@@ -440,6 +1080,461 @@ mod tests {
         assert!(validate_selector("refs/heads/main").is_ok());
     }
 
+    #[test]
+    fn parse_ls_remote_finds_requested_ref() {
+        let sha = "0123456789abcdef0123456789abcdef01234567";
+        let main = "fedcba9876543210fedcba9876543210fedcba98";
+        let out = format!("{sha}\tHEAD\n{main}\trefs/heads/main\n");
+        assert_eq!(
+            parse_ls_remote(&out, "HEAD"),
+            Some(Oid::from_str(sha).unwrap())
+        );
+        assert_eq!(
+            parse_ls_remote(&out, "refs/heads/main"),
+            Some(Oid::from_str(main).unwrap())
+        );
+        assert_eq!(parse_ls_remote(&out, "refs/heads/develop"), None);
+        assert_eq!(parse_ls_remote("", "HEAD"), None);
+        assert_eq!(parse_ls_remote("not-an-oid\tHEAD\n", "HEAD"), None);
+    }
+
+    #[test]
+    fn parse_progress_reads_git_progress_lines() {
+        let progress = |phase: &str, percent| {
+            Some(GitProgress {
+                phase: phase.to_string(),
+                percent,
+            })
+        };
+        assert_eq!(
+            parse_progress("Receiving objects:  45% (4500/10000), 1.20 MiB | 2.40 MiB/s"),
+            progress("Receiving objects", 45)
+        );
+        assert_eq!(
+            parse_progress("Receiving objects: 100% (10000/10000), 5.00 MiB | 3.1 MiB/s, done."),
+            progress("Receiving objects", 100)
+        );
+        assert_eq!(
+            parse_progress("Resolving deltas:   3% (12/400)"),
+            progress("Resolving deltas", 3)
+        );
+        assert_eq!(
+            parse_progress("remote: Counting objects:  10% (1/10)"),
+            progress("Counting objects", 10)
+        );
+        for line in [
+            "Cloning into bare repository 'x.clone.tmp'...",
+            "remote: Enumerating objects: 42, done.",
+            "warning: redirecting to https://github.com/a/b.git/",
+            "fatal: repository 'https://github.com/a/b.git/' not found",
+            "Receiving objects: 250% (1/1)",
+            "",
+        ] {
+            assert_eq!(parse_progress(line), None, "{line}");
+        }
+    }
+
+    #[test]
+    fn run_with_progress_splits_progress_from_messages() {
+        let mut cmd = Command::new("sh");
+        cmd.args([
+            "-c",
+            "printf 'Cloning into x...\\nReceiving objects:  10%% (1/10)\\rReceiving objects:  10%% (1/10)\\rReceiving objects: 100%% (10/10), done.\\nfatal: boom\\n' >&2; exit 3",
+        ]);
+        let seen = std::sync::Mutex::new(Vec::new());
+        let (status, messages) =
+            run_with_progress(cmd, &|p| seen.lock().unwrap().push(p.percent)).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(messages, "Cloning into x...\nfatal: boom\n");
+        // Repeated updates are reported once.
+        assert_eq!(*seen.lock().unwrap(), [10, 100]);
+    }
+
+    #[test]
+    fn aborted_clone_removes_its_temporary_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        // A stand-in git that starts writing the clone and then hangs.
+        let script = dir.path().join("git");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho $$ > {}\nfor last; do :; done\nmkdir -p \"$last\"\n\
+                 touch \"$last/HEAD\"\nexec sleep 60\n",
+                dir.path().join("pid").display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let cli = GitCli {
+            git_path: script.to_string_lossy().into_owned(),
+            ..GitCli::with_proxy(None)
+        };
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let dest = dir.path().join("mirrors/octocat/hello-world.git");
+        let temporary = dest.with_extension("clone.tmp");
+
+        let clone = {
+            let dest = dest.clone();
+            std::thread::spawn(move || cli.clone_blobless(&key, &dest))
+        };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while !temporary.join("HEAD").exists() {
+            assert!(std::time::Instant::now() < deadline, "clone never started");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+
+        // Only this test's process: other tests may be cloning too.
+        let pid: u32 = std::fs::read_to_string(dir.path().join("pid"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        assert_eq!(signal_running(|running| running == pid), 1);
+        assert!(matches!(
+            clone.join().unwrap(),
+            Err(GitError::CloneError(_))
+        ));
+        assert!(!temporary.exists());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn clone_falls_back_to_full_when_the_server_refuses_filters() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        // A stand-in git whose server refuses filtered clones, and logs the
+        // arguments of each call.
+        let script = dir.path().join("git");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh
+echo \"$*\" >> {}
+case \"$*\" in *--filter*)
+                 echo 'fatal: dumb http transport does not support shallow capabilities' >&2
+                 exit 128;;
+esac
+for last; do :; done
+mkdir -p \"$last\"
+",
+                dir.path().join("calls").display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let cli = GitCli {
+            git_path: script.to_string_lossy().into_owned(),
+            ..GitCli::with_proxy(None)
+        };
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let dest = dir.path().join("mirrors/octocat/hello-world.git");
+
+        cli.clone_blobless(&key, &dest).unwrap();
+        let calls = std::fs::read_to_string(dir.path().join("calls")).unwrap();
+        let calls: Vec<&str> = calls.lines().collect();
+        assert_eq!(calls.len(), 2, "{calls:?}");
+        assert!(calls[0].contains("--filter=blob:none"));
+        assert!(!calls[1].contains("--filter"));
+        assert!(is_full_clone(&dest));
+        assert!(fetch_filter(&dest).is_empty());
+
+        assert!(filter_unsupported(
+            "warning: filtering not recognized by server, ignoring"
+        ));
+        assert!(!filter_unsupported("fatal: repository not found"));
+    }
+
+    #[test]
+    fn disk_full_clones_and_fetches_clean_up_after_themselves() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        // A stand-in git that writes part of a pack and runs out of space.
+        let script = dir.path().join("git");
+        std::fs::write(
+            &script,
+            "#!/bin/sh
+for dest; do :; done
+while [ $# -gt 0 ]; do [ \"$1\" = -C ] && dest=\"$2\"; shift; done
+mkdir -p \"$dest/objects/pack\"
+touch \"$dest/objects/pack/tmp_pack_Xa1b2c\" \"$dest/objects/pack/pack-1.pack\"
+echo 'fatal: write error: No space left on device' >&2
+echo 'fatal: index-pack failed' >&2
+exit 128
+",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let cli = GitCli {
+            git_path: script.to_string_lossy().into_owned(),
+            ..GitCli::with_proxy(None)
+        };
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let dest = dir.path().join("mirrors/octocat/hello-world.git");
+
+        assert!(matches!(
+            cli.clone_blobless(&key, &dest),
+            Err(GitError::DiskFull(_))
+        ));
+        assert!(!dest.with_extension("clone.tmp").exists());
+        assert!(!dest.exists());
+
+        std::fs::create_dir_all(&dest).unwrap();
+        assert!(matches!(
+            cli.fetch_blobless(&dest),
+            Err(GitError::DiskFull(_))
+        ));
+        let pack = dest.join("objects/pack");
+        assert!(!pack.join("tmp_pack_Xa1b2c").exists());
+        assert!(pack.join("pack-1.pack").exists());
+
+        assert!(is_disk_full("error: Disk quota exceeded"));
+        assert!(!is_disk_full("fatal: repository not found"));
+    }
+
+    #[test]
+    fn clone_args_add_reference_when_given() {
+        let url = "https://github.com/octocat/hello-world.git";
+        let dest = Path::new("/cache/mirrors/octocat/hello-world.clone.tmp");
+        let args = |reference, progress| -> Vec<String> {
+            clone_args(url, dest, reference, progress, true)
+                .into_iter()
+                .map(|a| a.into_string().unwrap())
+                .collect()
+        };
+
+        assert_eq!(
+            args(None, false),
+            [
+                "clone",
+                "--bare",
+                "--filter=blob:none",
+                url,
+                "/cache/mirrors/octocat/hello-world.clone.tmp"
+            ]
+        );
+        assert_eq!(
+            args(
+                Some(Path::new("/cache/mirrors/upstream/hello-world.git")),
+                true
+            ),
+            [
+                "clone",
+                "--bare",
+                "--filter=blob:none",
+                "--progress",
+                "--reference-if-able",
+                "/cache/mirrors/upstream/hello-world.git",
+                url,
+                "/cache/mirrors/octocat/hello-world.clone.tmp"
+            ]
+        );
+    }
+
+    #[test]
+    fn borrowed_objects_survive_dissociating() {
+        let dir = tempdir().unwrap();
+        let reference = dir.path().join("upstream.git");
+        let mirror = dir.path().join("fork.git");
+
+        let repo = init_bare(&reference);
+        let one = commit(&repo, "one", &[("README", "shared")]);
+        let blob = repo.blob(b"shared").unwrap();
+        let status = Command::new("git")
+            .args(["clone", "--bare", "--quiet"])
+            .arg(&reference)
+            .arg(&mirror)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let cli = GitCli::with_proxy(None);
+        assert!(alternates(&mirror).is_empty());
+        cli.borrow_objects(&mirror, &reference).unwrap();
+        assert_eq!(alternates(&mirror), [reference.join("objects")]);
+        // Borrowing twice doesn't list the reference twice.
+        cli.borrow_objects(&mirror, &reference).unwrap();
+        assert_eq!(alternates(&mirror).len(), 1);
+        assert!(open_repository(&mirror).unwrap().find_commit(one).is_ok());
+
+        cli.dissociate(&mirror).unwrap();
+        assert!(alternates(&mirror).is_empty());
+        std::fs::remove_dir_all(&reference).unwrap();
+        let repo = open_repository(&mirror).unwrap();
+        assert!(repo.find_commit(one).is_ok());
+        assert!(repo.find_blob(blob).is_ok());
+    }
+
+    #[test]
+    fn parse_redirect_reads_git_warning() {
+        let stderr = "Cloning into bare repository 'x.clone.tmp'...\n\
+                      warning: redirecting to https://github.com/new-owner/New-Name.git/\n";
+        let key = parse_redirect(stderr).unwrap();
+        assert_eq!(key.to_string(), "new-owner/New-Name");
+        assert_eq!(key.canonical().to_string(), "new-owner/new-name");
+
+        assert_eq!(
+            parse_redirect("warning: redirecting to https://github.com/a/b/\n")
+                .map(|k| k.to_string()),
+            Some("a/b".to_string())
+        );
+        assert!(parse_redirect("Cloning into bare repository...\n").is_none());
+        assert!(parse_redirect("warning: redirecting to nowhere\n").is_none());
+    }
+
+    #[test]
+    fn blocked_repos_are_unavailable() {
+        for stderr in [
+            "remote: Repository unavailable due to DMCA takedown.\n\
+             remote: See the takedown notice for more details:\n\
+             fatal: unable to access 'https://github.com/a/b.git/': The requested URL returned error: 451\n",
+            "remote: Access to this repository has been disabled by GitHub Staff.\n\
+             fatal: unable to access 'https://github.com/a/b.git/': The requested URL returned error: 403\n",
+            "remote: Repository access blocked\n\
+             fatal: unable to access 'https://github.com/a/b.git/': The requested URL returned error: 403\n",
+            "remote: You must accept the terms of service before cloning.\n",
+            "fatal: unable to access 'https://github.com/a/b.git/': The requested URL returned error: 451\n",
+        ] {
+            assert!(
+                matches!(
+                    remote_failure(stderr, GitError::CloneError),
+                    GitError::Unavailable(_)
+                ),
+                "{stderr:?} should be unavailable"
+            );
+        }
+
+        for stderr in [
+            "remote: Repository not found.\n\
+             fatal: repository 'https://github.com/a/b.git/' not found\n",
+            "fatal: unable to access 'https://github.com/a/b.git/': Could not resolve host: github.com\n",
+            "fatal: unable to access 'https://github.com/a/b.git/': The requested URL returned error: 500\n",
+        ] {
+            assert!(!is_unavailable(stderr), "{stderr:?} is retryable");
+        }
+        assert!(matches!(
+            remote_failure("error: RPC failed; curl 56\n", GitError::FetchError),
+            GitError::FetchError(_)
+        ));
+    }
+
+    #[test]
+    fn validate_commit_sha_requires_hex() {
+        assert!(validate_commit_sha("0123abcd").is_ok());
+        assert!(validate_commit_sha(&"a".repeat(40)).is_ok());
+        for bad in ["", "abc", "main", "-abcdef", "abcd..ef", "abcdefg"] {
+            assert!(
+                matches!(validate_commit_sha(bad), Err(GitError::InvalidInput(_))),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn fetch_commit_requires_a_full_sha() {
+        let dir = tempdir().unwrap();
+        let cli = GitCli::with_proxy(None);
+        for short in ["0123abcd", &"a".repeat(39), &"a".repeat(41)] {
+            assert!(
+                matches!(
+                    cli.fetch_commit(dir.path(), short),
+                    Err(GitError::InvalidInput(_))
+                ),
+                "{short:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn validate_branch_name_rejects_bad_refs() {
+        assert!(validate_branch_name("develop").is_ok());
+        assert!(validate_branch_name("release/2.x").is_ok());
+        for bad in ["", "-x", "a..b", "a b", "trailing/", "x.lock", "a~1", "a:b"] {
+            assert!(
+                matches!(validate_branch_name(bad), Err(GitError::InvalidInput(_))),
+                "{bad:?} should be rejected"
+            );
+        }
+    }
+
+    fn command_args(cli: &GitCli) -> Vec<String> {
+        cli.command()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn command_injects_configured_proxy() {
+        let cli = GitCli::with_proxy(Some("http://proxy.local:3128".to_string()));
+        let args = command_args(&cli);
+        let idx = args
+            .iter()
+            .position(|arg| arg == "http.proxy=http://proxy.local:3128")
+            .expect("proxy config arg present");
+        assert_eq!(args[idx - 1], "-c");
+    }
+
+    #[test]
+    fn command_without_proxy_has_no_proxy_arg() {
+        let args = command_args(&GitCli::with_proxy(None));
+        assert!(!args.iter().any(|arg| arg.starts_with("http.proxy=")));
+    }
+
+    #[test]
+    fn command_sets_versioned_user_agent() {
+        let args = command_args(&GitCli::with_proxy(None));
+        let idx = args
+            .iter()
+            .position(|arg| arg.starts_with("http.userAgent="))
+            .expect("user agent config arg present");
+        assert_eq!(args[idx - 1], "-c");
+        assert!(
+            args[idx].contains(env!("CARGO_PKG_VERSION")),
+            "{}",
+            args[idx]
+        );
+
+        let args = command_args(&GitCli::with_proxy(None).with_user_agent("acme-mirror/1.0"));
+        assert!(args.contains(&"http.userAgent=acme-mirror/1.0".to_string()));
+    }
+
+    #[test]
+    fn proxy_lookup_follows_precedence() {
+        let vars = |pairs: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                pairs
+                    .iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+
+        assert_eq!(
+            proxy_from_lookup(vars(&[
+                ("HTTP_PROXY", "http://http"),
+                ("HTTPS_PROXY", "http://https"),
+                ("GHFS_HTTP_PROXY", "http://ghfs"),
+            ])),
+            Some("http://ghfs".to_string())
+        );
+        assert_eq!(
+            proxy_from_lookup(vars(&[
+                ("http_proxy", "http://http"),
+                ("HTTPS_PROXY", "http://https"),
+            ])),
+            Some("http://https".to_string())
+        );
+        assert_eq!(
+            proxy_from_lookup(vars(&[("HTTPS_PROXY", " "), ("http_proxy", "http://http")])),
+            Some("http://http".to_string())
+        );
+        assert_eq!(proxy_from_lookup(vars(&[])), None);
+    }
+
     #[test]
     fn list_branches_and_tags() {
         let (repo, _dir) = make_local_repo();
@@ -485,7 +1580,7 @@ mod tests {
         let repo = open_repository(&dest).unwrap();
         assert!(repo.is_bare());
         // HEAD must be resolvable even without any blobs locally.
-        let head = resolve_head(&repo).unwrap();
+        let head = resolve_head(&repo).unwrap().unwrap();
         assert!(repo.find_commit(head).is_ok());
     }
 
@@ -9,13 +9,16 @@
 pub mod blob;
 pub mod git;
 pub mod ref_selector;
+#[allow(clippy::module_inception)]
 pub mod store;
 pub mod tree;
 
 pub use blob::{BlobCache, BlobError, Hydrator};
-pub use git::{GitCli, GitError, resolve_head, resolve_revision};
-pub use ref_selector::{BY_REF_ROOT, RefSelector, VirtualNode, decode_ref, encode_ref};
-pub use store::{Store, StoreError};
+pub use git::{GitCli, GitError, GitProgress, resolve_head, resolve_revision};
+pub use ref_selector::{
+    BY_REF_ROOT, RefSelector, TAG_REF_PREFIX, VirtualNode, decode_ref, encode_ref, tag_name,
+};
+pub use store::{FOLLOW_RENAMES_ENV, ProgressSink, REMOTE_PRECHECK_ENV, Store, StoreError};
 pub use tree::{EntryKind, TreeCache, TreeEntry, TreeError, TreeReader};
 
 /// Best-effort redaction of embedded credentials from a git error string.
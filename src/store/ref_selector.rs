@@ -26,6 +26,20 @@ use crate::types::{Owner, RepoKey};
 /// Reserved first path component that selects the by-ref namespace.
 pub const BY_REF_ROOT: &str = "by-ref";
 
+/// Prefix of full tag selectors. Tags are treated as immutable: a
+/// `refs/tags/<name>` selector is cached like a commit OID, and fetched by
+/// name if the mirror doesn't have it yet.
+pub const TAG_REF_PREFIX: &str = "refs/tags/";
+
+/// The tag name a full `refs/tags/<name>` selector refers to, if it is one.
+/// Short names aren't recognized: a branch of the same name may appear later
+/// and make them ambiguous.
+pub fn tag_name(selector: &str) -> Option<&str> {
+    selector
+        .strip_prefix(TAG_REF_PREFIX)
+        .filter(|name| !name.is_empty())
+}
+
 /// Encode a raw ref name into a single path component.
 ///
 /// `/` -> `%2F`, `%` -> `%25`. Everything else passes through. The result is
@@ -176,6 +190,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn only_full_tag_refs_name_tags() {
+        assert_eq!(tag_name("refs/tags/v1.2.3"), Some("v1.2.3"));
+        assert_eq!(tag_name("refs/tags/release/1.0"), Some("release/1.0"));
+        assert_eq!(tag_name("refs/tags/"), None);
+        assert_eq!(tag_name("v1.2.3"), None);
+        assert_eq!(tag_name("refs/heads/v1.2.3"), None);
+    }
+
     #[test]
     fn decode_rejects_malformed() {
         assert!(decode_ref(b"foo%").is_none()); // dangling
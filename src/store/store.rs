@@ -6,16 +6,20 @@
 //! here; the FUSE and NFS adapters share it.
 
 use git2::{Oid, Repository};
-use std::path::PathBuf;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use thiserror::Error;
 
 use super::blob::{BlobCache, Hydrator};
-use super::git::{self, GitCli, GitError};
+use super::git::{self, GitCli, GitError, GitProgress};
+use super::ref_selector::tag_name;
 use super::tree::{TreeCache, TreeEntry, TreeReader};
 
-use crate::cache::CachePaths;
 use crate::cache::lock::RepoLock;
+use crate::cache::{CachePaths, is_complete_mirror};
+use crate::github::ApiClient;
 use crate::types::RepoKey;
 
 /// Errors returned by [`Store`] operations.
@@ -31,6 +35,16 @@ pub enum StoreError {
     RepoNotFound(String),
     #[error("lock acquisition failed")]
     LockFailed,
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String),
+    #[error("busy: {0}")]
+    Busy(String),
+    #[error("repository has no commits yet: {0}")]
+    NoCommits(String),
+    #[error("interrupted")]
+    Interrupted,
+    #[error("the daemon is shutting down")]
+    ShuttingDown,
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
@@ -49,6 +63,92 @@ pub struct Store {
     tree_cache: TreeCache,
     blob_cache: BlobCache,
     open: dashmap::DashMap<RepoKey, Arc<Mutex<OpenRepo>>>,
+    /// One-off commits served as HEAD until the next refresh (`ghfs sync
+    /// --commit`). Shared across clones so the fs sees the worker's writes.
+    head_overrides: Arc<dashmap::DashMap<RepoKey, Oid>>,
+    /// Ask the remote for its HEAD before each refresh and skip the fetch
+    /// when it matches the mirror's. See [`Store::with_remote_precheck`].
+    remote_precheck: bool,
+    /// Move clones of renamed repos under their new name. See
+    /// [`Store::with_follow_renames`].
+    follow_renames: bool,
+    /// Receives git's progress during clones and fetches. See
+    /// [`Store::with_progress`].
+    progress: Option<ProgressSink>,
+}
+
+/// Callback receiving clone and fetch progress for a repo.
+pub type ProgressSink = Arc<dyn Fn(&RepoKey, GitProgress) + Send + Sync>;
+
+/// Environment variable enabling the pre-fetch remote HEAD check (`1` or
+/// `true`).
+pub const REMOTE_PRECHECK_ENV: &str = "GHFS_REMOTE_PRECHECK";
+
+/// Environment variable enabling [`Store::with_follow_renames`] (`1` or
+/// `true`).
+pub const FOLLOW_RENAMES_ENV: &str = "GHFS_FOLLOW_RENAMES";
+
+#[derive(Deserialize)]
+struct ApiRepo {
+    parent: Option<ApiParent>,
+}
+
+#[derive(Deserialize)]
+struct ApiParent {
+    full_name: String,
+}
+
+/// The repo `key` was forked from, according to the GitHub API. Lookup
+/// failures just mean the clone doesn't borrow objects.
+fn fork_parent(key: &RepoKey) -> Option<RepoKey> {
+    let repo: ApiRepo = match ApiClient::shared().get_json(&format!("repos/{key}")) {
+        Ok(repo) => repo,
+        Err(e) => {
+            log::debug!("Looking up the fork parent of {key} failed: {e}");
+            return None;
+        }
+    };
+    repo.parent?.full_name.parse().ok()
+}
+
+/// Acquire the repo lock at `path`, mapping a timeout to
+/// [`StoreError::LockFailed`].
+fn acquire_lock(path: &Path) -> Result<RepoLock, StoreError> {
+    RepoLock::acquire(path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::TimedOut {
+            StoreError::LockFailed
+        } else {
+            StoreError::Io(e)
+        }
+    })
+}
+
+/// The repo a mirror directory (`mirrors/<owner>/<repo>.git`) belongs to.
+fn mirror_key(mirror: &Path) -> Option<RepoKey> {
+    let repo = mirror.file_name()?.to_str()?.strip_suffix(".git")?;
+    let owner = mirror.parent()?.file_name()?.to_str()?;
+    format!("{owner}/{repo}").parse().ok()
+}
+
+/// Delete `mirror` if it exists but git can't use it, e.g. a directory
+/// left behind by an interrupted clone or a crashed tool. Returns whether
+/// it was deleted, in which case the caller clones afresh.
+fn discard_incomplete_mirror(mirror: &Path) -> std::io::Result<bool> {
+    if !mirror.exists() || (is_complete_mirror(mirror) && git::open_repository(mirror).is_ok()) {
+        return Ok(false);
+    }
+    log::warn!("Removing unusable mirror {}", mirror.display());
+    std::fs::remove_dir_all(mirror)?;
+    Ok(true)
+}
+
+/// Whether a refresh must fetch, given the mirror's HEAD and the remote's
+/// (`None` when unknown). Only a confirmed match skips the fetch.
+fn needs_fetch(local: Option<Oid>, remote: Option<Oid>) -> bool {
+    match (local, remote) {
+        (Some(local), Some(remote)) => local != remote,
+        _ => true,
+    }
 }
 
 impl Store {
@@ -62,14 +162,53 @@ impl Store {
             tree_cache: TreeCache::new(),
             blob_cache,
             open: dashmap::DashMap::new(),
+            head_overrides: Arc::new(dashmap::DashMap::new()),
+            remote_precheck: false,
+            follow_renames: false,
+            progress: None,
         }
     }
 
+    /// Check the remote's HEAD with `git ls-remote` before each refresh and
+    /// skip the fetch when it matches the mirror's HEAD. Saves the fetch
+    /// for large, quiet repos at the cost of an extra round trip for the
+    /// rest. Branches and tags other than HEAD only update once HEAD moves.
+    pub fn with_remote_precheck(mut self, enabled: bool) -> Self {
+        self.remote_precheck = enabled;
+        self
+    }
+
+    /// When a clone reports that GitHub redirected it (the repo was renamed
+    /// or transferred), keep the mirror under the new name and alias the
+    /// old name to it. Otherwise the mirror stays under the requested name.
+    pub fn with_follow_renames(mut self, enabled: bool) -> Self {
+        self.follow_renames = enabled;
+        self
+    }
+
+    /// Report git's progress to `sink` while cloning and fetching.
+    pub fn with_progress(mut self, sink: ProgressSink) -> Self {
+        self.progress = Some(sink);
+        self
+    }
+
+    /// A progress callback for `key`, if a sink is set.
+    fn progress_for<'a>(&'a self, key: &'a RepoKey) -> Option<impl Fn(GitProgress) + 'a> {
+        self.progress
+            .as_ref()
+            .map(|sink| move |progress: GitProgress| sink(key, progress))
+    }
+
     /// Return the cache paths.
     pub fn paths(&self) -> &CachePaths {
         &self.paths
     }
 
+    /// Number of parsed trees held in the shared tree cache.
+    pub fn cached_trees(&self) -> usize {
+        self.tree_cache.entry_count()
+    }
+
     /// Ensure the mirror for `key` is present (cloning bloblessly if missing)
     /// and opened, returning a cloned handle to the locked open repo. Safe to
     /// call concurrently; a per-repo flock serializes the clone.
@@ -94,11 +233,22 @@ impl Store {
         }
 
         let mirror = self.paths.mirror_dir(key);
-        if !mirror.exists() {
-            self.cli.clone_blobless(key, &mirror)?;
+        let progress = self.progress_for(key);
+        discard_incomplete_mirror(&mirror)?;
+        if !mirror.exists()
+            && let Some(renamed) = self.cli.clone_blobless_reporting(
+                key,
+                &mirror,
+                self.clone_reference(key).as_deref(),
+                progress.as_ref().map(|f| f as &dyn Fn(GitProgress)),
+            )?
+            && renamed.canonical() != key.canonical()
+        {
+            self.follow_rename(key, &renamed)?;
         }
+        self.repair_alternates(&mirror)?;
         let repo = git::open_repository(&mirror)?;
-        let hydrator = Hydrator::new(mirror.clone(), self.blob_cache.clone());
+        let hydrator = Hydrator::new(self.cli.clone(), mirror.clone(), self.blob_cache.clone());
         let handle = Arc::new(Mutex::new(OpenRepo { repo, hydrator }));
         //Insert, keeping the first entry if raced.
         self.open
@@ -107,13 +257,228 @@ impl Store {
         Ok(handle)
     }
 
+    /// The cached mirror a clone of `key` should borrow objects from: the
+    /// repo it was explicitly linked to (`ghfs link`), or else the fork
+    /// parent GitHub reports when an API token is available.
+    fn clone_reference(&self, key: &RepoKey) -> Option<PathBuf> {
+        let reference = self.paths.reference(key).or_else(|| {
+            if ApiClient::shared().has_token() {
+                fork_parent(key)
+            } else {
+                None
+            }
+        })?;
+        if reference.canonical() == key.canonical() {
+            return None;
+        }
+        // Resolve aliases so the alternates entry names the real mirror.
+        let mirror = std::fs::canonicalize(self.paths.mirror_dir(&reference)).ok()?;
+        if !mirror.join("objects").is_dir() {
+            return None;
+        }
+        log::info!("{key}: borrowing objects from {reference}");
+        Some(mirror)
+    }
+
+    /// Point alternates entries that no longer exist, e.g. because the cache
+    /// was moved, at the mirror with the same `<owner>/<repo>.git` in the
+    /// current cache. Entries with no such mirror are left for git to
+    /// report.
+    fn repair_alternates(&self, mirror: &Path) -> Result<(), StoreError> {
+        let alternates = git::alternates(mirror);
+        if alternates.iter().all(|alt| alt.is_dir()) {
+            return Ok(());
+        }
+        let repaired: Vec<PathBuf> = alternates
+            .into_iter()
+            .map(|alt| {
+                if alt.is_dir() {
+                    return alt;
+                }
+                let moved = alt
+                    .parent()
+                    .and_then(mirror_key)
+                    .and_then(|key| std::fs::canonicalize(self.paths.mirror_dir(&key)).ok())
+                    .map(|dir| dir.join("objects"))
+                    .filter(|objects| objects.is_dir());
+                match moved {
+                    Some(objects) => {
+                        log::warn!(
+                            "{}: borrowed objects moved from {} to {}; updating alternates",
+                            mirror.display(),
+                            alt.display(),
+                            objects.display()
+                        );
+                        objects
+                    }
+                    None => alt,
+                }
+            })
+            .collect();
+        git::set_alternates(mirror, &repaired)?;
+        Ok(())
+    }
+
+    /// Link `key` to `reference` so its mirror borrows `reference`'s
+    /// objects. Takes effect on the next clone of `key`; if both are
+    /// already cached, the existing mirror is switched over now. Returns
+    /// whether objects are shared already.
+    pub fn link(&self, key: &RepoKey, reference: &RepoKey) -> Result<bool, StoreError> {
+        if key.canonical() == reference.canonical() {
+            return Err(GitError::InvalidInput(format!("{key} can't reference itself")).into());
+        }
+        self.paths.set_reference(key, reference)?;
+
+        let mirror = self.paths.mirror_dir(key);
+        let Ok(reference_mirror) = std::fs::canonicalize(self.paths.mirror_dir(reference)) else {
+            return Ok(false);
+        };
+        if !mirror.exists() {
+            return Ok(false);
+        }
+        let _lock = acquire_lock(&self.paths.lock_path(key))?;
+        self.cli.borrow_objects(&mirror, &reference_mirror)?;
+        self.open.remove(key);
+        Ok(true)
+    }
+
+    /// Mirrors that list `mirror`'s object store in their alternates, with
+    /// the repos they belong to.
+    fn dependents(&self, mirror: &Path) -> Vec<(RepoKey, PathBuf)> {
+        let Ok(objects) = std::fs::canonicalize(mirror.join("objects")) else {
+            return Vec::new();
+        };
+        let mut dependents = Vec::new();
+        for owner in self.paths.owner_dirs() {
+            let Ok(repos) = std::fs::read_dir(owner) else {
+                continue;
+            };
+            for repo in repos.flatten() {
+                let dependent = repo.path();
+                // Aliases point at mirrors visited under their own name.
+                if dependent.is_symlink() || dependent == mirror {
+                    continue;
+                }
+                let borrows = git::alternates(&dependent)
+                    .iter()
+                    .any(|alt| std::fs::canonicalize(alt).is_ok_and(|alt| alt == objects));
+                if let Some(key) = mirror_key(&dependent).filter(|_| borrows) {
+                    dependents.push((key, dependent));
+                }
+            }
+        }
+        dependents
+    }
+
+    /// Copy borrowed objects into every mirror that lists `mirror` in its
+    /// alternates, so deleting `mirror` doesn't break them.
+    fn dissociate_dependents(&self, mirror: &Path) -> Result<(), StoreError> {
+        for (dependent_key, dependent) in self.dependents(mirror) {
+            let _lock = acquire_lock(&self.paths.lock_path(&dependent_key))?;
+            log::info!(
+                "{dependent_key}: copying objects borrowed from {} before it is removed",
+                mirror.display()
+            );
+            self.cli.dissociate(&dependent)?;
+            self.open.remove(&dependent_key);
+        }
+        Ok(())
+    }
+
+    /// Repack `key`'s mirror and delete objects no ref reaches right away,
+    /// instead of after git's usual grace period. Commits only reachable
+    /// from before a force-push stop resolving through `by-ref`.
+    ///
+    /// Refused while a `--commit` HEAD is being served, since that commit
+    /// may be one of the unreachable ones, and while another mirror borrows
+    /// objects from this one for the same reason.
+    pub fn prune(&self, key: &RepoKey) -> Result<(), StoreError> {
+        let mirror = self.paths.mirror_dir(key);
+        if !mirror.exists() {
+            return Err(StoreError::RepoNotFound(key.to_string()));
+        }
+        let _lock = acquire_lock(&self.paths.lock_path(key))?;
+        if let Some(commit) = self.head_overrides.get(key) {
+            return Err(StoreError::Busy(format!(
+                "{key} is serving {} until its next sync",
+                *commit
+            )));
+        }
+        if let Some((dependent, _)) = self.dependents(&mirror).into_iter().next() {
+            return Err(StoreError::Busy(format!(
+                "{dependent} borrows objects from {key}"
+            )));
+        }
+        self.cli.prune_unreachable(&mirror)?;
+        self.open.remove(key);
+        Ok(())
+    }
+
+    /// Handle a fresh clone of `key` that GitHub redirected to `renamed`:
+    /// move it under the new name and alias the old one, or just log when
+    /// renames aren't followed. The caller holds `key`'s lock.
+    fn follow_rename(&self, key: &RepoKey, renamed: &RepoKey) -> Result<(), StoreError> {
+        if !self.follow_renames {
+            log::warn!(
+                "{key} was renamed to {renamed}; caching under the old name \
+                 (set {FOLLOW_RENAMES_ENV}=1 to follow renames)"
+            );
+            return Ok(());
+        }
+        let _lock = RepoLock::acquire(&self.paths.lock_path(renamed)).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::TimedOut {
+                StoreError::LockFailed
+            } else {
+                StoreError::Io(e)
+            }
+        })?;
+        let mirror = self.paths.mirror_dir(key);
+        let target = self.paths.mirror_dir(renamed);
+        if target.exists() {
+            // Already cached under the new name; keep that copy.
+            std::fs::remove_dir_all(&mirror)?;
+        } else {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(&mirror, &target)?;
+        }
+        self.paths.alias(key, renamed)?;
+        log::info!("{key} was renamed to {renamed}; cached under the new name");
+        Ok(())
+    }
+
+    /// When the repo's mirror last fetched, or `None` if it isn't cached.
+    pub fn last_synced(&self, key: &RepoKey) -> Option<SystemTime> {
+        git::last_fetch_time(&self.paths.mirror_dir(key))
+    }
+
     /// Refresh a repo's refs by fetching incrementally (blobless). No-op safe
     /// to call periodically; the mirror must already exist or it will be
     /// created.
+    ///
+    /// Holds the repo lock while fetching, so refreshes of one repo run one
+    /// at a time and never overlap [`Store::remove`] or [`Store::prune`].
     pub fn refresh(&self, key: &RepoKey) -> Result<(), StoreError> {
         let handle = self.ensure_open(key)?;
+        let _lock = acquire_lock(&self.paths.lock_path(key))?;
+        let mirror = self.paths.mirror_dir(key);
+        // Removed while we waited for the lock.
+        if !mirror.exists() {
+            return Err(StoreError::RepoNotFound(key.to_string()));
+        }
         let guard = handle.lock().expect("open repo poisoned");
-        self.cli.fetch_blobless(&self.paths.mirror_dir(key))?;
+        if self.remote_precheck && !self.remote_moved(key, &guard.repo, &mirror) {
+            log::debug!("{key}: remote head unchanged, skipping fetch");
+            drop(guard);
+            self.head_overrides.remove(key);
+            return Ok(());
+        }
+        let progress = self.progress_for(key);
+        self.cli.fetch_blobless_reporting(
+            &mirror,
+            progress.as_ref().map(|f| f as &dyn Fn(GitProgress)),
+        )?;
         // Drop the guard; opened repo caches libgit2's ref cache though, so
         // path resolution after a fetch should re-open if stale. For now the
         // tree cache is keyed by OID (immutable) so stale ref pointers are the
@@ -122,18 +487,181 @@ impl Store {
         drop(guard);
         // Removing the cached handle forces a reopen so libgit2 sees new refs.
         self.open.remove(key);
+        // A refresh returns the repo to tracking HEAD.
+        self.head_overrides.remove(key);
         Ok(())
     }
 
-    /// Resolve the default-branch (HEAD) commit for `key`.
+    /// Whether `origin`'s side of the ref `key` serves (HEAD, or the branch
+    /// set with [`Store::set_default_branch`]) differs from the mirror's.
+    /// Errors from either side count as "moved" so the fetch still runs.
+    fn remote_moved(&self, key: &RepoKey, repo: &Repository, mirror: &std::path::Path) -> bool {
+        let local = match self.paths.default_branch(key) {
+            Some(branch) => git::resolve_revision(repo, &format!("refs/heads/{branch}")).ok(),
+            None => git::resolve_head(repo).ok().flatten(),
+        };
+        let remote = match self.remote_head(key) {
+            Ok(oid) => Some(oid),
+            Err(e) => {
+                log::debug!("ls-remote for {} failed: {e}", mirror.display());
+                None
+            }
+        };
+        needs_fetch(local, remote)
+    }
+
+    /// The commit `origin` has for the ref `key` serves (HEAD, or the
+    /// branch set with [`Store::set_default_branch`]), asked with one
+    /// `git ls-remote` and without fetching anything.
+    pub fn remote_head(&self, key: &RepoKey) -> Result<Oid, StoreError> {
+        let refname = match self.paths.default_branch(key) {
+            Some(branch) => format!("refs/heads/{branch}"),
+            None => "HEAD".to_string(),
+        };
+        Ok(self
+            .cli
+            .ls_remote_ref(&self.paths.mirror_dir(key), &refname)?)
+    }
+
+    /// Serve `sha` as the repo's HEAD until the next [`Store::refresh`],
+    /// fetching the commit first if the mirror doesn't have it. Nothing is
+    /// pinned: the next sync returns the repo to the remote's HEAD.
+    pub fn advance_to(&self, key: &RepoKey, sha: &str) -> Result<Oid, StoreError> {
+        git::validate_commit_sha(sha)?;
+        let commit = match self.resolve_revision(key, sha) {
+            Ok(oid) => oid,
+            Err(StoreError::Git(GitError::RefNotFound(_))) => {
+                {
+                    let _lock = acquire_lock(&self.paths.lock_path(key))?;
+                    self.cli.fetch_commit(&self.paths.mirror_dir(key), sha)?;
+                    self.open.remove(key);
+                }
+                // Reopening may clone, which takes the lock itself.
+                self.resolve_revision(key, sha)?
+            }
+            Err(e) => return Err(e),
+        };
+        self.head_overrides.insert(key.clone(), commit);
+        Ok(commit)
+    }
+
+    /// Delete everything cached for `key` except shared blobs: the open
+    /// handle, the mirror directory, and the lock file. Blobs are
+    /// content-addressed and shared across repos, so they are left for GC.
+    /// Returns whether a mirror was present.
+    pub fn remove(&self, key: &RepoKey) -> Result<bool, StoreError> {
+        self.remove_unless(key, || None)
+    }
+
+    /// [`Store::remove`], unless `in_use` (asked while holding the repo
+    /// lock) gives a reason to keep the repo, which is returned as
+    /// [`StoreError::Busy`].
+    pub fn remove_unless(
+        &self,
+        key: &RepoKey,
+        in_use: impl FnOnce() -> Option<String>,
+    ) -> Result<bool, StoreError> {
+        let lock_path = self.paths.lock_path(key);
+        let lock = match RepoLock::acquire(&lock_path) {
+            Ok(lock) => lock,
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                return Err(StoreError::LockFailed);
+            }
+            Err(e) => return Err(StoreError::Io(e)),
+        };
+        if let Some(reason) = in_use() {
+            return Err(StoreError::Busy(reason));
+        }
+
+        self.open.remove(key);
+        self.head_overrides.remove(key);
+
+        let mirror = self.paths.mirror_dir(key);
+        let existed = mirror.exists();
+        if existed {
+            self.dissociate_dependents(&mirror)?;
+            std::fs::remove_dir_all(&mirror)?;
+        }
+        let _ = std::fs::remove_file(self.paths.reference_path(key));
+        let _ = self.paths.clear_default_branch(key);
+        // Drop the owner directory once its last mirror is gone, and its
+        // shard once the last owner is.
+        if let Some(owner_dir) = mirror.parent()
+            && std::fs::remove_dir(owner_dir).is_ok()
+            && self.paths.is_sharded()
+            && let Some(shard) = owner_dir.parent()
+        {
+            let _ = std::fs::remove_dir(shard);
+        }
+
+        drop(lock);
+        match std::fs::remove_file(&lock_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(StoreError::Io(e)),
+        }
+        Ok(existed)
+    }
+
+    /// Resolve the default-branch (HEAD) commit for `key`, or the commit
+    /// set by [`Store::advance_to`] if one is active. A branch set with
+    /// [`Store::set_default_branch`] takes the place of HEAD while the
+    /// mirror has it. A mirror with no commits yet is
+    /// [`StoreError::NoCommits`].
     pub fn resolve_head(&self, key: &RepoKey) -> Result<Oid, StoreError> {
+        if let Some(commit) = self.head_overrides.get(key) {
+            return Ok(*commit);
+        }
         let handle = self.ensure_open(key)?;
         let guard = handle.lock().expect("open repo poisoned");
-        Ok(git::resolve_head(&guard.repo)?)
+        if let Some(branch) = self.paths.default_branch(key) {
+            match git::resolve_revision(&guard.repo, &format!("refs/heads/{branch}")) {
+                Ok(commit) => return Ok(commit),
+                Err(e) => log::debug!("{key}: branch {branch} unavailable ({e}), serving HEAD"),
+            }
+        }
+        git::resolve_head(&guard.repo)?.ok_or_else(|| StoreError::NoCommits(key.to_string()))
+    }
+
+    /// Serve `branch` as `key`'s default branch instead of the remote's
+    /// HEAD, or go back to HEAD with `None`. The setting lives in the cache,
+    /// so it survives restarts; it applies from the next resolution.
+    pub fn set_default_branch(
+        &self,
+        key: &RepoKey,
+        branch: Option<&str>,
+    ) -> Result<(), StoreError> {
+        match branch {
+            Some(branch) => {
+                git::validate_branch_name(branch)?;
+                self.paths.set_default_branch(key, branch)?;
+            }
+            None => self.paths.clear_default_branch(key)?,
+        }
+        Ok(())
     }
 
     /// Resolve a ref selector to a commit OID.
     pub fn resolve_revision(&self, key: &RepoKey, selector: &str) -> Result<Oid, StoreError> {
+        let resolved = self.resolve_local(key, selector);
+        // A tag the mirror lacks (pushed since the last fetch) is fetched by
+        // name; tags don't move, so one found locally is never re-fetched.
+        let Some(tag) = tag_name(selector) else {
+            return resolved;
+        };
+        if !matches!(resolved, Err(StoreError::Git(GitError::RefNotFound(_)))) {
+            return resolved;
+        }
+        log::info!("{key}: fetching tag {tag}");
+        if let Err(e) = self.cli.fetch_tag(&self.paths.mirror_dir(key), tag) {
+            log::warn!("{key}: fetching tag {tag} failed: {e}");
+            return resolved;
+        }
+        self.open.remove(key);
+        self.resolve_local(key, selector)
+    }
+
+    fn resolve_local(&self, key: &RepoKey, selector: &str) -> Result<Oid, StoreError> {
         let handle = self.ensure_open(key)?;
         let guard = handle.lock().expect("open repo poisoned");
         Ok(git::resolve_revision(&guard.repo, selector)?)
@@ -161,6 +689,14 @@ impl Store {
         Ok(reader.root_tree(commit)?)
     }
 
+    /// The root tree to serve for a repo with no commits yet.
+    pub fn empty_tree(&self, key: &RepoKey) -> Result<Oid, StoreError> {
+        let handle = self.ensure_open(key)?;
+        let guard = handle.lock().expect("open repo poisoned");
+        let reader = TreeReader::new(&guard.repo, &self.tree_cache);
+        Ok(reader.empty_tree()?)
+    }
+
     /// All direct children of a tree (for `readdir`).
     pub fn tree_entries(&self, key: &RepoKey, tree: Oid) -> Result<Arc<[TreeEntry]>, StoreError> {
         let handle = self.ensure_open(key)?;
@@ -189,3 +725,443 @@ impl Store {
         Ok(guard.hydrator.hydrate(oid)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git_fixtures::{commit, commit_tree, init_bare};
+    use tempfile::tempdir;
+
+    #[test]
+    fn incomplete_mirrors_are_discarded_for_a_fresh_clone() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let mirror = paths.mirror_dir(&key);
+
+        assert!(!discard_incomplete_mirror(&mirror).unwrap());
+
+        // An empty directory, and one killed before it got its refs.
+        std::fs::create_dir_all(&mirror).unwrap();
+        assert!(discard_incomplete_mirror(&mirror).unwrap());
+        assert!(!mirror.exists());
+        std::fs::create_dir_all(mirror.join("objects").join("pack")).unwrap();
+        std::fs::write(mirror.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert!(discard_incomplete_mirror(&mirror).unwrap());
+        assert!(!mirror.exists());
+
+        // A complete mirror, even one with no commits yet, is kept.
+        git2::Repository::init_bare(&mirror).unwrap();
+        assert!(!discard_incomplete_mirror(&mirror).unwrap());
+        assert!(mirror.join("HEAD").exists());
+    }
+
+    #[test]
+    fn remove_deletes_mirror_and_lock() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let other: RepoKey = "octocat/spoon-knife".parse().unwrap();
+
+        let mirror = paths.mirror_dir(&key);
+        git2::Repository::init_bare(&mirror).unwrap();
+        git2::Repository::init_bare(paths.mirror_dir(&other)).unwrap();
+        std::fs::create_dir_all(paths.locks_dir()).unwrap();
+        std::fs::write(paths.lock_path(&key), b"").unwrap();
+
+        let store = Store::new(paths.clone());
+        assert!(matches!(
+            store.remove_unless(&key, || Some("in use".to_string())),
+            Err(StoreError::Busy(_))
+        ));
+        assert!(mirror.exists());
+        assert!(store.remove(&key).unwrap());
+
+        assert!(!mirror.exists());
+        assert!(!paths.lock_path(&key).exists());
+        // Sibling mirrors under the same owner are untouched.
+        assert!(paths.mirror_dir(&other).exists());
+
+        assert!(store.remove(&other).unwrap());
+        assert!(!paths.mirrors_dir().join("octocat").exists());
+    }
+
+    #[test]
+    fn explicit_link_picks_clone_reference() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let fork: RepoKey = "someone/hello-world".parse().unwrap();
+        let upstream: RepoKey = "octocat/hello-world".parse().unwrap();
+        let store = Store::new(paths.clone());
+
+        // Neither is cached: the link is only recorded.
+        assert!(!store.link(&fork, &upstream).unwrap());
+        assert_eq!(paths.reference(&fork), Some(upstream.clone()));
+        assert_eq!(store.clone_reference(&fork), None);
+
+        git2::Repository::init_bare(paths.mirror_dir(&upstream)).unwrap();
+        assert_eq!(
+            store.clone_reference(&fork),
+            Some(std::fs::canonicalize(paths.mirror_dir(&upstream)).unwrap())
+        );
+
+        assert!(matches!(
+            store.link(&upstream, &upstream),
+            Err(StoreError::Git(GitError::InvalidInput(_)))
+        ));
+    }
+
+    #[test]
+    fn remove_keeps_dependent_mirrors_readable() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let fork: RepoKey = "someone/hello-world".parse().unwrap();
+        let upstream: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (_, head) = mirror_with_two_commits(&paths, &upstream);
+        git2::Repository::init_bare(paths.mirror_dir(&fork)).unwrap();
+
+        let store = Store::new(paths.clone());
+        assert!(store.link(&fork, &upstream).unwrap());
+        // The fork's branch resolves through the borrowed objects.
+        git::open_repository(&paths.mirror_dir(&fork))
+            .unwrap()
+            .reference("refs/heads/main", head, true, "fork")
+            .unwrap();
+
+        assert!(store.remove(&upstream).unwrap());
+        assert!(git::alternates(&paths.mirror_dir(&fork)).is_empty());
+        assert!(
+            git::open_repository(&paths.mirror_dir(&fork))
+                .unwrap()
+                .find_commit(head)
+                .is_ok()
+        );
+
+        assert!(store.remove(&fork).unwrap());
+        assert!(!paths.reference_path(&fork).exists());
+    }
+
+    #[test]
+    fn moved_cache_repairs_alternates() {
+        let dir = tempdir().unwrap();
+        let old_root = dir.path().join("old");
+        let paths = CachePaths::new(&old_root);
+        let fork: RepoKey = "someone/hello-world".parse().unwrap();
+        let upstream: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (_, head) = mirror_with_two_commits(&paths, &upstream);
+        git2::Repository::init_bare(paths.mirror_dir(&fork)).unwrap();
+        assert!(Store::new(paths.clone()).link(&fork, &upstream).unwrap());
+        git::open_repository(&paths.mirror_dir(&fork))
+            .unwrap()
+            .reference("refs/heads/main", head, true, "fork")
+            .unwrap();
+
+        let new_root = dir.path().join("new");
+        std::fs::rename(&old_root, &new_root).unwrap();
+        let paths = CachePaths::new(&new_root);
+        let store = Store::new(paths.clone());
+        assert_eq!(store.resolve_revision(&fork, "main").unwrap(), head);
+        let alternates = git::alternates(&paths.mirror_dir(&fork));
+        assert_eq!(alternates.len(), 1);
+        assert!(alternates[0].starts_with(std::fs::canonicalize(&new_root).unwrap()));
+    }
+
+    #[test]
+    fn prune_drops_force_pushed_commits_unless_borrowed() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (first, second) = mirror_with_two_commits(&paths, &key);
+        // Force-push `main` back to the first commit.
+        git::open_repository(&paths.mirror_dir(&key))
+            .unwrap()
+            .reference("refs/heads/main", first, true, "force-push")
+            .unwrap();
+
+        let store = Store::new(paths.clone());
+        let fork: RepoKey = "someone/hello-world".parse().unwrap();
+        git2::Repository::init_bare(paths.mirror_dir(&fork)).unwrap();
+        store.link(&fork, &key).unwrap();
+        assert!(matches!(store.prune(&key), Err(StoreError::Busy(_))));
+
+        store.remove(&fork).unwrap();
+        store.prune(&key).unwrap();
+        let repo = git::open_repository(&paths.mirror_dir(&key)).unwrap();
+        assert!(repo.find_commit(first).is_ok());
+        assert!(repo.find_commit(second).is_err());
+
+        let missing: RepoKey = "octocat/missing".parse().unwrap();
+        assert!(matches!(
+            store.prune(&missing),
+            Err(StoreError::RepoNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn tags_resolve_locally_and_missing_ones_are_fetched() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (first, second) = mirror_with_two_commits(&paths, &key);
+        let mirror = git::open_repository(&paths.mirror_dir(&key)).unwrap();
+        mirror
+            .reference("refs/tags/v1.0", first, false, "tag")
+            .unwrap();
+
+        // No origin yet: a tag found locally never needs the network, and
+        // stays put when a refresh moves the branch.
+        let store = Store::new(paths.clone());
+        assert_eq!(
+            store.resolve_revision(&key, "refs/tags/v1.0").unwrap(),
+            first
+        );
+        mirror
+            .reference("refs/heads/main", second, true, "refresh")
+            .unwrap();
+        store.open.remove(&key);
+        assert_eq!(
+            store.resolve_revision(&key, "refs/tags/v1.0").unwrap(),
+            first
+        );
+
+        // A tag pushed since the last fetch is fetched by name.
+        let upstream = dir.path().join("upstream.git");
+        let status = std::process::Command::new("git")
+            .args(["clone", "--bare", "--quiet"])
+            .arg(paths.mirror_dir(&key))
+            .arg(&upstream)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        git::open_repository(&upstream)
+            .unwrap()
+            .reference("refs/tags/v2.0", second, false, "tag")
+            .unwrap();
+        mirror.remote("origin", upstream.to_str().unwrap()).unwrap();
+        assert_eq!(
+            store.resolve_revision(&key, "refs/tags/v2.0").unwrap(),
+            second
+        );
+        assert!(mirror.find_reference("refs/tags/v2.0").is_ok());
+
+        assert!(matches!(
+            store.resolve_revision(&key, "refs/tags/v3.0"),
+            Err(StoreError::Git(GitError::RefNotFound(_)))
+        ));
+    }
+
+    /// Init a bare mirror for `key` with two commits on `main`; returns
+    /// `(first, second)`.
+    fn mirror_with_two_commits(paths: &CachePaths, key: &RepoKey) -> (Oid, Oid) {
+        let repo = init_bare(&paths.mirror_dir(key));
+        let first = commit(&repo, "one", &[]);
+        let second = commit(&repo, "two", &[]);
+        (first, second)
+    }
+
+    #[test]
+    fn advance_to_serves_requested_commit_as_head() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (first, second) = mirror_with_two_commits(&paths, &key);
+
+        let store = Store::new(paths);
+        assert_eq!(store.resolve_head(&key).unwrap(), second);
+
+        let sha = first.to_string();
+        assert_eq!(store.advance_to(&key, &sha).unwrap(), first);
+        assert_eq!(store.resolve_head(&key).unwrap(), first);
+        // Clones share the override, so the mount sees the worker's change.
+        assert_eq!(store.clone().resolve_head(&key).unwrap(), first);
+        // Abbreviated SHAs of known commits resolve too.
+        assert_eq!(store.advance_to(&key, &sha[..12]).unwrap(), first);
+    }
+
+    #[test]
+    fn advance_to_rejects_non_sha() {
+        let dir = tempdir().unwrap();
+        let paths = CachePaths::new(dir.path());
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let (_, second) = mirror_with_two_commits(&paths, &key);
+
+        let store = Store::new(paths);
+        assert!(matches!(
+            store.advance_to(&key, "main"),
+            Err(StoreError::Git(GitError::InvalidInput(_)))
+        ));
+        assert_eq!(store.resolve_head(&key).unwrap(), second);
+    }
+
+    #[test]
+    fn needs_fetch_skips_only_on_confirmed_match() {
+        let a = Oid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap();
+        let b = Oid::from_str("fedcba9876543210fedcba9876543210fedcba98").unwrap();
+        assert!(!needs_fetch(Some(a), Some(a)));
+        assert!(needs_fetch(Some(a), Some(b)));
+        assert!(needs_fetch(None, Some(a)));
+        assert!(needs_fetch(Some(a), None));
+    }
+
+    #[test]
+    fn remote_precheck_skips_fetch_until_head_moves() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let upstream_paths = CachePaths::new(dir.path().join("upstream"));
+        let (_, head) = mirror_with_two_commits(&upstream_paths, &key);
+        let upstream = git2::Repository::open_bare(upstream_paths.mirror_dir(&key)).unwrap();
+
+        let paths = CachePaths::new(dir.path().join("cache"));
+        let mirror = init_bare(&paths.mirror_dir(&key));
+        mirror
+            .remote("origin", upstream.path().to_str().unwrap())
+            .unwrap();
+
+        let store = Store::new(paths).with_remote_precheck(true);
+        store.refresh(&key).unwrap();
+        assert_eq!(store.resolve_head(&key).unwrap(), head);
+
+        // A new branch alone doesn't move HEAD, so it isn't fetched.
+        let head_commit = upstream.find_commit(head).unwrap();
+        upstream.branch("feature", &head_commit, false).unwrap();
+        store.refresh(&key).unwrap();
+        assert!(store.resolve_revision(&key, "feature").is_err());
+
+        let moved = commit(&upstream, "three", &[]);
+        store.refresh(&key).unwrap();
+        assert_eq!(store.resolve_head(&key).unwrap(), moved);
+        assert!(store.resolve_revision(&key, "feature").is_ok());
+    }
+
+    #[test]
+    fn configured_branch_replaces_head_through_refreshes() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let upstream_paths = CachePaths::new(dir.path().join("upstream"));
+        let (first, head) = mirror_with_two_commits(&upstream_paths, &key);
+        let upstream = git2::Repository::open_bare(upstream_paths.mirror_dir(&key)).unwrap();
+        let develop = upstream.find_commit(first).unwrap();
+        upstream.branch("develop", &develop, false).unwrap();
+
+        let paths = CachePaths::new(dir.path().join("cache"));
+        let mirror = init_bare(&paths.mirror_dir(&key));
+        mirror
+            .remote("origin", upstream.path().to_str().unwrap())
+            .unwrap();
+
+        let store = Store::new(paths).with_remote_precheck(true);
+        assert!(matches!(
+            store.set_default_branch(&key, Some("a..b")),
+            Err(StoreError::Git(GitError::InvalidInput(_)))
+        ));
+        store.set_default_branch(&key, Some("develop")).unwrap();
+        store.refresh(&key).unwrap();
+        assert_eq!(store.resolve_head(&key).unwrap(), first);
+
+        // The precheck watches the configured branch, not HEAD.
+        let moved = commit_tree(
+            &upstream,
+            "refs/heads/develop",
+            "develop",
+            &develop.tree().unwrap(),
+        );
+        store.refresh(&key).unwrap();
+        assert_eq!(store.resolve_head(&key).unwrap(), moved);
+
+        store.set_default_branch(&key, None).unwrap();
+        assert_eq!(store.resolve_head(&key).unwrap(), head);
+
+        // A branch the mirror doesn't have falls back to HEAD.
+        store.set_default_branch(&key, Some("gone")).unwrap();
+        assert_eq!(store.resolve_head(&key).unwrap(), head);
+    }
+
+    #[test]
+    fn resolving_during_refreshes_never_fails() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let upstream_paths = CachePaths::new(dir.path().join("upstream"));
+        mirror_with_two_commits(&upstream_paths, &key);
+        let upstream_dir = upstream_paths.mirror_dir(&key);
+
+        let paths = CachePaths::new(dir.path().join("cache"));
+        let mirror = init_bare(&paths.mirror_dir(&key));
+        mirror
+            .remote("origin", upstream_dir.to_str().unwrap())
+            .unwrap();
+
+        let store = Store::new(paths);
+        store.refresh(&key).unwrap();
+
+        // Each refresh moves HEAD and drops the cached handle while readers
+        // hold the old one. Objects are never deleted, so every commit a
+        // reader resolved stays readable.
+        let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let writer = {
+            let store = store.clone();
+            let key = key.clone();
+            let stop = Arc::clone(&stop);
+            std::thread::spawn(move || {
+                let upstream = git2::Repository::open_bare(&upstream_dir).unwrap();
+                for i in 0..10 {
+                    commit(&upstream, &format!("c{i}"), &[]);
+                    store.refresh(&key).unwrap();
+                }
+                stop.store(true, std::sync::atomic::Ordering::SeqCst);
+            })
+        };
+
+        while !stop.load(std::sync::atomic::Ordering::SeqCst) {
+            let head = store.resolve_head(&key).unwrap();
+            let tree = store.root_tree(&key, head).unwrap();
+            store.tree_entries(&key, tree).unwrap();
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn refresh_waits_for_the_repo_lock() {
+        let dir = tempdir().unwrap();
+        let key: RepoKey = "octocat/hello-world".parse().unwrap();
+        let upstream_paths = CachePaths::new(dir.path().join("upstream"));
+        let (_, head) = mirror_with_two_commits(&upstream_paths, &key);
+        let upstream_dir = upstream_paths.mirror_dir(&key);
+
+        let paths = CachePaths::new(dir.path().join("cache"));
+        let mirror = init_bare(&paths.mirror_dir(&key));
+        mirror
+            .remote("origin", upstream_dir.to_str().unwrap())
+            .unwrap();
+        let store = Store::new(paths.clone());
+
+        // As if a removal or prune of the repo were in progress.
+        let lock = RepoLock::acquire(&paths.lock_path(&key)).unwrap();
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        let refresh = {
+            let store = store.clone();
+            let key = key.clone();
+            std::thread::spawn(move || {
+                let result = store.refresh(&key);
+                done_tx.send(()).unwrap();
+                result
+            })
+        };
+        assert!(
+            done_rx
+                .recv_timeout(std::time::Duration::from_millis(300))
+                .is_err(),
+            "refresh ran while the repo was locked"
+        );
+        drop(lock);
+        refresh.join().unwrap().unwrap();
+        assert_eq!(store.resolve_head(&key).unwrap(), head);
+    }
+
+    #[test]
+    fn remove_missing_repo_is_noop() {
+        let dir = tempdir().unwrap();
+        let store = Store::new(CachePaths::new(dir.path()));
+        let key: RepoKey = "octocat/missing".parse().unwrap();
+        assert!(!store.remove(&key).unwrap());
+    }
+}
@@ -47,6 +47,19 @@ pub enum EntryKind {
     Gitlink,
 }
 
+impl EntryKind {
+    /// The canonical git file mode for entries of this kind.
+    pub fn git_mode(self) -> u32 {
+        match self {
+            Self::Tree => 0o040000,
+            Self::Blob => 0o100644,
+            Self::Executable => 0o100755,
+            Self::Symlink => 0o120000,
+            Self::Gitlink => 0o160000,
+        }
+    }
+}
+
 /// A single entry in a git tree: a name plus its object identity.
 #[derive(Debug, Clone)]
 pub struct TreeEntry {
@@ -63,7 +76,7 @@ pub struct TreeEntry {
 /// Cache of parsed trees keyed by tree OID, shared across all commits/refs.
 #[derive(Clone)]
 pub struct TreeCache {
-    inner: Arc<Mutex<lru::LruCache<Oid, Vec<TreeEntry>>>>,
+    inner: Arc<Mutex<lru::LruCache<Oid, Arc<[TreeEntry]>>>>,
 }
 
 impl TreeCache {
@@ -76,30 +89,34 @@ impl TreeCache {
         }
     }
 
+    /// Number of parsed trees currently cached.
+    pub fn entry_count(&self) -> usize {
+        self.inner.lock().expect("tree cache poisoned").len()
+    }
+
     /// Parse a tree object into entries, or return a cached copy.
     ///
     /// The returned [`Arc`] is cheap to clone and lets the caller hold a
     /// snapshot of the entries without holding the cache lock while iterating.
     fn get_or_parse(&self, repo: &Repository, oid: Oid) -> Result<Arc<[TreeEntry]>, TreeError> {
-        // Fast path: cache hit. Clone the entries into a shared allocation so
-        // the caller doesn't hold the lock while iterating / returning to the
-        // FS layer.
+        // Fast path: cache hit. The entries are shared, so the caller can
+        // iterate without holding the lock or copying a large directory.
         {
             let mut guard = self.inner.lock().expect("tree cache poisoned");
             if let Some(entries) = guard.get(&oid) {
-                return Ok(entries.iter().cloned().collect());
+                return Ok(Arc::clone(entries));
             }
         }
 
         // Miss: parse the tree outside the lock, then insert.
         let tree = repo.find_tree(oid).map_err(|e| map_tree_lookup(e, oid))?;
-        let parsed = parse_tree(&tree)?;
+        let parsed: Arc<[TreeEntry]> = parse_tree(&tree)?.into();
 
         {
             let mut guard = self.inner.lock().expect("tree cache poisoned");
-            guard.put(oid, parsed.clone());
+            guard.put(oid, Arc::clone(&parsed));
         }
-        Ok(parsed.into())
+        Ok(parsed)
     }
 }
 
@@ -134,9 +151,16 @@ impl<'a> TreeReader<'a> {
         Ok(commit.tree_id())
     }
 
+    /// The empty tree's OID, written into the repository so it reads like
+    /// any other tree. Serves as the root of a repository with no commits.
+    pub fn empty_tree(&self) -> Result<Oid, TreeError> {
+        let empty = self.repo.treebuilder(None).and_then(|b| b.write());
+        empty.map_err(|e| TreeError::Git(e.into()))
+    }
+
     /// Return all direct children of a tree, as a shared slice snapshot. Used
-    /// by `readdir`. Name order matches git's on-disk tree ordering (already
-    /// sorted in git's canonical order, which is fine for directory listings).
+    /// by `readdir`. Entries are sorted by name bytes, so a listing can be
+    /// served in pages straight from the slice.
     pub fn entries(&self, tree: Oid) -> Result<Arc<[TreeEntry]>, TreeError> {
         self.cache.get_or_parse(self.repo, tree)
     }
@@ -197,6 +221,9 @@ fn parse_tree(tree: &Tree<'_>) -> Result<Vec<TreeEntry>, TreeError> {
             mode,
         });
     }
+    // git's order sorts trees as if their names ended in `/`; listings want
+    // plain byte order.
+    out.sort_by(|a, b| a.name.cmp(&b.name));
     Ok(out)
 }
 
@@ -315,6 +342,7 @@ mod tests {
         assert!(names.iter().any(|n| n == b"sub"));
         assert!(names.iter().any(|n| n == b"run.sh"));
         assert!(names.iter().any(|n| n == b"link"));
+        assert!(names.is_sorted());
     }
 
     #[test]
@@ -397,6 +425,7 @@ mod tests {
         let root = reader.root_tree(head).unwrap();
         let e1 = reader.entries(root).unwrap();
         let e2 = reader.entries(root).unwrap();
-        assert_eq!(e1.len(), e2.len());
+        // A hit shares the cached entries rather than copying them.
+        assert!(Arc::ptr_eq(&e1, &e2));
     }
 }
@@ -0,0 +1,10 @@
+//! Wall-clock helpers shared by the daemon, filesystem, and API client.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current Unix timestamp in seconds, or 0 if the clock is before the epoch.
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
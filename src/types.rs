@@ -1,5 +1,6 @@
 //! Shared types for ghfs
 
+use std::borrow::Borrow;
 use std::fmt;
 use std::str::FromStr;
 use thiserror::Error;
@@ -33,6 +34,16 @@ pub enum ParseError {
 pub struct Owner(String);
 
 impl Owner {
+    /// Wraps `name` without validating it.
+    ///
+    /// Only for names that never reach a cache path or a clone URL, such as
+    /// a state row being renamed. A name that breaks the rules above could
+    /// escape the cache, so anything read from disk, the state database, a
+    /// user or a remote goes through [`str::parse`] instead.
+    pub fn new_unchecked(name: impl Into<String>) -> Self {
+        Owner(name.into())
+    }
+
     /// Returns the owner name as a string slice
     pub fn as_str(&self) -> &str {
         &self.0
@@ -71,6 +82,19 @@ impl fmt::Display for Owner {
     }
 }
 
+impl AsRef<str> for Owner {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Derived `Hash`/`Eq` delegate to the inner `String`, so they agree with `str`.
+impl Borrow<str> for Owner {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
 /// A GitHub repository name
 ///
 /// Validation rules:
@@ -81,6 +105,13 @@ impl fmt::Display for Owner {
 pub struct Repo(String);
 
 impl Repo {
+    /// Wraps `name` without validating it.
+    ///
+    /// The same caveats as [`Owner::new_unchecked`] apply.
+    pub fn new_unchecked(name: impl Into<String>) -> Self {
+        Repo(name.into())
+    }
+
     /// Returns the repository name as a string slice
     pub fn as_str(&self) -> &str {
         &self.0
@@ -115,6 +146,19 @@ impl fmt::Display for Repo {
     }
 }
 
+impl AsRef<str> for Repo {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Derived `Hash`/`Eq` delegate to the inner `String`, so they agree with `str`.
+impl Borrow<str> for Repo {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
 /// Identifies a specific GitHub repository (owner + repo)
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RepoKey {
@@ -129,6 +173,28 @@ impl RepoKey {
     pub fn new(owner: Owner, repo: Repo) -> Self {
         Self { owner, repo }
     }
+
+    /// The form GitHub treats as the same repository: lowercase, without
+    /// trailing `.git`s. The cache is laid out by this form.
+    pub fn canonical(&self) -> RepoKey {
+        let mut repo = self.repo.as_str().to_ascii_lowercase();
+        // Strip every suffix so canonicalizing is idempotent: `a.git.git`
+        // and `a.git` both become `a`.
+        while let Some(stripped) = repo.strip_suffix(".git")
+            && !stripped.is_empty()
+        {
+            repo.truncate(stripped.len());
+        }
+        RepoKey {
+            owner: Owner(self.owner.as_str().to_ascii_lowercase()),
+            repo: Repo(repo),
+        }
+    }
+
+    /// Whether the key is already in [`RepoKey::canonical`] form.
+    pub fn is_canonical(&self) -> bool {
+        *self == self.canonical()
+    }
 }
 
 impl FromStr for RepoKey {
@@ -179,6 +245,7 @@ impl fmt::Display for GenerationId {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     mod owner_tests {
         use super::*;
@@ -236,6 +303,26 @@ mod tests {
             let result = "my/org".parse::<Owner>();
             assert_eq!(result, Err(ParseError::InvalidCharacter('/')));
         }
+
+        #[test]
+        fn owner_map_lookup_by_str() {
+            let mut map = HashMap::new();
+            map.insert("octocat".parse::<Owner>().unwrap(), 1);
+            assert_eq!(map.get("octocat"), Some(&1));
+            assert_eq!(map.get("other"), None);
+        }
+
+        #[test]
+        fn unchecked_owner_keeps_the_string() {
+            let owner = Owner::new_unchecked("Oct_cat");
+            assert_eq!(owner.as_str(), "Oct_cat");
+            // Parsing the same name still rejects it.
+            assert_eq!(
+                "Oct_cat".parse::<Owner>(),
+                Err(ParseError::InvalidCharacter('_'))
+            );
+            assert_eq!(Owner::new_unchecked("octocat"), "octocat".parse().unwrap());
+        }
     }
 
     mod repo_tests {
@@ -276,6 +363,36 @@ mod tests {
             let result = "repo@v1".parse::<Repo>();
             assert_eq!(result, Err(ParseError::InvalidCharacter('@')));
         }
+
+        #[test]
+        fn repo_map_lookup_by_str() {
+            let mut map = HashMap::new();
+            map.insert("hello-world".parse::<Repo>().unwrap(), 1);
+            assert_eq!(map.get("hello-world"), Some(&1));
+            assert!(map.contains_key("hello-world"));
+            assert!(!map.contains_key("Hello-World"));
+        }
+
+        #[test]
+        fn unchecked_repo_keeps_the_string() {
+            let repo = Repo::new_unchecked(".hidden");
+            assert_eq!(repo.to_string(), ".hidden");
+            assert_eq!(
+                ".hidden".parse::<Repo>(),
+                Err(ParseError::InvalidStart('.'))
+            );
+            assert_eq!(
+                Repo::new_unchecked("hello-world"),
+                "hello-world".parse().unwrap()
+            );
+        }
+
+        #[test]
+        fn repo_as_ref_str() {
+            let repo: Repo = "my-repo".parse().unwrap();
+            let s: &str = repo.as_ref();
+            assert_eq!(s, "my-repo");
+        }
     }
 
     mod repo_key_tests {
@@ -288,6 +405,22 @@ mod tests {
             assert_eq!(key.repo.as_str(), "hello-world");
         }
 
+        #[test]
+        fn canonical_lowercases_and_strips_git_suffix() {
+            let key: RepoKey = "Octocat/Hello-World.git".parse().unwrap();
+            let canonical = key.canonical();
+            assert_eq!(canonical.to_string(), "octocat/hello-world");
+            assert!(!key.is_canonical());
+            assert!(canonical.is_canonical());
+            assert_eq!(canonical.canonical(), canonical);
+        }
+
+        #[test]
+        fn canonical_strips_repeated_git_suffixes() {
+            let key: RepoKey = "octocat/hello.git.GIT".parse().unwrap();
+            assert_eq!(key.canonical().to_string(), "octocat/hello");
+        }
+
         #[test]
         fn invalid_repo_key_no_slash() {
             let result = "octocat".parse::<RepoKey>();
@@ -318,4 +451,99 @@ mod tests {
             assert!(matches!(result, Err(ParseError::InvalidRepo(_))));
         }
     }
+
+    /// Round-trip and rejection properties over generated names. The
+    /// generators are regexes, so failures shrink toward short inputs.
+    mod prop_tests {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn owner_str() -> impl Strategy<Value = String> {
+            "[A-Za-z0-9]([A-Za-z0-9-]{0,37}[A-Za-z0-9])?"
+        }
+
+        fn repo_str() -> impl Strategy<Value = String> {
+            "[A-Za-z0-9_-][A-Za-z0-9._-]{0,50}"
+        }
+
+        fn key_str() -> impl Strategy<Value = String> {
+            (owner_str(), repo_str()).prop_map(|(owner, repo)| format!("{owner}/{repo}"))
+        }
+
+        /// Insert `c` somewhere after the first character of `s`.
+        fn insert_after_first(s: &str, index: prop::sample::Index, c: char) -> String {
+            let at = 1 + index.index(s.len());
+            let mut out = s.to_string();
+            out.insert(at, c);
+            out
+        }
+
+        proptest! {
+            #[test]
+            fn owner_round_trips(s in owner_str()) {
+                prop_assert_eq!(s.parse::<Owner>().unwrap().to_string(), s);
+            }
+
+            #[test]
+            fn repo_round_trips(s in repo_str()) {
+                prop_assert_eq!(s.parse::<Repo>().unwrap().to_string(), s);
+            }
+
+            #[test]
+            fn repo_key_round_trips(s in key_str()) {
+                prop_assert_eq!(s.parse::<RepoKey>().unwrap().to_string(), s);
+            }
+
+            #[test]
+            fn anything_that_parses_round_trips(s in "\\PC{0,40}") {
+                if let Ok(key) = s.parse::<RepoKey>() {
+                    prop_assert_eq!(key.to_string(), s);
+                }
+            }
+
+            #[test]
+            fn canonical_is_idempotent_and_reparses(s in key_str()) {
+                let canonical = s.parse::<RepoKey>().unwrap().canonical();
+                prop_assert!(canonical.is_canonical());
+                prop_assert_eq!(canonical.canonical(), canonical.clone());
+                prop_assert_eq!(canonical.to_string().parse::<RepoKey>().unwrap(), canonical);
+            }
+
+            #[test]
+            fn owner_rejects_foreign_characters(
+                s in owner_str(),
+                index in any::<prop::sample::Index>(),
+                c in prop::sample::select(vec![' ', '/', '_', '.', '@', ':', 'é']),
+            ) {
+                let bad = insert_after_first(&s, index, c);
+                prop_assert_eq!(bad.parse::<Owner>(), Err(ParseError::InvalidCharacter(c)));
+            }
+
+            #[test]
+            fn repo_rejects_foreign_characters(
+                s in repo_str(),
+                index in any::<prop::sample::Index>(),
+                c in prop::sample::select(vec![' ', '/', '@', ':', '~', 'é']),
+            ) {
+                let bad = insert_after_first(&s, index, c);
+                prop_assert_eq!(bad.parse::<Repo>(), Err(ParseError::InvalidCharacter(c)));
+            }
+
+            #[test]
+            fn owner_rejects_edge_hyphens(s in owner_str()) {
+                prop_assert_eq!(format!("-{s}").parse::<Owner>(), Err(ParseError::InvalidStart('-')));
+                prop_assert_eq!(format!("{s}-").parse::<Owner>(), Err(ParseError::InvalidEnd('-')));
+            }
+
+            #[test]
+            fn repo_rejects_leading_dot(s in repo_str()) {
+                prop_assert_eq!(format!(".{s}").parse::<Repo>(), Err(ParseError::InvalidStart('.')));
+            }
+
+            #[test]
+            fn key_without_slash_is_missing_separator(s in "[A-Za-z0-9._-]{0,40}") {
+                prop_assert_eq!(s.parse::<RepoKey>(), Err(ParseError::MissingSeparator));
+            }
+        }
+    }
 }